@@ -200,3 +200,58 @@ async fn test_query_latest_entry() {
         .await
         .expect("Failed to cleanup");
 }
+
+// Test that a duplicate email is rejected with a unique violation (23505)
+// The signup handler maps this code to 409 Conflict
+#[tokio::test]
+async fn test_duplicate_email_unique_violation() {
+    let connection_string = std::env::var("DATABASE_URL").unwrap_or_else(|_| {
+        std::env::var("DATABASE_URL").expect("DATABASE_URL environment variable must be set!")
+    });
+
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .connect(&connection_string)
+        .await
+        .expect("Failed to connect to Postgres.");
+
+    let email = format!("duplicate-{}@example.com", uuid::Uuid::new_v4());
+    let first_id = uuid::Uuid::new_v4();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, email, name, password_hash, created_at)
+        VALUES ($1, $2, 'First', 'hash', NOW())
+        "#,
+        first_id,
+        email
+    )
+    .execute(&pool)
+    .await
+    .expect("Failed to insert first user");
+
+    // Same email, different user_id
+    let err = sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, email, name, password_hash, created_at)
+        VALUES ($1, $2, 'Second', 'hash', NOW())
+        "#,
+        uuid::Uuid::new_v4(),
+        email
+    )
+    .execute(&pool)
+    .await
+    .expect_err("Duplicate email should be rejected");
+
+    let code = err
+        .as_database_error()
+        .and_then(|e| e.code())
+        .map(|c| c.to_string());
+    assert_eq!(code.as_deref(), Some("23505"));
+
+    // Cleanup
+    sqlx::query!("DELETE FROM users WHERE user_id = $1", first_id)
+        .execute(&pool)
+        .await
+        .expect("Failed to cleanup");
+}
@@ -300,8 +300,8 @@ function processData(rawData) {
         state.longestInactive = timerSeconds;
     }
     
-    // Trigger alert when backend signals it once per threshold crossing
-    if (alertTriggered && timerSeconds % 60 === 0) {
+    // Backend sends the alert once per crossing, then again every ALERT_COOLDOWN_SECONDS
+    if (alertTriggered) {
         triggerAlert(timerSeconds);
     }
 
@@ -494,15 +494,38 @@ function parseJwt(token) {
 function setupAuth() {
     const token = localStorage.getItem('token');
     if (token) {
-        const payload = parseJwt(token);
-        if (payload && payload.name) {
-            document.getElementById('userName').textContent = payload.name;
-        }
+        // Prefer the server-authoritative profile; fall back to the token claims
+        fetch('/auth/me', { headers: { 'Authorization': `Bearer ${token}` } })
+            .then(response => response.ok ? response.json() : Promise.reject(response.status))
+            .then(profile => {
+                document.getElementById('userName').textContent = profile.name;
+            })
+            .catch(() => {
+                const payload = parseJwt(token);
+                if (payload && payload.name) {
+                    document.getElementById('userName').textContent = payload.name;
+                }
+            });
     }
 
     // Logout handler
-    document.getElementById('logoutBtn').addEventListener('click', function() {
+    document.getElementById('logoutBtn').addEventListener('click', async function() {
+        const token = localStorage.getItem('token');
+        const refreshToken = localStorage.getItem('refresh_token');
+        if (token) {
+            // Revoke this session's tokens server-side; ignore failures and sign out locally anyway
+            await fetch('/logout', {
+                method: 'POST',
+                headers: {
+                    'Authorization': `Bearer ${token}`,
+                    'Content-Type': 'application/json'
+                },
+                body: JSON.stringify({ refresh_token: refreshToken || '' })
+            }).catch(() => {});
+        }
         localStorage.removeItem('token');
+        localStorage.removeItem('refresh_token');
+        localStorage.removeItem('token_exp');
         window.location.href = '/login.html';
     });
 
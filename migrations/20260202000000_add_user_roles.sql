@@ -0,0 +1,6 @@
+-- Add role-based access control to users
+-- 'user' is the default; 'admin' unlocks aggregate analytics endpoints
+
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS role VARCHAR(20) NOT NULL DEFAULT 'user'
+    CHECK (role IN ('user', 'admin'));
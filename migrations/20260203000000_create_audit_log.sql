@@ -0,0 +1,15 @@
+-- Create audit_log table for authentication events
+-- One row per login attempt (success, bad password, rate-limited)
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id SERIAL PRIMARY KEY,
+    user_id UUID REFERENCES users(user_id) ON DELETE SET NULL,
+    email TEXT NOT NULL,
+    event_type VARCHAR(32) NOT NULL,
+    ip_address TEXT,
+    success BOOLEAN NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_created ON audit_log(created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_audit_log_email ON audit_log(email, created_at DESC);
@@ -0,0 +1,6 @@
+-- Require email verification before login
+-- Existing accounts predate verification and are treated as verified
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS verified BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE users SET verified = TRUE;
@@ -0,0 +1,9 @@
+-- Create classification_thresholds table for calibrated threshold overrides
+-- Single row (id = 1); when present it takes precedence over THRESH_FIDGET / THRESH_ACTIVE
+
+CREATE TABLE IF NOT EXISTS classification_thresholds (
+    id SMALLINT PRIMARY KEY DEFAULT 1 CHECK (id = 1),
+    thresh_fidget REAL NOT NULL,
+    thresh_active REAL NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
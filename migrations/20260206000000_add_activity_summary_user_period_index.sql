@@ -0,0 +1,9 @@
+-- Composite index for the FHIR analytics queries, which always filter on
+-- user_id and period_type and read the newest dates first:
+--   get_user_analytics:   WHERE user_id = $1 AND period_type = 'daily' ORDER BY date DESC
+--   get_latest_analytics: newest row per user for one period_type
+-- The UNIQUE(user_id, date, period_type) index can't serve the date ordering
+-- for a single period_type, so those plans sorted or walked every period's rows.
+
+CREATE INDEX IF NOT EXISTS idx_activity_summary_user_period_date
+    ON activity_summary(user_id, period_type, date DESC);
@@ -0,0 +1,7 @@
+-- Indexes for the data-retention job, which deletes raw readings in batches:
+--   sensor_data:   WHERE timestamp < $cutoff LIMIT $batch
+--   sedentary_log: WHERE created_at < $cutoff LIMIT $batch
+-- Without them every batch scans the whole table to find the old rows.
+
+CREATE INDEX IF NOT EXISTS idx_sensor_data_timestamp ON sensor_data(timestamp);
+CREATE INDEX IF NOT EXISTS idx_sedentary_log_created_at ON sedentary_log(created_at);
@@ -15,16 +15,20 @@ import psycopg2
 import pandas as pd
 import numpy as np
 from sklearn.cluster import KMeans
-from datetime import datetime, timedelta
+from datetime import datetime, time, timedelta
 import json
 import os
 from typing import Dict, List, Tuple, Optional
+from zoneinfo import ZoneInfo
 
 # Environment variables - Database
 DB_URL = os.environ.get("DATABASE_URL")
 if not DB_URL:
     raise ValueError("DATABASE_URL environment variable must be set")
 
+# Environment variables - Day boundaries (IANA name, same TIMEZONE as the server)
+TIMEZONE = ZoneInfo(os.environ.get("TIMEZONE") or "UTC")
+
 # Environment variables - Activity Thresholds
 THRESH_FIDGET = float(os.environ.get("THRESH_FIDGET", "0.020"))
 THRESH_ACTIVE = float(os.environ.get("THRESH_ACTIVE", "0.040"))
@@ -41,6 +45,13 @@ LOINC_CODE = os.environ.get("LOINC_CODE", "87705-0")
 LOINC_DISPLAY = os.environ.get("LOINC_DISPLAY", "Sedentary activity 24 hour")
 
 
+def local_day_bounds(day, tz: ZoneInfo = TIMEZONE) -> Tuple[datetime, datetime]:
+    """Local midnight at the start of `day` and of the next day in `tz`"""
+    start = datetime.combine(day, time.min, tzinfo=tz)
+    end = datetime.combine(day + timedelta(days=1), time.min, tzinfo=tz)
+    return start, end
+
+
 class SedentaryAnalytics:
     """ML-powered sedentary behavior analytics engine"""
 
@@ -88,32 +99,33 @@ class SedentaryAnalytics:
 
         return df['user_id'].astype(str).tolist()
 
-    def load_user_data(self, user_id: Optional[str], hours: int = 24) -> pd.DataFrame:
+    def load_user_data(self, user_id: Optional[str], day) -> pd.DataFrame:
         """
         Load sensor data for a specific user or all data if user_id is None
 
         Args:
             user_id: UUID of user, or None for all data
-            hours: Number of hours to look back
+            day: Local calendar day (TIMEZONE) to load, midnight to midnight
 
         Returns:
             DataFrame with columns: created_at, acceleration_val, state, timer_seconds
         """
-        query = f"""
+        start, end = local_day_bounds(day)
+        query = """
             SELECT
                 created_at,
                 acceleration_val,
                 state,
                 timer_seconds
             FROM sedentary_log
-            WHERE created_at > NOW() - INTERVAL '{hours} HOURS'
+            WHERE created_at >= %s AND created_at < %s
             ORDER BY created_at ASC
         """
 
-        df = pd.read_sql(query, self.conn)
+        df = pd.read_sql(query, self.conn, params=(start, end))
 
         if df.empty:
-            print(f"No data found for {'user ' + user_id if user_id else 'any user'} in last {hours} hours")
+            print(f"No data found for {'user ' + user_id if user_id else 'any user'} on {day}")
         else:
             print(f"Loaded {len(df)} data points for {'user ' + user_id if user_id else 'all users'}")
 
@@ -464,7 +476,7 @@ class SedentaryAnalytics:
         """Main entry point for nightly ML analysis"""
         print("=" * 60)
         print("NIGHTLY SEDENTARY BEHAVIOR ANALYSIS")
-        print(f"   Timestamp: {datetime.now().isoformat()}")
+        print(f"   Timestamp: {datetime.now(TIMEZONE).isoformat()}")
         print(f"   LOINC Code: {LOINC_CODE} ({LOINC_DISPLAY})")
         print("=" * 60)
 
@@ -479,14 +491,15 @@ class SedentaryAnalytics:
                 # Try running for NULL user (single-user mode)
                 users = [None]
 
-            today = datetime.now().date()
+            # Summaries are dated by the local day, not the UTC one
+            today = datetime.now(TIMEZONE).date()
 
             # Process each user
             for user_id in users:
                 print(f"\n Processing {'user ' + str(user_id) if user_id else 'default user'}...")
 
                 # Load daily data
-                df = self.load_user_data(user_id, hours=24)
+                df = self.load_user_data(user_id, today)
 
                 if df.empty:
                     print(f"   ⏭  Skipping - no data")
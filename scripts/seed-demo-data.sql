@@ -5,8 +5,8 @@
 TRUNCATE sedentary_log RESTART IDENTITY;
 
 -- Create demo user (email: demo@example.com, password: password)
-INSERT INTO users (user_id, email, password_hash, name, created_at) VALUES
-('a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11', 'demo@example.com', '$argon2id$v=19$m=65536,t=3,p=4$c29tZXNhbHQ$RdescudvJCsgt3ub+b+dWRWJTmaaJObG', 'Demo User', NOW())
+INSERT INTO users (user_id, email, password_hash, name, verified, created_at) VALUES
+('a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11', 'demo@example.com', '$argon2id$v=19$m=65536,t=3,p=4$c29tZXNhbHQ$RdescudvJCsgt3ub+b+dWRWJTmaaJObG', 'Demo User', TRUE, NOW())
 ON CONFLICT (email) DO NOTHING;
 
 -- Insert demo data with varied activity patterns
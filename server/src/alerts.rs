@@ -0,0 +1,155 @@
+use crate::auth::AuthUser;
+use crate::state::AppState;
+use crate::stats::{date_start, local_date};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct AlertsQuery {
+    // Local date (TIMEZONE); defaults to today
+    date: Option<NaiveDate>,
+}
+
+/// One stored reading, as far as alert detection needs it
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertRow {
+    pub timer_seconds: i32,
+    pub alert: bool,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// A sedentary alert: the first and last alerting readings of one sedentary
+/// period, and how long the timer had run by the end of it
+#[derive(Debug, Serialize, PartialEq)]
+pub struct AlertEvent {
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    // Time the alert stayed raised
+    pub duration_seconds: i64,
+    // Longest sedentary timer seen while alerting
+    pub peak_timer_seconds: i32,
+}
+
+/// Collapses alert rows (ordered by timestamp) into one event per sedentary
+/// period. A period lasts until the timer goes back down (activity or a manual
+/// reset); fidgeting only pauses it, so the alert stays one event. Alerts are
+/// only stored once per cooldown, so an open event runs on to the last reading
+/// whose timer was still climbing, alerting or not.
+pub fn collapse_alerts(rows: &[AlertRow]) -> Vec<AlertEvent> {
+    let mut events: Vec<AlertEvent> = Vec::new();
+    let mut open = false;
+    let mut previous_timer = 0;
+
+    for row in rows {
+        if row.timer_seconds < previous_timer {
+            open = false;
+        }
+        previous_timer = row.timer_seconds;
+
+        match events.last_mut() {
+            Some(event) if open && row.timer_seconds > event.peak_timer_seconds => {
+                event.ended_at = row.timestamp;
+                event.duration_seconds = (row.timestamp - event.started_at).num_seconds();
+                event.peak_timer_seconds = row.timer_seconds;
+            }
+            Some(_) if open => {}
+            _ if row.alert => {
+                events.push(AlertEvent {
+                    started_at: row.timestamp,
+                    ended_at: row.timestamp,
+                    duration_seconds: 0,
+                    peak_timer_seconds: row.timer_seconds,
+                });
+                open = true;
+            }
+            _ => {}
+        }
+    }
+    events
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+/// Sedentary alerts raised on one local day, one entry per sedentary period
+/// Endpoint: GET /api/alerts/user/:user_id?date=YYYY-MM-DD (own data, or any user as admin)
+pub async fn get_user_alerts(
+    user: AuthUser,
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    Query(params): Query<AlertsQuery>,
+) -> Response {
+    let Ok(user_uuid) = Uuid::parse_str(&user_id) else {
+        return error(StatusCode::BAD_REQUEST, "Invalid user ID format");
+    };
+    if user.user_id != user_uuid && user.role != "admin" {
+        return error(
+            StatusCode::FORBIDDEN,
+            "Not permitted to view this user's alerts",
+        );
+    }
+
+    let tz = state.config.timezone;
+    let date = params.date.unwrap_or_else(|| local_date(tz, Utc::now()));
+    let Some(next_day) = date.checked_add_days(Days::new(1)) else {
+        return error(StatusCode::BAD_REQUEST, "Invalid date");
+    };
+
+    // Only alerting rows, timer resets and the last reading of each sitting
+    // matter, not every second of the day
+    let rows = sqlx::query_as!(
+        AlertRow,
+        r#"
+        SELECT timer_seconds AS "timer_seconds!", alert AS "alert!", timestamp AS "timestamp!"
+        FROM (
+            SELECT
+                timer_seconds,
+                alert_triggered AS alert,
+                timestamp,
+                LAG(timer_seconds) OVER (ORDER BY timestamp) AS previous_timer,
+                LEAD(timer_seconds) OVER (ORDER BY timestamp) AS next_timer
+            FROM sensor_data
+            WHERE user_id = $1 AND timestamp >= $2 AND timestamp < $3
+        ) readings
+        WHERE alert
+            OR timer_seconds < previous_timer
+            OR timer_seconds > next_timer
+            OR next_timer IS NULL
+        ORDER BY timestamp
+        "#,
+        user_uuid,
+        date_start(tz, date),
+        date_start(tz, next_day)
+    )
+    .fetch_all(&state.db)
+    .await;
+
+    match rows {
+        Ok(rows) => {
+            let alerts = collapse_alerts(&rows);
+            Json(json!({
+                "user_id": user_uuid,
+                "date": date,
+                "total": alerts.len(),
+                "alerts": alerts,
+            }))
+            .into_response()
+        }
+        Err(e) => {
+            eprintln!("Database error fetching alerts: {:?}", e);
+            error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch alerts")
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "alerts_tests.rs"]
+mod tests;
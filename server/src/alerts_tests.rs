@@ -0,0 +1,124 @@
+use super::*;
+use chrono::Duration;
+
+fn start() -> DateTime<Utc> {
+    "2026-03-10T09:00:00Z".parse().unwrap()
+}
+
+// One row per second from `start() + offset`, timers counting up from `timer`
+fn run(offset: i64, timer: i32, alert_from: i32, seconds: i32) -> Vec<AlertRow> {
+    (0..seconds)
+        .map(|i| AlertRow {
+            timer_seconds: timer + i,
+            alert: timer + i >= alert_from,
+            timestamp: start() + Duration::seconds(offset + i as i64),
+        })
+        .collect()
+}
+
+fn reset(offset: i64) -> AlertRow {
+    AlertRow {
+        timer_seconds: 0,
+        alert: false,
+        timestamp: start() + Duration::seconds(offset),
+    }
+}
+
+// Alert Collapsing Tests
+
+#[test]
+fn test_no_alert_rows_no_events() {
+    assert!(collapse_alerts(&run(0, 0, 1200, 60)).is_empty());
+    assert!(collapse_alerts(&[]).is_empty());
+}
+
+#[test]
+fn test_alert_run_collapses_to_one_event() {
+    let rows = run(0, 1195, 1200, 30);
+    let events = collapse_alerts(&rows);
+    assert_eq!(
+        events,
+        vec![AlertEvent {
+            started_at: start() + Duration::seconds(5),
+            ended_at: start() + Duration::seconds(29),
+            duration_seconds: 24,
+            peak_timer_seconds: 1224,
+        }]
+    );
+}
+
+#[test]
+fn test_reset_starts_a_new_event() {
+    let mut rows = run(0, 1200, 1200, 10);
+    rows.push(reset(10));
+    rows.extend(run(100, 1200, 1200, 5));
+
+    let events = collapse_alerts(&rows);
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].peak_timer_seconds, 1209);
+    assert_eq!(events[1].started_at, start() + Duration::seconds(100));
+    assert_eq!(events[1].duration_seconds, 4);
+}
+
+#[test]
+fn test_fidget_pause_keeps_one_event() {
+    // Fidgeting holds the timer steady; the alert is still the same sitting
+    let mut rows = run(0, 1200, 1200, 5);
+    rows.extend(run(5, 1204, 1200, 1));
+    rows.extend(run(6, 1204, 1200, 1));
+    rows.extend(run(7, 1205, 1200, 3));
+
+    let events = collapse_alerts(&rows);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].peak_timer_seconds, 1207);
+    assert_eq!(events[0].ended_at, start() + Duration::seconds(9));
+}
+
+#[test]
+fn test_filtered_rows_still_split_on_reset() {
+    // The query only returns alerting rows and resets
+    let rows = vec![
+        run(0, 1300, 1200, 1).remove(0),
+        run(1, 1301, 1200, 1).remove(0),
+        reset(50),
+        run(2000, 1200, 1200, 1).remove(0),
+    ];
+    assert_eq!(collapse_alerts(&rows).len(), 2);
+}
+
+#[test]
+fn test_cooldown_alerts_run_to_last_sedentary_reading() {
+    // Alerts stored once per cooldown; the query adds the sitting's last
+    // reading before the reset, which is not alerting itself
+    let at = |offset: i64, timer: i32, alert: bool| AlertRow {
+        timer_seconds: timer,
+        alert,
+        timestamp: start() + Duration::seconds(offset),
+    };
+    let rows = vec![
+        at(0, 1200, true),
+        at(300, 1500, true),
+        at(420, 1620, false),
+        reset(421),
+    ];
+
+    assert_eq!(
+        collapse_alerts(&rows),
+        vec![AlertEvent {
+            started_at: start(),
+            ended_at: start() + Duration::seconds(420),
+            duration_seconds: 420,
+            peak_timer_seconds: 1620,
+        }]
+    );
+}
+
+#[test]
+fn test_event_serializes_fields() {
+    let event = &collapse_alerts(&run(0, 1200, 1200, 3))[0];
+    let json = serde_json::to_value(event).unwrap();
+    assert_eq!(json["started_at"], "2026-03-10T09:00:00Z");
+    assert_eq!(json["ended_at"], "2026-03-10T09:00:02Z");
+    assert_eq!(json["duration_seconds"], 2);
+    assert_eq!(json["peak_timer_seconds"], 1202);
+}
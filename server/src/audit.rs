@@ -0,0 +1,134 @@
+use crate::{auth::AdminUser, state::AppState};
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Json},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::PgPool;
+use std::net::SocketAddr;
+use uuid::Uuid;
+
+// Audit event types written by the login handler
+pub const LOGIN_SUCCESS: &str = "login_success";
+pub const LOGIN_FAILED: &str = "login_failed";
+pub const LOGIN_RATE_LIMITED: &str = "login_rate_limited";
+pub const LOGIN_UNVERIFIED: &str = "login_unverified";
+
+/// Resolves the client IP: first `X-Forwarded-For` entry, else the socket address
+pub fn client_ip(headers: &HeaderMap, addr: SocketAddr) -> String {
+    headers
+        .get("X-Forwarded-For")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(',').next())
+        .map(|ip| ip.trim().to_string())
+        .filter(|ip| !ip.is_empty())
+        .unwrap_or_else(|| addr.ip().to_string())
+}
+
+/// Writes one authentication event to `audit_log`.
+/// Failures are logged rather than returned so auditing never blocks a login.
+pub async fn record_auth_event(
+    pool: &PgPool,
+    user_id: Option<Uuid>,
+    email: &str,
+    event_type: &str,
+    ip_address: &str,
+    success: bool,
+) {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO audit_log (user_id, email, event_type, ip_address, success)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        user_id,
+        email,
+        event_type,
+        ip_address,
+        success
+    )
+    .execute(pool)
+    .await;
+
+    if let Err(e) = result {
+        eprintln!("DB Error (audit_log): {}", e);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    #[serde(default = "default_limit")]
+    limit: i64,
+}
+
+fn default_limit() -> i64 {
+    100
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditEvent {
+    id: i32,
+    user_id: Option<Uuid>,
+    email: String,
+    event_type: String,
+    ip_address: Option<String>,
+    success: bool,
+    created_at: DateTime<Utc>,
+}
+
+/// Most recent authentication events, newest first
+/// Endpoint: GET /auth/audit?limit=N (admin only)
+pub async fn get_audit_log(
+    _admin: AdminUser,
+    State(state): State<AppState>,
+    Query(params): Query<AuditQuery>,
+) -> impl IntoResponse {
+    let limit = params.limit.clamp(1, 1000);
+
+    let result = sqlx::query!(
+        r#"
+        SELECT id, user_id, email, event_type, ip_address, success, created_at
+        FROM audit_log
+        ORDER BY created_at DESC
+        LIMIT $1
+        "#,
+        limit
+    )
+    .fetch_all(&state.db)
+    .await;
+
+    match result {
+        Ok(rows) => {
+            let events: Vec<AuditEvent> = rows
+                .into_iter()
+                .map(|row| AuditEvent {
+                    id: row.id,
+                    user_id: row.user_id,
+                    email: row.email,
+                    event_type: row.event_type,
+                    ip_address: row.ip_address,
+                    success: row.success,
+                    created_at: row.created_at,
+                })
+                .collect();
+
+            (StatusCode::OK, Json(events)).into_response()
+        }
+        Err(e) => {
+            eprintln!("Database error: {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({
+                    "error": "Failed to fetch audit log"
+                })),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "audit_tests.rs"]
+mod tests;
@@ -0,0 +1,37 @@
+use super::*;
+
+fn socket() -> SocketAddr {
+    "10.0.0.5:54321".parse().unwrap()
+}
+
+// Client IP Resolution Tests
+
+#[test]
+fn test_client_ip_falls_back_to_socket() {
+    let headers = HeaderMap::new();
+    assert_eq!(client_ip(&headers, socket()), "10.0.0.5");
+}
+
+#[test]
+fn test_client_ip_uses_forwarded_for() {
+    let mut headers = HeaderMap::new();
+    headers.insert("X-Forwarded-For", "203.0.113.7".parse().unwrap());
+    assert_eq!(client_ip(&headers, socket()), "203.0.113.7");
+}
+
+#[test]
+fn test_client_ip_takes_first_forwarded_hop() {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "X-Forwarded-For",
+        "203.0.113.7, 198.51.100.2, 10.0.0.1".parse().unwrap(),
+    );
+    assert_eq!(client_ip(&headers, socket()), "203.0.113.7");
+}
+
+#[test]
+fn test_client_ip_ignores_empty_forwarded_for() {
+    let mut headers = HeaderMap::new();
+    headers.insert("X-Forwarded-For", "".parse().unwrap());
+    assert_eq!(client_ip(&headers, socket()), "10.0.0.5");
+}
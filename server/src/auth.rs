@@ -1,18 +1,53 @@
+use crate::config::{AuthConfig, Config};
+use crate::state::AppState;
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
+    Argon2, Params, Version,
+};
 use axum::{
     async_trait,
-    extract::FromRequestParts,
+    extract::{FromRef, FromRequestParts, Query},
     http::{request::Parts, StatusCode},
     response::{IntoResponse, Response},
 };
+use chrono::{DateTime, Duration, Utc};
 use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rand::{distributions::Alphanumeric, Rng};
+use redis::AsyncCommands;
 use serde::{Deserialize, Serialize};
-use std::env;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
 use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Signing algorithm plus the matching key pair used for access tokens
+pub struct JwtKeys {
+    pub algorithm: Algorithm,
+    pub encoding: EncodingKey,
+    pub decoding: DecodingKey,
+}
 
-fn jwt_secret() -> Vec<u8> {
-    env::var("JWT_SECRET")
-        .expect("JWT_SECRET environment variable must be set!")
-        .into_bytes()
+impl JwtKeys {
+    /// Shared-secret (HS256) keys
+    pub fn hs256(secret: &[u8]) -> Self {
+        Self {
+            algorithm: Algorithm::HS256,
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+        }
+    }
+
+    /// Public/private key (RS256) keys from PEM-encoded RSA keys
+    pub fn rs256(
+        private_pem: &[u8],
+        public_pem: &[u8],
+    ) -> Result<Self, jsonwebtoken::errors::Error> {
+        Ok(Self {
+            algorithm: Algorithm::RS256,
+            encoding: EncodingKey::from_rsa_pem(private_pem)?,
+            decoding: DecodingKey::from_rsa_pem(public_pem)?,
+        })
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -20,33 +55,203 @@ pub struct Claims {
     pub sub: String,
     pub name: String,
     pub exp: usize,
+    pub jti: String,
+    pub role: String,
+}
+
+/// A signed access token together with its expiry (seconds since the epoch)
+pub struct AccessToken {
+    pub token: String,
+    pub exp: usize,
 }
 
-pub fn create_jwt(user_id: &str, name: &str) -> Result<String, jsonwebtoken::errors::Error> {
+/// Body of a successful `/login` or `/auth/refresh`
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct LoginResponse {
+    pub token: String,
+    pub token_type: String,
+    // Access token expiry, as seconds since the epoch and as a timestamp
+    pub exp: usize,
+    pub expires_at: DateTime<Utc>,
+    pub refresh_token: String,
+    pub user_id: Uuid,
+}
+
+impl LoginResponse {
+    pub fn new(access: AccessToken, refresh_token: String, user_id: Uuid) -> Self {
+        Self {
+            expires_at: DateTime::from_timestamp(access.exp as i64, 0).unwrap_or_default(),
+            exp: access.exp,
+            token: access.token,
+            token_type: "Bearer".to_string(),
+            refresh_token,
+            user_id,
+        }
+    }
+}
+
+/// Signs an access token with the configured keys, valid for JWT_EXPIRY_SECONDS
+pub fn create_jwt(
+    config: &Config,
+    user_id: &str,
+    name: &str,
+    role: &str,
+) -> Result<AccessToken, jsonwebtoken::errors::Error> {
     let expiration = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
         .as_secs() as usize
-        + 3600; // 1 hour
+        + config.auth.jwt_expiry_seconds;
 
     let claims = Claims {
         sub: user_id.to_owned(),
         name: name.to_owned(),
         exp: expiration,
+        jti: Uuid::new_v4().to_string(),
+        role: role.to_owned(),
     };
 
-    encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(&jwt_secret()),
+    Ok(AccessToken {
+        token: encode_claims(&claims, &config.jwt_keys)?,
+        exp: expiration,
+    })
+}
+
+fn encode_claims(claims: &Claims, keys: &JwtKeys) -> Result<String, jsonwebtoken::errors::Error> {
+    encode(&Header::new(keys.algorithm), claims, &keys.encoding)
+}
+
+/// Validates a token against the configured algorithm only, so a token signed
+/// with any other algorithm is rejected even if its signature would verify
+fn decode_claims(token: &str, keys: &JwtKeys) -> Result<Claims, jsonwebtoken::errors::Error> {
+    decode::<Claims>(token, &keys.decoding, &Validation::new(keys.algorithm))
+        .map(|data| data.claims)
+}
+
+/// Generates a random 64-character alphanumeric token (refresh, reset, ...)
+pub fn generate_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(64)
+        .map(char::from)
+        .collect()
+}
+
+/// Argon2id cost parameters from ARGON2_MEMORY_KIB / ARGON2_ITERATIONS /
+/// ARGON2_PARALLELISM (the crate defaults unless set)
+pub fn argon2_params(auth: &AuthConfig) -> Result<Params, argon2::Error> {
+    Params::new(
+        auth.argon2_memory_kib,
+        auth.argon2_iterations,
+        auth.argon2_parallelism,
+        None,
     )
 }
 
+/// The Argon2id hasher every password hash is made with
+pub fn argon2_config(auth: &AuthConfig) -> Argon2<'static> {
+    let params = argon2_params(auth).expect("Argon2 parameters are validated at startup");
+    Argon2::new(argon2::Algorithm::Argon2id, Version::V0x13, params)
+}
+
+/// Hashes a password with Argon2id and a fresh salt (PHC string format)
+pub fn hash_password(
+    password: &str,
+    auth: &AuthConfig,
+) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    argon2_config(auth)
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+}
+
+/// A real hash of a random password, made with the configured parameters.
+/// Login verifies against it for unknown emails, so they cost as much as a
+/// wrong password for a real account even after the cost settings change.
+pub fn dummy_password_hash(auth: &AuthConfig) -> String {
+    hash_password(&generate_token(), auth).expect("Argon2 hashing a random token succeeds")
+}
+
+/// Hashes a refresh token for storage (only the SHA-256 digest is kept in the DB)
+pub fn hash_refresh_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Issues a refresh token valid for `ttl_days` and stores its hash in `refresh_tokens`
+pub async fn create_refresh_token(
+    pool: &PgPool,
+    user_id: Uuid,
+    ttl_days: i64,
+) -> Result<String, sqlx::Error> {
+    let token = generate_token();
+    let expires_at = Utc::now() + Duration::days(ttl_days);
+
+    sqlx::query!(
+        r#"
+        INSERT INTO refresh_tokens (user_id, token_hash, expires_at)
+        VALUES ($1, $2, $3)
+        "#,
+        user_id,
+        hash_refresh_token(&token),
+        expires_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(token)
+}
+
+/// Revokes every outstanding refresh token for a user (logout everywhere, password reset)
+pub async fn revoke_refresh_tokens(pool: &PgPool, user_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE refresh_tokens
+        SET revoked_at = NOW()
+        WHERE user_id = $1 AND revoked_at IS NULL
+        "#,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Revokes one of the user's refresh tokens (logging out a single session);
+/// false if it wasn't theirs or was already revoked
+pub async fn revoke_refresh_token(
+    pool: &PgPool,
+    user_id: Uuid,
+    token: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE refresh_tokens
+        SET revoked_at = NOW()
+        WHERE user_id = $1 AND token_hash = $2 AND revoked_at IS NULL
+        "#,
+        user_id,
+        hash_refresh_token(token)
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Redis key marking an access token (by `jti`) as revoked
+pub fn revoked_key(jti: &str) -> String {
+    format!("revoked:{}", jti)
+}
+
 #[derive(Debug)]
 pub struct AuthUser {
-    pub user_id: String,
+    pub user_id: Uuid,
     #[allow(dead_code)]
     pub name: String,
+    pub jti: String,
+    pub exp: usize,
+    pub role: String,
 }
 
 /// Custom rejection
@@ -71,11 +276,12 @@ impl IntoResponse for AuthError {
 #[async_trait]
 impl<S> FromRequestParts<S> for AuthUser
 where
+    AppState: FromRef<S>,
     S: Send + Sync,
 {
     type Rejection = AuthError;
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         let auth_header = parts
             .headers
             .get("Authorization")
@@ -90,19 +296,149 @@ where
             }
         };
 
-        let token = &header[7..];
-        let token_data = decode::<Claims>(
-            token,
-            &DecodingKey::from_secret(&jwt_secret()),
-            &Validation::new(Algorithm::HS256),
-        )
-        .map_err(|_| AuthError {
-            message: "Invalid token",
-        })?;
+        authenticate(&header[7..], &AppState::from_ref(state)).await
+    }
+}
 
-        Ok(AuthUser {
-            user_id: token_data.claims.sub,
-            name: token_data.claims.name.clone(),
-        })
+/// Validates an access token: signature and expiry, then the logout denylist
+pub async fn authenticate(token: &str, state: &AppState) -> Result<AuthUser, AuthError> {
+    let claims = decode_claims(token, &state.config.jwt_keys).map_err(|_| AuthError {
+        message: "Invalid token",
+    })?;
+
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| AuthError {
+        message: "Malformed subject",
+    })?;
+
+    // Check the logout denylist
+    let revoked: redis::RedisResult<bool> =
+        match state.redis.get_multiplexed_async_connection().await {
+            Ok(mut con) => con.exists(revoked_key(&claims.jti)).await,
+            Err(e) => Err(e),
+        };
+
+    match revoked {
+        Ok(true) => {
+            return Err(AuthError {
+                message: "Token revoked",
+            })
+        }
+        Ok(false) => {}
+        Err(_) if state.config.auth.strict_revocation => {
+            return Err(AuthError {
+                message: "Unable to verify token",
+            })
+        }
+        Err(e) => eprintln!("Redis unavailable for revocation check: {e:?}"),
+    }
+
+    Ok(AuthUser {
+        user_id,
+        name: claims.name,
+        jti: claims.jti,
+        exp: claims.exp,
+        role: claims.role,
+    })
+}
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+/// The bearer token of a stream request: the Authorization header, else
+/// `?token=` because browsers' EventSource can't set headers
+pub fn stream_token(parts: &Parts) -> Option<String> {
+    let header = parts
+        .headers
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+    if let Some(token) = header {
+        return Some(token.to_string());
+    }
+    Query::<TokenQuery>::try_from_uri(&parts.uri)
+        .ok()
+        .and_then(|Query(query)| query.token)
+        .filter(|token| !token.is_empty())
+}
+
+/// Subscriber of `/events` or `/ws`. Streams are open by default and a valid
+/// token only scopes them to the user's own readings; a token that is sent but
+/// invalid (expired, revoked, forged) is always a 401, never an anonymous
+/// stream. With STREAM_AUTH_REQUIRED a missing token is a 401 too.
+pub struct StreamUser(pub Option<AuthUser>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for StreamUser
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let state = AppState::from_ref(state);
+        match stream_token(parts) {
+            Some(token) => authenticate(&token, &state)
+                .await
+                .map(|user| StreamUser(Some(user))),
+            None if state.config.auth.stream_auth_required => Err(AuthError {
+                message: "Missing Authorization header or token parameter",
+            }),
+            None => Ok(StreamUser(None)),
+        }
+    }
+}
+
+/// An authenticated user whose token carries the `admin` role
+#[derive(Debug)]
+pub struct AdminUser(#[allow(dead_code)] pub AuthUser);
+
+/// Rejection for admin-only routes: 401 when not authenticated, 403 when not an admin
+pub enum AdminError {
+    Unauthenticated(AuthError),
+    Forbidden,
+}
+
+impl IntoResponse for AdminError {
+    fn into_response(self) -> Response {
+        match self {
+            AdminError::Unauthenticated(err) => err.into_response(),
+            AdminError::Forbidden => (
+                StatusCode::FORBIDDEN,
+                [(
+                    "WWW-Authenticate",
+                    r#"Bearer realm="Sedentary Tracker", error="insufficient_scope""#,
+                )],
+                "Admin access required",
+            )
+                .into_response(),
+        }
     }
 }
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AdminUser
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AdminError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = AuthUser::from_request_parts(parts, state)
+            .await
+            .map_err(AdminError::Unauthenticated)?;
+
+        if user.role != "admin" {
+            return Err(AdminError::Forbidden);
+        }
+
+        Ok(AdminUser(user))
+    }
+}
+
+#[cfg(test)]
+#[path = "auth_tests.rs"]
+mod tests;
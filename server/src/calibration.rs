@@ -0,0 +1,206 @@
+use crate::{
+    auth::AdminUser, metrics::Subscriber, models::ProcessedState, serial::Thresholds,
+    state::AppState,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{timeout_at, Instant};
+
+// Calibration window bounds (seconds)
+const DEFAULT_CALIBRATION_SECONDS: u64 = 30;
+const MAX_CALIBRATION_SECONDS: u64 = 300;
+
+// Keeps a silent sensor from producing a zero fidget threshold
+const MIN_THRESHOLD: f32 = 0.001;
+
+#[derive(Debug, Deserialize)]
+pub struct CalibrateQuery {
+    #[serde(default = "default_seconds")]
+    seconds: u64,
+    #[serde(default)]
+    apply: bool,
+}
+
+fn default_seconds() -> u64 {
+    DEFAULT_CALIBRATION_SECONDS
+}
+
+/// Nearest-rank percentile of an ascending slice
+fn percentile(sorted: &[f32], p: f32) -> f32 {
+    let rank = (p * sorted.len() as f32 / 100.0).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Suggests thresholds from smoothed acceleration recorded while the wearer
+/// mostly sits with some normal movement: the median becomes the fidget
+/// threshold and the 90th percentile the active threshold.
+pub fn suggest_thresholds(values: &[f32]) -> Option<Thresholds> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+
+    let fidget = percentile(&sorted, 50.0).max(MIN_THRESHOLD);
+    // Without movement in the sample, keep active clear of fidget
+    let p90 = percentile(&sorted, 90.0);
+    let active = if p90 > fidget { p90 } else { fidget * 2.0 };
+    Some(Thresholds { fidget, active })
+}
+
+/// Loads thresholds saved by a previous calibration or update, if any
+pub async fn load_thresholds(pool: &PgPool) -> Option<Thresholds> {
+    let result = sqlx::query!(
+        r#"SELECT thresh_fidget, thresh_active FROM classification_thresholds WHERE id = 1"#
+    )
+    .fetch_optional(pool)
+    .await;
+
+    match result {
+        Ok(row) => row.map(|r| Thresholds {
+            fidget: r.thresh_fidget,
+            active: r.thresh_active,
+        }),
+        Err(e) => {
+            eprintln!("DB Error (classification_thresholds): {}", e);
+            None
+        }
+    }
+}
+
+pub async fn save_thresholds(pool: &PgPool, thresholds: Thresholds) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO classification_thresholds (id, thresh_fidget, thresh_active, updated_at)
+        VALUES (1, $1, $2, NOW())
+        ON CONFLICT (id) DO UPDATE
+        SET thresh_fidget = EXCLUDED.thresh_fidget,
+            thresh_active = EXCLUDED.thresh_active,
+            updated_at = NOW()
+        "#,
+        thresholds.fidget,
+        thresholds.active
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Replaces the live thresholds; the next reading is classified with the new values.
+/// Saved so they survive a restart.
+/// Endpoint: PUT /api/config/thresholds (admin only)
+pub async fn update_thresholds(
+    _admin: AdminUser,
+    State(state): State<AppState>,
+    Json(thresholds): Json<Thresholds>,
+) -> Response {
+    if let Err(message) = thresholds.validate() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": message
+            })),
+        )
+            .into_response();
+    }
+
+    if let Err(e) = save_thresholds(&state.db, thresholds).await {
+        eprintln!("Failed to save thresholds: {e:?}");
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "error": "Failed to save thresholds"
+            })),
+        )
+            .into_response();
+    }
+    state.thresholds.set(thresholds);
+    println!(
+        "Thresholds updated (fidget: {}, active: {})",
+        thresholds.fidget, thresholds.active
+    );
+
+    (StatusCode::OK, Json(thresholds)).into_response()
+}
+
+/// Records live readings for `?seconds=N` (default 30, max 300) and suggests thresholds.
+/// With `?apply=true` the suggestion is saved and used by the classifier immediately.
+/// Endpoint: POST /api/calibrate (admin only)
+pub async fn calibrate(
+    _admin: AdminUser,
+    State(state): State<AppState>,
+    Query(params): Query<CalibrateQuery>,
+) -> Response {
+    let seconds = params.seconds.clamp(1, MAX_CALIBRATION_SECONDS);
+    let mut rx = state.tx.subscribe();
+    let deadline = Instant::now() + Duration::from_secs(seconds);
+
+    let mut values = Vec::new();
+    while let Ok(msg) = timeout_at(deadline, rx.recv()).await {
+        let msg = match msg {
+            Ok(msg) => msg,
+            // A few skipped samples don't matter for percentiles; keep recording
+            Err(RecvError::Lagged(skipped)) => {
+                state
+                    .metrics
+                    .record_lagged(Subscriber::Calibration, skipped);
+                continue;
+            }
+            Err(RecvError::Closed) => break,
+        };
+        if let Ok(reading) = serde_json::from_str::<ProcessedState>(&msg) {
+            values.push(reading.val);
+        }
+    }
+
+    let Some(suggested) = suggest_thresholds(&values) else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({
+                "error": "No readings received during calibration"
+            })),
+        )
+            .into_response();
+    };
+
+    if params.apply {
+        if let Err(e) = save_thresholds(&state.db, suggested).await {
+            eprintln!("Failed to save thresholds: {e:?}");
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({
+                    "error": "Failed to save thresholds"
+                })),
+            )
+                .into_response();
+        }
+        state.thresholds.set(suggested);
+        println!(
+            "Calibrated thresholds applied (fidget: {}, active: {})",
+            suggested.fidget, suggested.active
+        );
+    }
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "samples": values.len(),
+            "duration_seconds": seconds,
+            "suggested": suggested,
+            "applied": params.apply
+        })),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+#[path = "calibration_tests.rs"]
+mod tests;
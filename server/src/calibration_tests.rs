@@ -0,0 +1,41 @@
+use super::*;
+
+// Threshold Suggestion Tests
+
+#[test]
+fn test_suggest_thresholds_empty() {
+    assert_eq!(suggest_thresholds(&[]), None);
+}
+
+#[test]
+fn test_suggest_thresholds_uses_median_and_p90() {
+    let values: Vec<f32> = (1..=10).map(|i| i as f32 / 100.0).collect();
+    let suggested = suggest_thresholds(&values).unwrap();
+
+    assert!((suggested.fidget - 0.05).abs() < 1e-6);
+    assert!((suggested.active - 0.09).abs() < 1e-6);
+}
+
+#[test]
+fn test_suggest_thresholds_ignores_input_order() {
+    let values = [0.09, 0.01, 0.05, 0.03, 0.07, 0.02, 0.10, 0.04, 0.08, 0.06];
+    let suggested = suggest_thresholds(&values).unwrap();
+
+    assert!((suggested.fidget - 0.05).abs() < 1e-6);
+}
+
+#[test]
+fn test_suggest_thresholds_without_movement_keeps_gap() {
+    let suggested = suggest_thresholds(&[0.01; 20]).unwrap();
+
+    assert!((suggested.fidget - 0.01).abs() < 1e-6);
+    assert!((suggested.active - 0.02).abs() < 1e-6);
+}
+
+#[test]
+fn test_suggest_thresholds_silent_sensor_has_floor() {
+    let suggested = suggest_thresholds(&[0.0; 5]).unwrap();
+
+    assert_eq!(suggested.fidget, MIN_THRESHOLD);
+    assert!(suggested.active > suggested.fidget);
+}
@@ -0,0 +1,917 @@
+use crate::auth::{argon2_params, JwtKeys};
+use crate::cors::{parse_allowed_origins, AllowedOrigins};
+use crate::daily_totals::parse_timezone;
+use crate::fallback::{parse_fallback_source, FallbackSource};
+use crate::security_headers::{parse_frame_options, parse_header_setting, SecurityHeaders};
+use crate::serial::{
+    parse_baud_rates, parse_device_user_map, parse_serial_format, parse_serial_ports,
+    parse_smoothing_mode, parse_smoothing_window, parse_timer_source, FusionWeights, SerialFormat,
+    SmoothingMode, Thresholds, TimerSource, DEFAULT_BAUD_CANDIDATES, DEFAULT_HYSTERESIS,
+    DEFAULT_SMOOTHING_WINDOW,
+};
+use crate::simulation::{parse_activity_profile, ActivityProfile, DEFAULT_SIMULATION_PROFILE};
+use argon2::Params;
+use chrono_tz::Tz;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Every setting the server reads from the environment, parsed and validated
+/// once at startup. Each section's `Default` holds the documented defaults.
+pub struct Config {
+    pub database_url: String,
+    pub redis_url: String,
+    pub jwt_keys: JwtKeys,
+    // Owner for readings that don't carry one (DB worker, sensor_data fallback)
+    pub default_user_id: Option<Uuid>,
+    // Timezone whose midnight starts a new day
+    pub timezone: Tz,
+    pub server: ServerConfig,
+    pub serial: SerialConfig,
+    pub db_worker: DbWorkerConfig,
+    pub fallback: FallbackConfig,
+    pub replay: ReplayConfig,
+    pub simulation: SimulationConfig,
+    pub retention: RetentionConfig,
+    pub auth: AuthConfig,
+    pub fhir: FhirConfig,
+    pub score: ScoreConfig,
+}
+
+pub struct ServerConfig {
+    pub address: SocketAddr,
+    pub frontend_dir: String,
+    pub compression: bool,
+    pub allowed_origins: AllowedOrigins,
+    pub security_headers: SecurityHeaders,
+    pub shutdown_grace: Duration,
+    pub health_check_timeout: Duration,
+    pub max_stream_connections: usize,
+    // WebSocket ping cadence, and how long a ping may go unanswered before the socket is closed
+    pub ws_ping_interval: Duration,
+    pub ws_pong_timeout: Duration,
+    // SSE keepalive cadence and text; sent as a named `keepalive` event
+    // (data = the text) instead of a comment when `sse_keepalive_event` is set
+    pub sse_keepalive: Duration,
+    pub sse_keepalive_text: String,
+    pub sse_keepalive_event: bool,
+    // Messages buffered per broadcast channel before slow receivers lag
+    pub broadcast_capacity: usize,
+    // Readings kept in each Redis history list
+    pub history_limit: isize,
+    pub skip_history: bool,
+    // Analytics queries slower than this are logged at warn
+    pub slow_query: Duration,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            address: SocketAddr::from(([0, 0, 0, 0], 8000)),
+            frontend_dir: concat!(env!("CARGO_MANIFEST_DIR"), "/../frontend").to_string(),
+            compression: false,
+            allowed_origins: AllowedOrigins::SameOrigin,
+            security_headers: SecurityHeaders::default(),
+            shutdown_grace: Duration::from_secs(10),
+            health_check_timeout: Duration::from_millis(2000),
+            max_stream_connections: 500,
+            ws_ping_interval: Duration::from_secs(15),
+            ws_pong_timeout: Duration::from_secs(10),
+            sse_keepalive: Duration::from_secs(15),
+            sse_keepalive_text: "keepalive".to_string(),
+            sse_keepalive_event: false,
+            broadcast_capacity: 100,
+            history_limit: 500,
+            skip_history: false,
+            slow_query: Duration::from_millis(500),
+        }
+    }
+}
+
+pub struct SerialConfig {
+    pub ports: Vec<String>,
+    pub baud_rate: u32,
+    // Try BAUD_CANDIDATES when BAUD_RATE yields no readings within the timeout
+    pub baud_auto_detect: bool,
+    pub baud_candidates: Vec<u32>,
+    pub baud_detect_timeout: Duration,
+    pub device_user_map: HashMap<String, Uuid>,
+    pub thresholds: Thresholds,
+    pub hysteresis: f32,
+    pub alert_limit_sec: u64,
+    // Sedentary seconds between repeated alerts in one sitting (0 alerts on every reading)
+    pub alert_cooldown_sec: u64,
+    pub smoothing_window: usize,
+    pub smoothing_mode: SmoothingMode,
+    pub pir_debounce_samples: u32,
+    pub malformed_warn_ratio: f64,
+    pub reconnect_max: Duration,
+    // Largest plausible acceleration delta; readings above it are rejected
+    pub max_acc: f32,
+    pub timer_source: TimerSource,
+    // Longest gap between readings credited to the sedentary timer (elapsed source)
+    pub timer_max_gap: Duration,
+    pub format: SerialFormat,
+    pub fusion: FusionWeights,
+}
+
+impl Default for SerialConfig {
+    fn default() -> Self {
+        Self {
+            ports: Vec::new(),
+            baud_rate: 9600,
+            baud_auto_detect: false,
+            baud_candidates: DEFAULT_BAUD_CANDIDATES.to_vec(),
+            baud_detect_timeout: Duration::from_secs(5),
+            device_user_map: HashMap::new(),
+            thresholds: Thresholds {
+                fidget: 0.020,
+                active: 0.040,
+            },
+            hysteresis: DEFAULT_HYSTERESIS,
+            alert_limit_sec: 1200,
+            alert_cooldown_sec: 300,
+            smoothing_window: DEFAULT_SMOOTHING_WINDOW,
+            smoothing_mode: SmoothingMode::Mean,
+            pir_debounce_samples: 1,
+            malformed_warn_ratio: 0.2,
+            reconnect_max: Duration::from_secs(30),
+            max_acc: 16.0,
+            timer_source: TimerSource::Elapsed,
+            timer_max_gap: Duration::from_secs(10),
+            format: SerialFormat::Json,
+            fusion: FusionWeights::default(),
+        }
+    }
+}
+
+pub struct DbWorkerConfig {
+    pub batch_size: usize,
+    pub batch_interval: Duration,
+}
+
+impl Default for DbWorkerConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 100,
+            batch_interval: Duration::from_millis(500),
+        }
+    }
+}
+
+pub struct FallbackConfig {
+    pub enabled: bool,
+    pub timeout_seconds: u64,
+    pub batch_size: i64,
+    pub replay_interval_ms: u64,
+    pub source: FallbackSource,
+    pub synthetic: bool,
+}
+
+impl Default for FallbackConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            timeout_seconds: 10,
+            batch_size: 500,
+            replay_interval_ms: 100,
+            source: FallbackSource::SedentaryLog,
+            synthetic: false,
+        }
+    }
+}
+
+pub struct ReplayConfig {
+    pub log_path: String,
+    pub speed_ms: u64,
+    pub max_gap_ms: u64,
+    pub max_upload_bytes: usize,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        Self {
+            log_path: "arduino_data.log".to_string(),
+            speed_ms: 50, // 50ms between readings for ~20x speed
+            max_gap_ms: 5000,
+            max_upload_bytes: 10 * 1024 * 1024,
+        }
+    }
+}
+
+impl ReplayConfig {
+    /// Request body cap for uploads: the file limit plus room for multipart headers,
+    /// so an oversized file is reported by the size check rather than cut off mid-stream
+    pub fn upload_body_limit(&self) -> usize {
+        self.max_upload_bytes.saturating_add(64 * 1024)
+    }
+}
+
+/// Synthetic device readings instead of serial hardware (SIMULATION_MODE)
+pub struct SimulationConfig {
+    pub enabled: bool,
+    pub profile: ActivityProfile,
+    // Time between simulated readings
+    pub tick: Duration,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            profile: parse_activity_profile(DEFAULT_SIMULATION_PROFILE)
+                .expect("default simulation profile is valid"),
+            tick: Duration::from_millis(1000),
+        }
+    }
+}
+
+/// Pruning of raw readings (sedentary_log, sensor_data)
+pub struct RetentionConfig {
+    // Whole local days of readings kept; 0 disables pruning
+    pub days: u32,
+    // Rows deleted per statement, per table
+    pub batch_size: i64,
+    pub interval: Duration,
+    // Summarize days missing from activity_summary before deleting their readings
+    pub summarize: bool,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            days: 90,
+            batch_size: 5000,
+            interval: Duration::from_secs(24 * 3600),
+            summarize: true,
+        }
+    }
+}
+
+pub struct AuthConfig {
+    pub jwt_expiry_seconds: usize,
+    // When true, tokens are rejected if the revocation denylist can't be checked
+    pub strict_revocation: bool,
+    pub refresh_token_ttl_days: i64,
+    pub login_max_attempts: i32,
+    pub login_window_seconds: i64,
+    // Progressive lockout is only enabled when LOGIN_LOCKOUT_SECONDS is set
+    pub login_lockout_seconds: Option<u64>,
+    pub password_min_length: usize,
+    // Enables POST /api/admin/seed-users; keep off in production
+    pub seeding_enabled: bool,
+    // `/events` and `/ws` reject requests without a valid token
+    pub stream_auth_required: bool,
+    // Prints password reset tokens to stdout; development only
+    pub reset_log_tokens: bool,
+    // Argon2id cost for new password hashes (and the login dummy hash)
+    pub argon2_memory_kib: u32,
+    pub argon2_iterations: u32,
+    pub argon2_parallelism: u32,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            jwt_expiry_seconds: 3600,
+            strict_revocation: false,
+            refresh_token_ttl_days: 30,
+            login_max_attempts: 5,
+            login_window_seconds: 60,
+            login_lockout_seconds: None,
+            password_min_length: 8,
+            seeding_enabled: false,
+            stream_auth_required: false,
+            reset_log_tokens: false,
+            argon2_memory_kib: Params::DEFAULT_M_COST,
+            argon2_iterations: Params::DEFAULT_T_COST,
+            argon2_parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+pub struct FhirConfig {
+    pub loinc_code: String,
+    pub loinc_display: String,
+    pub loinc_system: String,
+    // Unit system for Observation quantities
+    pub unit_system: String,
+    // Prefix for Bundle paging links, e.g. https://tracker.example.org (relative links when empty)
+    pub base_url: String,
+}
+
+impl Default for FhirConfig {
+    fn default() -> Self {
+        Self {
+            loinc_code: "87705-0".to_string(),
+            loinc_display: "Sedentary activity 24 hour".to_string(),
+            loinc_system: "http://loinc.org".to_string(),
+            unit_system: "http://unitsofmeasure.org".to_string(),
+            base_url: String::new(),
+        }
+    }
+}
+
+/// Weights of the daily activity score (see `daily_summary::compute_activity_score`)
+pub struct ScoreConfig {
+    // Share of a fidget minute that counts as movement (0-1)
+    pub fidget_weight: f64,
+    // Fraction of the day spent moving that earns the full 100 points
+    pub target_movement_share: f64,
+    // Points deducted per sedentary alert
+    pub alert_penalty: f64,
+    // Points deducted per hour of the longest unbroken sedentary period
+    pub sedentary_hour_penalty: f64,
+}
+
+impl Default for ScoreConfig {
+    fn default() -> Self {
+        Self {
+            fidget_weight: 0.5,
+            target_movement_share: 0.5,
+            alert_penalty: 5.0,
+            sedentary_hour_penalty: 10.0,
+        }
+    }
+}
+
+/// Every problem found while loading, reported together
+#[derive(Debug)]
+pub struct ConfigError(pub Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for problem in &self.0 {
+            writeln!(f, "  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads variables through `lookup`, recording a problem instead of silently
+/// falling back when a value is set but unusable. Empty values count as unset.
+struct Loader<F> {
+    lookup: F,
+    errors: Vec<String>,
+}
+
+impl<F: Fn(&str) -> Option<String>> Loader<F> {
+    fn raw(&self, key: &str) -> Option<String> {
+        (self.lookup)(key)
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    }
+
+    fn string(&self, key: &str, default: String) -> String {
+        self.raw(key).unwrap_or(default)
+    }
+
+    fn required(&mut self, key: &str) -> String {
+        self.raw(key).unwrap_or_else(|| {
+            self.errors.push(format!("{} must be set", key));
+            String::new()
+        })
+    }
+
+    fn with<T>(
+        &mut self,
+        key: &str,
+        default: T,
+        parse: impl FnOnce(&str) -> Result<T, String>,
+    ) -> T {
+        match self.raw(key) {
+            None => default,
+            Some(raw) => parse(&raw).unwrap_or_else(|e| {
+                self.errors.push(format!("{}: {}", key, e));
+                default
+            }),
+        }
+    }
+
+    fn parse<T: FromStr>(&mut self, key: &str, default: T) -> T {
+        self.with(key, default, |raw| {
+            raw.parse().map_err(|_| format!("invalid value '{}'", raw))
+        })
+    }
+
+    fn optional<T: FromStr>(&mut self, key: &str) -> Option<T> {
+        self.with(key, None, |raw| {
+            raw.parse()
+                .map(Some)
+                .map_err(|_| format!("invalid value '{}'", raw))
+        })
+    }
+
+    fn flag(&mut self, key: &str, default: bool) -> bool {
+        self.with(key, default, |raw| {
+            match raw.to_ascii_lowercase().as_str() {
+                "true" => Ok(true),
+                "false" => Ok(false),
+                _ => Err(format!("'{}' must be true or false", raw)),
+            }
+        })
+    }
+
+    fn check(&mut self, ok: bool, problem: impl Into<String>) {
+        if !ok {
+            self.errors.push(problem.into());
+        }
+    }
+}
+
+/// JWT_ALGORITHM selects HS256 (JWT_SECRET) or RS256 (JWT_PRIVATE_KEY_PEM / JWT_PUBLIC_KEY_PEM)
+fn load_jwt_keys<F: Fn(&str) -> Option<String>>(env: &mut Loader<F>) -> JwtKeys {
+    let placeholder = || JwtKeys::hs256(b"");
+    match env.string("JWT_ALGORITHM", "HS256".to_string()).as_str() {
+        "HS256" => JwtKeys::hs256(env.required("JWT_SECRET").as_bytes()),
+        "RS256" => {
+            let private_pem = env.required("JWT_PRIVATE_KEY_PEM");
+            let public_pem = env.required("JWT_PUBLIC_KEY_PEM");
+            if private_pem.is_empty() || public_pem.is_empty() {
+                return placeholder();
+            }
+            JwtKeys::rs256(private_pem.as_bytes(), public_pem.as_bytes()).unwrap_or_else(|_| {
+                env.errors.push(
+                    "JWT_PRIVATE_KEY_PEM / JWT_PUBLIC_KEY_PEM must be valid RSA PEM keys"
+                        .to_string(),
+                );
+                placeholder()
+            })
+        }
+        other => {
+            env.errors.push(format!(
+                "JWT_ALGORITHM: unsupported '{}' (expected HS256 or RS256)",
+                other
+            ));
+            placeholder()
+        }
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_lookup(|key| env::var(key).ok())
+    }
+
+    /// Loads from any key lookup (the process environment in production)
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let mut env = Loader {
+            lookup,
+            errors: Vec::new(),
+        };
+
+        let database_url = env.required("DATABASE_URL");
+        let redis_url = env.required("REDIS_URL");
+        let jwt_keys = load_jwt_keys(&mut env);
+        let default_user_id = env.optional::<Uuid>("DEFAULT_USER_ID");
+        let timezone = env.with("TIMEZONE", Tz::UTC, parse_timezone);
+
+        let defaults = ServerConfig::default();
+        let server = ServerConfig {
+            address: env.parse("SERVER_ADDRESS", defaults.address),
+            frontend_dir: env.string("FRONTEND_DIR", defaults.frontend_dir),
+            compression: env.flag("ENABLE_COMPRESSION", defaults.compression),
+            allowed_origins: env.with("ALLOWED_ORIGINS", defaults.allowed_origins, |raw| {
+                parse_allowed_origins(Some(raw))
+            }),
+            security_headers: SecurityHeaders {
+                content_security_policy: env.with(
+                    "CONTENT_SECURITY_POLICY",
+                    defaults.security_headers.content_security_policy,
+                    parse_header_setting,
+                ),
+                frame_options: env.with(
+                    "X_FRAME_OPTIONS",
+                    defaults.security_headers.frame_options,
+                    parse_frame_options,
+                ),
+                referrer_policy: env.with(
+                    "REFERRER_POLICY",
+                    defaults.security_headers.referrer_policy,
+                    parse_header_setting,
+                ),
+            },
+            shutdown_grace: Duration::from_secs(
+                env.parse("SHUTDOWN_GRACE_SECONDS", defaults.shutdown_grace.as_secs()),
+            ),
+            health_check_timeout: Duration::from_millis(env.parse(
+                "HEALTH_CHECK_TIMEOUT_MS",
+                defaults.health_check_timeout.as_millis() as u64,
+            )),
+            max_stream_connections: env
+                .parse("MAX_STREAM_CONNECTIONS", defaults.max_stream_connections),
+            ws_ping_interval: Duration::from_secs(env.parse(
+                "WS_PING_INTERVAL_SECONDS",
+                defaults.ws_ping_interval.as_secs(),
+            )),
+            ws_pong_timeout: Duration::from_secs(env.parse(
+                "WS_PONG_TIMEOUT_SECONDS",
+                defaults.ws_pong_timeout.as_secs(),
+            )),
+            sse_keepalive: Duration::from_secs(
+                env.parse("SSE_KEEPALIVE_SECONDS", defaults.sse_keepalive.as_secs()),
+            ),
+            sse_keepalive_text: env.string("SSE_KEEPALIVE_TEXT", defaults.sse_keepalive_text),
+            sse_keepalive_event: env.flag("SSE_KEEPALIVE_EVENT", defaults.sse_keepalive_event),
+            broadcast_capacity: env.parse("BROADCAST_CAPACITY", defaults.broadcast_capacity),
+            history_limit: env.parse("SENSOR_HISTORY_LIMIT", defaults.history_limit),
+            skip_history: env.flag("SKIP_HISTORY", defaults.skip_history),
+            slow_query: Duration::from_millis(
+                env.parse("SLOW_QUERY_MS", defaults.slow_query.as_millis() as u64),
+            ),
+        };
+        env.check(
+            !server.health_check_timeout.is_zero(),
+            "HEALTH_CHECK_TIMEOUT_MS must be greater than 0",
+        );
+        env.check(
+            server.max_stream_connections > 0,
+            "MAX_STREAM_CONNECTIONS must be greater than 0",
+        );
+        env.check(
+            !server.ws_ping_interval.is_zero(),
+            "WS_PING_INTERVAL_SECONDS must be greater than 0",
+        );
+        env.check(
+            !server.ws_pong_timeout.is_zero(),
+            "WS_PONG_TIMEOUT_SECONDS must be greater than 0",
+        );
+        env.check(
+            !server.sse_keepalive.is_zero(),
+            "SSE_KEEPALIVE_SECONDS must be greater than 0",
+        );
+        env.check(
+            !server.sse_keepalive_text.contains(['\r', '\n']),
+            "SSE_KEEPALIVE_TEXT must be a single line",
+        );
+        env.check(
+            server.broadcast_capacity > 0,
+            "BROADCAST_CAPACITY must be greater than 0",
+        );
+        env.check(
+            server.history_limit > 0,
+            "SENSOR_HISTORY_LIMIT must be greater than 0",
+        );
+
+        // The simulator replaces the serial listeners, so no port is needed
+        let simulation_enabled = env.flag("SIMULATION_MODE", false);
+
+        let defaults = SerialConfig::default();
+        // SERIAL_PORTS (comma separated) wins over the single SERIAL_PORT
+        let ports = env
+            .raw("SERIAL_PORTS")
+            .map(|raw| parse_serial_ports(&raw))
+            .filter(|ports| !ports.is_empty())
+            .or_else(|| env.raw("SERIAL_PORT").map(|port| vec![port]))
+            .unwrap_or_default();
+        env.check(
+            simulation_enabled || !ports.is_empty(),
+            "SERIAL_PORT or SERIAL_PORTS must be set",
+        );
+        // Auto-detect starts from the first candidate when BAUD_RATE is unset
+        let baud_auto_detect = env.flag("BAUD_AUTO_DETECT", defaults.baud_auto_detect);
+        let baud_candidates = env.with(
+            "BAUD_CANDIDATES",
+            defaults.baud_candidates,
+            parse_baud_rates,
+        );
+        let baud_rate = match env.raw("BAUD_RATE") {
+            None if simulation_enabled => defaults.baud_rate,
+            None if baud_auto_detect => baud_candidates[0],
+            None => {
+                env.errors.push("BAUD_RATE must be set".to_string());
+                defaults.baud_rate
+            }
+            Some(_) => env.parse("BAUD_RATE", defaults.baud_rate),
+        };
+        let serial = SerialConfig {
+            ports,
+            baud_rate,
+            baud_auto_detect,
+            baud_candidates,
+            baud_detect_timeout: Duration::from_secs(env.parse(
+                "BAUD_DETECT_TIMEOUT_SECONDS",
+                defaults.baud_detect_timeout.as_secs(),
+            )),
+            device_user_map: env.with(
+                "DEVICE_USER_MAP",
+                defaults.device_user_map,
+                parse_device_user_map,
+            ),
+            thresholds: Thresholds {
+                fidget: env.parse("THRESH_FIDGET", defaults.thresholds.fidget),
+                active: env.parse("THRESH_ACTIVE", defaults.thresholds.active),
+            },
+            hysteresis: env.parse("THRESH_HYSTERESIS", defaults.hysteresis),
+            alert_limit_sec: env.parse("ALERT_LIMIT_SECONDS", defaults.alert_limit_sec),
+            alert_cooldown_sec: env.parse("ALERT_COOLDOWN_SECONDS", defaults.alert_cooldown_sec),
+            smoothing_window: env.with("SMOOTHING_WINDOW", defaults.smoothing_window, |raw| {
+                parse_smoothing_window(Some(raw))
+            }),
+            smoothing_mode: match env.raw("SMOOTHING_MODE") {
+                None => defaults.smoothing_mode,
+                Some(mode) => {
+                    let alpha = env.raw("SMOOTHING_ALPHA");
+                    env.with("SMOOTHING_MODE", defaults.smoothing_mode, |_| {
+                        parse_smoothing_mode(Some(&mode), alpha.as_deref())
+                    })
+                }
+            },
+            pir_debounce_samples: env.parse("PIR_DEBOUNCE_SAMPLES", defaults.pir_debounce_samples),
+            malformed_warn_ratio: env
+                .parse("SERIAL_MALFORMED_WARN_RATIO", defaults.malformed_warn_ratio),
+            reconnect_max: Duration::from_secs(env.parse(
+                "SERIAL_RECONNECT_MAX_SECONDS",
+                defaults.reconnect_max.as_secs(),
+            )),
+            max_acc: env.parse("SENSOR_ACC_MAX", defaults.max_acc),
+            timer_source: env.with(
+                "SEDENTARY_TIMER_SOURCE",
+                defaults.timer_source,
+                parse_timer_source,
+            ),
+            timer_max_gap: Duration::from_secs(env.parse(
+                "SEDENTARY_TIMER_MAX_GAP_SECONDS",
+                defaults.timer_max_gap.as_secs(),
+            )),
+            format: env.with("SERIAL_FORMAT", defaults.format, parse_serial_format),
+            fusion: FusionWeights {
+                pir: env.parse("FUSION_PIR_WEIGHT", defaults.fusion.pir),
+                acc: env.parse("FUSION_ACC_WEIGHT", defaults.fusion.acc),
+                min_reset_confidence: env.parse(
+                    "FUSION_MIN_RESET_CONFIDENCE",
+                    defaults.fusion.min_reset_confidence,
+                ),
+            },
+        };
+        env.check(serial.baud_rate > 0, "BAUD_RATE must be greater than 0");
+        env.check(
+            !serial.baud_detect_timeout.is_zero(),
+            "BAUD_DETECT_TIMEOUT_SECONDS must be greater than 0",
+        );
+        if let Err(problem) = serial.thresholds.validate() {
+            env.errors
+                .push(format!("THRESH_FIDGET/THRESH_ACTIVE: {}", problem));
+        }
+        env.check(
+            serial.hysteresis.is_finite() && serial.hysteresis >= 0.0,
+            "THRESH_HYSTERESIS must be 0 or greater",
+        );
+        env.check(
+            serial.alert_limit_sec > 0,
+            "ALERT_LIMIT_SECONDS must be greater than 0",
+        );
+        env.check(
+            [serial.fusion.pir, serial.fusion.acc]
+                .iter()
+                .all(|w| w.is_finite() && *w >= 0.0)
+                && serial.fusion.pir + serial.fusion.acc > 0.0,
+            "FUSION_PIR_WEIGHT and FUSION_ACC_WEIGHT must be 0 or greater and not both 0",
+        );
+        env.check(
+            (0.0..=1.0).contains(&serial.fusion.min_reset_confidence),
+            "FUSION_MIN_RESET_CONFIDENCE must be between 0 and 1",
+        );
+        env.check(
+            serial.pir_debounce_samples > 0,
+            "PIR_DEBOUNCE_SAMPLES must be greater than 0",
+        );
+        env.check(
+            (0.0..=1.0).contains(&serial.malformed_warn_ratio),
+            "SERIAL_MALFORMED_WARN_RATIO must be between 0 and 1",
+        );
+        env.check(
+            !serial.reconnect_max.is_zero(),
+            "SERIAL_RECONNECT_MAX_SECONDS must be greater than 0",
+        );
+        env.check(
+            serial.max_acc.is_finite() && serial.max_acc > serial.thresholds.active,
+            "SENSOR_ACC_MAX must be greater than THRESH_ACTIVE",
+        );
+        env.check(
+            !serial.timer_max_gap.is_zero(),
+            "SEDENTARY_TIMER_MAX_GAP_SECONDS must be greater than 0",
+        );
+
+        let defaults = DbWorkerConfig::default();
+        let db_worker = DbWorkerConfig {
+            batch_size: env.parse("DB_BATCH_SIZE", defaults.batch_size),
+            batch_interval: Duration::from_millis(env.parse(
+                "DB_BATCH_INTERVAL_MS",
+                defaults.batch_interval.as_millis() as u64,
+            )),
+        };
+        env.check(
+            db_worker.batch_size > 0,
+            "DB_BATCH_SIZE must be greater than 0",
+        );
+        env.check(
+            !db_worker.batch_interval.is_zero(),
+            "DB_BATCH_INTERVAL_MS must be greater than 0",
+        );
+
+        let defaults = FallbackConfig::default();
+        let fallback = FallbackConfig {
+            enabled: !env.flag("DISABLE_FALLBACK", !defaults.enabled),
+            timeout_seconds: env.parse("FALLBACK_TIMEOUT_SECONDS", defaults.timeout_seconds),
+            batch_size: env.parse("FALLBACK_BATCH_SIZE", defaults.batch_size),
+            replay_interval_ms: env
+                .parse("FALLBACK_REPLAY_INTERVAL_MS", defaults.replay_interval_ms),
+            source: env.with("FALLBACK_SOURCE", defaults.source, |raw| {
+                parse_fallback_source(Some(raw), default_user_id)
+            }),
+            synthetic: env.flag("FALLBACK_SYNTHETIC", defaults.synthetic),
+        };
+        env.check(
+            fallback.timeout_seconds > 0,
+            "FALLBACK_TIMEOUT_SECONDS must be greater than 0",
+        );
+        env.check(
+            fallback.batch_size > 0,
+            "FALLBACK_BATCH_SIZE must be greater than 0",
+        );
+
+        let defaults = ReplayConfig::default();
+        let replay = ReplayConfig {
+            log_path: env.string("REPLAY_LOG_PATH", defaults.log_path),
+            speed_ms: env.parse("REPLAY_SPEED_MS", defaults.speed_ms),
+            max_gap_ms: env.parse("REPLAY_MAX_GAP_MS", defaults.max_gap_ms),
+            max_upload_bytes: env.parse("MAX_REPLAY_UPLOAD_BYTES", defaults.max_upload_bytes),
+        };
+        env.check(
+            replay.max_upload_bytes > 0,
+            "MAX_REPLAY_UPLOAD_BYTES must be greater than 0",
+        );
+
+        let defaults = SimulationConfig::default();
+        let simulation = SimulationConfig {
+            enabled: simulation_enabled,
+            profile: env.with(
+                "SIMULATION_PROFILE",
+                defaults.profile,
+                parse_activity_profile,
+            ),
+            tick: Duration::from_millis(
+                env.parse("SIMULATION_TICK_MS", defaults.tick.as_millis() as u64),
+            ),
+        };
+        env.check(
+            !simulation.tick.is_zero(),
+            "SIMULATION_TICK_MS must be greater than 0",
+        );
+
+        let defaults = RetentionConfig::default();
+        let retention = RetentionConfig {
+            days: env.parse("RETENTION_DAYS", defaults.days),
+            batch_size: env.parse("RETENTION_BATCH_SIZE", defaults.batch_size),
+            interval: Duration::from_secs(
+                env.parse::<u64>(
+                    "RETENTION_INTERVAL_HOURS",
+                    defaults.interval.as_secs() / 3600,
+                )
+                .saturating_mul(3600),
+            ),
+            summarize: env.flag("RETENTION_SUMMARIZE", defaults.summarize),
+        };
+        env.check(
+            retention.batch_size > 0,
+            "RETENTION_BATCH_SIZE must be greater than 0",
+        );
+        env.check(
+            !retention.interval.is_zero(),
+            "RETENTION_INTERVAL_HOURS must be greater than 0",
+        );
+
+        let defaults = AuthConfig::default();
+        let auth = AuthConfig {
+            jwt_expiry_seconds: env.parse("JWT_EXPIRY_SECONDS", defaults.jwt_expiry_seconds),
+            strict_revocation: env.flag("STRICT_REVOCATION", defaults.strict_revocation),
+            refresh_token_ttl_days: env
+                .parse("REFRESH_TOKEN_TTL_DAYS", defaults.refresh_token_ttl_days),
+            login_max_attempts: env.parse("LOGIN_MAX_ATTEMPTS", defaults.login_max_attempts),
+            login_window_seconds: env.parse("LOGIN_WINDOW_SECONDS", defaults.login_window_seconds),
+            // 0 leaves the lockout disabled
+            login_lockout_seconds: env
+                .optional::<u64>("LOGIN_LOCKOUT_SECONDS")
+                .filter(|&secs| secs > 0),
+            password_min_length: env.parse("PASSWORD_MIN_LENGTH", defaults.password_min_length),
+            seeding_enabled: env.flag("SEEDING_ENABLED", defaults.seeding_enabled),
+            stream_auth_required: env.flag("STREAM_AUTH_REQUIRED", defaults.stream_auth_required),
+            reset_log_tokens: env.flag("PASSWORD_RESET_LOG_TOKENS", defaults.reset_log_tokens),
+            argon2_memory_kib: env.parse("ARGON2_MEMORY_KIB", defaults.argon2_memory_kib),
+            argon2_iterations: env.parse("ARGON2_ITERATIONS", defaults.argon2_iterations),
+            argon2_parallelism: env.parse("ARGON2_PARALLELISM", defaults.argon2_parallelism),
+        };
+        if let Err(e) = argon2_params(&auth) {
+            env.errors.push(format!(
+                "ARGON2_MEMORY_KIB/ARGON2_ITERATIONS/ARGON2_PARALLELISM: {}",
+                e
+            ));
+        }
+        env.check(
+            auth.jwt_expiry_seconds > 0,
+            "JWT_EXPIRY_SECONDS must be greater than 0",
+        );
+        env.check(
+            auth.refresh_token_ttl_days > 0,
+            "REFRESH_TOKEN_TTL_DAYS must be greater than 0",
+        );
+        env.check(
+            auth.login_max_attempts > 0,
+            "LOGIN_MAX_ATTEMPTS must be greater than 0",
+        );
+        env.check(
+            auth.login_window_seconds > 0,
+            "LOGIN_WINDOW_SECONDS must be greater than 0",
+        );
+        env.check(
+            auth.password_min_length > 0,
+            "PASSWORD_MIN_LENGTH must be greater than 0",
+        );
+
+        let defaults = FhirConfig::default();
+        let fhir = FhirConfig {
+            loinc_code: env.string("LOINC_CODE", defaults.loinc_code),
+            loinc_display: env.string("LOINC_DISPLAY", defaults.loinc_display),
+            loinc_system: env.string("LOINC_SYSTEM", defaults.loinc_system),
+            unit_system: env.string("FHIR_SYSTEM", defaults.unit_system),
+            base_url: env.string("FHIR_BASE_URL", defaults.base_url),
+        };
+
+        let defaults = ScoreConfig::default();
+        let score = ScoreConfig {
+            fidget_weight: env.parse("SCORE_FIDGET_WEIGHT", defaults.fidget_weight),
+            target_movement_share: env.parse(
+                "SCORE_TARGET_MOVEMENT_SHARE",
+                defaults.target_movement_share,
+            ),
+            alert_penalty: env.parse("SCORE_ALERT_PENALTY", defaults.alert_penalty),
+            sedentary_hour_penalty: env.parse(
+                "SCORE_SEDENTARY_HOUR_PENALTY",
+                defaults.sedentary_hour_penalty,
+            ),
+        };
+        env.check(
+            (0.0..=1.0).contains(&score.fidget_weight),
+            "SCORE_FIDGET_WEIGHT must be between 0 and 1",
+        );
+        env.check(
+            score.target_movement_share > 0.0 && score.target_movement_share <= 1.0,
+            "SCORE_TARGET_MOVEMENT_SHARE must be greater than 0 and at most 1",
+        );
+        env.check(
+            score.alert_penalty.is_finite() && score.alert_penalty >= 0.0,
+            "SCORE_ALERT_PENALTY must be 0 or greater",
+        );
+        env.check(
+            score.sedentary_hour_penalty.is_finite() && score.sedentary_hour_penalty >= 0.0,
+            "SCORE_SEDENTARY_HOUR_PENALTY must be 0 or greater",
+        );
+
+        if !env.errors.is_empty() {
+            return Err(ConfigError(env.errors));
+        }
+        Ok(Self {
+            database_url,
+            redis_url,
+            jwt_keys,
+            default_user_id,
+            timezone,
+            server,
+            serial,
+            db_worker,
+            fallback,
+            replay,
+            simulation,
+            retention,
+            auth,
+            fhir,
+            score,
+        })
+    }
+
+    /// Defaults plus the required connection settings, for unit tests
+    #[cfg(test)]
+    pub fn for_tests() -> Self {
+        Self::from_lookup(|key| {
+            let value = match key {
+                "DATABASE_URL" => "postgres://localhost/test",
+                "REDIS_URL" => "redis://127.0.0.1/",
+                "JWT_SECRET" => "test-secret",
+                "SERIAL_PORT" => "/dev/null",
+                "BAUD_RATE" => "9600",
+                _ => return None,
+            };
+            Some(value.to_string())
+        })
+        .expect("test config is valid")
+    }
+}
+
+#[cfg(test)]
+#[path = "config_tests.rs"]
+mod tests;
@@ -0,0 +1,396 @@
+use super::*;
+
+const REQUIRED: &[(&str, &str)] = &[
+    ("DATABASE_URL", "postgres://localhost/test"),
+    ("REDIS_URL", "redis://127.0.0.1/"),
+    ("JWT_SECRET", "test-secret"),
+    ("SERIAL_PORT", "/dev/ttyACM0"),
+    ("BAUD_RATE", "9600"),
+];
+
+/// The required settings, overridden (or removed, with None) by `changes`
+fn load(changes: &[(&str, Option<&str>)]) -> Result<Config, ConfigError> {
+    let mut vars: HashMap<String, String> = REQUIRED
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    for (key, value) in changes {
+        match value {
+            Some(value) => vars.insert(key.to_string(), value.to_string()),
+            None => vars.remove(*key),
+        };
+    }
+    Config::from_lookup(|key| vars.get(key).cloned())
+}
+
+fn problems(changes: &[(&str, Option<&str>)]) -> Vec<String> {
+    match load(changes) {
+        Ok(_) => panic!("expected configuration errors"),
+        Err(ConfigError(problems)) => problems,
+    }
+}
+
+// Defaults Tests
+
+#[test]
+fn test_required_settings_load_with_defaults() {
+    let config = load(&[]).unwrap();
+    assert_eq!(config.database_url, "postgres://localhost/test");
+    assert_eq!(config.serial.ports, vec!["/dev/ttyACM0".to_string()]);
+    assert_eq!(config.serial.baud_rate, 9600);
+    assert_eq!(config.serial.thresholds.fidget, 0.020);
+    assert_eq!(config.serial.thresholds.active, 0.040);
+    assert_eq!(config.serial.alert_limit_sec, 1200);
+    assert_eq!(config.serial.alert_cooldown_sec, 300);
+    assert_eq!(config.server.address, "0.0.0.0:8000".parse().unwrap());
+    assert_eq!(config.timezone, Tz::UTC);
+    assert!(config.fallback.enabled);
+    assert!(!config.server.compression);
+    assert_eq!(config.auth.login_lockout_seconds, None);
+    assert_eq!(config.server.broadcast_capacity, 100);
+    assert_eq!(config.server.slow_query, Duration::from_millis(500));
+    assert_eq!(config.server.ws_ping_interval, Duration::from_secs(15));
+    assert_eq!(config.server.ws_pong_timeout, Duration::from_secs(10));
+}
+
+#[test]
+fn test_empty_value_counts_as_unset() {
+    let config = load(&[("FHIR_BASE_URL", Some("")), ("THRESH_FIDGET", Some("  "))]).unwrap();
+    assert_eq!(config.fhir.base_url, "");
+    assert_eq!(config.serial.thresholds.fidget, 0.020);
+}
+
+#[test]
+fn test_serial_ports_take_precedence() {
+    let config = load(&[("SERIAL_PORTS", Some("/dev/ttyUSB0, /dev/ttyUSB1"))]).unwrap();
+    assert_eq!(
+        config.serial.ports,
+        vec!["/dev/ttyUSB0".to_string(), "/dev/ttyUSB1".to_string()]
+    );
+}
+
+#[test]
+fn test_disable_fallback_turns_monitor_off() {
+    let config = load(&[("DISABLE_FALLBACK", Some("true"))]).unwrap();
+    assert!(!config.fallback.enabled);
+}
+
+#[test]
+fn test_zero_lockout_leaves_lockout_disabled() {
+    let config = load(&[("LOGIN_LOCKOUT_SECONDS", Some("0"))]).unwrap();
+    assert_eq!(config.auth.login_lockout_seconds, None);
+    let config = load(&[("LOGIN_LOCKOUT_SECONDS", Some("300"))]).unwrap();
+    assert_eq!(config.auth.login_lockout_seconds, Some(300));
+}
+
+#[test]
+fn test_reset_tokens_not_logged_by_default() {
+    assert!(!load(&[]).unwrap().auth.reset_log_tokens);
+    let config = load(&[("PASSWORD_RESET_LOG_TOKENS", Some("true"))]).unwrap();
+    assert!(config.auth.reset_log_tokens);
+}
+
+// Validation Tests
+
+#[test]
+fn test_missing_required_settings_rejected() {
+    let problems = problems(&[
+        ("DATABASE_URL", None),
+        ("SERIAL_PORT", None),
+        ("BAUD_RATE", None),
+    ]);
+    assert!(problems.contains(&"DATABASE_URL must be set".to_string()));
+    assert!(problems.contains(&"SERIAL_PORT or SERIAL_PORTS must be set".to_string()));
+    assert!(problems.contains(&"BAUD_RATE must be set".to_string()));
+}
+
+#[test]
+fn test_simulation_mode_needs_no_serial_port() {
+    let config = load(&[
+        ("SIMULATION_MODE", Some("true")),
+        ("SERIAL_PORT", None),
+        ("BAUD_RATE", None),
+        ("SIMULATION_TICK_MS", Some("250")),
+    ])
+    .unwrap();
+    assert!(config.simulation.enabled);
+    assert!(config.serial.ports.is_empty());
+    assert_eq!(config.simulation.tick, Duration::from_millis(250));
+}
+
+#[test]
+fn test_invalid_simulation_profile_rejected() {
+    let problems = problems(&[("SIMULATION_PROFILE", Some("9am=active"))]);
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].starts_with("SIMULATION_PROFILE"));
+}
+
+#[test]
+fn test_serial_format() {
+    let config = load(&[("SERIAL_FORMAT", Some("csv"))]).unwrap();
+    assert_eq!(config.serial.format, SerialFormat::Csv);
+
+    let problems = problems(&[("SERIAL_FORMAT", Some("xml"))]);
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].starts_with("SERIAL_FORMAT"));
+}
+
+#[test]
+fn test_retention_settings() {
+    let config = load(&[]).unwrap();
+    assert_eq!(config.retention.days, 90);
+    assert!(config.retention.summarize);
+
+    let config = load(&[
+        ("RETENTION_DAYS", Some("0")),
+        ("RETENTION_INTERVAL_HOURS", Some("6")),
+    ])
+    .unwrap();
+    assert_eq!(config.retention.days, 0);
+    assert_eq!(config.retention.interval, Duration::from_secs(6 * 3600));
+
+    let problems = problems(&[
+        ("RETENTION_BATCH_SIZE", Some("0")),
+        ("RETENTION_INTERVAL_HOURS", Some("0")),
+    ]);
+    assert_eq!(
+        problems,
+        vec![
+            "RETENTION_BATCH_SIZE must be greater than 0",
+            "RETENTION_INTERVAL_HOURS must be greater than 0",
+        ]
+    );
+}
+
+#[test]
+fn test_typo_in_number_rejected() {
+    let problems = problems(&[("THRESH_ACTIVE", Some("0.04O"))]);
+    assert_eq!(problems, vec!["THRESH_ACTIVE: invalid value '0.04O'"]);
+}
+
+#[test]
+fn test_zero_broadcast_capacity_rejected() {
+    let problems = problems(&[("BROADCAST_CAPACITY", Some("0"))]);
+    assert_eq!(problems, vec!["BROADCAST_CAPACITY must be greater than 0"]);
+}
+
+#[test]
+fn test_zero_ws_ping_interval_rejected() {
+    let problems = problems(&[("WS_PING_INTERVAL_SECONDS", Some("0"))]);
+    assert_eq!(
+        problems,
+        vec!["WS_PING_INTERVAL_SECONDS must be greater than 0"]
+    );
+}
+
+#[test]
+fn test_sse_keepalive_settings() {
+    let config = load(&[]).unwrap();
+    assert_eq!(config.server.sse_keepalive, Duration::from_secs(15));
+    assert_eq!(config.server.sse_keepalive_text, "keepalive");
+    assert!(!config.server.sse_keepalive_event);
+
+    let config = load(&[
+        ("SSE_KEEPALIVE_SECONDS", Some("5")),
+        ("SSE_KEEPALIVE_TEXT", Some("ping")),
+        ("SSE_KEEPALIVE_EVENT", Some("true")),
+    ])
+    .unwrap();
+    assert_eq!(config.server.sse_keepalive, Duration::from_secs(5));
+    assert_eq!(config.server.sse_keepalive_text, "ping");
+    assert!(config.server.sse_keepalive_event);
+
+    let problems = problems(&[
+        ("SSE_KEEPALIVE_SECONDS", Some("0")),
+        ("SSE_KEEPALIVE_TEXT", Some("two\nlines")),
+    ]);
+    assert_eq!(
+        problems,
+        vec![
+            "SSE_KEEPALIVE_SECONDS must be greater than 0",
+            "SSE_KEEPALIVE_TEXT must be a single line",
+        ]
+    );
+}
+
+#[test]
+fn test_security_header_settings() {
+    let config = load(&[]).unwrap();
+    assert_eq!(config.server.security_headers, SecurityHeaders::default());
+
+    let config = load(&[
+        (
+            "CONTENT_SECURITY_POLICY",
+            Some("default-src 'self'; frame-ancestors https://portal.example.org"),
+        ),
+        ("X_FRAME_OPTIONS", Some("off")),
+        ("REFERRER_POLICY", Some("no-referrer")),
+    ])
+    .unwrap();
+    let headers = config.server.security_headers;
+    assert_eq!(
+        headers.content_security_policy.unwrap(),
+        "default-src 'self'; frame-ancestors https://portal.example.org"
+    );
+    assert_eq!(headers.frame_options, None);
+    assert_eq!(headers.referrer_policy.unwrap(), "no-referrer");
+
+    let problems = problems(&[("X_FRAME_OPTIONS", Some("ALLOWALL"))]);
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].starts_with("X_FRAME_OPTIONS"));
+}
+
+#[test]
+fn test_fusion_weights() {
+    let config = load(&[]).unwrap();
+    assert_eq!(config.serial.fusion, FusionWeights::default());
+
+    let config = load(&[
+        ("FUSION_PIR_WEIGHT", Some("1")),
+        ("FUSION_ACC_WEIGHT", Some("0")),
+        ("FUSION_MIN_RESET_CONFIDENCE", Some("0")),
+    ])
+    .unwrap();
+    assert_eq!(config.serial.fusion.pir, 1.0);
+    assert_eq!(config.serial.fusion.min_reset_confidence, 0.0);
+
+    let problems = problems(&[
+        ("FUSION_PIR_WEIGHT", Some("0")),
+        ("FUSION_ACC_WEIGHT", Some("0")),
+        ("FUSION_MIN_RESET_CONFIDENCE", Some("1.5")),
+    ]);
+    assert_eq!(
+        problems,
+        vec![
+            "FUSION_PIR_WEIGHT and FUSION_ACC_WEIGHT must be 0 or greater and not both 0",
+            "FUSION_MIN_RESET_CONFIDENCE must be between 0 and 1",
+        ]
+    );
+}
+
+#[test]
+fn test_baud_auto_detect() {
+    let config = load(&[]).unwrap();
+    assert!(!config.serial.baud_auto_detect);
+    assert_eq!(config.serial.baud_candidates, vec![9600, 57600, 115200]);
+
+    // BAUD_RATE becomes optional: detection starts at the first candidate
+    let config = load(&[
+        ("BAUD_RATE", None),
+        ("BAUD_AUTO_DETECT", Some("true")),
+        ("BAUD_CANDIDATES", Some("115200,57600")),
+        ("BAUD_DETECT_TIMEOUT_SECONDS", Some("3")),
+    ])
+    .unwrap();
+    assert_eq!(config.serial.baud_rate, 115200);
+    assert_eq!(config.serial.baud_candidates, vec![115200, 57600]);
+    assert_eq!(config.serial.baud_detect_timeout, Duration::from_secs(3));
+
+    let problems = problems(&[
+        ("BAUD_CANDIDATES", Some("9600,fast")),
+        ("BAUD_DETECT_TIMEOUT_SECONDS", Some("0")),
+    ]);
+    assert_eq!(problems.len(), 2);
+    assert!(problems[0].starts_with("BAUD_CANDIDATES"));
+    assert_eq!(
+        problems[1],
+        "BAUD_DETECT_TIMEOUT_SECONDS must be greater than 0"
+    );
+}
+
+#[test]
+fn test_inverted_thresholds_rejected() {
+    let problems = problems(&[
+        ("THRESH_FIDGET", Some("0.05")),
+        ("THRESH_ACTIVE", Some("0.04")),
+    ]);
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].starts_with("THRESH_FIDGET/THRESH_ACTIVE"));
+}
+
+#[test]
+fn test_acc_max_below_active_threshold_rejected() {
+    let problems = problems(&[("SENSOR_ACC_MAX", Some("0.03"))]);
+    assert_eq!(
+        problems,
+        vec!["SENSOR_ACC_MAX must be greater than THRESH_ACTIVE".to_string()]
+    );
+}
+
+#[test]
+fn test_invalid_argon2_params_rejected() {
+    let problems = problems(&[("ARGON2_PARALLELISM", Some("0"))]);
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].starts_with("ARGON2_MEMORY_KIB/ARGON2_ITERATIONS/ARGON2_PARALLELISM"));
+}
+
+#[test]
+fn test_all_problems_reported_together() {
+    let error = load(&[
+        ("REDIS_URL", None),
+        ("SERVER_ADDRESS", Some("localhost")),
+        ("ENABLE_COMPRESSION", Some("yes")),
+        ("DB_BATCH_SIZE", Some("0")),
+    ])
+    .err()
+    .unwrap();
+    assert_eq!(error.0.len(), 4);
+    let report = error.to_string();
+    assert!(report.contains("  - REDIS_URL must be set\n"));
+    assert!(report.contains("SERVER_ADDRESS: invalid value 'localhost'"));
+    assert!(report.contains("ENABLE_COMPRESSION: 'yes' must be true or false"));
+    assert!(report.contains("DB_BATCH_SIZE must be greater than 0"));
+}
+
+#[test]
+fn test_sensor_data_source_requires_default_user() {
+    let problems = problems(&[("FALLBACK_SOURCE", Some("sensor_data"))]);
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].contains("DEFAULT_USER_ID"));
+
+    let user = Uuid::new_v4().to_string();
+    let config = load(&[
+        ("FALLBACK_SOURCE", Some("sensor_data")),
+        ("DEFAULT_USER_ID", Some(&user)),
+    ])
+    .unwrap();
+    assert!(matches!(
+        config.fallback.source,
+        FallbackSource::SensorData(_)
+    ));
+}
+
+#[test]
+fn test_unknown_timezone_rejected() {
+    let problems = problems(&[("TIMEZONE", Some("Mars/Olympus"))]);
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].starts_with("TIMEZONE:"));
+}
+
+#[test]
+fn test_unsupported_jwt_algorithm_rejected() {
+    let problems = problems(&[("JWT_ALGORITHM", Some("none"))]);
+    assert!(problems[0].contains("unsupported 'none'"));
+}
+
+#[test]
+fn test_invalid_allowed_origin_rejected() {
+    let problems = problems(&[("ALLOWED_ORIGINS", Some("localhost:5173"))]);
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].starts_with("ALLOWED_ORIGINS:"));
+}
+
+#[test]
+fn test_score_weights_validated() {
+    let config = load(&[("SCORE_FIDGET_WEIGHT", Some("0.25"))]).unwrap();
+    assert_eq!(config.score.fidget_weight, 0.25);
+    assert_eq!(config.score.target_movement_share, 0.5);
+
+    let problems = problems(&[
+        ("SCORE_FIDGET_WEIGHT", Some("1.5")),
+        ("SCORE_TARGET_MOVEMENT_SHARE", Some("0")),
+        ("SCORE_ALERT_PENALTY", Some("-1")),
+    ]);
+    assert_eq!(problems.len(), 3);
+    assert!(problems[0].starts_with("SCORE_FIDGET_WEIGHT"));
+}
@@ -0,0 +1,87 @@
+use axum::http::{header, HeaderValue, Method};
+use std::fmt;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// Which browser origins may call the API from another host (ALLOWED_ORIGINS)
+#[derive(Debug, Clone, PartialEq)]
+pub enum AllowedOrigins {
+    // No CORS headers at all: browsers keep the default same-origin policy
+    SameOrigin,
+    // `*`, for local development only
+    Any,
+    List(Vec<HeaderValue>),
+}
+
+impl fmt::Display for AllowedOrigins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AllowedOrigins::SameOrigin => write!(f, "same origin only"),
+            AllowedOrigins::Any => write!(f, "*"),
+            AllowedOrigins::List(list) => {
+                let origins: Vec<_> = list.iter().filter_map(|o| o.to_str().ok()).collect();
+                write!(f, "{}", origins.join(", "))
+            }
+        }
+    }
+}
+
+/// Parses ALLOWED_ORIGINS: unset for same-origin only, `*`, or a comma-separated
+/// list of exact origins such as `http://localhost:5173,https://dash.example.org`
+pub fn parse_allowed_origins(raw: Option<&str>) -> Result<AllowedOrigins, String> {
+    let Some(raw) = raw.map(str::trim).filter(|r| !r.is_empty()) else {
+        return Ok(AllowedOrigins::SameOrigin);
+    };
+    if raw == "*" {
+        return Ok(AllowedOrigins::Any);
+    }
+
+    let mut origins = Vec::new();
+    for origin in raw.split(',').map(str::trim).filter(|o| !o.is_empty()) {
+        if origin == "*" {
+            return Err("'*' cannot be combined with other origins".to_string());
+        }
+        // Browsers send scheme://host[:port] with no path, so nothing else can ever match
+        let host = origin
+            .strip_prefix("http://")
+            .or_else(|| origin.strip_prefix("https://"));
+        match host {
+            Some(host) if !host.is_empty() && !host.contains('/') => {}
+            _ => {
+                return Err(format!(
+                    "'{}' is not an origin (expected e.g. http://localhost:5173)",
+                    origin
+                ))
+            }
+        }
+        let value = HeaderValue::from_str(origin)
+            .map_err(|_| format!("'{}' is not a valid header value", origin))?;
+        origins.push(value);
+    }
+    Ok(AllowedOrigins::List(origins))
+}
+
+/// CORS for a separately hosted dashboard, or None to stay same-origin only.
+/// Auth travels in the `Authorization` header, so credentials are never allowed.
+pub fn cors_layer(origins: &AllowedOrigins) -> Option<CorsLayer> {
+    let allow_origin = match origins {
+        AllowedOrigins::SameOrigin => return None,
+        AllowedOrigins::Any => AllowOrigin::any(),
+        AllowedOrigins::List(list) => AllowOrigin::list(list.iter().cloned()),
+    };
+    Some(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods([
+                Method::GET,
+                Method::POST,
+                Method::PUT,
+                Method::DELETE,
+                Method::OPTIONS,
+            ])
+            .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE, header::ACCEPT]),
+    )
+}
+
+#[cfg(test)]
+#[path = "cors_tests.rs"]
+mod tests;
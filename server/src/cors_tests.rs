@@ -0,0 +1,120 @@
+use super::*;
+use axum::{body::Body, http::Request, routing::get, Router};
+use tower::ServiceExt;
+
+fn app(origins: &AllowedOrigins) -> Router {
+    let app = Router::new().route(
+        "/events",
+        get(|| async {
+            (
+                [(header::CONTENT_TYPE, "text/event-stream")],
+                "data: {}\n\n",
+            )
+        }),
+    );
+    match cors_layer(origins) {
+        Some(layer) => app.layer(layer),
+        None => app,
+    }
+}
+
+async fn allow_origin(app: Router, request: Request<Body>) -> Option<String> {
+    let response = app.oneshot(request).await.unwrap();
+    response
+        .headers()
+        .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        .map(|v| v.to_str().unwrap().to_string())
+}
+
+fn get_from(origin: &str) -> Request<Body> {
+    Request::get("/events")
+        .header(header::ORIGIN, origin)
+        .body(Body::empty())
+        .unwrap()
+}
+
+// Parsing Tests
+
+#[test]
+fn test_unset_is_same_origin() {
+    assert_eq!(parse_allowed_origins(None), Ok(AllowedOrigins::SameOrigin));
+    assert_eq!(
+        parse_allowed_origins(Some("  ")),
+        Ok(AllowedOrigins::SameOrigin)
+    );
+}
+
+#[test]
+fn test_wildcard() {
+    assert_eq!(parse_allowed_origins(Some("*")), Ok(AllowedOrigins::Any));
+}
+
+#[test]
+fn test_origin_list() {
+    let parsed =
+        parse_allowed_origins(Some("http://localhost:5173, https://dash.example.org")).unwrap();
+    assert_eq!(
+        parsed,
+        AllowedOrigins::List(vec![
+            HeaderValue::from_static("http://localhost:5173"),
+            HeaderValue::from_static("https://dash.example.org"),
+        ])
+    );
+}
+
+#[test]
+fn test_invalid_origins_rejected() {
+    assert!(parse_allowed_origins(Some("localhost:5173")).is_err());
+    assert!(parse_allowed_origins(Some("http://localhost:5173/")).is_err());
+    assert!(parse_allowed_origins(Some("https://a.example.org,*")).is_err());
+}
+
+// Layer Tests
+
+#[tokio::test]
+async fn test_same_origin_sends_no_cors_headers() {
+    let app = app(&AllowedOrigins::SameOrigin);
+    assert_eq!(
+        allow_origin(app, get_from("http://localhost:5173")).await,
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_listed_origin_allowed_on_event_stream() {
+    let origins = parse_allowed_origins(Some("http://localhost:5173")).unwrap();
+    assert_eq!(
+        allow_origin(app(&origins), get_from("http://localhost:5173")).await,
+        Some("http://localhost:5173".to_string())
+    );
+    assert_eq!(
+        allow_origin(app(&origins), get_from("http://evil.example")).await,
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_preflight_allows_authorization_header() {
+    let origins = parse_allowed_origins(Some("*")).unwrap();
+    let request = Request::builder()
+        .method(Method::OPTIONS)
+        .uri("/events")
+        .header(header::ORIGIN, "http://localhost:5173")
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+        .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "authorization")
+        .body(Body::empty())
+        .unwrap();
+    let response = app(&origins).oneshot(request).await.unwrap();
+    let headers = response.headers();
+    assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    assert!(headers[header::ACCESS_CONTROL_ALLOW_HEADERS]
+        .to_str()
+        .unwrap()
+        .contains("authorization"));
+}
+
+#[test]
+fn test_display_lists_origins() {
+    let parsed = parse_allowed_origins(Some("http://a.example,http://b.example")).unwrap();
+    assert_eq!(parsed.to_string(), "http://a.example, http://b.example");
+}
@@ -0,0 +1,334 @@
+use crate::alerts::{collapse_alerts, AlertRow};
+use crate::auth::AdminUser;
+use crate::config::{Config, ScoreConfig};
+use crate::models::ActivityState;
+use crate::state::AppState;
+use crate::stats::{date_start, local_date, to_minutes};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{Days, NaiveDate, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+// Runs this long after local midnight so the DB worker has flushed the day's last batch
+const AFTER_MIDNIGHT: Duration = Duration::from_secs(60);
+
+/// Seconds of reading time per state for one user's day, plus the longest
+/// sedentary timer reached
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DayCounts {
+    pub sedentary_sec: i64,
+    pub fidget_sec: i64,
+    pub active_sec: i64,
+    pub longest_timer_sec: i32,
+}
+
+/// One user's day as stored in `activity_summary` (period_type 'daily')
+#[derive(Debug, Clone, PartialEq)]
+pub struct DailySummary {
+    pub sedentary_minutes: f32,
+    pub fidget_minutes: f32,
+    pub active_minutes: f32,
+    pub total_minutes: f32,
+    pub sedentary_percentage: f32,
+    // Fidgeting counts as activity, as in the ML analytics job
+    pub active_percentage: f32,
+    pub dominant_state: ActivityState,
+    pub activity_score: i32,
+    pub alert_count: i32,
+    pub longest_sedentary_period: i32,
+}
+
+fn percentage(part: i64, total: i64) -> f32 {
+    ((part as f64 / total as f64) * 10_000.0).round() as f32 / 100.0
+}
+
+/// The 0-100 daily activity score stored in `activity_summary.activity_score`.
+///
+/// Movement is active time plus fidget time scaled by `fidget_weight`. Moving
+/// for `target_movement_share` of the tracked day earns 100 points, less is
+/// scored proportionally. From that, `alert_penalty` points are deducted per
+/// sedentary alert and `sedentary_hour_penalty` points per hour of the longest
+/// sedentary period (`longest_sedentary`, seconds), so one long sitting costs
+/// more than the same time broken up. The result is rounded and clamped to
+/// 0-100; a day with no tracked time scores 0.
+pub fn compute_activity_score(
+    sedentary_min: f64,
+    active_min: f64,
+    fidget_min: f64,
+    alert_count: u32,
+    longest_sedentary: u32,
+    weights: &ScoreConfig,
+) -> i32 {
+    let total = sedentary_min + active_min + fidget_min;
+    if total <= 0.0 {
+        return 0;
+    }
+
+    let movement = active_min + fidget_min * weights.fidget_weight;
+    let base = (movement / total / weights.target_movement_share).min(1.0) * 100.0;
+    let penalty = alert_count as f64 * weights.alert_penalty
+        + longest_sedentary as f64 / 3600.0 * weights.sedentary_hour_penalty;
+
+    (base - penalty).round().clamp(0.0, 100.0) as i32
+}
+
+/// Aggregates a day's counts, or `None` when the day has no readings.
+/// The dominant state is the one with the most seconds (sedentary wins ties).
+pub fn summarize(
+    counts: DayCounts,
+    alert_count: usize,
+    weights: &ScoreConfig,
+) -> Option<DailySummary> {
+    let total = counts.sedentary_sec + counts.fidget_sec + counts.active_sec;
+    if total == 0 {
+        return None;
+    }
+
+    let seconds = |state| match state {
+        ActivityState::Sedentary => counts.sedentary_sec,
+        ActivityState::Fidget => counts.fidget_sec,
+        ActivityState::Active => counts.active_sec,
+    };
+    let dominant_state = ActivityState::ALL
+        .into_iter()
+        .max_by_key(|&state| seconds(state))
+        .unwrap_or(ActivityState::Sedentary);
+    let minutes = |seconds: i64| seconds as f64 / 60.0;
+
+    Some(DailySummary {
+        sedentary_minutes: to_minutes(counts.sedentary_sec) as f32,
+        fidget_minutes: to_minutes(counts.fidget_sec) as f32,
+        active_minutes: to_minutes(counts.active_sec) as f32,
+        total_minutes: to_minutes(total) as f32,
+        sedentary_percentage: percentage(counts.sedentary_sec, total),
+        active_percentage: percentage(counts.active_sec + counts.fidget_sec, total),
+        dominant_state,
+        activity_score: compute_activity_score(
+            minutes(counts.sedentary_sec),
+            minutes(counts.active_sec),
+            minutes(counts.fidget_sec),
+            alert_count as u32,
+            counts.longest_timer_sec.max(0) as u32,
+            weights,
+        ),
+        alert_count: alert_count as i32,
+        longest_sedentary_period: counts.longest_timer_sec,
+    })
+}
+
+/// Computes and upserts the daily `activity_summary` row of every user with
+/// `sensor_data` on local day `date` (TIMEZONE). Rerunning a day overwrites
+/// its rows, so this is safe to call repeatedly. Returns the rows written.
+pub async fn summarize_day(
+    pool: &PgPool,
+    config: &Config,
+    date: NaiveDate,
+) -> Result<usize, sqlx::Error> {
+    let tz = config.timezone;
+    let start = date_start(tz, date);
+    let end = date_start(tz, date.checked_add_days(Days::new(1)).unwrap_or(date));
+
+    // Like the live daily totals, each distinct second of reading time counts once
+    let counts = sqlx::query!(
+        r#"
+        SELECT
+            user_id,
+            COUNT(DISTINCT date_trunc('second', timestamp))
+                FILTER (WHERE state = 'SEDENTARY') AS "sedentary_seconds!",
+            COUNT(DISTINCT date_trunc('second', timestamp))
+                FILTER (WHERE state = 'FIDGET') AS "fidget_seconds!",
+            COUNT(DISTINCT date_trunc('second', timestamp))
+                FILTER (WHERE state = 'ACTIVE') AS "active_seconds!",
+            MAX(timer_seconds) AS "longest_timer!"
+        FROM sensor_data
+        WHERE timestamp >= $1 AND timestamp < $2
+        GROUP BY user_id
+        "#,
+        start,
+        end
+    )
+    .fetch_all(pool)
+    .await?;
+
+    // Same rows as the alert history endpoint, for every user at once
+    let alert_rows = sqlx::query!(
+        r#"
+        SELECT
+            user_id AS "user_id!",
+            timer_seconds AS "timer_seconds!",
+            alert AS "alert!",
+            timestamp AS "timestamp!"
+        FROM (
+            SELECT
+                user_id,
+                timer_seconds,
+                alert_triggered AS alert,
+                timestamp,
+                LAG(timer_seconds) OVER (PARTITION BY user_id ORDER BY timestamp) AS previous_timer
+            FROM sensor_data
+            WHERE timestamp >= $1 AND timestamp < $2
+        ) readings
+        WHERE alert OR timer_seconds < previous_timer
+        ORDER BY user_id, timestamp
+        "#,
+        start,
+        end
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut alerts: HashMap<Uuid, Vec<AlertRow>> = HashMap::new();
+    for row in alert_rows {
+        alerts.entry(row.user_id).or_default().push(AlertRow {
+            timer_seconds: row.timer_seconds,
+            alert: row.alert,
+            timestamp: row.timestamp,
+        });
+    }
+
+    let mut written = 0;
+    for row in counts {
+        let day = DayCounts {
+            sedentary_sec: row.sedentary_seconds,
+            fidget_sec: row.fidget_seconds,
+            active_sec: row.active_seconds,
+            longest_timer_sec: row.longest_timer,
+        };
+        let alert_count = alerts
+            .get(&row.user_id)
+            .map_or(0, |rows| collapse_alerts(rows).len());
+        let Some(summary) = summarize(day, alert_count, &config.score) else {
+            continue;
+        };
+
+        sqlx::query!(
+            r#"
+            INSERT INTO activity_summary (
+                user_id, date, period_type,
+                sedentary_minutes, fidget_minutes, active_minutes, total_minutes,
+                sedentary_percentage, active_percentage,
+                dominant_state, activity_score,
+                alert_count, longest_sedentary_period
+            )
+            VALUES ($1, $2, 'daily', $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            ON CONFLICT (user_id, date, period_type)
+            DO UPDATE SET
+                sedentary_minutes = EXCLUDED.sedentary_minutes,
+                fidget_minutes = EXCLUDED.fidget_minutes,
+                active_minutes = EXCLUDED.active_minutes,
+                total_minutes = EXCLUDED.total_minutes,
+                sedentary_percentage = EXCLUDED.sedentary_percentage,
+                active_percentage = EXCLUDED.active_percentage,
+                dominant_state = EXCLUDED.dominant_state,
+                activity_score = EXCLUDED.activity_score,
+                alert_count = EXCLUDED.alert_count,
+                longest_sedentary_period = EXCLUDED.longest_sedentary_period,
+                updated_at = NOW()
+            "#,
+            row.user_id,
+            date,
+            summary.sedentary_minutes,
+            summary.fidget_minutes,
+            summary.active_minutes,
+            summary.total_minutes,
+            summary.sedentary_percentage,
+            summary.active_percentage,
+            summary.dominant_state.as_str(),
+            summary.activity_score,
+            summary.alert_count,
+            summary.longest_sedentary_period
+        )
+        .execute(pool)
+        .await?;
+        written += 1;
+    }
+
+    Ok(written)
+}
+
+/// Summarizes the previous local day shortly after each midnight (TIMEZONE).
+/// It also runs once at startup, catching up on a midnight the server was down for.
+pub fn spawn_daily_summary_job(
+    config: Arc<Config>,
+    pool: PgPool,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    let tz = config.timezone;
+    println!("Daily summary job started (timezone: {})", tz);
+
+    tokio::spawn(async move {
+        loop {
+            let now = Utc::now();
+            let today = local_date(tz, now);
+            if let Some(yesterday) = today.checked_sub_days(Days::new(1)) {
+                match summarize_day(&pool, &config, yesterday).await {
+                    Ok(rows) => println!("Daily summary for {}: {} user(s)", yesterday, rows),
+                    Err(e) => eprintln!("Daily summary error for {}: {}", yesterday, e),
+                }
+            }
+
+            let next_run = today
+                .checked_add_days(Days::new(1))
+                .map(|tomorrow| date_start(tz, tomorrow))
+                .unwrap_or(now);
+            let wait = (next_run - now).to_std().unwrap_or_default() + AFTER_MIDNIGHT;
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = shutdown.cancelled() => break,
+            }
+        }
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SummarizeQuery {
+    // Local date (TIMEZONE); defaults to yesterday
+    date: Option<NaiveDate>,
+}
+
+/// Recomputes one day's daily summaries now instead of waiting for midnight
+/// Endpoint: POST /api/analytics/summarize?date=YYYY-MM-DD (admin only)
+pub async fn run_daily_summary(
+    _admin: AdminUser,
+    State(state): State<AppState>,
+    Query(params): Query<SummarizeQuery>,
+) -> Response {
+    let tz = state.config.timezone;
+    let Some(date) = params
+        .date
+        .or_else(|| local_date(tz, Utc::now()).checked_sub_days(Days::new(1)))
+    else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "Invalid date"})),
+        )
+            .into_response();
+    };
+
+    match summarize_day(&state.db, &state.config, date).await {
+        Ok(rows) => Json(json!({"date": date, "users": rows})).into_response(),
+        Err(e) => {
+            eprintln!("Daily summary error for {}: {:?}", date, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": "Failed to compute daily summaries"})),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "daily_summary_tests.rs"]
+mod tests;
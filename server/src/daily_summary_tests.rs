@@ -0,0 +1,132 @@
+use super::*;
+
+fn counts(sedentary_sec: i64, fidget_sec: i64, active_sec: i64) -> DayCounts {
+    DayCounts {
+        sedentary_sec,
+        fidget_sec,
+        active_sec,
+        longest_timer_sec: 0,
+    }
+}
+
+fn score(sedentary: f64, active: f64, fidget: f64, alerts: u32, longest: u32) -> i32 {
+    compute_activity_score(
+        sedentary,
+        active,
+        fidget,
+        alerts,
+        longest,
+        &ScoreConfig::default(),
+    )
+}
+
+// Activity Score Tests
+
+#[test]
+fn test_all_sedentary_scores_zero() {
+    assert_eq!(score(480.0, 0.0, 0.0, 0, 0), 0);
+    // Penalties can't take the score below zero
+    assert_eq!(score(480.0, 0.0, 0.0, 20, 8 * 3600), 0);
+}
+
+#[test]
+fn test_balanced_active_day_scores_high() {
+    // 240 sedentary, 180 active, 60 fidget: 210 of 480 minutes moving
+    assert_eq!(score(240.0, 180.0, 60.0, 0, 1800), 83);
+}
+
+#[test]
+fn test_moving_past_target_is_capped() {
+    assert_eq!(score(0.0, 480.0, 0.0, 0, 0), 100);
+    assert_eq!(score(100.0, 300.0, 0.0, 0, 0), 100);
+}
+
+#[test]
+fn test_no_tracked_time_scores_zero() {
+    assert_eq!(score(0.0, 0.0, 0.0, 3, 0), 0);
+}
+
+#[test]
+fn test_fidgeting_counts_as_partial_movement() {
+    let fidgeting = score(300.0, 0.0, 300.0, 0, 0);
+    assert_eq!(fidgeting, 50);
+    assert!(fidgeting < score(300.0, 300.0, 0.0, 0, 0));
+}
+
+#[test]
+fn test_alerts_and_long_sitting_are_penalized() {
+    let base = score(300.0, 300.0, 0.0, 0, 0);
+    assert_eq!(score(300.0, 300.0, 0.0, 2, 0), base - 10);
+    assert_eq!(score(300.0, 300.0, 0.0, 0, 2 * 3600), base - 20);
+}
+
+#[test]
+fn test_weights_are_configurable() {
+    let weights = ScoreConfig {
+        fidget_weight: 1.0,
+        target_movement_share: 1.0,
+        alert_penalty: 0.0,
+        sedentary_hour_penalty: 0.0,
+    };
+    // With neutral weights the score is the share of the day spent moving
+    assert_eq!(
+        compute_activity_score(250.0, 150.0, 100.0, 4, 7200, &weights),
+        50
+    );
+}
+
+// Summary Tests
+
+fn summary_of(counts: DayCounts, alert_count: usize) -> Option<DailySummary> {
+    summarize(counts, alert_count, &ScoreConfig::default())
+}
+
+#[test]
+fn test_empty_day_has_no_summary() {
+    assert_eq!(summary_of(DayCounts::default(), 0), None);
+}
+
+#[test]
+fn test_minutes_and_percentages() {
+    let summary = summary_of(counts(2700, 600, 300), 2).unwrap();
+    assert_eq!(summary.sedentary_minutes, 45.0);
+    assert_eq!(summary.fidget_minutes, 10.0);
+    assert_eq!(summary.active_minutes, 5.0);
+    assert_eq!(summary.total_minutes, 60.0);
+    assert_eq!(summary.sedentary_percentage, 75.0);
+    assert_eq!(summary.active_percentage, 25.0);
+    assert_eq!(summary.alert_count, 2);
+}
+
+#[test]
+fn test_percentages_round_to_two_places() {
+    let summary = summary_of(counts(2, 0, 1), 0).unwrap();
+    assert_eq!(summary.sedentary_percentage, 66.67);
+    assert_eq!(summary.active_percentage, 33.33);
+}
+
+#[test]
+fn test_summary_score_uses_compute_activity_score() {
+    let day = DayCounts {
+        longest_timer_sec: 1800,
+        ..counts(14400, 3600, 10800)
+    };
+    assert_eq!(summary_of(day, 0).unwrap().activity_score, 83);
+}
+
+#[test]
+fn test_dominant_state_is_most_time() {
+    let dominant = |c| summary_of(c, 0).unwrap().dominant_state;
+    assert_eq!(dominant(counts(10, 30, 20)), ActivityState::Fidget);
+    assert_eq!(dominant(counts(10, 5, 20)), ActivityState::Active);
+    assert_eq!(dominant(counts(20, 5, 20)), ActivityState::Sedentary);
+}
+
+#[test]
+fn test_longest_period_is_peak_timer() {
+    let day = DayCounts {
+        longest_timer_sec: 1500,
+        ..counts(1500, 0, 0)
+    };
+    assert_eq!(summary_of(day, 1).unwrap().longest_sedentary_period, 1500);
+}
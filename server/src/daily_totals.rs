@@ -0,0 +1,57 @@
+use crate::models::{ActivityState, DailyTotals};
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+
+/// Timezone whose midnight starts a new day (TIMEZONE, an IANA name such as "Europe/London")
+pub fn parse_timezone(name: &str) -> Result<Tz, String> {
+    name.parse()
+        .map_err(|_| format!("unknown timezone '{}' (expected an IANA name)", name))
+}
+
+/// Running seconds-per-state totals for one stream, reset at local midnight.
+///
+/// Each serial port and each replay run owns its own accumulator, and
+/// fallback backfill doesn't feed one, so no reading is counted twice.
+/// Each distinct second of reading time counts once.
+pub struct DailyAccumulator {
+    tz: Tz,
+    day: Option<NaiveDate>,
+    last_second: Option<i64>,
+    totals: DailyTotals,
+}
+
+impl DailyAccumulator {
+    pub fn new(tz: Tz) -> Self {
+        Self {
+            tz,
+            day: None,
+            last_second: None,
+            totals: DailyTotals::default(),
+        }
+    }
+
+    /// Counts one classified reading and returns the totals so far today
+    pub fn observe(&mut self, state: ActivityState, timestamp: DateTime<Utc>) -> DailyTotals {
+        let day = timestamp.with_timezone(&self.tz).date_naive();
+        if self.day != Some(day) {
+            self.day = Some(day);
+            self.totals = DailyTotals::default();
+        }
+
+        let second = timestamp.timestamp();
+        if self.last_second != Some(second) {
+            self.last_second = Some(second);
+            match state {
+                ActivityState::Sedentary => self.totals.daily_sedentary_sec += 1,
+                ActivityState::Active => self.totals.daily_active_sec += 1,
+                ActivityState::Fidget => self.totals.daily_fidget_sec += 1,
+            }
+        }
+
+        self.totals
+    }
+}
+
+#[cfg(test)]
+#[path = "daily_totals_tests.rs"]
+mod tests;
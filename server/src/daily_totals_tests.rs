@@ -0,0 +1,60 @@
+use super::*;
+use chrono::TimeZone;
+
+fn utc(h: u32, m: u32, s: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 1, 6, h, m, s).unwrap()
+}
+
+// Daily Accumulator Tests
+
+#[test]
+fn test_counts_seconds_per_state() {
+    let mut daily = DailyAccumulator::new(Tz::UTC);
+    daily.observe(ActivityState::Sedentary, utc(9, 0, 0));
+    daily.observe(ActivityState::Sedentary, utc(9, 0, 1));
+    daily.observe(ActivityState::Fidget, utc(9, 0, 2));
+    let totals = daily.observe(ActivityState::Active, utc(9, 0, 3));
+
+    assert_eq!(
+        totals,
+        DailyTotals {
+            daily_sedentary_sec: 2,
+            daily_active_sec: 1,
+            daily_fidget_sec: 1,
+        }
+    );
+}
+
+#[test]
+fn test_same_second_counted_once() {
+    let mut daily = DailyAccumulator::new(Tz::UTC);
+    daily.observe(ActivityState::Sedentary, utc(9, 0, 0));
+    let totals = daily.observe(ActivityState::Sedentary, utc(9, 0, 0));
+
+    assert_eq!(totals.daily_sedentary_sec, 1);
+}
+
+#[test]
+fn test_resets_at_utc_midnight() {
+    let mut daily = DailyAccumulator::new(Tz::UTC);
+    daily.observe(ActivityState::Sedentary, utc(23, 59, 58));
+    daily.observe(ActivityState::Sedentary, utc(23, 59, 59));
+    let totals = daily.observe(
+        ActivityState::Sedentary,
+        Utc.with_ymd_and_hms(2026, 1, 7, 0, 0, 0).unwrap(),
+    );
+
+    assert_eq!(totals.daily_sedentary_sec, 1);
+}
+
+#[test]
+fn test_resets_at_local_midnight() {
+    // 22:59:59 UTC is 23:59:59 in Berlin (UTC+1 in January)
+    let mut daily = DailyAccumulator::new(chrono_tz::Europe::Berlin);
+    daily.observe(ActivityState::Active, utc(22, 59, 58));
+    let before = daily.observe(ActivityState::Active, utc(22, 59, 59));
+    let after = daily.observe(ActivityState::Active, utc(23, 0, 0));
+
+    assert_eq!(before.daily_active_sec, 2);
+    assert_eq!(after.daily_active_sec, 1);
+}
@@ -1,58 +1,355 @@
-use crate::models::ProcessedState;
+use crate::{
+    config::Config,
+    metrics::{Metrics, Subscriber},
+    models::ProcessedState,
+};
 use sqlx::PgPool;
-use std::env;
-use tokio::sync::broadcast;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
-pub async fn spawn_db_worker(pool: PgPool, mut rx: broadcast::Receiver<String>) {
+/// Collects readings until `size` are waiting; each returned batch is one DB round trip
+pub struct Batcher {
+    size: usize,
+    pending: Vec<ProcessedState>,
+}
+
+impl Batcher {
+    pub fn new(size: usize) -> Self {
+        Self {
+            size,
+            pending: Vec::with_capacity(size),
+        }
+    }
+
+    /// Buffers a reading, handing back the full batch once `size` is reached
+    pub fn push(&mut self, reading: ProcessedState) -> Option<Vec<ProcessedState>> {
+        self.pending.push(reading);
+        if self.pending.len() >= self.size {
+            Some(self.take())
+        } else {
+            None
+        }
+    }
+
+    /// Whatever is buffered (interval tick or shutdown)
+    pub fn take(&mut self) -> Vec<ProcessedState> {
+        std::mem::replace(&mut self.pending, Vec::with_capacity(self.size))
+    }
+}
+
+// Attempts per batch before a transient failure is parked in the dead-letter buffer
+const WRITE_ATTEMPTS: u32 = 3;
+const INITIAL_RETRY_BACKOFF: Duration = Duration::from_millis(200);
+// Longest a batch is retried before it is parked, so an outage can't hold up
+// the receive loop (and the channel behind it) for longer
+const MAX_RETRY_TIME: Duration = Duration::from_secs(2);
+
+// Readings kept for a later retry while the database is unreachable
+const DEAD_LETTER_CAPACITY: usize = 10_000;
+
+/// SQLSTATEs worth retrying: connection exceptions (08), insufficient
+/// resources (53), operator intervention such as a restart (57P),
+/// serialization failures and deadlocks
+fn is_transient_sqlstate(code: &str) -> bool {
+    code.starts_with("08")
+        || code.starts_with("53")
+        || code.starts_with("57P")
+        || code == "40001"
+        || code == "40P01"
+}
+
+/// Connection-level failures are transient; constraint violations and other
+/// rejections of the data itself would fail again and are not retried
+pub fn is_transient(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut | sqlx::Error::Protocol(_) => true,
+        sqlx::Error::Database(db) => db.code().is_some_and(|c| is_transient_sqlstate(&c)),
+        _ => false,
+    }
+}
+
+/// Batches that failed transiently, retried after the next successful write.
+/// Bounded: when full, the oldest readings are dropped.
+pub struct DeadLetter {
+    capacity: usize,
+    batches: VecDeque<Vec<ProcessedState>>,
+    len: usize,
+}
+
+impl DeadLetter {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            batches: VecDeque::new(),
+            len: 0,
+        }
+    }
+
+    /// Parks a batch, returning how many older readings were evicted to fit it
+    pub fn push(&mut self, batch: Vec<ProcessedState>) -> usize {
+        self.len += batch.len();
+        self.batches.push_back(batch);
+        let mut evicted = 0;
+        while self.len > self.capacity {
+            let Some(oldest) = self.batches.pop_front() else {
+                break;
+            };
+            self.len -= oldest.len();
+            evicted += oldest.len();
+        }
+        evicted
+    }
+
+    pub fn pop(&mut self) -> Option<Vec<ProcessedState>> {
+        let batch = self.batches.pop_front()?;
+        self.len -= batch.len();
+        Some(batch)
+    }
+
+    /// Puts a batch back at the front after another failed attempt
+    pub fn requeue(&mut self, batch: Vec<ProcessedState>) {
+        self.len += batch.len();
+        self.batches.push_front(batch);
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+pub async fn spawn_db_worker(
+    config: Arc<Config>,
+    pool: PgPool,
+    mut rx: broadcast::Receiver<String>,
+    metrics: Arc<Metrics>,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
     tokio::spawn(async move {
         println!("Logic Logger Started...");
 
-        while let Ok(json_msg) = rx.recv().await {
-            // We deserialize the PROCESSED output, not the raw input
-            if let Ok(data) = serde_json::from_str::<ProcessedState>(&json_msg) {
-                // Save to 'sedentary_log'
-                // We use valid data derived from our Logic Engine
-                let result = sqlx::query!(
-                    r#"
-                    INSERT INTO sedentary_log (state, timer_seconds, acceleration_val)
-                    VALUES ($1, $2, $3)
-                    "#,
-                    data.state,
-                    data.timer as i32,
-                    data.val
+        // Owner for readings that don't carry their own user_id
+        let default_user = config.default_user_id;
+
+        // Flushed at DB_BATCH_SIZE readings or every DB_BATCH_INTERVAL_MS, whichever comes first
+        let mut batcher = Batcher::new(config.db_worker.batch_size);
+        let mut dead_letter = DeadLetter::new(DEAD_LETTER_CAPACITY);
+        let mut ticker = tokio::time::interval(config.db_worker.batch_interval);
+
+        loop {
+            let batch = tokio::select! {
+                msg = rx.recv() => match msg {
+                    // We deserialize the PROCESSED output, not the raw input
+                    Ok(json_msg) => match serde_json::from_str::<ProcessedState>(&json_msg) {
+                        // Fallback backfill/synthetic readings are already stored or not real
+                        Ok(data) if data.replayed => None,
+                        Ok(data) => batcher.push(data),
+                        Err(_) => None,
+                    },
+                    // Fell behind the channel: those readings are gone, but keep persisting
+                    Err(RecvError::Lagged(skipped)) => {
+                        eprintln!("DB worker lagged, skipped {} readings", skipped);
+                        metrics.record_lagged(Subscriber::DbWorker, skipped);
+                        None
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                _ = ticker.tick() => Some(batcher.take()),
+                _ = shutdown.cancelled() => break,
+            };
+
+            if let Some(batch) = batch {
+                write_batch(
+                    &pool,
+                    batch,
+                    default_user,
+                    &metrics,
+                    &mut dead_letter,
+                    &shutdown,
                 )
-                .execute(&pool)
                 .await;
+            }
+        }
 
-                if let Err(e) = result {
-                    eprintln!("DB Error (sedentary_log): {}", e);
-                }
+        // Shutting down or channel gone: don't lose what is still buffered
+        write_batch(
+            &pool,
+            batcher.take(),
+            default_user,
+            &metrics,
+            &mut dead_letter,
+            &shutdown,
+        )
+        .await;
+        println!("Logic Logger stopped");
+    })
+}
 
-                // Mirror to sensor_data for user-level statistics (if DEFAULT_USER_ID is set)
-                if let Ok(default_user) = env::var("DEFAULT_USER_ID") {
-                    if let Ok(user_uuid) = Uuid::parse_str(&default_user) {
-                        let sensor_result = sqlx::query!(
-                            r#"
-                            INSERT INTO sensor_data (user_id, state, timer_seconds, acceleration_val, alert_triggered, timestamp)
-                            VALUES ($1, $2, $3, $4, $5, $6)
-                            "#,
-                            user_uuid,
-                            data.state,
-                            data.timer as i32,
-                            data.val,
-                            data.alert,
-                            data.timestamp
-                        )
-                        .execute(&pool)
-                        .await;
-
-                        if let Err(e) = sensor_result {
-                            eprintln!("DB Error (sensor_data): {}", e);
-                        }
-                    }
+async fn write_batch(
+    pool: &PgPool,
+    batch: Vec<ProcessedState>,
+    default_user: Option<Uuid>,
+    metrics: &Metrics,
+    dead_letter: &mut DeadLetter,
+    shutdown: &CancellationToken,
+) {
+    if batch.is_empty() {
+        return;
+    }
+    match insert_with_retry(pool, &batch, default_user, shutdown).await {
+        Ok(()) => retry_dead_letter(pool, default_user, metrics, dead_letter).await,
+        Err(e) if is_transient(&e) => {
+            eprintln!(
+                "DB Error (batch of {}), keeping for retry: {}",
+                batch.len(),
+                e
+            );
+            metrics.record_db_error();
+            let evicted = dead_letter.push(batch);
+            if evicted > 0 {
+                eprintln!(
+                    "Dead-letter buffer full, dropped {} oldest readings",
+                    evicted
+                );
+            }
+            eprintln!("{} readings awaiting retry", dead_letter.len());
+        }
+        Err(e) => {
+            eprintln!("DB Error (batch of {}): {}", batch.len(), e);
+            metrics.record_db_error();
+        }
+    }
+}
+
+/// The database is reachable again: write parked batches until one fails
+async fn retry_dead_letter(
+    pool: &PgPool,
+    default_user: Option<Uuid>,
+    metrics: &Metrics,
+    dead_letter: &mut DeadLetter,
+) {
+    while let Some(batch) = dead_letter.pop() {
+        match insert_batch(pool, &batch, default_user).await {
+            Ok(()) => println!(
+                "Recovered {} readings from the dead-letter buffer",
+                batch.len()
+            ),
+            Err(e) if is_transient(&e) => {
+                dead_letter.requeue(batch);
+                break;
+            }
+            Err(e) => {
+                eprintln!("DB Error (dead-letter batch of {}): {}", batch.len(), e);
+                metrics.record_db_error();
+            }
+        }
+    }
+}
+
+/// Retries transient failures with exponential backoff for up to
+/// MAX_RETRY_TIME; permanent ones return at once. A shutdown cuts the backoff
+/// short and returns the last error.
+async fn insert_with_retry(
+    pool: &PgPool,
+    batch: &[ProcessedState],
+    default_user: Option<Uuid>,
+    shutdown: &CancellationToken,
+) -> Result<(), sqlx::Error> {
+    let deadline = Instant::now() + MAX_RETRY_TIME;
+    let mut backoff = INITIAL_RETRY_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match insert_batch(pool, batch, default_user).await {
+            Err(e)
+                if attempt < WRITE_ATTEMPTS
+                    && is_transient(&e)
+                    && Instant::now() + backoff <= deadline =>
+            {
+                eprintln!("DB write attempt {} failed, retrying: {}", attempt, e);
+                tokio::select! {
+                    _ = tokio::time::sleep(backoff) => {}
+                    _ = shutdown.cancelled() => return Err(e),
                 }
+                backoff *= 2;
+                attempt += 1;
             }
+            result => return result,
         }
-    });
+    }
 }
+
+/// User a reading is mirrored to in `sensor_data`: its own `user_id` (device
+/// mapping / per-user streams), else DEFAULT_USER_ID, else none
+pub fn sensor_data_owner(reading: &ProcessedState, default_user: Option<Uuid>) -> Option<Uuid> {
+    reading.user_id.or(default_user)
+}
+
+/// Multi-row inserts into `sedentary_log` (and `sensor_data`) in a single transaction
+async fn insert_batch(
+    pool: &PgPool,
+    batch: &[ProcessedState],
+    default_user: Option<Uuid>,
+) -> Result<(), sqlx::Error> {
+    let states: Vec<String> = batch.iter().map(|d| d.state.to_string()).collect();
+    let timers: Vec<i32> = batch.iter().map(|d| d.timer as i32).collect();
+    let vals: Vec<f32> = batch.iter().map(|d| d.val).collect();
+
+    let mut tx = pool.begin().await?;
+
+    // Save to 'sedentary_log'
+    // We use valid data derived from our Logic Engine
+    sqlx::query!(
+        r#"
+        INSERT INTO sedentary_log (state, timer_seconds, acceleration_val)
+        SELECT * FROM UNNEST($1::varchar[], $2::int4[], $3::float4[])
+        "#,
+        &states,
+        &timers,
+        &vals
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    // Mirror to sensor_data for user-level statistics; readings without an owner are skipped
+    let owned: Vec<(Uuid, &ProcessedState)> = batch
+        .iter()
+        .filter_map(|d| sensor_data_owner(d, default_user).map(|user| (user, d)))
+        .collect();
+    if !owned.is_empty() {
+        let users: Vec<Uuid> = owned.iter().map(|(user, _)| *user).collect();
+        let states: Vec<String> = owned.iter().map(|(_, d)| d.state.to_string()).collect();
+        let timers: Vec<i32> = owned.iter().map(|(_, d)| d.timer as i32).collect();
+        let vals: Vec<f32> = owned.iter().map(|(_, d)| d.val).collect();
+        let alerts: Vec<bool> = owned.iter().map(|(_, d)| d.alert).collect();
+        let timestamps: Vec<_> = owned.iter().map(|(_, d)| d.timestamp).collect();
+        sqlx::query!(
+            r#"
+            INSERT INTO sensor_data (user_id, state, timer_seconds, acceleration_val, alert_triggered, timestamp)
+            SELECT * FROM UNNEST($1::uuid[], $2::varchar[], $3::int4[], $4::float4[], $5::bool[], $6::timestamptz[])
+            "#,
+            &users,
+            &states,
+            &timers,
+            &vals,
+            &alerts,
+            &timestamps
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await
+}
+
+#[cfg(test)]
+#[path = "db_worker_tests.rs"]
+mod tests;
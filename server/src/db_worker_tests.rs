@@ -0,0 +1,198 @@
+use super::*;
+use crate::models::ActivityState;
+use chrono::Utc;
+
+fn reading(timer: u64) -> ProcessedState {
+    ProcessedState {
+        state: ActivityState::Sedentary,
+        timer,
+        val: 0.01,
+        alert: false,
+        timestamp: Utc::now(),
+        user_id: None,
+        daily: None,
+        classification_confidence: None,
+        replayed: false,
+    }
+}
+
+// Batching Tests
+
+#[test]
+fn test_batcher_1000_readings_take_few_round_trips() {
+    let mut batcher = Batcher::new(100);
+    let mut round_trips = 0;
+    let mut rows = Vec::new();
+
+    for timer in 0..1000 {
+        if let Some(batch) = batcher.push(reading(timer)) {
+            round_trips += 1;
+            rows.extend(batch);
+        }
+    }
+    let rest = batcher.take();
+    if !rest.is_empty() {
+        round_trips += 1;
+        rows.extend(rest);
+    }
+
+    assert_eq!(round_trips, 10);
+    assert_eq!(rows.len(), 1000, "every reading lands");
+    assert!(rows.iter().map(|r| r.timer).eq(0..1000), "order preserved");
+}
+
+#[test]
+fn test_batcher_take_flushes_partial_batch() {
+    let mut batcher = Batcher::new(100);
+    for timer in 0..7 {
+        assert!(batcher.push(reading(timer)).is_none());
+    }
+    assert_eq!(batcher.take().len(), 7);
+    assert!(batcher.take().is_empty());
+}
+
+// Retry Classification Tests
+
+#[test]
+fn test_connection_errors_are_transient() {
+    let io = std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "refused");
+    assert!(is_transient(&sqlx::Error::Io(io)));
+    assert!(is_transient(&sqlx::Error::PoolTimedOut));
+}
+
+#[test]
+fn test_data_errors_are_permanent() {
+    assert!(!is_transient(&sqlx::Error::RowNotFound));
+    assert!(!is_transient(&sqlx::Error::ColumnNotFound("x".into())));
+}
+
+#[test]
+fn test_transient_sqlstates() {
+    assert!(is_transient_sqlstate("08006")); // connection failure
+    assert!(is_transient_sqlstate("57P01")); // admin shutdown (DB restart)
+    assert!(is_transient_sqlstate("40001")); // serialization failure
+    assert!(!is_transient_sqlstate("23503")); // foreign key violation
+    assert!(!is_transient_sqlstate("23505")); // unique violation
+}
+
+// Dead-Letter Buffer Tests
+
+#[test]
+fn test_dead_letter_evicts_oldest_when_full() {
+    let mut dead_letter = DeadLetter::new(5);
+    assert_eq!(dead_letter.push((0..3).map(reading).collect()), 0);
+    assert_eq!(dead_letter.push((3..6).map(reading).collect()), 3);
+    assert_eq!(dead_letter.len(), 3);
+    assert_eq!(dead_letter.pop().unwrap()[0].timer, 3);
+    assert!(dead_letter.pop().is_none());
+}
+
+#[test]
+fn test_dead_letter_requeue_keeps_order() {
+    let mut dead_letter = DeadLetter::new(100);
+    dead_letter.push(vec![reading(1)]);
+    dead_letter.push(vec![reading(2)]);
+
+    let first = dead_letter.pop().unwrap();
+    dead_letter.requeue(first);
+
+    assert_eq!(dead_letter.len(), 2);
+    assert_eq!(dead_letter.pop().unwrap()[0].timer, 1);
+}
+
+// sensor_data Owner Tests
+
+#[test]
+fn test_owner_prefers_payload_user_id() {
+    let own = Uuid::new_v4();
+    let default = Uuid::new_v4();
+    let mut tagged = reading(1);
+    tagged.user_id = Some(own);
+    assert_eq!(sensor_data_owner(&tagged, Some(default)), Some(own));
+}
+
+#[test]
+fn test_owner_falls_back_to_default_user() {
+    let default = Uuid::new_v4();
+    assert_eq!(sensor_data_owner(&reading(1), Some(default)), Some(default));
+}
+
+#[test]
+fn test_owner_none_skips_sensor_data() {
+    assert_eq!(sensor_data_owner(&reading(1), None), None);
+}
+
+// Broadcast Lag Tests
+
+#[tokio::test]
+async fn test_worker_keeps_running_after_lag() {
+    let (tx, rx) = broadcast::channel(2);
+    let metrics = Arc::new(Metrics::default());
+    let shutdown = CancellationToken::new();
+
+    // Overflow the channel before the worker reads anything. Replayed readings
+    // are never written, so the lazy pool is not touched.
+    let replayed = serde_json::to_string(&ProcessedState {
+        replayed: true,
+        ..reading(1)
+    })
+    .unwrap();
+    for _ in 0..5 {
+        tx.send(replayed.clone()).unwrap();
+    }
+
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .connect_lazy("postgres://localhost/unused")
+        .unwrap();
+    let handle = spawn_db_worker(
+        Arc::new(Config::for_tests()),
+        pool,
+        rx,
+        metrics.clone(),
+        shutdown.clone(),
+    )
+    .await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert!(!handle.is_finished(), "worker stopped after lagging");
+    assert!(metrics
+        .render(true)
+        .contains("sedentary_broadcast_lag_events_total{subscriber=\"db_worker\"} 1\n"));
+
+    shutdown.cancel();
+    handle.await.unwrap();
+}
+
+// Retry Timing Tests
+
+fn unreachable_pool() -> PgPool {
+    sqlx::postgres::PgPoolOptions::new()
+        .acquire_timeout(std::time::Duration::from_millis(100))
+        .connect_lazy("postgres://postgres@127.0.0.1:1/sedentary")
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_retry_gives_up_within_max_retry_time() {
+    let started = Instant::now();
+    let result = insert_with_retry(
+        &unreachable_pool(),
+        &[reading(1)],
+        None,
+        &CancellationToken::new(),
+    )
+    .await;
+    assert!(result.is_err_and(|e| is_transient(&e)));
+    assert!(started.elapsed() <= MAX_RETRY_TIME + std::time::Duration::from_millis(500));
+}
+
+#[tokio::test]
+async fn test_shutdown_cuts_retry_backoff_short() {
+    let shutdown = CancellationToken::new();
+    shutdown.cancel();
+    let started = Instant::now();
+    let result = insert_with_retry(&unreachable_pool(), &[reading(1)], None, &shutdown).await;
+    assert!(result.is_err());
+    // No backoff is waited out once shutdown has begun
+    assert!(started.elapsed() < INITIAL_RETRY_BACKOFF);
+}
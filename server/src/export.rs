@@ -0,0 +1,259 @@
+use crate::auth::{AdminUser, AuthError, AuthUser};
+use crate::fhir_analytics::date_range;
+use crate::models::ActivityState;
+use crate::state::AppState;
+use crate::stats::{date_start, local_date};
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use chrono_tz::Tz;
+use futures::StreamExt;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct ExportParams {
+    #[serde(default = "default_period")]
+    period: String,
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+}
+
+fn default_period() -> String {
+    "daily".to_string()
+}
+
+pub const CSV_HEADER: &str = "date,period_type,sedentary_minutes,fidget_minutes,active_minutes,total_minutes,sedentary_percentage,active_percentage,activity_score,dominant_state,alert_count,longest_sedentary_period\n";
+
+/// Quotes a field only when it contains a delimiter, quote or newline
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+/// Activity summaries as CSV, streamed row by row
+/// Endpoint: GET /api/export/user/:user_id.csv (own data, or any user as admin)
+pub async fn export_user_csv(
+    State(state): State<AppState>,
+    Path(file): Path<String>,
+    Query(params): Query<ExportParams>,
+    user: Result<AuthUser, AuthError>,
+) -> Response {
+    // The route segment is "<uuid>.csv"
+    let Some(user_id) = file.strip_suffix(".csv") else {
+        return error(
+            StatusCode::NOT_FOUND,
+            "Export must be requested as <user_id>.csv",
+        );
+    };
+    let user = match user {
+        Ok(user) => user,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let Ok(user_uuid) = Uuid::parse_str(user_id) else {
+        return error(StatusCode::BAD_REQUEST, "Invalid user ID format");
+    };
+    if user.user_id != user_uuid && user.role != "admin" {
+        return error(StatusCode::FORBIDDEN, "Not permitted to export this user");
+    }
+    let (start, end) = match date_range(
+        params.start,
+        params.end,
+        local_date(state.config.timezone, Utc::now()),
+    ) {
+        Ok(range) => range,
+        Err(message) => return error(StatusCode::BAD_REQUEST, message),
+    };
+
+    let pool = state.db.clone();
+    let period = params.period;
+    let body = async_stream::stream! {
+        yield Ok::<_, sqlx::Error>(CSV_HEADER.to_string());
+
+        let mut rows = sqlx::query!(
+            r#"
+            SELECT
+                date,
+                period_type,
+                sedentary_minutes,
+                fidget_minutes,
+                active_minutes,
+                total_minutes,
+                sedentary_percentage,
+                active_percentage,
+                activity_score,
+                dominant_state,
+                alert_count,
+                longest_sedentary_period
+            FROM activity_summary
+            WHERE user_id = $1 AND period_type = $2
+              AND ($3::date IS NULL OR date >= $3)
+              AND ($4::date IS NULL OR date <= $4)
+            ORDER BY date
+            "#,
+            user_uuid,
+            period,
+            start,
+            end
+        )
+        .fetch(&pool);
+
+        while let Some(row) = rows.next().await {
+            match row {
+                Ok(row) => yield Ok(format!(
+                    "{},{},{},{},{},{},{},{},{},{},{},{}\n",
+                    row.date,
+                    csv_field(&row.period_type),
+                    row.sedentary_minutes,
+                    row.fidget_minutes,
+                    row.active_minutes,
+                    row.total_minutes,
+                    row.sedentary_percentage,
+                    row.active_percentage,
+                    row.activity_score,
+                    csv_field(&row.dominant_state),
+                    row.alert_count,
+                    row.longest_sedentary_period
+                )),
+                Err(e) => {
+                    // Abort the transfer so the client sees a truncated download
+                    eprintln!("Database error during CSV export: {:?}", e);
+                    yield Err(e);
+                    break;
+                }
+            }
+        }
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"activity-{}.csv\"", user_uuid),
+            ),
+        ],
+        Body::from_stream(body),
+    )
+        .into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogExportParams {
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+}
+
+/// One `sedentary_log` row as a replayable log line: the local time in a
+/// `[...]` prefix (skipped by the replay parser) and the reading as the
+/// Arduino sends it. PIR isn't stored, so ACTIVE rows carry `pir: 1` to
+/// replay as active; `acc` is the stored smoothed value.
+pub fn log_line(tz: Tz, created_at: DateTime<Utc>, state: &str, acc: f32) -> String {
+    let local = created_at.with_timezone(&tz);
+    let pir = i32::from(state == ActivityState::Active.as_str());
+    format!(
+        "[{}] {{\"ts\":\"{}\",\"pir\":{},\"acc\":{},\"datetime\":\"{}\"}}\n",
+        local.format("%Y-%m-%d %H:%M:%S%.3f"),
+        local.format("%H:%M:%S"),
+        pir,
+        acc,
+        created_at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+    )
+}
+
+/// Download name for an export of `from..=to` (either end open)
+pub fn log_filename(from: Option<NaiveDate>, to: Option<NaiveDate>) -> String {
+    match (from, to) {
+        (None, None) => "sedentary_log.log".to_string(),
+        (from, to) => format!(
+            "sedentary_log_{}_{}.log",
+            from.map_or("start".to_string(), |d| d.to_string()),
+            to.map_or("end".to_string(), |d| d.to_string())
+        ),
+    }
+}
+
+/// Raw `sedentary_log` rows as a log file `replay_log_file` (and the replay
+/// upload) accepts, streamed oldest first; `from`/`to` are local dates
+/// (TIMEZONE), both inclusive
+/// Endpoint: GET /api/export/log (admin only)
+pub async fn export_log(
+    _admin: AdminUser,
+    State(state): State<AppState>,
+    Query(params): Query<LogExportParams>,
+) -> Response {
+    let tz = state.config.timezone;
+    let (from, to) = match date_range(params.from, params.to, local_date(tz, Utc::now())) {
+        Ok(range) => range,
+        Err(message) => return error(StatusCode::BAD_REQUEST, message),
+    };
+    let since = from.map(|date| date_start(tz, date));
+    let until = to
+        .and_then(|date| date.checked_add_days(Days::new(1)))
+        .map(|date| date_start(tz, date));
+
+    let pool = state.db.clone();
+    let body = async_stream::stream! {
+        let mut rows = sqlx::query!(
+            r#"
+            SELECT state, acceleration_val, created_at AS "created_at!"
+            FROM sedentary_log
+            WHERE created_at IS NOT NULL
+              AND ($1::timestamptz IS NULL OR created_at >= $1)
+              AND ($2::timestamptz IS NULL OR created_at < $2)
+            ORDER BY created_at, id
+            "#,
+            since,
+            until
+        )
+        .fetch(&pool);
+
+        while let Some(row) = rows.next().await {
+            match row {
+                Ok(row) => yield Ok::<_, sqlx::Error>(log_line(
+                    tz,
+                    row.created_at,
+                    &row.state,
+                    row.acceleration_val.unwrap_or_default(),
+                )),
+                Err(e) => {
+                    // Abort the transfer so the client sees a truncated download
+                    eprintln!("Database error during log export: {:?}", e);
+                    yield Err(e);
+                    break;
+                }
+            }
+        }
+    };
+
+    (
+        [
+            (
+                header::CONTENT_TYPE,
+                "text/plain; charset=utf-8".to_string(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", log_filename(from, to)),
+            ),
+        ],
+        Body::from_stream(body),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+#[path = "export_tests.rs"]
+mod tests;
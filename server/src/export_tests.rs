@@ -0,0 +1,70 @@
+use super::*;
+
+// CSV Formatting Tests
+
+#[test]
+fn test_csv_field_plain() {
+    assert_eq!(csv_field("SEDENTARY"), "SEDENTARY");
+}
+
+#[test]
+fn test_csv_field_quotes_delimiters() {
+    assert_eq!(csv_field("a,b"), "\"a,b\"");
+    assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+}
+
+#[test]
+fn test_csv_header_columns() {
+    let columns: Vec<&str> = CSV_HEADER.trim_end().split(',').collect();
+    assert_eq!(columns.len(), 12);
+    assert_eq!(columns[0], "date");
+    assert!(columns.contains(&"activity_score"));
+    assert!(columns.contains(&"dominant_state"));
+    assert!(columns.contains(&"alert_count"));
+}
+
+// Log Export Tests
+
+fn at(raw: &str) -> DateTime<Utc> {
+    raw.parse().unwrap()
+}
+
+#[test]
+fn test_log_line_is_a_replayable_reading() {
+    let line = log_line(
+        chrono_tz::Europe::Berlin,
+        at("2026-01-23T15:12:03.123Z"),
+        "SEDENTARY",
+        0.045,
+    );
+    assert_eq!(
+        line,
+        "[2026-01-23 16:12:03.123] {\"ts\":\"16:12:03\",\"pir\":0,\"acc\":0.045,\"datetime\":\"2026-01-23T15:12:03.123Z\"}\n"
+    );
+    assert!(crate::replay::contains_reading(
+        line.as_bytes(),
+        crate::serial::SerialFormat::Json
+    ));
+}
+
+#[test]
+fn test_log_line_marks_active_rows_with_pir() {
+    let line = log_line(Tz::UTC, at("2026-01-23T09:00:00Z"), "ACTIVE", 0.01);
+    assert!(line.contains("\"pir\":1"));
+    let line = log_line(Tz::UTC, at("2026-01-23T09:00:00Z"), "FIDGET", 0.03);
+    assert!(line.contains("\"pir\":0"));
+}
+
+#[test]
+fn test_log_filename_names_the_range() {
+    let date = |raw: &str| Some(raw.parse::<NaiveDate>().unwrap());
+    assert_eq!(log_filename(None, None), "sedentary_log.log");
+    assert_eq!(
+        log_filename(date("2026-01-01"), date("2026-01-31")),
+        "sedentary_log_2026-01-01_2026-01-31.log"
+    );
+    assert_eq!(
+        log_filename(None, date("2026-01-31")),
+        "sedentary_log_start_2026-01-31.log"
+    );
+}
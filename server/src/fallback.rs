@@ -1,40 +1,80 @@
-use crate::models::ProcessedState;
+use crate::auth::AdminUser;
+use crate::config::Config;
+use crate::history::{push_history, SENSOR_HISTORY_KEY};
+use crate::models::{ActivityState, ProcessedState};
+use crate::serial::{AlertCooldown, SharedThresholds};
+use crate::state::AppState;
+use crate::synthetic::SyntheticGenerator;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
 use chrono::{DateTime, Utc};
-use redis::AsyncCommands;
+use rand::{rngs::StdRng, SeedableRng};
+use serde_json::json;
 use sqlx::PgPool;
-use std::env;
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
 use tokio::time::interval;
-
-// Configuration for fallback behavior
-fn fallback_timeout_seconds() -> u64 {
-    env::var("FALLBACK_TIMEOUT_SECONDS")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(10)
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// Table a backfill replays from (FALLBACK_SOURCE)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FallbackSource {
+    // Global log of every classified reading
+    SedentaryLog,
+    // One user's mirrored readings (DEFAULT_USER_ID)
+    SensorData(Uuid),
 }
 
-fn fallback_batch_size() -> i64 {
-    env::var("FALLBACK_BATCH_SIZE")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(500)
+impl std::fmt::Display for FallbackSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FallbackSource::SedentaryLog => write!(f, "sedentary_log"),
+            FallbackSource::SensorData(user) => write!(f, "sensor_data (user {})", user),
+        }
+    }
 }
 
-fn fallback_replay_interval_ms() -> u64 {
-    env::var("FALLBACK_REPLAY_INTERVAL_MS")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(100)
+/// `sensor_data` needs a user to scope to; anything unusable is a config error
+pub fn parse_fallback_source(
+    raw: Option<&str>,
+    default_user: Option<Uuid>,
+) -> Result<FallbackSource, String> {
+    match raw.map(str::trim) {
+        None | Some("") | Some("sedentary_log") => Ok(FallbackSource::SedentaryLog),
+        Some("sensor_data") => default_user.map(FallbackSource::SensorData).ok_or_else(|| {
+            "FALLBACK_SOURCE=sensor_data requires a valid DEFAULT_USER_ID".to_string()
+        }),
+        Some(other) => Err(format!(
+            "Unknown FALLBACK_SOURCE '{}' (expected sedentary_log or sensor_data)",
+            other
+        )),
+    }
 }
 
 // Shared state for tracking last data received
 pub struct FallbackState {
     last_data_time: AtomicU64,
     is_fallback_active: AtomicBool,
+    device_lost: AtomicBool,
+    last_backfill_rows: AtomicU64,
+    // Runtime toggle: the monitor starts no automatic backfills while set
+    paused: AtomicBool,
+    backfilling: AtomicBool,
+    // Newest reading time clients have been sent (µs since epoch, 0 = none yet)
+    seen_until_micros: AtomicI64,
+}
+
+impl Default for FallbackState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl FallbackState {
@@ -42,12 +82,19 @@ impl FallbackState {
         Self {
             last_data_time: AtomicU64::new(current_timestamp()),
             is_fallback_active: AtomicBool::new(false),
+            device_lost: AtomicBool::new(false),
+            last_backfill_rows: AtomicU64::new(0),
+            paused: AtomicBool::new(false),
+            backfilling: AtomicBool::new(false),
+            seen_until_micros: AtomicI64::new(0),
         }
     }
 
     pub fn record_data_received(&self) {
         self.last_data_time
             .store(current_timestamp(), Ordering::SeqCst);
+        self.record_seen(Utc::now());
+        self.device_lost.store(false, Ordering::SeqCst);
         if self.is_fallback_active.load(Ordering::SeqCst) {
             self.is_fallback_active.store(false, Ordering::SeqCst);
             println!("Hardware reconnected - exiting fallback mode");
@@ -59,6 +106,19 @@ impl FallbackState {
         current_timestamp().saturating_sub(last)
     }
 
+    /// Called by a serial listener that lost its device and is retrying,
+    /// so the monitor can backfill without waiting for the idle timeout
+    pub fn record_device_lost(&self) {
+        self.device_lost.store(true, Ordering::SeqCst);
+    }
+
+    /// True when hardware data has stopped and a backfill hasn't started yet
+    pub fn needs_backfill(&self, timeout_seconds: u64) -> bool {
+        !self.is_in_fallback()
+            && (self.device_lost.load(Ordering::SeqCst)
+                || self.seconds_since_last_data() >= timeout_seconds)
+    }
+
     pub fn is_in_fallback(&self) -> bool {
         self.is_fallback_active.load(Ordering::SeqCst)
     }
@@ -68,6 +128,47 @@ impl FallbackState {
             println!("Hardware unavailable - entering fallback mode");
         }
     }
+
+    /// Rows fetched by the most recent backfill (0 if it found none)
+    pub fn record_backfill(&self, rows: u64) {
+        self.last_backfill_rows.store(rows, Ordering::SeqCst);
+    }
+
+    pub fn last_backfill_rows(&self) -> u64 {
+        self.last_backfill_rows.load(Ordering::SeqCst)
+    }
+
+    /// Clients have been sent readings up to `timestamp` (live or backfilled)
+    pub fn record_seen(&self, timestamp: DateTime<Utc>) {
+        self.seen_until_micros
+            .fetch_max(timestamp.timestamp_micros(), Ordering::SeqCst);
+    }
+
+    /// Lower bound for the next backfill; `None` until anything was sent,
+    /// in which case the full window is replayed once
+    pub fn seen_until(&self) -> Option<DateTime<Utc>> {
+        match self.seen_until_micros.load(Ordering::SeqCst) {
+            0 => None,
+            micros => DateTime::from_timestamp_micros(micros),
+        }
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Claims the single backfill slot; `false` if one is already running
+    pub fn try_begin_backfill(&self) -> bool {
+        !self.backfilling.swap(true, Ordering::SeqCst)
+    }
+
+    pub fn end_backfill(&self) {
+        self.backfilling.store(false, Ordering::SeqCst);
+    }
 }
 
 fn current_timestamp() -> u64 {
@@ -77,123 +178,262 @@ fn current_timestamp() -> u64 {
         .as_secs()
 }
 
+/// Settings and handles a backfill broadcasts and caches through
+pub struct FallbackContext {
+    pub config: Arc<Config>,
+    pub pool: PgPool,
+    pub tx: broadcast::Sender<String>,
+    pub redis_client: redis::Client,
+    pub thresholds: SharedThresholds,
+}
+
+impl FallbackContext {
+    pub fn from_state(state: &AppState) -> Self {
+        Self {
+            config: state.config.clone(),
+            pool: state.db.clone(),
+            tx: state.tx.clone(),
+            redis_client: state.redis.clone(),
+            thresholds: state.thresholds.clone(),
+        }
+    }
+}
+
 // Spawns the fallback monitor that watches for data gaps
 // and backfills from the database when hardware is unavailable
 pub fn spawn_fallback_monitor(
-    pool: PgPool,
-    tx: broadcast::Sender<String>,
-    redis_client: redis::Client,
+    context: FallbackContext,
     fallback_state: Arc<FallbackState>,
-) {
-    let timeout = fallback_timeout_seconds();
-    let batch_size = fallback_batch_size();
-    let replay_interval = fallback_replay_interval_ms();
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    let settings = &context.config.fallback;
+    let timeout = settings.timeout_seconds;
 
     println!(
-        "Fallback monitor started (timeout: {}s, batch: {} rows, replay: {}ms)",
-        timeout, batch_size, replay_interval
+        "Fallback monitor started (source: {}, timeout: {}s, batch: {} rows, replay: {}ms, synthetic: {})",
+        settings.source,
+        timeout,
+        settings.batch_size,
+        settings.replay_interval_ms,
+        settings.synthetic
     );
 
     tokio::spawn(async move {
         let mut check_interval = interval(Duration::from_secs(1));
 
         loop {
-            check_interval.tick().await;
+            tokio::select! {
+                _ = check_interval.tick() => {}
+                _ = shutdown.cancelled() => break,
+            }
 
-            let seconds_idle = fallback_state.seconds_since_last_data();
+            // Paused at runtime: skip the timeout logic entirely
+            if fallback_state.is_paused() {
+                continue;
+            }
 
-            if seconds_idle >= timeout && !fallback_state.is_in_fallback() {
+            if fallback_state.needs_backfill(timeout) && fallback_state.try_begin_backfill() {
                 fallback_state.enter_fallback();
 
                 // Fetch historical data from database
-                if let Err(e) = backfill_from_database(
-                    &pool,
-                    &tx,
-                    &redis_client,
-                    batch_size,
-                    replay_interval,
-                    &fallback_state,
-                )
-                .await
-                {
+                if let Err(e) = backfill_from_database(&context, &fallback_state, &shutdown).await {
                     eprintln!("Fallback backfill error: {}", e);
                 }
+                fallback_state.end_backfill();
             }
         }
-    });
+    })
 }
 
-/// Fetches the last N rows from sedentary_log and broadcasts them
-async fn backfill_from_database(
+/// The last `batch_size` rows newer than `seen_until`, oldest first for replay
+async fn fetch_backfill(
     pool: &PgPool,
-    tx: &broadcast::Sender<String>,
-    redis_client: &redis::Client,
+    source: FallbackSource,
     batch_size: i64,
-    replay_interval_ms: u64,
+    seen_until: Option<DateTime<Utc>>,
+    alert_limit: u64,
+    alert_cooldown: u64,
+) -> Result<Vec<ProcessedState>, sqlx::Error> {
+    let mut readings: Vec<ProcessedState> = match source {
+        FallbackSource::SedentaryLog => sqlx::query!(
+            r#"
+            SELECT id, state, timer_seconds, acceleration_val, created_at
+            FROM sedentary_log
+            WHERE ($2::timestamptz IS NULL OR created_at > $2)
+            ORDER BY created_at DESC
+            LIMIT $1
+            "#,
+            batch_size,
+            seen_until
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        // Rows with a state this server doesn't classify (legacy values) are skipped
+        .filter_map(|row| {
+            Some(from_log_row(
+                row.state.parse().ok()?,
+                row.timer_seconds,
+                row.acceleration_val,
+                row.created_at,
+                alert_limit,
+            ))
+        })
+        .collect(),
+        FallbackSource::SensorData(user) => sqlx::query!(
+            r#"
+            SELECT state, timer_seconds, acceleration_val, alert_triggered, timestamp
+            FROM sensor_data
+            WHERE user_id = $1
+              AND ($3::timestamptz IS NULL OR timestamp > $3)
+            ORDER BY timestamp DESC
+            LIMIT $2
+            "#,
+            user,
+            batch_size,
+            seen_until
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .filter_map(|row| {
+            Some(from_sensor_row(
+                user,
+                row.state.parse().ok()?,
+                row.timer_seconds,
+                row.acceleration_val,
+                row.alert_triggered,
+                row.timestamp,
+            ))
+        })
+        .collect(),
+    };
+
+    // Reverse to replay in chronological order (oldest to newest)
+    readings.reverse();
+    apply_alert_cooldown(&mut readings, alert_cooldown);
+    Ok(readings)
+}
+
+/// Thins stored alert flags (one per second while over the limit, or per
+/// alert edge) down to what the live pipeline would send now
+pub fn apply_alert_cooldown(readings: &mut [ProcessedState], cooldown: u64) {
+    let mut alerts = AlertCooldown::new(cooldown);
+    for reading in readings {
+        reading.alert = alerts.observe(reading.timer, reading.alert);
+    }
+}
+
+/// `sedentary_log` has nullable columns and no alert flag, so the alert is
+/// derived from the timer and `alert_limit` (ALERT_LIMIT_SECONDS)
+pub fn from_log_row(
+    state: ActivityState,
+    timer_seconds: Option<i32>,
+    acceleration_val: Option<f32>,
+    created_at: Option<DateTime<Utc>>,
+    alert_limit: u64,
+) -> ProcessedState {
+    let timer = timer_seconds.unwrap_or(0).max(0) as u64;
+    ProcessedState {
+        state,
+        timer,
+        val: acceleration_val.unwrap_or(0.0),
+        alert: timer >= alert_limit,
+        timestamp: created_at.unwrap_or_else(Utc::now),
+        user_id: None,
+        // Backfilled rows were already counted when first recorded
+        daily: None,
+        classification_confidence: None,
+        replayed: true,
+    }
+}
+
+/// `sensor_data` rows keep their owner, reading time and recorded alert flag
+pub fn from_sensor_row(
+    user: Uuid,
+    state: ActivityState,
+    timer_seconds: i32,
+    acceleration_val: f32,
+    alert_triggered: bool,
+    timestamp: DateTime<Utc>,
+) -> ProcessedState {
+    ProcessedState {
+        state,
+        timer: timer_seconds.max(0) as u64,
+        val: acceleration_val,
+        alert: alert_triggered,
+        timestamp,
+        user_id: Some(user),
+        daily: None,
+        classification_confidence: None,
+        replayed: true,
+    }
+}
+
+/// Fetches up to the last N rows from the configured source that are newer
+/// than anything clients were already sent, and broadcasts them. With
+/// FALLBACK_SYNTHETIC and nothing to replay, synthetic readings are streamed
+/// instead until hardware returns.
+async fn backfill_from_database(
+    context: &FallbackContext,
     fallback_state: &Arc<FallbackState>,
+    shutdown: &CancellationToken,
 ) -> Result<(), sqlx::Error> {
-    println!("Backfilling {} rows from database...", batch_size);
+    let config = &context.config;
+    let batch_size = config.fallback.batch_size;
+    // Only the gap: rows clients haven't seen, so reconnect blips don't
+    // re-push old readings and send the dashboard backwards in time
+    let seen_until = fallback_state.seen_until();
+    match seen_until {
+        Some(since) => println!(
+            "Backfilling up to {} rows newer than {}...",
+            batch_size, since
+        ),
+        None => println!("Backfilling {} rows from database...", batch_size),
+    }
 
     // Get Redis connection for caching
-    let redis_conn = redis_client.get_multiplexed_async_connection().await.ok();
-
-    // Fetch last N rows, ordered by created_at ascending (oldest first for replay)
-    let rows = sqlx::query!(
-        r#"
-        SELECT id, state, timer_seconds, acceleration_val, created_at
-        FROM sedentary_log
-        ORDER BY created_at DESC
-        LIMIT $1
-        "#,
-        batch_size
+    let mut redis_conn = context
+        .redis_client
+        .get_multiplexed_async_connection()
+        .await
+        .ok();
+
+    let readings = fetch_backfill(
+        &context.pool,
+        config.fallback.source,
+        batch_size,
+        seen_until,
+        config.serial.alert_limit_sec,
+        config.serial.alert_cooldown_sec,
     )
-    .fetch_all(pool)
     .await?;
 
-    if rows.is_empty() {
-        println!("No historical data available for backfill");
+    fallback_state.record_backfill(readings.len() as u64);
+    let replay_delay = Duration::from_millis(config.fallback.replay_interval_ms);
+
+    if readings.is_empty() {
+        println!("No unseen historical data to backfill");
+        if config.fallback.synthetic {
+            stream_synthetic(
+                context,
+                &mut redis_conn,
+                replay_delay,
+                fallback_state,
+                shutdown,
+            )
+            .await;
+        }
         return Ok(());
     }
 
-    println!("Retrieved {} rows for backfill", rows.len());
-
-    // Reverse to replay in chronological order (oldest to newest)
-    let rows_chronological: Vec<_> = rows.into_iter().rev().collect();
-
-    let replay_delay = Duration::from_millis(replay_interval_ms);
+    println!("Retrieved {} rows for backfill", readings.len());
 
-    for row in rows_chronological {
-        // Check if real hardware data arrived - exit fallback early
-        if !fallback_state.is_in_fallback() {
-            println!("Hardware reconnected during backfill - stopping replay");
+    for processed in readings {
+        if !keep_broadcasting(fallback_state, shutdown) {
             break;
         }
-
-        // Convert DB row to ProcessedState
-        let timestamp: DateTime<Utc> = row.created_at.unwrap_or_else(Utc::now);
-
-        let timer = row.timer_seconds.unwrap_or(0) as u64;
-        let alert_threshold = crate::serial::alert_limit_sec();
-
-        let processed = ProcessedState {
-            state: row.state,
-            timer,
-            val: row.acceleration_val.unwrap_or(0.0),
-            alert: timer >= alert_threshold,
-            timestamp,
-        };
-
-        // Serialize and broadcast + cache to Redis
-        if let Ok(json) = serde_json::to_string(&processed) {
-            // Broadcast to connected clients
-            let _ = tx.send(json.clone());
-
-            // Cache in Redis for new clients
-            if let Some(ref mut con) = redis_conn.clone() {
-                let _: Result<(), _> = con.lpush("sensor_history", &json).await;
-                let _: Result<(), _> = con.ltrim("sensor_history", 0, 99).await;
-            }
-        }
+        broadcast_reading(context, &mut redis_conn, &processed, fallback_state).await;
 
         // Small delay between replays to avoid flooding the frontend
         tokio::time::sleep(replay_delay).await;
@@ -202,3 +442,125 @@ async fn backfill_from_database(
     println!("Backfill complete");
     Ok(())
 }
+
+/// False once real hardware data arrived (fallback cleared) or on shutdown
+fn keep_broadcasting(fallback_state: &FallbackState, shutdown: &CancellationToken) -> bool {
+    if !fallback_state.is_in_fallback() {
+        println!("Hardware reconnected during backfill - stopping replay");
+        return false;
+    }
+    if shutdown.is_cancelled() {
+        println!("Shutting down - stopping backfill replay");
+        return false;
+    }
+    true
+}
+
+/// Broadcasts to connected clients and caches in Redis for new ones
+async fn broadcast_reading(
+    context: &FallbackContext,
+    redis_conn: &mut Option<redis::aio::MultiplexedConnection>,
+    processed: &ProcessedState,
+    fallback_state: &FallbackState,
+) {
+    if let Ok(json) = serde_json::to_string(processed) {
+        let _ = context.tx.send(json.clone());
+        fallback_state.record_seen(processed.timestamp);
+
+        if let Some(con) = redis_conn.as_mut() {
+            let limit = context.config.server.history_limit;
+            let _ = push_history(con, &[SENSOR_HISTORY_KEY], &[json], limit).await;
+        }
+    }
+}
+
+/// Streams generated readings stamped with the current time until the
+/// device comes back, classified with the live thresholds
+async fn stream_synthetic(
+    context: &FallbackContext,
+    redis_conn: &mut Option<redis::aio::MultiplexedConnection>,
+    replay_delay: Duration,
+    fallback_state: &FallbackState,
+    shutdown: &CancellationToken,
+) {
+    println!("Streaming synthetic readings until hardware returns");
+    let mut generator = SyntheticGenerator::new(
+        StdRng::from_entropy(),
+        context.thresholds.current(),
+        context.config.serial.alert_limit_sec,
+        context.config.serial.alert_cooldown_sec,
+    );
+
+    while keep_broadcasting(fallback_state, shutdown) {
+        let processed = generator.next_reading(Utc::now());
+        broadcast_reading(context, redis_conn, &processed, fallback_state).await;
+        tokio::time::sleep(replay_delay).await;
+    }
+}
+
+/// Whether hardware data is flowing, for ops checks without reading logs
+/// Endpoint: GET /api/fallback/status
+pub async fn get_fallback_status(State(state): State<AppState>) -> impl IntoResponse {
+    let fallback = &state.fallback_state;
+    Json(json!({
+        "in_fallback": fallback.is_in_fallback(),
+        "seconds_since_last_data": fallback.seconds_since_last_data(),
+        "timeout_seconds": state.config.fallback.timeout_seconds,
+        "last_backfill_rows": fallback.last_backfill_rows(),
+        "paused": fallback.is_paused(),
+    }))
+}
+
+/// Enters fallback and runs one backfill pass now, whatever the idle timer says
+/// Endpoint: POST /api/fallback/trigger (admin only)
+pub async fn trigger_fallback(_admin: AdminUser, State(state): State<AppState>) -> Response {
+    let fallback_state = state.fallback_state.clone();
+    if !fallback_state.try_begin_backfill() {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({"error": "A backfill is already running"})),
+        )
+            .into_response();
+    }
+    fallback_state.enter_fallback();
+
+    tokio::spawn(async move {
+        if let Err(e) = backfill_from_database(
+            &FallbackContext::from_state(&state),
+            &fallback_state,
+            &state.shutdown,
+        )
+        .await
+        {
+            eprintln!("Fallback backfill error: {}", e);
+        }
+        fallback_state.end_backfill();
+    });
+
+    (
+        StatusCode::ACCEPTED,
+        Json(json!({"status": "backfill started"})),
+    )
+        .into_response()
+}
+
+/// Endpoint: POST /api/fallback/pause (admin only)
+pub async fn pause_fallback(_admin: AdminUser, State(state): State<AppState>) -> impl IntoResponse {
+    state.fallback_state.set_paused(true);
+    println!("Fallback monitor paused");
+    Json(json!({"paused": true}))
+}
+
+/// Endpoint: POST /api/fallback/resume (admin only)
+pub async fn resume_fallback(
+    _admin: AdminUser,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    state.fallback_state.set_paused(false);
+    println!("Fallback monitor resumed");
+    Json(json!({"paused": false}))
+}
+
+#[cfg(test)]
+#[path = "fallback_tests.rs"]
+mod tests;
@@ -0,0 +1,182 @@
+use super::*;
+
+// Backfill Trigger Tests
+
+#[test]
+fn test_fresh_state_does_not_need_backfill() {
+    let state = FallbackState::new();
+    assert!(!state.needs_backfill(10));
+}
+
+#[test]
+fn test_device_lost_triggers_backfill_before_timeout() {
+    let state = FallbackState::new();
+    state.record_device_lost();
+    assert!(state.needs_backfill(10));
+}
+
+#[test]
+fn test_no_backfill_while_already_in_fallback() {
+    let state = FallbackState::new();
+    state.record_device_lost();
+    state.enter_fallback();
+    assert!(!state.needs_backfill(10));
+}
+
+#[test]
+fn test_data_received_clears_device_lost() {
+    let state = FallbackState::new();
+    state.record_device_lost();
+    state.record_data_received();
+    assert!(!state.needs_backfill(10));
+}
+
+#[test]
+fn test_idle_timeout_triggers_backfill() {
+    let state = FallbackState::new();
+    assert!(state.needs_backfill(0));
+}
+
+#[test]
+fn test_last_backfill_rows_recorded() {
+    let state = FallbackState::new();
+    assert_eq!(state.last_backfill_rows(), 0);
+    state.record_backfill(250);
+    assert_eq!(state.last_backfill_rows(), 250);
+    state.record_backfill(0);
+    assert_eq!(state.last_backfill_rows(), 0);
+}
+
+#[test]
+fn test_pause_toggle() {
+    let state = FallbackState::new();
+    assert!(!state.is_paused());
+    state.set_paused(true);
+    assert!(state.is_paused());
+    state.set_paused(false);
+    assert!(!state.is_paused());
+}
+
+#[test]
+fn test_single_backfill_at_a_time() {
+    let state = FallbackState::new();
+    assert!(state.try_begin_backfill());
+    assert!(!state.try_begin_backfill());
+    state.end_backfill();
+    assert!(state.try_begin_backfill());
+}
+
+#[test]
+fn test_seen_until_starts_empty() {
+    let state = FallbackState::new();
+    assert_eq!(state.seen_until(), None);
+}
+
+#[test]
+fn test_seen_until_only_moves_forward() {
+    let state = FallbackState::new();
+    let later: DateTime<Utc> = "2026-01-23T10:00:05.123456Z".parse().unwrap();
+    let earlier: DateTime<Utc> = "2026-01-23T10:00:01Z".parse().unwrap();
+    state.record_seen(later);
+    state.record_seen(earlier);
+    assert_eq!(state.seen_until(), Some(later));
+}
+
+#[test]
+fn test_live_data_marks_seen() {
+    let state = FallbackState::new();
+    let before = Utc::now();
+    state.record_data_received();
+    assert!(state.seen_until().unwrap() >= before - chrono::Duration::milliseconds(1));
+}
+
+// Fallback Source Tests
+
+#[test]
+fn test_source_defaults_to_sedentary_log() {
+    assert_eq!(
+        parse_fallback_source(None, None),
+        Ok(FallbackSource::SedentaryLog)
+    );
+    assert_eq!(
+        parse_fallback_source(Some("sedentary_log"), Some(Uuid::new_v4())),
+        Ok(FallbackSource::SedentaryLog)
+    );
+}
+
+#[test]
+fn test_sensor_data_source_scoped_to_default_user() {
+    let user = Uuid::new_v4();
+    assert_eq!(
+        parse_fallback_source(Some("sensor_data"), Some(user)),
+        Ok(FallbackSource::SensorData(user))
+    );
+}
+
+#[test]
+fn test_sensor_data_source_needs_default_user() {
+    assert!(parse_fallback_source(Some("sensor_data"), None).is_err());
+}
+
+#[test]
+fn test_unknown_source_rejected() {
+    let err = parse_fallback_source(Some("redis"), None).unwrap_err();
+    assert!(err.contains("redis"));
+}
+
+#[test]
+fn test_log_row_conversion_fills_gaps() {
+    let reading = from_log_row(ActivityState::Sedentary, None, None, None, 1200);
+    assert_eq!(reading.timer, 0);
+    assert_eq!(reading.val, 0.0);
+    assert!(!reading.alert);
+    assert_eq!(reading.user_id, None);
+    assert!(reading.daily.is_none());
+    assert!(reading.replayed);
+}
+
+#[test]
+fn test_log_row_alert_derived_from_timer() {
+    let reading = from_log_row(
+        ActivityState::Sedentary,
+        Some(100_000),
+        Some(0.01),
+        None,
+        1200,
+    );
+    assert!(reading.alert);
+}
+
+#[test]
+fn test_backfill_alerts_thinned_by_cooldown() {
+    let mut readings: Vec<_> = (1195..1700)
+        .map(|timer| {
+            from_log_row(
+                ActivityState::Sedentary,
+                Some(timer),
+                Some(0.01),
+                None,
+                1200,
+            )
+        })
+        .collect();
+    apply_alert_cooldown(&mut readings, 300);
+    let alerting: Vec<u64> = readings
+        .iter()
+        .filter(|r| r.alert)
+        .map(|r| r.timer)
+        .collect();
+    assert_eq!(alerting, vec![1200, 1500]);
+}
+
+#[test]
+fn test_sensor_row_keeps_owner_and_alert() {
+    let user = Uuid::new_v4();
+    let at: DateTime<Utc> = "2026-01-23T10:00:00Z".parse().unwrap();
+    let reading = from_sensor_row(user, ActivityState::Active, 5, 0.3, true, at);
+    assert_eq!(reading.user_id, Some(user));
+    assert!(reading.alert);
+    assert_eq!(reading.timestamp, at);
+    assert_eq!(reading.timer, 5);
+    assert!(reading.replayed);
+}
@@ -1,6 +1,14 @@
+use crate::auth::{AuthError, AuthUser};
 use crate::state::AppState;
-use axum::{extract::State, http::StatusCode, response::Json};
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
 
 #[allow(non_snake_case)]
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -34,10 +42,149 @@ pub struct Reference {
     pub reference: String,
 }
 
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct FhirPatient {
+    pub resourceType: String,
+    pub id: String,
+    pub identifier: Vec<Identifier>,
+    pub name: Vec<HumanName>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Identifier {
+    pub system: String,
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct HumanName {
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub family: Option<String>,
+    pub given: Vec<String>,
+}
+
+/// Last word is the family name, the rest are given names; a single word is given only
+pub fn human_name(name: &str) -> HumanName {
+    let mut parts: Vec<String> = name.split_whitespace().map(str::to_string).collect();
+    let family = if parts.len() > 1 { parts.pop() } else { None };
+    HumanName {
+        text: name.trim().to_string(),
+        family,
+        given: parts,
+    }
+}
+
+/// Maps a `users` row to a minimal FHIR Patient
+pub fn patient_resource(user_id: Uuid, name: &str) -> FhirPatient {
+    FhirPatient {
+        resourceType: "Patient".to_string(),
+        id: user_id.to_string(),
+        identifier: vec![Identifier {
+            system: "urn:ietf:rfc:3986".to_string(),
+            value: format!("urn:uuid:{}", user_id),
+        }],
+        name: vec![human_name(name)],
+    }
+}
+
+/// FHIR `OperationOutcome` resource with a single issue
+pub fn operation_outcome(severity: &str, code: &str, diagnostics: &str) -> Value {
+    json!({
+        "resourceType": "OperationOutcome",
+        "issue": [{
+            "severity": severity,
+            "code": code,
+            "diagnostics": diagnostics
+        }]
+    })
+}
+
+/// Error response carrying an `OperationOutcome` as `application/fhir+json`
+pub fn fhir_error(status: StatusCode, code: &str, diagnostics: &str) -> Response {
+    (
+        status,
+        [(header::CONTENT_TYPE, "application/fhir+json")],
+        Json(operation_outcome("error", code, diagnostics)),
+    )
+        .into_response()
+}
+
+// GET /api/fhir/Patient/:user_id
+// Users may read their own Patient; admins may read any
+pub async fn get_patient(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    user: Result<AuthUser, AuthError>,
+) -> Response {
+    let Ok(user) = user else {
+        return fhir_error(StatusCode::UNAUTHORIZED, "login", "Authentication required");
+    };
+    let Ok(user_uuid) = Uuid::parse_str(&user_id) else {
+        return fhir_error(StatusCode::BAD_REQUEST, "invalid", "Invalid user ID format");
+    };
+    if user.user_id != user_uuid && user.role != "admin" {
+        return fhir_error(
+            StatusCode::FORBIDDEN,
+            "forbidden",
+            "Not permitted to read this Patient",
+        );
+    }
+
+    let row = sqlx::query!(
+        "SELECT user_id, name FROM users WHERE user_id = $1",
+        user_uuid
+    )
+    .fetch_optional(&state.db)
+    .await;
+
+    match row {
+        Ok(Some(row)) => (
+            [(header::CONTENT_TYPE, "application/fhir+json")],
+            Json(patient_resource(row.user_id, &row.name)),
+        )
+            .into_response(),
+        Ok(None) => fhir_error(
+            StatusCode::NOT_FOUND,
+            "not-found",
+            &format!("Patient/{} not found", user_uuid),
+        ),
+        Err(e) => {
+            eprintln!("Database error: {:?}", e);
+            fhir_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "exception",
+                "Failed to fetch patient",
+            )
+        }
+    }
+}
+
+/// Weak validator for the latest-observation response: it only changes when
+/// a newer `sedentary_log` row becomes the latest one
+pub fn latest_observation_etag(id: i32, created_at: Option<DateTime<Utc>>) -> String {
+    let millis = created_at.map_or(0, |t| t.timestamp_millis());
+    format!("W/\"{}-{}\"", id, millis)
+}
+
+/// Whether an `If-None-Match` header lists `etag` (weak comparison) or is `*`
+pub fn etag_matches(if_none_match: Option<&HeaderValue>, etag: &str) -> bool {
+    let Some(value) = if_none_match.and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    value
+        .split(',')
+        .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
+
 // GET /api/fhir/observation/latest
+// Sends an ETag; a matching If-None-Match gets 304 Not Modified without a body
 pub async fn get_latest_observation(
     State(state): State<AppState>,
-) -> Result<Json<Vec<FhirObservation>>, StatusCode> {
+    headers: HeaderMap,
+) -> Result<Response, Response> {
     // 1. Fetch the latest reading from the NEW table (sedentary_log)
     let rec = sqlx::query!(
         r#"
@@ -49,11 +196,32 @@ pub async fn get_latest_observation(
     )
     .fetch_optional(&state.db)
     .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .map_err(|e| {
+        eprintln!("Database error: {:?}", e);
+        fhir_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "exception",
+            "Failed to fetch latest observation",
+        )
+    })?;
 
     match rec {
         Some(row) => {
-            let timestamp = row.created_at.map(|t| t.to_rfc3339()).unwrap_or_default();
+            // Pollers revalidate every time; unchanged data costs a 304
+            let etag = latest_observation_etag(row.id, row.created_at);
+            let cache_headers = [
+                (header::ETAG, etag.clone()),
+                (header::CACHE_CONTROL, "no-cache".to_string()),
+            ];
+            if etag_matches(headers.get(header::IF_NONE_MATCH), &etag) {
+                return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+            }
+
+            let tz = state.config.timezone;
+            let timestamp = row
+                .created_at
+                .map(|t| t.with_timezone(&tz).to_rfc3339())
+                .unwrap_or_default();
             let id = row.id.to_string();
 
             // 2. Map "Sedentary State" to FHIR Observation
@@ -97,9 +265,13 @@ pub async fn get_latest_observation(
             };
 
             // Return both observations
-            Ok(Json(vec![state_obs, timer_obs]))
+            Ok((cache_headers, Json(vec![state_obs, timer_obs])).into_response())
         }
-        None => Err(StatusCode::NOT_FOUND),
+        None => Err(fhir_error(
+            StatusCode::NOT_FOUND,
+            "not-found",
+            "No observations recorded yet",
+        )),
     }
 }
 
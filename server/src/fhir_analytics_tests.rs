@@ -0,0 +1,479 @@
+use super::*;
+
+fn date(s: &str) -> NaiveDate {
+    s.parse().unwrap()
+}
+
+// Date Range Tests
+
+#[test]
+fn test_date_range_unfiltered() {
+    assert_eq!(date_range(None, None, date("2026-02-10")), Ok((None, None)));
+}
+
+#[test]
+fn test_date_range_start_only_ends_today() {
+    assert_eq!(
+        date_range(Some(date("2026-01-01")), None, date("2026-02-10")),
+        Ok((Some(date("2026-01-01")), Some(date("2026-02-10"))))
+    );
+}
+
+#[test]
+fn test_date_range_full_month() {
+    let range = date_range(
+        Some(date("2026-01-01")),
+        Some(date("2026-01-31")),
+        date("2026-02-10"),
+    );
+    assert_eq!(
+        range,
+        Ok((Some(date("2026-01-01")), Some(date("2026-01-31"))))
+    );
+}
+
+#[test]
+fn test_date_range_single_day() {
+    let day = Some(date("2026-01-15"));
+    assert_eq!(date_range(day, day, date("2026-02-10")), Ok((day, day)));
+}
+
+#[test]
+fn test_date_range_rejects_inverted() {
+    assert!(date_range(
+        Some(date("2026-02-01")),
+        Some(date("2026-01-01")),
+        date("2026-02-10")
+    )
+    .is_err());
+}
+
+#[test]
+fn test_date_range_end_only() {
+    assert_eq!(
+        date_range(None, Some(date("2026-01-31")), date("2026-02-10")),
+        Ok((None, Some(date("2026-01-31"))))
+    );
+}
+
+// Paging Link Tests
+
+fn relations(links: &[BundleLink]) -> Vec<&str> {
+    links.iter().map(|l| l.relation.as_str()).collect()
+}
+
+#[test]
+fn test_page_links_first_page() {
+    let links = page_links("/u", "period=daily", 0, 10, 25);
+    assert_eq!(relations(&links), vec!["self", "next"]);
+    assert_eq!(links[1].url, "/u?period=daily&_count=10&offset=10");
+}
+
+#[test]
+fn test_page_links_middle_page() {
+    let links = page_links("/u", "period=daily", 10, 10, 25);
+    assert_eq!(relations(&links), vec!["self", "previous", "next"]);
+    assert_eq!(links[1].url, "/u?period=daily&_count=10&offset=0");
+    assert_eq!(links[2].url, "/u?period=daily&_count=10&offset=20");
+}
+
+#[test]
+fn test_page_links_last_page() {
+    let links = page_links("/u", "period=daily", 20, 10, 25);
+    assert_eq!(relations(&links), vec!["self", "previous"]);
+}
+
+#[test]
+fn test_page_links_single_page() {
+    let links = page_links("/u", "period=daily", 0, 30, 5);
+    assert_eq!(relations(&links), vec!["self"]);
+    assert_eq!(links[0].url, "/u?period=daily&_count=30&offset=0");
+}
+
+#[test]
+fn test_page_links_previous_clamps_at_zero() {
+    let links = page_links("/u", "period=daily", 5, 10, 25);
+    assert_eq!(links[1].url, "/u?period=daily&_count=10&offset=0");
+}
+
+#[test]
+fn test_search_query_keeps_date_filter() {
+    assert_eq!(
+        search_query("weekly", Some(date("2026-01-01")), Some(date("2026-01-31"))),
+        "period=weekly&start=2026-01-01&end=2026-01-31"
+    );
+    assert_eq!(search_query("daily", None, None), "period=daily");
+}
+
+// Rollup Tests
+
+fn summary(date_str: &str) -> SummaryRow {
+    SummaryRow {
+        id: format!("weekly-{}", date_str),
+        date: date(date_str),
+        created_at: Utc::now(),
+        sedentary_minutes: 300.0,
+        fidget_minutes: 100.0,
+        active_minutes: 200.0,
+        dominant_state: "SEDENTARY".to_string(),
+        activity_score: 55,
+        alert_count: 4,
+        longest_sedentary_period: 2400,
+        days: 1,
+    }
+}
+
+#[test]
+fn test_rollup_parse() {
+    assert_eq!(Rollup::parse("daily"), Some(Rollup::Daily));
+    assert_eq!(Rollup::parse("weekly"), Some(Rollup::Weekly));
+    assert_eq!(Rollup::parse("monthly"), Some(Rollup::Monthly));
+    assert_eq!(Rollup::parse("yearly"), None);
+    assert_eq!(Rollup::parse("Weekly"), None);
+}
+
+#[test]
+fn test_rollup_week_end() {
+    // ISO week starting Monday 2026-01-05
+    assert_eq!(
+        Rollup::Weekly.period_end(date("2026-01-05")),
+        date("2026-01-11")
+    );
+}
+
+#[test]
+fn test_rollup_month_end() {
+    assert_eq!(
+        Rollup::Monthly.period_end(date("2026-02-01")),
+        date("2026-02-28")
+    );
+    assert_eq!(
+        Rollup::Monthly.period_end(date("2024-02-01")),
+        date("2024-02-29")
+    );
+    assert_eq!(
+        Rollup::Monthly.period_end(date("2026-12-01")),
+        date("2026-12-31")
+    );
+}
+
+#[test]
+fn test_rollup_observation_uses_effective_period() {
+    let obs = summary_observation(
+        &FhirConfig::default(),
+        Tz::UTC,
+        "u1",
+        Rollup::Weekly,
+        &summary("2026-01-05"),
+    );
+    let json = serde_json::to_value(&obs).unwrap();
+    assert!(json.get("effectiveDateTime").is_none());
+    assert_eq!(json["effectivePeriod"]["start"], "2026-01-05");
+    assert_eq!(json["effectivePeriod"]["end"], "2026-01-11");
+    assert_eq!(json["id"], "activity-summary-weekly-2026-01-05");
+}
+
+#[test]
+fn test_daily_observation_uses_effective_date_time() {
+    let obs = summary_observation(
+        &FhirConfig::default(),
+        Tz::UTC,
+        "u1",
+        Rollup::Daily,
+        &summary("2026-01-05"),
+    );
+    let json = serde_json::to_value(&obs).unwrap();
+    assert!(json.get("effectivePeriod").is_none());
+    assert!(json["effectiveDateTime"].is_string());
+    assert_eq!(json["subject"]["reference"], "Patient/u1");
+}
+
+#[test]
+fn test_daily_effective_date_time_in_local_zone() {
+    // 21:30 in Brisbane (UTC+10) is still 11:30 UTC on the same date
+    let mut row = summary("2026-03-10");
+    row.created_at = "2026-03-10T11:30:00Z".parse().unwrap();
+    let json = serde_json::to_value(summary_observation(
+        &FhirConfig::default(),
+        chrono_tz::Australia::Brisbane,
+        "u1",
+        Rollup::Daily,
+        &row,
+    ))
+    .unwrap();
+    assert_eq!(json["effectiveDateTime"], "2026-03-10T21:30:00+10:00");
+}
+
+// Sedentary Hours Tests
+
+#[test]
+fn test_sedentary_hours_partial_day() {
+    // 480 sedentary of 600 tracked minutes is 8 real hours, not 19.2 scaled ones
+    let mut row = summary("2026-01-05");
+    row.sedentary_minutes = 480.0;
+    assert!((sedentary_hours_per_day(&row) - 8.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_sedentary_hours_rollup_averages_days() {
+    let mut row = summary("2026-01-05");
+    row.sedentary_minutes = 7.0 * 480.0;
+    row.days = 7;
+    assert!((sedentary_hours_per_day(&row) - 8.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_sedentary_hours_empty_row() {
+    let mut row = summary("2026-01-05");
+    row.sedentary_minutes = 0.0;
+    row.days = 0;
+    assert_eq!(sedentary_hours_per_day(&row), 0.0);
+}
+
+#[test]
+fn test_observation_value_is_sedentary_hours() {
+    let mut row = summary("2026-01-05");
+    row.sedentary_minutes = 480.0;
+    let json = serde_json::to_value(summary_observation(
+        &FhirConfig::default(),
+        Tz::UTC,
+        "u1",
+        Rollup::Daily,
+        &row,
+    ))
+    .unwrap();
+    assert_eq!(json["valueQuantity"]["value"], 8.0);
+    assert_eq!(json["valueQuantity"]["unit"], "h/(24.h)");
+}
+
+// Component Tests
+
+fn component<'a>(json: &'a serde_json::Value, code: &str) -> &'a serde_json::Value {
+    json["component"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["code"]["coding"][0]["code"] == code)
+        .unwrap_or_else(|| panic!("missing component {}", code))
+}
+
+#[test]
+fn test_observation_minute_components() {
+    let json = serde_json::to_value(summary_observation(
+        &FhirConfig::default(),
+        Tz::UTC,
+        "u1",
+        Rollup::Daily,
+        &summary("2026-01-05"),
+    ))
+    .unwrap();
+    for (code, value) in [
+        ("CUSTOM-SEDENTARY-MINUTES", 300.0),
+        ("CUSTOM-FIDGET-MINUTES", 100.0),
+        ("CUSTOM-ACTIVE-MINUTES", 200.0),
+    ] {
+        let c = component(&json, code);
+        assert_eq!(c["valueQuantity"]["value"], value);
+        assert_eq!(c["valueQuantity"]["unit"], "min");
+        assert_eq!(c["valueQuantity"]["code"], "min");
+    }
+}
+
+#[test]
+fn test_observation_longest_sedentary_component() {
+    let json = serde_json::to_value(summary_observation(
+        &FhirConfig::default(),
+        Tz::UTC,
+        "u1",
+        Rollup::Daily,
+        &summary("2026-01-05"),
+    ))
+    .unwrap();
+    let c = component(&json, "CUSTOM-LONGEST-SEDENTARY");
+    assert_eq!(c["valueQuantity"]["value"], 2400.0);
+    assert_eq!(c["valueQuantity"]["unit"], "s");
+    assert_eq!(json["component"].as_array().unwrap().len(), 7);
+}
+
+// Streaming Bundle Tests
+
+fn streamed_bundle(total: i64, rows: &[SummaryRow]) -> String {
+    let bundle = FhirBundle {
+        resource_type: "Bundle".to_string(),
+        bundle_type: "searchset".to_string(),
+        total,
+        link: page_links("http://x/a", "period=daily", 0, 30, total),
+    };
+    let mut body = bundle_opening(&bundle).unwrap();
+    for (i, row) in rows.iter().enumerate() {
+        let observation =
+            summary_observation(&FhirConfig::default(), Tz::UTC, "u1", Rollup::Daily, row);
+        body.push_str(&bundle_entry_chunk(observation, i == 0).unwrap());
+    }
+    body.push_str(BUNDLE_CLOSE);
+    body
+}
+
+#[test]
+fn test_streamed_bundle_matches_whole_serialization() {
+    let rows = [summary("2026-01-06"), summary("2026-01-05")];
+    let body = streamed_bundle(2, &rows);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+
+    let entries: Vec<_> = rows
+        .iter()
+        .map(|row| {
+            serde_json::json!({
+                "resource": summary_observation(
+                    &FhirConfig::default(),
+                    Tz::UTC,
+                    "u1",
+                    Rollup::Daily,
+                    row,
+                )
+            })
+        })
+        .collect();
+    assert_eq!(json["resourceType"], "Bundle");
+    assert_eq!(json["type"], "searchset");
+    assert_eq!(json["total"], 2);
+    assert_eq!(json["link"][0]["relation"], "self");
+    assert_eq!(json["entry"], serde_json::Value::Array(entries));
+    // Key order is the same as when the Bundle was serialized in one piece
+    assert!(body.starts_with(r#"{"resourceType":"Bundle","type":"searchset","total":2,"link":["#));
+}
+
+#[test]
+fn test_streamed_bundle_without_rows() {
+    let body = streamed_bundle(0, &[]);
+    assert!(body.ends_with(r#","entry":[]}"#));
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["entry"].as_array().unwrap().len(), 0);
+}
+
+// $summary Tests
+
+#[test]
+fn test_summary_range_defaults_to_last_30_days() {
+    let today = date("2026-03-31");
+    assert_eq!(
+        summary_range(None, None, today),
+        Ok((date("2026-03-02"), today))
+    );
+    assert_eq!(
+        summary_range(None, Some(date("2026-01-30")), today),
+        Ok((date("2026-01-01"), date("2026-01-30")))
+    );
+    assert_eq!(
+        summary_range(Some(date("2026-03-20")), None, today),
+        Ok((date("2026-03-20"), today))
+    );
+}
+
+#[test]
+fn test_summary_range_rejects_inverted() {
+    assert!(summary_range(
+        Some(date("2026-02-01")),
+        Some(date("2026-01-01")),
+        date("2026-03-31")
+    )
+    .is_err());
+}
+
+fn period(days: i32) -> PeriodSummary {
+    PeriodSummary {
+        start: date("2026-01-01"),
+        end: date("2026-01-30"),
+        days,
+        mean_sedentary_hours: Some(6.5),
+        median_sedentary_hours: Some(6.0),
+        max_sedentary_hours: Some(9.25),
+        total_alerts: 12,
+        best_day: Some((date("2026-01-14"), 88)),
+        worst_day: Some((date("2026-01-03"), 21)),
+    }
+}
+
+#[test]
+fn test_period_summary_observation() {
+    let json = serde_json::to_value(period_summary_observation(
+        &FhirConfig::default(),
+        "u1",
+        &period(20),
+    ))
+    .unwrap();
+    assert_eq!(json["id"], "activity-summary-2026-01-01-2026-01-30");
+    assert_eq!(json["code"]["coding"][0]["code"], "87705-0");
+    assert_eq!(json["effectivePeriod"]["start"], "2026-01-01");
+    assert_eq!(json["effectivePeriod"]["end"], "2026-01-30");
+    assert!(json.get("effectiveDateTime").is_none());
+    assert_eq!(json["valueQuantity"]["value"], 6.5);
+    assert_eq!(json["valueQuantity"]["unit"], "h/(24.h)");
+
+    assert_eq!(component(&json, "CUSTOM-DAY-COUNT")["valueInteger"], 20);
+    assert_eq!(component(&json, "CUSTOM-ALERT-COUNT")["valueInteger"], 12);
+    assert_eq!(
+        component(&json, "CUSTOM-MEDIAN-SEDENTARY-HOURS")["valueQuantity"]["value"],
+        6.0
+    );
+    assert_eq!(
+        component(&json, "CUSTOM-MAX-SEDENTARY-HOURS")["valueQuantity"]["value"],
+        9.25
+    );
+    assert_eq!(
+        component(&json, "CUSTOM-BEST-DAY")["valueString"],
+        "2026-01-14"
+    );
+    assert_eq!(
+        component(&json, "CUSTOM-BEST-DAY-SCORE")["valueInteger"],
+        88
+    );
+    assert_eq!(
+        component(&json, "CUSTOM-WORST-DAY")["valueString"],
+        "2026-01-03"
+    );
+    assert_eq!(
+        component(&json, "CUSTOM-WORST-DAY-SCORE")["valueInteger"],
+        21
+    );
+}
+
+#[test]
+fn test_empty_period_summary_has_only_counts() {
+    let empty = PeriodSummary {
+        mean_sedentary_hours: None,
+        median_sedentary_hours: None,
+        max_sedentary_hours: None,
+        total_alerts: 0,
+        best_day: None,
+        worst_day: None,
+        ..period(0)
+    };
+    let json = serde_json::to_value(period_summary_observation(
+        &FhirConfig::default(),
+        "u1",
+        &empty,
+    ))
+    .unwrap();
+    assert!(json["valueQuantity"].is_null());
+    assert_eq!(json["component"].as_array().unwrap().len(), 2);
+    assert_eq!(component(&json, "CUSTOM-DAY-COUNT")["valueInteger"], 0);
+}
+
+// Query Timing Tests
+
+#[test]
+fn test_server_timing_in_milliseconds() {
+    use std::time::Duration;
+    assert_eq!(server_timing(Duration::from_micros(12_345)), "db;dur=12.3");
+    assert_eq!(server_timing(Duration::ZERO), "db;dur=0.0");
+}
+
+#[test]
+fn test_warn_if_slow_only_past_threshold() {
+    use std::time::Duration;
+    let threshold = Duration::from_millis(500);
+    assert!(!warn_if_slow("test", Duration::from_millis(499), threshold));
+    assert!(!warn_if_slow("test", threshold, threshold));
+    assert!(warn_if_slow("test", Duration::from_millis(501), threshold));
+}
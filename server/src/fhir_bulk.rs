@@ -0,0 +1,150 @@
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::auth::{AuthError, AuthUser};
+use crate::fhir::fhir_error;
+use crate::fhir_analytics::{summary_observation, Rollup, SummaryRow};
+use crate::state::AppState;
+
+// Rows fetched per keyset page while streaming the export
+const EXPORT_PAGE_SIZE: i64 = 500;
+
+#[derive(Debug, Deserialize)]
+pub struct ExportParams {
+    #[serde(rename = "_since")]
+    since: Option<String>,
+}
+
+/// Parses `_since` as a FHIR instant (RFC 3339 with offset)
+pub fn parse_since(since: Option<&str>) -> Result<Option<DateTime<Utc>>, &'static str> {
+    match since {
+        None => Ok(None),
+        Some(value) => DateTime::parse_from_rfc3339(value)
+            .map(|t| Some(t.with_timezone(&Utc)))
+            .map_err(|_| "_since must be an RFC 3339 instant, e.g. 2026-01-01T00:00:00Z"),
+    }
+}
+
+/// Every user's daily activity-summary Observations as FHIR Bulk Data NDJSON
+/// Endpoint: GET /api/fhir/$export (admin only), optional `_since` for incremental exports
+pub async fn bulk_export(
+    State(state): State<AppState>,
+    Query(params): Query<ExportParams>,
+    user: Result<AuthUser, AuthError>,
+) -> Response {
+    let Ok(user) = user else {
+        return fhir_error(StatusCode::UNAUTHORIZED, "login", "Authentication required");
+    };
+    if user.role != "admin" {
+        return fhir_error(
+            StatusCode::FORBIDDEN,
+            "forbidden",
+            "Bulk export requires an admin",
+        );
+    }
+    let since = match parse_since(params.since.as_deref()) {
+        Ok(since) => since,
+        Err(message) => return fhir_error(StatusCode::BAD_REQUEST, "invalid", message),
+    };
+
+    let pool = state.db.clone();
+    let config = state.config.clone();
+    let body = async_stream::stream! {
+        // Keyset pagination on id keeps one page in memory at a time
+        let mut last_id = 0;
+        loop {
+            let page = sqlx::query!(
+                r#"
+                SELECT
+                    id,
+                    user_id AS "user_id!",
+                    date,
+                    sedentary_minutes,
+                    fidget_minutes,
+                    active_minutes,
+                    dominant_state,
+                    activity_score,
+                    alert_count,
+                    longest_sedentary_period,
+                    created_at
+                FROM activity_summary
+                WHERE period_type = 'daily' AND user_id IS NOT NULL
+                  AND ($1::timestamptz IS NULL OR created_at > $1)
+                  AND id > $2
+                ORDER BY id
+                LIMIT $3
+                "#,
+                since,
+                last_id,
+                EXPORT_PAGE_SIZE
+            )
+            .fetch_all(&pool)
+            .await;
+
+            let rows = match page {
+                Ok(rows) => rows,
+                Err(e) => {
+                    // Abort the transfer so the client sees a truncated download
+                    eprintln!("Database error during FHIR bulk export: {:?}", e);
+                    yield Err(e);
+                    break;
+                }
+            };
+            let Some(last) = rows.last() else {
+                break;
+            };
+            last_id = last.id;
+            let full_page = rows.len() as i64 == EXPORT_PAGE_SIZE;
+
+            let mut chunk = String::new();
+            for row in rows {
+                let user_id = row.user_id.to_string();
+                let summary = SummaryRow {
+                    id: row.id.to_string(),
+                    date: row.date,
+                    created_at: row.created_at,
+                    sedentary_minutes: row.sedentary_minutes,
+                    fidget_minutes: row.fidget_minutes,
+                    active_minutes: row.active_minutes,
+                    dominant_state: row.dominant_state,
+                    activity_score: row.activity_score,
+                    alert_count: row.alert_count,
+                    longest_sedentary_period: row.longest_sedentary_period,
+                    days: 1,
+                };
+                let observation = summary_observation(
+                    &config.fhir,
+                    config.timezone,
+                    &user_id,
+                    Rollup::Daily,
+                    &summary,
+                );
+                if let Ok(line) = serde_json::to_string(&observation) {
+                    chunk.push_str(&line);
+                    chunk.push('\n');
+                }
+            }
+            yield Ok::<_, sqlx::Error>(chunk);
+
+            if !full_page {
+                break;
+            }
+        }
+    };
+
+    (
+        [(header::CONTENT_TYPE, "application/fhir+ndjson")],
+        Body::from_stream(body),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+#[path = "fhir_bulk_tests.rs"]
+mod tests;
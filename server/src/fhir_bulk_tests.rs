@@ -0,0 +1,28 @@
+use super::*;
+
+// Since Parameter Tests
+
+#[test]
+fn test_parse_since_absent() {
+    assert_eq!(parse_since(None), Ok(None));
+}
+
+#[test]
+fn test_parse_since_utc() {
+    let since = parse_since(Some("2026-01-01T00:00:00Z")).unwrap().unwrap();
+    assert_eq!(since.to_rfc3339(), "2026-01-01T00:00:00+00:00");
+}
+
+#[test]
+fn test_parse_since_converts_offset() {
+    let since = parse_since(Some("2026-01-01T02:00:00+02:00"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(since.to_rfc3339(), "2026-01-01T00:00:00+00:00");
+}
+
+#[test]
+fn test_parse_since_rejects_bare_date() {
+    assert!(parse_since(Some("2026-01-01")).is_err());
+    assert!(parse_since(Some("yesterday")).is_err());
+}
@@ -1,4 +1,5 @@
 use super::*;
+use chrono::TimeZone;
 
 #[test]
 fn test_coding_serialization() {
@@ -204,3 +205,84 @@ fn test_fhir_observation_clone() {
     let cloned = obs.clone();
     assert_eq!(obs, cloned);
 }
+
+// Patient Resource Tests
+
+#[test]
+fn test_human_name_splits_family_and_given() {
+    let name = human_name("Ada Byron Lovelace");
+    assert_eq!(name.family.as_deref(), Some("Lovelace"));
+    assert_eq!(name.given, vec!["Ada", "Byron"]);
+    assert_eq!(name.text, "Ada Byron Lovelace");
+}
+
+#[test]
+fn test_human_name_single_word_is_given() {
+    let name = human_name("Cher");
+    assert_eq!(name.family, None);
+    assert_eq!(name.given, vec!["Cher"]);
+}
+
+#[test]
+fn test_patient_resource_shape() {
+    let id = Uuid::new_v4();
+    let json = serde_json::to_value(patient_resource(id, "Grace Hopper")).unwrap();
+
+    assert_eq!(json["resourceType"], "Patient");
+    assert_eq!(json["id"], id.to_string());
+    assert_eq!(json["identifier"][0]["value"], format!("urn:uuid:{}", id));
+    assert_eq!(json["name"][0]["family"], "Hopper");
+    assert_eq!(json["name"][0]["given"][0], "Grace");
+}
+
+#[test]
+fn test_operation_outcome_shape() {
+    let outcome = operation_outcome("error", "not-found", "Patient/x not found");
+    assert_eq!(outcome["resourceType"], "OperationOutcome");
+    assert_eq!(outcome["issue"][0]["severity"], "error");
+    assert_eq!(outcome["issue"][0]["code"], "not-found");
+    assert_eq!(outcome["issue"][0]["diagnostics"], "Patient/x not found");
+}
+
+#[test]
+fn test_fhir_error_keeps_status_and_sets_fhir_content_type() {
+    let response = fhir_error(StatusCode::BAD_REQUEST, "invalid", "Invalid user ID format");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/fhir+json"
+    );
+}
+
+// Conditional GET Tests
+
+#[test]
+fn test_latest_observation_etag_is_weak_and_changes_with_row() {
+    let at = Utc.with_ymd_and_hms(2026, 1, 6, 12, 0, 0).unwrap();
+    let etag = latest_observation_etag(42, Some(at));
+    assert!(etag.starts_with("W/\"42-"));
+    assert_eq!(etag, latest_observation_etag(42, Some(at)));
+    assert_ne!(etag, latest_observation_etag(43, Some(at)));
+    assert_ne!(
+        etag,
+        latest_observation_etag(42, Some(at + chrono::Duration::milliseconds(1)))
+    );
+}
+
+#[test]
+fn test_etag_matches_if_none_match() {
+    let etag = "W/\"42-1000\"";
+    let header = |value: &str| HeaderValue::from_str(value).unwrap();
+
+    assert!(etag_matches(Some(&header(etag)), etag));
+    // Weak comparison ignores the W/ prefix
+    assert!(etag_matches(Some(&header("\"42-1000\"")), etag));
+    assert!(etag_matches(
+        Some(&header("\"other\", W/\"42-1000\"")),
+        etag
+    ));
+    assert!(etag_matches(Some(&header("*")), etag));
+
+    assert!(!etag_matches(Some(&header("W/\"41-1000\"")), etag));
+    assert!(!etag_matches(None, etag));
+}
@@ -0,0 +1,99 @@
+use crate::state::AppState;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use serde::Serialize;
+use serde_json::json;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// Outcome of one dependency probe
+#[derive(Debug, Serialize)]
+pub struct DependencyStatus {
+    pub up: bool,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Times `probe`, treating an error or a timeout as the dependency being down
+pub async fn check<F>(probe: F, timeout: Duration) -> DependencyStatus
+where
+    F: Future<Output = Result<(), String>>,
+{
+    let started = Instant::now();
+    let result = match tokio::time::timeout(timeout, probe).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("timed out after {}ms", timeout.as_millis())),
+    };
+    DependencyStatus {
+        up: result.is_ok(),
+        latency_ms: started.elapsed().as_millis() as u64,
+        error: result.err(),
+    }
+}
+
+/// 200 only when every critical dependency answered
+pub fn readiness_status(dependencies: &[&DependencyStatus]) -> StatusCode {
+    if dependencies.iter().all(|d| d.up) {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
+}
+
+async fn ping_postgres(state: &AppState) -> Result<(), String> {
+    sqlx::query("SELECT 1")
+        .execute(&state.db)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+async fn ping_redis(state: &AppState) -> Result<(), String> {
+    let mut con = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(|e| e.to_string())?;
+    redis::cmd("PING")
+        .query_async::<_, String>(&mut con)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Readiness probe: Postgres `SELECT 1` and Redis `PING`, 503 if either fails.
+/// Each check is bounded by HEALTH_CHECK_TIMEOUT_MS.
+/// Endpoint: GET /health
+pub async fn health_check(State(state): State<AppState>) -> impl IntoResponse {
+    let timeout = state.config.server.health_check_timeout;
+    let (postgres, redis) = tokio::join!(
+        check(ping_postgres(&state), timeout),
+        check(ping_redis(&state), timeout)
+    );
+
+    let status = readiness_status(&[&postgres, &redis]);
+    let body = json!({
+        "status": if status.is_success() { "healthy" } else { "unhealthy" },
+        "dependencies": {
+            "postgres": postgres,
+            "redis": redis,
+        },
+        "stream_connections": state.metrics.stream_connections(),
+        "max_stream_connections": state.config.server.max_stream_connections,
+    });
+    (status, Json(body))
+}
+
+/// Liveness probe: answers as long as the process serves requests
+/// Endpoint: GET /health/live
+pub async fn liveness() -> &'static str {
+    "Status: Healthy"
+}
+
+#[cfg(test)]
+#[path = "health_tests.rs"]
+mod tests;
@@ -0,0 +1,77 @@
+use super::*;
+
+// Dependency Check Tests
+
+#[tokio::test]
+async fn test_successful_probe_is_up() {
+    let status = check(async { Ok(()) }, Duration::from_secs(1)).await;
+    assert!(status.up);
+    assert!(status.error.is_none());
+}
+
+#[tokio::test]
+async fn test_failed_probe_reports_error() {
+    let status = check(
+        async { Err("connection refused".to_string()) },
+        Duration::from_secs(1),
+    )
+    .await;
+    assert!(!status.up);
+    assert_eq!(status.error.as_deref(), Some("connection refused"));
+}
+
+#[tokio::test]
+async fn test_slow_probe_times_out() {
+    let status = check(
+        async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        },
+        Duration::from_millis(20),
+    )
+    .await;
+    assert!(!status.up);
+    assert!(status.error.unwrap().contains("timed out"));
+    assert!(status.latency_ms < 5000);
+}
+
+#[test]
+fn test_up_status_omits_error_field() {
+    let status = DependencyStatus {
+        up: true,
+        latency_ms: 3,
+        error: None,
+    };
+    let json = serde_json::to_value(&status).unwrap();
+    assert_eq!(json, serde_json::json!({"up": true, "latency_ms": 3}));
+}
+
+// Readiness Tests
+
+#[test]
+fn test_ready_when_all_dependencies_up() {
+    let up = DependencyStatus {
+        up: true,
+        latency_ms: 1,
+        error: None,
+    };
+    assert_eq!(readiness_status(&[&up, &up]), StatusCode::OK);
+}
+
+#[test]
+fn test_unavailable_when_any_dependency_down() {
+    let up = DependencyStatus {
+        up: true,
+        latency_ms: 1,
+        error: None,
+    };
+    let down = DependencyStatus {
+        up: false,
+        latency_ms: 2000,
+        error: Some("timed out".to_string()),
+    };
+    assert_eq!(
+        readiness_status(&[&up, &down]),
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+}
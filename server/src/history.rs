@@ -0,0 +1,233 @@
+use crate::{auth::AdminUser, replay, state::AppState};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use futures::StreamExt;
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Combined history list SSE/WebSocket clients replay on connect
+pub const SENSOR_HISTORY_KEY: &str = "sensor_history";
+
+// Matches the combined list and its per-port and replay variants
+const HISTORY_KEY_PATTERN: &str = "sensor_history*";
+
+/// Which cached readings a connection is sent on connect: live
+/// `sensor_history` by default, or one running replay's (`?replay=<id>`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HistorySource {
+    #[default]
+    Live,
+    Replay(Uuid),
+}
+
+impl HistorySource {
+    pub fn from_query(replay: Option<Uuid>) -> Self {
+        replay.map_or(Self::Live, Self::Replay)
+    }
+}
+
+/// `?replay=<id>` on `/events`, `/ws` and GET /api/history
+#[derive(Debug, Default, Deserialize)]
+pub struct HistoryQuery {
+    pub replay: Option<Uuid>,
+}
+
+// Entries returned by GET /api/history without ?limit=
+const DEFAULT_INSPECT_LIMIT: isize = 50;
+
+/// LPUSH + LTRIM per key, keeping `limit` readings (SENSOR_HISTORY_LIMIT).
+/// `readings` go oldest first, so the newest ends up at the head of each list.
+pub fn history_pipeline<K: AsRef<str>>(
+    keys: &[K],
+    readings: &[String],
+    limit: isize,
+) -> redis::Pipeline {
+    let mut pipe = redis::pipe();
+    for key in keys {
+        pipe.lpush(key.as_ref(), readings)
+            .ignore()
+            .ltrim(key.as_ref(), 0, limit - 1)
+            .ignore();
+    }
+    pipe
+}
+
+/// The one history write shared by the serial, replay and fallback paths
+pub async fn push_history<K: AsRef<str>>(
+    con: &mut MultiplexedConnection,
+    keys: &[K],
+    readings: &[String],
+    limit: isize,
+) -> redis::RedisResult<()> {
+    if readings.is_empty() {
+        return Ok(());
+    }
+    history_pipeline(keys, readings, limit)
+        .query_async(con)
+        .await
+}
+
+/// The `timestamp` of a serialized reading, which doubles as its SSE event id
+pub fn reading_timestamp(payload: &str) -> Option<String> {
+    serde_json::from_str::<Value>(payload)
+        .ok()?
+        .get("timestamp")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Merges replayed history (oldest first) with live readings buffered while
+/// the replay ran; buffered readings already in the history are dropped. A
+/// duplicate is the exact same message, not just the same timestamp: two
+/// devices or users can report in the same second.
+pub fn merge_history(history: Vec<String>, buffered: Vec<String>) -> Vec<String> {
+    let replayed: HashSet<&str> = history.iter().map(String::as_str).collect();
+    let fresh: Vec<String> = buffered
+        .into_iter()
+        .filter(|m| !replayed.contains(m.as_str()))
+        .collect();
+    history.into_iter().chain(fresh).collect()
+}
+
+/// What a newly connected SSE or WebSocket client is sent before going live,
+/// oldest first: the latest SENSOR_HISTORY_LIMIT readings of `source` from Redis, then the
+/// readings `rx` buffered meanwhile, minus duplicates. Subscribe `rx` before
+/// calling so nothing falls between the replay and the live stream. Empty
+/// (leaving `rx` untouched) with SKIP_HISTORY=true or when Redis is unreachable.
+pub async fn replay_on_connect(
+    state: &AppState,
+    rx: &mut broadcast::Receiver<String>,
+    source: HistorySource,
+) -> Vec<String> {
+    if state.config.server.skip_history {
+        return Vec::new();
+    }
+    let Ok(mut con) = state.redis.get_multiplexed_async_connection().await else {
+        eprintln!("Failed to connect to Redis for stream history");
+        return Vec::new();
+    };
+
+    let limit = state.config.server.history_limit;
+    let history: Vec<String> = con
+        .lrange(replay::history_key(&state.replays, source), 0, limit - 1)
+        .await
+        .unwrap_or_else(|e| {
+            eprintln!("Redis error fetching history: {:?}", e);
+            vec![]
+        });
+
+    // Readings that arrived while the history was being fetched
+    let mut buffered = Vec::new();
+    while let Ok(msg) = rx.try_recv() {
+        buffered.push(msg);
+    }
+
+    // Reversed because lpush stores newest first
+    merge_history(history.into_iter().rev().collect(), buffered)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InspectQuery {
+    limit: Option<isize>,
+    replay: Option<Uuid>,
+}
+
+/// `?limit=` clamped to 1..=SENSOR_HISTORY_LIMIT
+pub fn inspect_limit(limit: Option<isize>, history_limit: isize) -> isize {
+    limit
+        .unwrap_or(DEFAULT_INSPECT_LIMIT)
+        .clamp(1, history_limit.max(1))
+}
+
+fn redis_unavailable(e: redis::RedisError) -> Response {
+    eprintln!("Redis error on history endpoint: {:?}", e);
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(json!({"error": "Redis unavailable"})),
+    )
+        .into_response()
+}
+
+/// The cached readings new SSE/WebSocket clients are replayed, newest first
+/// (a running replay's with `?replay=<id>`). Entries that aren't JSON are
+/// returned as strings.
+/// Endpoint: GET /api/history?limit=N (admin only)
+pub async fn get_history(
+    _admin: AdminUser,
+    State(state): State<AppState>,
+    Query(params): Query<InspectQuery>,
+) -> Response {
+    let key = replay::history_key(&state.replays, HistorySource::from_query(params.replay));
+    let limit = inspect_limit(params.limit, state.config.server.history_limit);
+    let result = async {
+        let mut con = state.redis.get_multiplexed_async_connection().await?;
+        redis::pipe()
+            .llen(&key)
+            .lrange(&key, 0, limit - 1)
+            .query_async::<_, (usize, Vec<String>)>(&mut con)
+            .await
+    }
+    .await;
+
+    match result {
+        Ok((total, entries)) => {
+            let entries: Vec<Value> = entries
+                .into_iter()
+                .map(|e| serde_json::from_str(&e).unwrap_or(Value::String(e)))
+                .collect();
+            Json(json!({"key": key, "total": total, "entries": entries})).into_response()
+        }
+        Err(e) => redis_unavailable(e),
+    }
+}
+
+/// Deletes `sensor_history` and every per-port/replay variant, so new
+/// clients start without stale readings. Reports the entries removed.
+/// Endpoint: POST /api/history/clear (admin only)
+pub async fn clear_history(_admin: AdminUser, State(state): State<AppState>) -> Response {
+    let result = async {
+        let mut con = state.redis.get_multiplexed_async_connection().await?;
+        let keys: Vec<String> = con
+            .scan_match::<_, String>(HISTORY_KEY_PATTERN)
+            .await?
+            .collect()
+            .await;
+        if keys.is_empty() {
+            return Ok((keys, 0));
+        }
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for key in &keys {
+            pipe.llen(key);
+        }
+        pipe.del(&keys).ignore();
+        let lengths: Vec<usize> = pipe.query_async(&mut con).await?;
+        Ok((keys, lengths.into_iter().sum::<usize>()))
+    }
+    .await;
+
+    match result {
+        Ok((keys, removed)) => {
+            println!(
+                "Cleared sensor history: {} entries in {} key(s)",
+                removed,
+                keys.len()
+            );
+            Json(json!({"keys": keys, "removed": removed})).into_response()
+        }
+        Err(e) => redis_unavailable(e),
+    }
+}
+
+#[cfg(test)]
+#[path = "history_tests.rs"]
+mod tests;
@@ -0,0 +1,99 @@
+use super::*;
+use crate::config::ServerConfig;
+
+fn packed(keys: &[&str], readings: &[&str]) -> String {
+    let readings: Vec<String> = readings.iter().map(|r| r.to_string()).collect();
+    let limit = ServerConfig::default().history_limit;
+    String::from_utf8_lossy(&history_pipeline(keys, &readings, limit).get_packed_pipeline())
+        .into_owned()
+}
+
+fn reading_at(second: u32) -> String {
+    serde_json::json!({
+        "state": "ACTIVE",
+        "timer": 0,
+        "val": 0.0,
+        "alert": false,
+        "timestamp": format!("2026-01-01T00:00:{second:02}Z")
+    })
+    .to_string()
+}
+
+// History Pipeline Tests
+
+#[test]
+fn test_pipeline_trims_to_shared_limit() {
+    let commands = packed(&[SENSOR_HISTORY_KEY], &["{\"a\":1}"]);
+    assert!(commands.contains("LPUSH"));
+    assert!(commands.contains("LTRIM"));
+    // Same cap the SSE history read uses, never a hardcoded 100
+    let stop = (ServerConfig::default().history_limit - 1).to_string();
+    assert!(commands.contains(&format!("${}\r\n{}\r\n", stop.len(), stop)));
+}
+
+#[test]
+fn test_pipeline_writes_every_key() {
+    let commands = packed(&[SENSOR_HISTORY_KEY, "sensor_history:COM3"], &["r1", "r2"]);
+    assert_eq!(commands.matches("LPUSH").count(), 2);
+    assert_eq!(commands.matches("LTRIM").count(), 2);
+    assert!(commands.contains("sensor_history:COM3"));
+}
+
+#[test]
+fn test_default_history_limit() {
+    assert_eq!(ServerConfig::default().history_limit, 500);
+}
+
+// History Handoff Tests
+
+#[test]
+fn test_merge_history_burst_at_boundary() {
+    // Readings 1-3 are in Redis; a burst of 2-5 hit the channel during the replay
+    let history: Vec<String> = (1..=3).map(reading_at).collect();
+    let buffered: Vec<String> = (2..=5).map(reading_at).collect();
+
+    let merged = merge_history(history, buffered);
+
+    let expected: Vec<String> = (1..=5).map(reading_at).collect();
+    assert_eq!(merged, expected, "no gap and no duplicate at the handoff");
+}
+
+#[test]
+fn test_merge_history_empty_history_keeps_buffer() {
+    let buffered: Vec<String> = (1..=2).map(reading_at).collect();
+    assert_eq!(merge_history(vec![], buffered.clone()), buffered);
+}
+
+#[test]
+fn test_merge_history_keeps_distinct_readings_sharing_a_timestamp() {
+    // Another device reported in the same second as the last replayed reading
+    let history = vec![reading_at(3)];
+    let other_device = reading_at(3).replace("\"ACTIVE\"", "\"SEDENTARY\"");
+    let buffered = vec![reading_at(3), other_device.clone()];
+
+    assert_eq!(
+        merge_history(history, buffered),
+        vec![reading_at(3), other_device]
+    );
+}
+
+#[test]
+fn test_reading_timestamp() {
+    assert_eq!(
+        reading_timestamp(&reading_at(7)).as_deref(),
+        Some("2026-01-01T00:00:07Z")
+    );
+    assert_eq!(reading_timestamp("{\"state\":\"ACTIVE\"}"), None);
+    assert_eq!(reading_timestamp("not json"), None);
+}
+
+// Inspect Limit Tests
+
+#[test]
+fn test_inspect_limit_defaults_and_clamps() {
+    assert_eq!(inspect_limit(None, 500), 50);
+    assert_eq!(inspect_limit(Some(0), 500), 1);
+    assert_eq!(inspect_limit(Some(-5), 500), 1);
+    assert_eq!(inspect_limit(Some(10_000), 500), 500);
+    assert_eq!(inspect_limit(None, 20), 20);
+}
@@ -0,0 +1,201 @@
+use axum::{
+    extract::DefaultBodyLimit,
+    routing::{get, post, put},
+    Router,
+};
+use state::AppState;
+use tower_http::compression::{
+    predicate::{NotForContentType, Predicate, SizeAbove},
+    CompressionLayer,
+};
+use tower_http::services::ServeDir;
+
+pub mod alerts;
+pub mod audit;
+pub mod auth;
+pub mod calibration;
+pub mod config;
+pub mod cors;
+pub mod daily_summary;
+pub mod daily_totals;
+pub mod db_worker;
+pub mod export;
+pub mod fallback;
+pub mod fhir;
+pub mod fhir_analytics;
+pub mod fhir_bulk;
+pub mod health;
+pub mod history;
+pub mod login;
+pub mod logout;
+pub mod metrics;
+pub mod models;
+pub mod password_reset;
+pub mod profile;
+pub mod refresh;
+pub mod replay;
+pub mod request_trace;
+pub mod retention;
+pub mod security_headers;
+pub mod serial;
+pub mod shutdown;
+pub mod signup;
+pub mod simulation;
+pub mod sse;
+pub mod state;
+pub mod state_change;
+pub mod stats;
+pub mod synthetic;
+pub mod websocket;
+
+/// Every route with its middleware, served from `state`. Connects to nothing
+/// itself, so tests can drive it with `tower::ServiceExt::oneshot`.
+pub fn build_app(state: AppState) -> Router {
+    let config = state.config.clone();
+    let app = Router::new()
+        // Real-Time Streaming (SSE primary, WebSocket fallback with the same history replay)
+        .route("/events", get(sse::sse_handler))
+        .route("/ws", get(websocket::ws_handler))
+        // FHIR Compliance API
+        .route(
+            "/api/fhir/observation/latest",
+            get(fhir::get_latest_observation),
+        )
+        // FHIR Patient (resolves Observation subject references)
+        .route("/api/fhir/Patient/:user_id", get(fhir::get_patient))
+        // FHIR Analytics API (LOINC 87705-0)
+        .route(
+            "/api/fhir/analytics/user/:user_id",
+            get(fhir_analytics::get_user_analytics),
+        )
+        .route(
+            "/api/fhir/analytics/user/:user_id/$summary",
+            get(fhir_analytics::get_user_analytics_summary),
+        )
+        .route(
+            "/api/fhir/analytics/latest",
+            get(fhir_analytics::get_latest_analytics),
+        )
+        // Recompute one day's activity_summary rows now (admin only)
+        .route(
+            "/api/analytics/summarize",
+            post(daily_summary::run_daily_summary),
+        )
+        // FHIR Bulk Data NDJSON export (admin only)
+        .route("/api/fhir/$export", get(fhir_bulk::bulk_export))
+        // Activity summary CSV export (own data, or any user as admin)
+        .route("/api/export/user/:file", get(export::export_user_csv))
+        // Raw sedentary_log as a replayable log file (admin only)
+        .route("/api/export/log", get(export::export_log))
+        // Signup form + handler
+        .route(
+            "/signup",
+            get(signup::show_signup_form).post(signup::signup_handler),
+        )
+        // Bulk demo/test accounts (admin only, SEEDING_ENABLED)
+        .route("/api/admin/seed-users", post(signup::seed_users))
+        // Email verification (token issued at signup)
+        .route("/auth/verify", get(signup::verify_email_handler))
+        // Login form + handler
+        .route(
+            "/login",
+            get(login::show_login_form).post(login::login_handler),
+        )
+        // Logout (revokes the current token)
+        .route("/logout", post(logout::logout_handler))
+        .route("/logout/all", post(logout::logout_all_handler))
+        // Access token renewal (rotates the refresh token)
+        .route("/auth/refresh", post(refresh::refresh_handler))
+        // Current user profile
+        .route("/auth/me", get(profile::get_current_user))
+        // Authentication audit trail (admin only)
+        .route("/auth/audit", get(audit::get_audit_log))
+        // Password reset (single-use, 15-minute tokens)
+        .route(
+            "/auth/forgot-password",
+            post(password_reset::forgot_password_handler),
+        )
+        .route(
+            "/auth/reset-password",
+            post(password_reset::reset_password_handler),
+        )
+        // Protected stats endpoint
+        .route("/stats", get(stats::get_user_stats))
+        // Sedentary alert history for one day (own data, or any user as admin)
+        .route("/api/alerts/user/:user_id", get(alerts::get_user_alerts))
+        // Threshold calibration from live readings (admin only)
+        .route("/api/calibrate", post(calibration::calibrate))
+        // Live threshold tuning (admin only)
+        .route(
+            "/api/config/thresholds",
+            put(calibration::update_thresholds),
+        )
+        // Cached stream history (admin only)
+        .route("/api/history", get(history::get_history))
+        .route("/api/history/clear", post(history::clear_history))
+        // Serial line counters (malformed lines, parse failures, resyncs)
+        .route("/api/serial/metrics", get(serial::get_serial_metrics))
+        // Hardware/fallback status for ops
+        .route("/api/fallback/status", get(fallback::get_fallback_status))
+        // Retention settings and the last prune of raw readings (admin only)
+        .route("/api/retention/status", get(retention::retention_status))
+        .route("/api/fallback/trigger", post(fallback::trigger_fallback))
+        .route("/api/fallback/pause", post(fallback::pause_fallback))
+        .route("/api/fallback/resume", post(fallback::resume_fallback))
+        // Prometheus scrape endpoint
+        .route("/metrics", get(metrics::get_metrics))
+        // Health Check
+        .route("/health", get(health::health_check))
+        .route("/health/live", get(health::liveness))
+        // Replay log data for testing/demo
+        .route("/api/replay", get(replay::start_replay))
+        .route(
+            "/api/replay/upload",
+            post(replay::upload_replay)
+                .layer(DefaultBodyLimit::max(config.replay.upload_body_limit())),
+        )
+        .route("/api/replay/:id/status", get(replay::replay_status))
+        .route("/api/replay/:id/pause", post(replay::pause_replay))
+        .route("/api/replay/:id/resume", post(replay::resume_replay))
+        .route("/api/replay/:id/stop", post(replay::stop_replay))
+        // Frontend Hosting
+        .nest_service("/", ServeDir::new(&config.server.frontend_dir))
+        .with_state(state);
+
+    // Optional gzip/deflate (some proxies mishandle compressed SSE)
+    let app = if config.server.compression {
+        println!("Response compression enabled");
+        app.layer(compression_layer())
+    } else {
+        app
+    };
+
+    // Cross-origin access for a separately hosted dashboard (ALLOWED_ORIGINS)
+    let app = match cors::cors_layer(&config.server.allowed_origins) {
+        Some(layer) => {
+            println!("CORS enabled for: {}", config.server.allowed_origins);
+            app.layer(layer)
+        }
+        None => app,
+    };
+
+    // CSP, nosniff, framing and referrer policy on every response (streams included)
+    let app = security_headers::with_security_headers(app, &config.server.security_headers);
+
+    // Per-request span and latency log, tagged with an x-request-id
+    request_trace::with_request_tracing(app)
+}
+
+/// Like tower-http's default predicate but also compresses `text/event-stream`.
+/// The encoder flushes whenever the event stream is idle, so each event (and
+/// keepalive) still reaches the client promptly instead of being buffered.
+fn compression_layer() -> CompressionLayer<impl Predicate> {
+    CompressionLayer::new()
+        .gzip(true)
+        .deflate(true)
+        .compress_when(
+            SizeAbove::default()
+                .and(NotForContentType::GRPC)
+                .and(NotForContentType::IMAGES),
+        )
+}
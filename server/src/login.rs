@@ -1,12 +1,33 @@
-use crate::{auth::create_jwt, state::AppState};
-use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use crate::{
+    audit::{
+        client_ip, record_auth_event, LOGIN_FAILED, LOGIN_RATE_LIMITED, LOGIN_SUCCESS,
+        LOGIN_UNVERIFIED,
+    },
+    auth::{argon2_config, create_jwt, create_refresh_token, LoginResponse},
+    state::AppState,
+};
+use argon2::{PasswordHash, PasswordVerifier};
 use axum::{
-    extract::{Form, State},
-    http::StatusCode,
-    response::{IntoResponse, Redirect, Response},
+    extract::{ConnectInfo, Form, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Redirect, Response},
 };
 use redis::AsyncCommands;
 use serde::Deserialize;
+use std::net::SocketAddr;
+
+// Consecutive exhausted windows before the longer lockout kicks in
+const LOCKOUT_STRIKES: i32 = 3;
+
+/// 429 response with a Retry-After header derived from the blocking key's TTL
+fn rate_limited_response(retry_after_secs: i64) -> Response {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, retry_after_secs.max(1).to_string())],
+        "Too many failed login attempts. Please try again later.".to_string(),
+    )
+        .into_response()
+}
 
 #[derive(Deserialize)]
 pub struct LoginForm {
@@ -18,11 +39,20 @@ pub async fn show_login_form() -> Redirect {
     Redirect::permanent("/login.html")
 }
 
-pub async fn login_handler(State(state): State<AppState>, Form(form): Form<LoginForm>) -> Response {
+pub async fn login_handler(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(form): Form<LoginForm>,
+) -> Response {
+    let ip_address = client_ip(&headers, addr);
+
     // Rate limiting: check failed login attempts per email
     let rate_limit_key = format!("login_attempts:{}", form.email);
-    let max_attempts = 5;
-    let attempt_window = 60;
+    let strikes_key = format!("login_strikes:{}", form.email);
+    let lockout_key = format!("login_lockout:{}", form.email);
+    let max_attempts = state.config.auth.login_max_attempts;
+    let attempt_window = state.config.auth.login_window_seconds;
 
     let mut redis_conn = match state.redis.get_multiplexed_async_connection().await {
         Ok(conn) => conn,
@@ -35,43 +65,60 @@ pub async fn login_handler(State(state): State<AppState>, Form(form): Form<Login
         }
     };
 
+    // A lockout (or an exhausted window) blocks the attempt before any password work
+    let lockout_ttl: i64 = redis_conn.ttl(&lockout_key).await.unwrap_or(-2);
     let attempts: i32 = redis_conn.get(&rate_limit_key).await.unwrap_or(0);
-    if attempts >= max_attempts {
-        return (
-            StatusCode::TOO_MANY_REQUESTS,
-            "Too many failed login attempts. Please try again later.".to_string(),
+    if lockout_ttl > 0 || attempts >= max_attempts {
+        record_auth_event(
+            &state.db,
+            None,
+            &form.email,
+            LOGIN_RATE_LIMITED,
+            &ip_address,
+            false,
         )
-            .into_response();
-    }
+        .await;
 
-    // Dummy hash for timing attack mitigation
-    let dummy_hash = "$argon2id$v=19$m=19456,t=2,p=1$dummy$dummy";
+        let retry_after = if lockout_ttl > 0 {
+            lockout_ttl
+        } else {
+            match redis_conn.ttl(&rate_limit_key).await.unwrap_or(-2) {
+                ttl if ttl > 0 => ttl,
+                _ => attempt_window,
+            }
+        };
+        return rate_limited_response(retry_after);
+    }
 
     // Fetch user by email
     let user_result = sqlx::query!(
-        r#"SELECT user_id, password_hash, name FROM users WHERE email = $1"#,
+        r#"SELECT user_id, password_hash, name, role, verified FROM users WHERE email = $1"#,
         form.email
     )
     .fetch_optional(&state.db)
     .await;
 
-    let (user_exists, user_id, user_name, password_hash) = match user_result {
-        Ok(Some(user)) => (
-            true,
-            Some(user.user_id.to_string()),
-            Some(user.name),
-            user.password_hash,
-        ),
-        Ok(None) => (false, None, None, dummy_hash.to_string()),
-        Err(e) => {
-            eprintln!("Database error: {e:?}");
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Internal Server Error.".to_string(),
-            )
-                .into_response();
-        }
-    };
+    let (user_exists, user_id, user_name, user_role, user_verified, password_hash) =
+        match user_result {
+            Ok(Some(user)) => (
+                true,
+                Some(user.user_id),
+                Some(user.name),
+                Some(user.role),
+                user.verified,
+                user.password_hash,
+            ),
+            // Dummy hash (same Argon2 cost) for timing attack mitigation
+            Ok(None) => (false, None, None, None, false, state.dummy_hash.to_string()),
+            Err(e) => {
+                eprintln!("Database error: {e:?}");
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal Server Error.".to_string(),
+                )
+                    .into_response();
+            }
+        };
 
     // Parse stored hash (or dummy hash if user doesn't exist)
     let parsed_hash = match PasswordHash::new(&password_hash) {
@@ -86,30 +133,114 @@ pub async fn login_handler(State(state): State<AppState>, Form(form): Form<Login
     };
 
     // Verify password (timing-safe: runs regardless of user existence)
-    let valid = Argon2::default()
+    let valid = argon2_config(&state.config.auth)
         .verify_password(form.password.as_bytes(), &parsed_hash)
         .is_ok();
 
-    if user_exists && valid {
-        // Clear rate limit counter on successful login
-        let _: () = redis_conn.del(&rate_limit_key).await.unwrap_or(());
+    if user_exists && valid && !user_verified {
+        // Only reported after the password checks out, so it can't reveal registered emails
+        record_auth_event(
+            &state.db,
+            user_id,
+            &form.email,
+            LOGIN_UNVERIFIED,
+            &ip_address,
+            false,
+        )
+        .await;
 
-        match create_jwt(&user_id.unwrap(), &user_name.unwrap()) {
-            Ok(token) => (StatusCode::OK, format!("{{\"token\":\"{}\"}}", token)).into_response(),
-            Err(_) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Failed to generate token".to_string(),
-            )
-                .into_response(),
+        (
+            StatusCode::FORBIDDEN,
+            "Please verify your email before logging in.".to_string(),
+        )
+            .into_response()
+    } else if user_exists && valid {
+        // Clear rate limit counters on successful login
+        let _: () = redis_conn
+            .del(&[&rate_limit_key, &strikes_key])
+            .await
+            .unwrap_or(());
+
+        let user_id = user_id.unwrap();
+        record_auth_event(
+            &state.db,
+            Some(user_id),
+            &form.email,
+            LOGIN_SUCCESS,
+            &ip_address,
+            true,
+        )
+        .await;
+
+        let token = match create_jwt(
+            &state.config,
+            &user_id.to_string(),
+            &user_name.unwrap(),
+            &user_role.unwrap(),
+        ) {
+            Ok(token) => token,
+            Err(_) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to generate token".to_string(),
+                )
+                    .into_response()
+            }
+        };
+
+        match create_refresh_token(&state.db, user_id, state.config.auth.refresh_token_ttl_days)
+            .await
+        {
+            Ok(refresh_token) => {
+                Json(LoginResponse::new(token, refresh_token, user_id)).into_response()
+            }
+            Err(e) => {
+                eprintln!("Failed to store refresh token: {e:?}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to generate token".to_string(),
+                )
+                    .into_response()
+            }
         }
     } else {
+        record_auth_event(
+            &state.db,
+            user_id,
+            &form.email,
+            LOGIN_FAILED,
+            &ip_address,
+            false,
+        )
+        .await;
+
         // Increment failed attempt counter
-        let _: () = redis_conn.incr(&rate_limit_key, 1).await.unwrap_or(());
+        let failures: i32 = redis_conn.incr(&rate_limit_key, 1).await.unwrap_or(0);
         let _: () = redis_conn
             .expire(&rate_limit_key, attempt_window)
             .await
             .unwrap_or(());
 
+        // This failure exhausted the window: count a strike, and lock out after
+        // LOCKOUT_STRIKES consecutive windows (a quiet window lets the strikes expire)
+        if failures == max_attempts {
+            if let Some(lockout_secs) = state.config.auth.login_lockout_seconds {
+                let strikes: i32 = redis_conn.incr(&strikes_key, 1).await.unwrap_or(0);
+                let _: () = redis_conn
+                    .expire(&strikes_key, attempt_window * 2)
+                    .await
+                    .unwrap_or(());
+
+                if strikes >= LOCKOUT_STRIKES {
+                    let _: () = redis_conn
+                        .set_ex(&lockout_key, 1, lockout_secs)
+                        .await
+                        .unwrap_or(());
+                    let _: () = redis_conn.del(&strikes_key).await.unwrap_or(());
+                }
+            }
+        }
+
         (
             StatusCode::UNAUTHORIZED,
             "Invalid email or password.".to_string(),
@@ -117,3 +248,7 @@ pub async fn login_handler(State(state): State<AppState>, Form(form): Form<Login
             .into_response()
     }
 }
+
+#[cfg(test)]
+#[path = "login_tests.rs"]
+mod tests;
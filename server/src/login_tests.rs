@@ -0,0 +1,22 @@
+use super::*;
+
+// Rate Limit Response Tests
+
+#[test]
+fn test_rate_limited_response_status() {
+    let response = rate_limited_response(42);
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[test]
+fn test_rate_limited_response_retry_after() {
+    let response = rate_limited_response(42);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "42");
+}
+
+#[test]
+fn test_rate_limited_response_retry_after_never_zero() {
+    // An expiring key can report 0/-1; clients should still wait at least a second
+    let response = rate_limited_response(0);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+}
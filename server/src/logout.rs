@@ -0,0 +1,87 @@
+use crate::{
+    auth::{revoke_refresh_token, revoke_refresh_tokens, revoked_key, AuthUser},
+    state::AppState,
+};
+use axum::{
+    extract::{Json, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use redis::AsyncCommands;
+use serde::Deserialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Deserialize)]
+pub struct LogoutRequest {
+    pub refresh_token: String,
+}
+
+/// Denylists the presented access token's `jti` in Redis until it would have
+/// expired anyway
+async fn revoke_access_token(state: &AppState, user: &AuthUser) -> Result<(), Response> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as usize;
+    let remaining = user.exp.saturating_sub(now).max(1) as u64;
+
+    let mut redis_conn = match state.redis.get_multiplexed_async_connection().await {
+        Ok(conn) => conn,
+        Err(_) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Service unavailable".to_string(),
+            )
+                .into_response());
+        }
+    };
+
+    let denylisted: redis::RedisResult<()> = redis_conn
+        .set_ex(revoked_key(&user.jti), 1, remaining)
+        .await;
+    if let Err(e) = denylisted {
+        eprintln!("Failed to revoke token: {e:?}");
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to revoke token".to_string(),
+        )
+            .into_response());
+    }
+    Ok(())
+}
+
+/// Ends the current session: revokes the presented access token and, when
+/// the body carries it (`{"refresh_token": ...}`), this session's refresh
+/// token. The user's other devices stay signed in.
+pub async fn logout_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+    body: Option<Json<LogoutRequest>>,
+) -> Response {
+    if let Err(response) = revoke_access_token(&state, &user).await {
+        return response;
+    }
+
+    if let Some(Json(req)) = body {
+        if let Err(e) = revoke_refresh_token(&state.db, user.user_id, &req.refresh_token).await {
+            eprintln!("Failed to revoke refresh token: {e:?}");
+        }
+    }
+
+    (StatusCode::OK, "Logged out".to_string()).into_response()
+}
+
+/// Signs the user out everywhere: revokes the presented access token and all
+/// of the user's refresh tokens, so no session can mint new access tokens.
+/// Access tokens issued to other sessions stay valid until they expire.
+pub async fn logout_all_handler(State(state): State<AppState>, user: AuthUser) -> Response {
+    if let Err(response) = revoke_access_token(&state, &user).await {
+        return response;
+    }
+
+    if let Err(e) = revoke_refresh_tokens(&state.db, user.user_id).await {
+        eprintln!("Failed to revoke refresh tokens: {e:?}");
+    }
+
+    (StatusCode::OK, "Logged out of all sessions".to_string()).into_response()
+}
@@ -1,171 +1,172 @@
-use axum::{extract::State, routing::get, Router};
 use dotenvy::dotenv;
-use std::env;
+use server::config::Config;
+use server::state::AppState;
+use server::{
+    build_app, calibration, daily_summary, db_worker, fallback, retention, serial, shutdown,
+    simulation,
+};
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::broadcast;
-use tower_http::services::ServeDir;
-
-mod auth;
-mod db_worker;
-mod fallback;
-mod fhir;
-mod fhir_analytics;
-mod login;
-mod models;
-mod replay;
-mod serial;
-mod signup;
-mod sse;
-mod state;
-mod websocket;
-
-use auth::AuthUser;
-use state::AppState;
+use std::thread;
+use tokio::task::JoinHandle;
+use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
 
-    // Initialize Logging
-    tracing_subscriber::fmt::init();
+    // Initialize Logging (RUST_LOG overrides the info default)
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .init();
     println!("Server initializing...");
 
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    // Every setting is read and validated here, before anything connects
+    let config = match Config::from_env() {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            eprintln!("Invalid configuration:\n{}", e);
+            std::process::exit(1);
+        }
+    };
 
     println!("Connecting to database...");
-    let pool = db::get_db_pool(&database_url)
+    let pool = db::get_db_pool(&config.database_url)
         .await
         .expect("Failed to connect to database");
     println!("Database connection established.");
 
     //  Redis Connection
-    let redis_url = env::var("REDIS_URL").expect("REDIS_URL must be set");
-    let redis_client = redis::Client::open(redis_url.as_str()).expect("Invalid Redis URL");
+    let redis_client = redis::Client::open(config.redis_url.as_str()).expect("Invalid Redis URL");
     println!("Redis client connected");
 
-    //  Create the Broadcast Channel
-    let (tx, _rx) = broadcast::channel(100);
-
-    // Fallback Monitor - backfills from DB when hardware is unavailable
-    let fallback_state = Arc::new(fallback::FallbackState::new());
-
-    //  Start Background Tasks/Data Pipeline
-    let serial_port = env::var("SERIAL_PORT").expect("SERIAL_PORT must be set");
-    let baud_rate: u32 = env::var("BAUD_RATE")
-        .expect("BAUD_RATE must be set")
-        .parse()
-        .expect("BAUD_RATE must be a valid number");
-    serial::spawn_serial_listener(
-        tx.clone(),
-        redis_client.clone(),
-        serial_port,
-        baud_rate,
-        fallback_state.clone(),
-    );
-
-    // Start fallback monitor (watches for data gaps and backfills from DB)
-    // Can be disabled with DISABLE_FALLBACK=true for local/replay mode
-    if env::var("DISABLE_FALLBACK")
-        .map(|v| v != "true")
-        .unwrap_or(true)
-    {
-        fallback::spawn_fallback_monitor(
-            pool.clone(),
-            tx.clone(),
-            redis_client.clone(),
-            fallback_state,
-        );
-    } else {
-        println!("Fallback monitor disabled");
-    }
-
-    // DB Worker/Storage
-    db_worker::spawn_db_worker(pool.clone(), tx.subscribe()).await;
-
-    //  Build the Application State
-    let app_state = AppState {
-        db: pool,
-        tx,
-        redis: redis_client,
+    // Thresholds saved at runtime take precedence over env values
+    let initial_thresholds = match calibration::load_thresholds(&pool).await {
+        Some(t) => {
+            println!(
+                "Using saved thresholds (fidget: {}, active: {})",
+                t.fidget, t.active
+            );
+            t
+        }
+        None => config.serial.thresholds,
     };
 
-    //  Define Routes
-    let app = Router::new()
-        // Real-Time Streaming (SSE primary, WebSocket fallback)
-        .route("/events", get(sse::sse_handler))
-        .route("/ws", get(websocket::ws_handler))
-        // FHIR Compliance API
-        .route(
-            "/api/fhir/observation/latest",
-            get(fhir::get_latest_observation),
-        )
-        // FHIR Analytics API (LOINC 87705-0)
-        .route(
-            "/api/fhir/analytics/user/:user_id",
-            get(fhir_analytics::get_user_analytics),
-        )
-        .route(
-            "/api/fhir/analytics/latest",
-            get(fhir_analytics::get_latest_analytics),
-        )
-        // Signup form + handler
-        .route(
-            "/signup",
-            get(signup::show_signup_form).post(signup::signup_handler),
-        )
-        // Login form + handler
-        .route(
-            "/login",
-            get(login::show_login_form).post(login::login_handler),
-        )
-        // Protected stats endpoint
-        .route("/stats", get(get_user_stats))
-        // Health Check
-        .route("/health", get(|| async { "Status: Healthy" }))
-        // Replay log data for testing/demo
-        .route("/api/replay", get(start_replay))
-        // Frontend Hosting
-        .nest_service(
-            "/",
-            ServeDir::new(env::var("FRONTEND_DIR").unwrap_or_else(|_| {
-                concat!(env!("CARGO_MANIFEST_DIR"), "/../frontend").to_string()
-            })),
-        )
-        .with_state(app_state);
+    let state = AppState::new(config.clone(), pool, redis_client, initial_thresholds);
+    let (background_tasks, serial_threads) = start_pipeline(&state).await;
+    let shutdown = state.shutdown.clone();
+    let app = build_app(state);
 
     // Start the Server
-    let server_addr = env::var("SERVER_ADDRESS").unwrap_or_else(|_| "0.0.0.0:8000".to_string());
-    let addr: SocketAddr = server_addr.parse().expect("Invalid SERVER_ADDRESS");
+    let addr = config.server.address;
     println!("Sedentary Tracker listening on http://{}", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
-}
+    // Connect info lets handlers fall back to the socket address for client IPs.
+    // On shutdown new connections are refused and open streams end themselves.
+    let server_shutdown = shutdown.clone();
+    let mut server = tokio::spawn(async move {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(async move { server_shutdown.cancelled().await })
+        .await
+    });
+
+    tokio::select! {
+        _ = shutdown::wait_for_signal() => shutdown.cancel(),
+        result = &mut server => {
+            result.unwrap().unwrap();
+            return;
+        }
+    }
 
-async fn get_user_stats(user: AuthUser) -> impl axum::response::IntoResponse {
-    format!(
-        "Fetching secret stats for {} (User ID: {})",
-        user.name, user.user_id
-    )
+    // Let the server, DB writes and Redis history drain, but not forever
+    let grace = config.server.shutdown_grace;
+    let drained = tokio::time::timeout(grace, async {
+        let _ = server.await;
+        for task in background_tasks {
+            let _ = task.await;
+        }
+        let _ = tokio::task::spawn_blocking(move || {
+            for thread in serial_threads {
+                let _ = thread.join();
+            }
+        })
+        .await;
+    })
+    .await;
+
+    match drained {
+        Ok(()) => println!("Shutdown complete"),
+        Err(_) => eprintln!(
+            "Shutdown grace period ({}s) elapsed, exiting",
+            grace.as_secs()
+        ),
+    }
 }
 
-async fn start_replay(State(state): State<AppState>) -> impl axum::response::IntoResponse {
-    let log_path = env::var("REPLAY_LOG_PATH").unwrap_or_else(|_| "arduino_data.log".to_string());
-    let replay_speed: u64 = env::var("REPLAY_SPEED_MS")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(50); // 50ms between readings for ~20x speed
-
-    replay::spawn_replay_task(
-        state.tx.clone(),
-        state.redis.clone(),
-        log_path.clone(),
-        replay_speed,
+/// Starts the data pipeline: one serial listener thread per device (each with
+/// its own smoothing buffer and timer), or the simulator in SIMULATION_MODE,
+/// the fallback monitor unless disabled,
+/// the DB worker, the daily summary job and the retention job. Everything stops when
+/// `state.shutdown` is cancelled.
+async fn start_pipeline(state: &AppState) -> (Vec<JoinHandle<()>>, Vec<thread::JoinHandle<()>>) {
+    let config = &state.config;
+    let pipeline = serial::SerialPipeline::from_state(state);
+    let serial_threads = if config.simulation.enabled {
+        vec![simulation::spawn_simulator(pipeline)]
+    } else {
+        config
+            .serial
+            .ports
+            .iter()
+            .map(|serial_port| serial::spawn_serial_listener(pipeline.clone(), serial_port.clone()))
+            .collect()
+    };
+    let mut background_tasks = Vec::new();
+
+    // Watches for data gaps and backfills from the DB (DISABLE_FALLBACK=true for local/replay mode)
+    if config.fallback.enabled {
+        background_tasks.push(fallback::spawn_fallback_monitor(
+            fallback::FallbackContext::from_state(state),
+            state.fallback_state.clone(),
+            state.shutdown.clone(),
+        ));
+    } else {
+        println!("Fallback monitor disabled");
+    }
+
+    // DB Worker/Storage
+    background_tasks.push(
+        db_worker::spawn_db_worker(
+            config.clone(),
+            state.db.clone(),
+            state.tx.subscribe(),
+            state.metrics.clone(),
+            state.shutdown.clone(),
+        )
+        .await,
     );
 
-    format!(
-        "Replay started from: {} (speed: {}ms per reading)",
-        log_path, replay_speed
-    )
+    // Fills activity_summary from sensor_data after each local midnight
+    background_tasks.push(daily_summary::spawn_daily_summary_job(
+        config.clone(),
+        state.db.clone(),
+        state.shutdown.clone(),
+    ));
+
+    // Deletes raw readings older than RETENTION_DAYS (summarizing them first)
+    background_tasks.extend(retention::spawn_retention_job(
+        config.clone(),
+        state.db.clone(),
+        state.retention.clone(),
+        state.metrics.clone(),
+        state.shutdown.clone(),
+    ));
+
+    (background_tasks, serial_threads)
 }
@@ -0,0 +1,292 @@
+use crate::models::ActivityState;
+use crate::retention::PruneStats;
+use crate::state::AppState;
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Json},
+};
+use serde_json::{json, Value};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// Label values for the current-state gauge; index 0 means no reading yet
+const STATES: [ActivityState; 3] = ActivityState::ALL;
+
+/// Process-wide counters and gauges rendered at GET /metrics
+#[derive(Default)]
+pub struct Metrics {
+    readings_ingested: AtomicU64,
+    current_state: AtomicU8,
+    broadcast_lagged: AtomicU64,
+    // Lag events per subscriber kind, indexed like Subscriber::ALL
+    lag_events: [AtomicU64; 4],
+    db_write_errors: AtomicU64,
+    stream_connections: AtomicUsize,
+    sse_connections: AtomicUsize,
+    ws_connections: AtomicUsize,
+    // Rows removed by the retention job, and when it last finished (Unix seconds)
+    pruned_sensor_data: AtomicU64,
+    pruned_sedentary_log: AtomicU64,
+    last_prune: AtomicU64,
+}
+
+/// Which streaming endpoint a connection belongs to
+#[derive(Clone, Copy)]
+pub enum StreamKind {
+    Sse,
+    WebSocket,
+}
+
+/// Which kind of broadcast receiver fell behind
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Subscriber {
+    DbWorker,
+    Sse,
+    WebSocket,
+    Calibration,
+}
+
+impl Subscriber {
+    const ALL: [Subscriber; 4] = [
+        Subscriber::DbWorker,
+        Subscriber::Sse,
+        Subscriber::WebSocket,
+        Subscriber::Calibration,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Subscriber::DbWorker => "db_worker",
+            Subscriber::Sse => "sse",
+            Subscriber::WebSocket => "websocket",
+            Subscriber::Calibration => "calibration",
+        }
+    }
+}
+
+/// Counts a streaming client for as long as it is held; dropping it
+/// (including when the client disconnects mid-stream) decrements the gauges
+pub struct ConnectionGuard {
+    metrics: Arc<Metrics>,
+    kind: StreamKind,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.metrics
+            .stream_connections
+            .fetch_sub(1, Ordering::Relaxed);
+        self.metrics
+            .gauge(self.kind)
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Metrics {
+    /// One classified reading from a serial device
+    pub fn record_reading(&self, state: ActivityState) {
+        self.readings_ingested.fetch_add(1, Ordering::Relaxed);
+        let index = STATES.iter().position(|s| *s == state).map_or(0, |i| i + 1);
+        self.current_state.store(index as u8, Ordering::Relaxed);
+    }
+
+    /// A broadcast receiver fell behind and skipped `skipped` messages
+    pub fn record_lagged(&self, subscriber: Subscriber, skipped: u64) {
+        self.broadcast_lagged.fetch_add(skipped, Ordering::Relaxed);
+        self.lag_events[subscriber as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_db_error(&self) {
+        self.db_write_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A retention run finished (possibly early, with its partial counts)
+    pub fn record_prune(&self, stats: &PruneStats) {
+        self.pruned_sensor_data
+            .fetch_add(stats.sensor_data_deleted, Ordering::Relaxed);
+        self.pruned_sedentary_log
+            .fetch_add(stats.sedentary_log_deleted, Ordering::Relaxed);
+        self.last_prune.store(
+            stats.finished_at.timestamp().max(0) as u64,
+            Ordering::Relaxed,
+        );
+    }
+
+    fn gauge(&self, kind: StreamKind) -> &AtomicUsize {
+        match kind {
+            StreamKind::Sse => &self.sse_connections,
+            StreamKind::WebSocket => &self.ws_connections,
+        }
+    }
+
+    /// Registers a streaming client, or `None` when `max` clients are already connected
+    pub fn try_connect(self: &Arc<Self>, kind: StreamKind, max: usize) -> Option<ConnectionGuard> {
+        if self.stream_connections.fetch_add(1, Ordering::Relaxed) >= max {
+            self.stream_connections.fetch_sub(1, Ordering::Relaxed);
+            return None;
+        }
+        self.gauge(kind).fetch_add(1, Ordering::Relaxed);
+        Some(ConnectionGuard {
+            metrics: self.clone(),
+            kind,
+        })
+    }
+
+    /// SSE and WebSocket clients currently connected
+    pub fn stream_connections(&self) -> usize {
+        self.stream_connections.load(Ordering::Relaxed)
+    }
+
+    /// Prometheus text exposition format (version 0.0.4)
+    pub fn render(&self, fallback_active: bool) -> String {
+        let mut out = String::new();
+
+        let _ = writeln!(
+            out,
+            "# HELP sedentary_readings_total Sensor readings classified from serial devices"
+        );
+        let _ = writeln!(out, "# TYPE sedentary_readings_total counter");
+        let _ = writeln!(
+            out,
+            "sedentary_readings_total {}",
+            self.readings_ingested.load(Ordering::Relaxed)
+        );
+
+        let current = self.current_state.load(Ordering::Relaxed) as usize;
+        let _ = writeln!(
+            out,
+            "# HELP sedentary_current_state Latest classified state (1 for the active label)"
+        );
+        let _ = writeln!(out, "# TYPE sedentary_current_state gauge");
+        for (i, state) in STATES.iter().enumerate() {
+            let value = u8::from(current == i + 1);
+            let _ = writeln!(out, "sedentary_current_state{{state=\"{state}\"}} {value}");
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP sedentary_broadcast_lagged_total Broadcast messages dropped for slow receivers"
+        );
+        let _ = writeln!(out, "# TYPE sedentary_broadcast_lagged_total counter");
+        let _ = writeln!(
+            out,
+            "sedentary_broadcast_lagged_total {}",
+            self.broadcast_lagged.load(Ordering::Relaxed)
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP sedentary_broadcast_lag_events_total Times a receiver fell behind the broadcast channel"
+        );
+        let _ = writeln!(out, "# TYPE sedentary_broadcast_lag_events_total counter");
+        for subscriber in Subscriber::ALL {
+            let _ = writeln!(
+                out,
+                "sedentary_broadcast_lag_events_total{{subscriber=\"{}\"}} {}",
+                subscriber.label(),
+                self.lag_events[subscriber as usize].load(Ordering::Relaxed)
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP sedentary_stream_connections Connected streaming clients"
+        );
+        let _ = writeln!(out, "# TYPE sedentary_stream_connections gauge");
+        let _ = writeln!(
+            out,
+            "sedentary_stream_connections{{transport=\"sse\"}} {}",
+            self.sse_connections.load(Ordering::Relaxed)
+        );
+        let _ = writeln!(
+            out,
+            "sedentary_stream_connections{{transport=\"websocket\"}} {}",
+            self.ws_connections.load(Ordering::Relaxed)
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP sedentary_db_write_errors_total Failed inserts in the database worker"
+        );
+        let _ = writeln!(out, "# TYPE sedentary_db_write_errors_total counter");
+        let _ = writeln!(
+            out,
+            "sedentary_db_write_errors_total {}",
+            self.db_write_errors.load(Ordering::Relaxed)
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP sedentary_retention_deleted_rows_total Raw readings deleted by the retention job"
+        );
+        let _ = writeln!(out, "# TYPE sedentary_retention_deleted_rows_total counter");
+        let _ = writeln!(
+            out,
+            "sedentary_retention_deleted_rows_total{{table=\"sensor_data\"}} {}",
+            self.pruned_sensor_data.load(Ordering::Relaxed)
+        );
+        let _ = writeln!(
+            out,
+            "sedentary_retention_deleted_rows_total{{table=\"sedentary_log\"}} {}",
+            self.pruned_sedentary_log.load(Ordering::Relaxed)
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP sedentary_retention_last_run_timestamp_seconds When the retention job last finished (0 before its first run)"
+        );
+        let _ = writeln!(
+            out,
+            "# TYPE sedentary_retention_last_run_timestamp_seconds gauge"
+        );
+        let _ = writeln!(
+            out,
+            "sedentary_retention_last_run_timestamp_seconds {}",
+            self.last_prune.load(Ordering::Relaxed)
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP sedentary_fallback_active 1 while replaying stored data because hardware is unavailable"
+        );
+        let _ = writeln!(out, "# TYPE sedentary_fallback_active gauge");
+        let _ = writeln!(
+            out,
+            "sedentary_fallback_active {}",
+            u8::from(fallback_active)
+        );
+
+        out
+    }
+}
+
+/// Admits a streaming client under MAX_STREAM_CONNECTIONS or builds the 503 rejection
+pub fn acquire_stream(
+    state: &AppState,
+    kind: StreamKind,
+) -> Result<ConnectionGuard, (StatusCode, Json<Value>)> {
+    state
+        .metrics
+        .try_connect(kind, state.config.server.max_stream_connections)
+        .ok_or_else(|| {
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(json!({"error": "Too many streaming connections"})),
+            )
+        })
+}
+
+/// Prometheus scrape endpoint
+/// Endpoint: GET /metrics
+pub async fn get_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(state.fallback_state.is_in_fallback()),
+    )
+}
+
+#[cfg(test)]
+#[path = "metrics_tests.rs"]
+mod tests;
@@ -0,0 +1,117 @@
+use super::*;
+
+// Prometheus Rendering Tests
+
+#[test]
+fn test_render_counts_readings_and_labels_current_state() {
+    let metrics = Metrics::default();
+    metrics.record_reading(ActivityState::Active);
+    metrics.record_reading(ActivityState::Sedentary);
+
+    let text = metrics.render(false);
+    assert!(text.contains("sedentary_readings_total 2\n"));
+    assert!(text.contains("sedentary_current_state{state=\"SEDENTARY\"} 1\n"));
+    assert!(text.contains("sedentary_current_state{state=\"ACTIVE\"} 0\n"));
+}
+
+#[test]
+fn test_render_no_state_before_first_reading() {
+    let text = Metrics::default().render(false);
+    assert!(!text.contains("sedentary_current_state{state=\"ACTIVE\"} 1"));
+    assert!(!text.contains("sedentary_current_state{state=\"FIDGET\"} 1"));
+    assert!(!text.contains("sedentary_current_state{state=\"SEDENTARY\"} 1"));
+}
+
+#[test]
+fn test_render_errors_lag_and_fallback() {
+    let metrics = Metrics::default();
+    metrics.record_db_error();
+    metrics.record_lagged(Subscriber::Sse, 7);
+
+    let text = metrics.render(true);
+    assert!(text.contains("sedentary_db_write_errors_total 1\n"));
+    assert!(text.contains("sedentary_broadcast_lagged_total 7\n"));
+    assert!(text.contains("sedentary_fallback_active 1\n"));
+}
+
+#[test]
+fn test_render_lag_events_per_subscriber() {
+    let metrics = Metrics::default();
+    metrics.record_lagged(Subscriber::Sse, 40);
+    metrics.record_lagged(Subscriber::Sse, 2);
+    metrics.record_lagged(Subscriber::DbWorker, 5);
+
+    let text = metrics.render(false);
+    assert!(text.contains("sedentary_broadcast_lagged_total 47\n"));
+    assert!(text.contains("sedentary_broadcast_lag_events_total{subscriber=\"sse\"} 2\n"));
+    assert!(text.contains("sedentary_broadcast_lag_events_total{subscriber=\"db_worker\"} 1\n"));
+    assert!(text.contains("sedentary_broadcast_lag_events_total{subscriber=\"websocket\"} 0\n"));
+}
+
+#[test]
+fn test_connection_guard_decrements_on_drop() {
+    let metrics = Arc::new(Metrics::default());
+    let sse = metrics.try_connect(StreamKind::Sse, 10).unwrap();
+    let _ws = metrics.try_connect(StreamKind::WebSocket, 10).unwrap();
+    assert_eq!(metrics.stream_connections(), 2);
+    assert!(metrics
+        .render(false)
+        .contains("sedentary_stream_connections{transport=\"sse\"} 1\n"));
+
+    drop(sse);
+    assert_eq!(metrics.stream_connections(), 1);
+    let text = metrics.render(false);
+    assert!(text.contains("sedentary_stream_connections{transport=\"sse\"} 0\n"));
+    assert!(text.contains("sedentary_stream_connections{transport=\"websocket\"} 1\n"));
+}
+
+#[test]
+fn test_every_metric_has_help_and_type() {
+    let text = Metrics::default().render(false);
+    let help = text.lines().filter(|l| l.starts_with("# HELP")).count();
+    let types = text.lines().filter(|l| l.starts_with("# TYPE")).count();
+    assert_eq!(help, 9);
+    assert_eq!(types, 9);
+}
+
+#[test]
+fn test_render_retention_counts_accumulate() {
+    let metrics = Metrics::default();
+    let finished_at = chrono::DateTime::from_timestamp(1_770_000_000, 0).unwrap();
+    let run = |sensor_data_deleted, sedentary_log_deleted| PruneStats {
+        cutoff: finished_at,
+        finished_at,
+        duration_ms: 5,
+        days_summarized: 0,
+        sensor_data_deleted,
+        sedentary_log_deleted,
+        batches: 1,
+        error: None,
+    };
+    metrics.record_prune(&run(100, 4));
+    metrics.record_prune(&run(20, 0));
+
+    let text = metrics.render(false);
+    assert!(text.contains("sedentary_retention_deleted_rows_total{table=\"sensor_data\"} 120\n"));
+    assert!(text.contains("sedentary_retention_deleted_rows_total{table=\"sedentary_log\"} 4\n"));
+    assert!(text.contains("sedentary_retention_last_run_timestamp_seconds 1770000000\n"));
+}
+
+// Connection Cap Tests
+
+#[test]
+fn test_try_connect_rejects_over_cap() {
+    let metrics = Arc::new(Metrics::default());
+    let first = metrics.try_connect(StreamKind::Sse, 2);
+    let _second = metrics.try_connect(StreamKind::WebSocket, 2);
+    assert!(first.is_some());
+    assert!(metrics.try_connect(StreamKind::Sse, 2).is_none());
+    assert_eq!(
+        metrics.stream_connections(),
+        2,
+        "rejected attempt must not count"
+    );
+
+    drop(first);
+    assert!(metrics.try_connect(StreamKind::Sse, 2).is_some());
+}
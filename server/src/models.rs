@@ -1,24 +1,136 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Classified activity level, sent on the wire and stored as the uppercase name
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ActivityState {
+    Sedentary,
+    Fidget,
+    Active,
+}
+
+impl ActivityState {
+    pub const ALL: [ActivityState; 3] = [
+        ActivityState::Active,
+        ActivityState::Fidget,
+        ActivityState::Sedentary,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ActivityState::Sedentary => "SEDENTARY",
+            ActivityState::Fidget => "FIDGET",
+            ActivityState::Active => "ACTIVE",
+        }
+    }
+}
+
+impl fmt::Display for ActivityState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ActivityState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ActivityState::ALL
+            .into_iter()
+            .find(|state| state.as_str() == s)
+            .ok_or_else(|| format!("unknown activity state '{}'", s))
+    }
+}
 
 // 1. RAW INPUT From Arduino
 // Format: {"ts":"12:34:56","pir":0,"acc":0.045}
+// Optionally with a full date: {"ts":"12:34:56","datetime":"2026-01-06T12:34:56Z",...}
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct RawReading {
     pub ts: String, // Timestamp from RTC (HH:MM:SS)
     pub pir: i32,   // PIR sensor (0 or 1)
     pub acc: f32,   // Acceleration delta magnitude
+    #[serde(default)]
+    pub datetime: Option<DateTime<Utc>>, // Full ISO-8601 timestamp, preferred over ts
+}
+
+impl RawReading {
+    /// Rejects values no working sensor produces, so a firmware bug can't reach
+    /// classification or the database: `acc` must be a finite magnitude between
+    /// 0 and `max_acc` (an overflowing number parses as infinity) and `pir` 0 or 1
+    pub fn validate(&self, max_acc: f32) -> Result<(), String> {
+        if !self.acc.is_finite() {
+            return Err(format!("acc {} is not a finite number", self.acc));
+        }
+        if !(0.0..=max_acc).contains(&self.acc) {
+            return Err(format!("acc {} is outside 0-{}", self.acc, max_acc));
+        }
+        if !matches!(self.pir, 0 | 1) {
+            return Err(format!("pir {} is not 0 or 1", self.pir));
+        }
+        Ok(())
+    }
 }
 
 // 2. PROCESSED OUTPUT (To Frontend & DB)
 // Classification is also done server-side in serial.rs
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct ProcessedState {
-    pub state: String,            // "ACTIVE", "FIDGET", "SEDENTARY"
-    pub timer: u64,               // Inactive seconds
-    pub val: f32,                 // Smoothed acceleration value
-    pub alert: bool,              // Trigger alert?
+    pub state: ActivityState, // Serialized as "ACTIVE", "FIDGET", "SEDENTARY"
+    pub timer: u64,           // Inactive seconds
+    pub val: f32,             // Smoothed acceleration value
+    pub alert: bool,          // Trigger alert?
     pub timestamp: DateTime<Utc>, // Full timestamp (UTC)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<Uuid>, // Owner of the device, if the port is bound to a user
+    #[serde(flatten, default, skip_serializing_if = "Option::is_none")]
+    pub daily: Option<DailyTotals>, // Running totals for the live stream (absent for backfill)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classification_confidence: Option<f32>, // 0-1 agreement of PIR and acceleration (absent for backfill)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub replayed: bool, // Fallback backfill or synthetic data; not stored again by the DB worker
+}
+
+// Seconds spent in each state since local midnight (see daily_totals.rs)
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub struct DailyTotals {
+    pub daily_sedentary_sec: u64,
+    pub daily_active_sec: u64,
+    pub daily_fidget_sec: u64,
+}
+
+// 3. STATE TRANSITION (SSE "state-change" events)
+// Emitted only when the classified state differs from the previous reading's
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct StateChange {
+    pub old_state: ActivityState,
+    pub new_state: ActivityState,
+    pub duration_seconds: u64, // Time spent in old_state, measured on reading timestamps
+    pub timestamp: DateTime<Utc>, // Timestamp of the first reading in new_state
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<Uuid>,
+}
+
+// Only the owner is needed to route a broadcast payload
+#[derive(Deserialize)]
+struct PayloadOwner {
+    #[serde(default)]
+    user_id: Option<Uuid>,
+}
+
+/// Whether a broadcast payload should be delivered to a stream subscriber.
+/// Anonymous subscribers receive everything; authenticated ones only their own events.
+pub fn visible_to(payload: &str, subscriber: Option<Uuid>) -> bool {
+    match subscriber {
+        None => true,
+        Some(user_id) => serde_json::from_str::<PayloadOwner>(payload)
+            .map(|owner| owner.user_id == Some(user_id))
+            .unwrap_or(false),
+    }
 }
 
 #[cfg(test)]
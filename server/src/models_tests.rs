@@ -40,16 +40,91 @@ fn test_raw_reading_high_acceleration() {
     assert!((reading.acc - 2.5).abs() < 0.001);
 }
 
+#[test]
+fn test_raw_reading_datetime_optional() {
+    let json = r#"{"ts": "12:34:56", "pir": 0, "acc": 0.01}"#;
+    let reading: RawReading = serde_json::from_str(json).unwrap();
+    assert_eq!(reading.datetime, None);
+}
+
+#[test]
+fn test_raw_reading_full_datetime() {
+    let json =
+        r#"{"ts": "12:34:56", "datetime": "2026-01-06T12:34:56+02:00", "pir": 0, "acc": 0.01}"#;
+    let reading: RawReading = serde_json::from_str(json).unwrap();
+    assert_eq!(
+        reading.datetime,
+        Some(Utc.with_ymd_and_hms(2026, 1, 6, 10, 34, 56).unwrap())
+    );
+}
+
+#[test]
+fn test_raw_reading_validate_accepts_sensor_range() {
+    let reading: RawReading =
+        serde_json::from_str(r#"{"ts": "12:00:00", "pir": 1, "acc": 4.875}"#).unwrap();
+    assert_eq!(reading.validate(16.0), Ok(()));
+}
+
+#[test]
+fn test_raw_reading_validate_rejects_implausible_values() {
+    let parse = |json: &str| serde_json::from_str::<RawReading>(json).unwrap();
+    // Too large for an f32, so it parses as infinity
+    assert!(parse(r#"{"ts": "12:00:00", "pir": 0, "acc": 1e39}"#)
+        .validate(16.0)
+        .is_err());
+    assert!(parse(r#"{"ts": "12:00:00", "pir": 0, "acc": 16.5}"#)
+        .validate(16.0)
+        .is_err());
+    assert!(parse(r#"{"ts": "12:00:00", "pir": 0, "acc": -0.1}"#)
+        .validate(16.0)
+        .is_err());
+    assert!(parse(r#"{"ts": "12:00:00", "pir": 46, "acc": 0.01}"#)
+        .validate(16.0)
+        .is_err());
+}
+
+// ActivityState Tests
+
+#[test]
+fn test_activity_state_json_roundtrip() {
+    for (state, wire) in [
+        (ActivityState::Sedentary, "\"SEDENTARY\""),
+        (ActivityState::Fidget, "\"FIDGET\""),
+        (ActivityState::Active, "\"ACTIVE\""),
+    ] {
+        assert_eq!(serde_json::to_string(&state).unwrap(), wire);
+        assert_eq!(serde_json::from_str::<ActivityState>(wire).unwrap(), state);
+    }
+}
+
+#[test]
+fn test_activity_state_from_str() {
+    for state in ActivityState::ALL {
+        assert_eq!(state.to_string().parse::<ActivityState>(), Ok(state));
+    }
+    assert!("WALKING".parse::<ActivityState>().is_err());
+    assert!("active".parse::<ActivityState>().is_err());
+}
+
+#[test]
+fn test_activity_state_rejects_unknown_json() {
+    assert!(serde_json::from_str::<ActivityState>("\"WALKING\"").is_err());
+}
+
 // ProcessedState Tests
 
 #[test]
 fn test_processed_state_serialization() {
     let state = ProcessedState {
-        state: "SEDENTARY".to_string(),
+        state: ActivityState::Sedentary,
         timer: 600,
         val: 0.02,
         alert: true,
         timestamp: Utc.with_ymd_and_hms(2026, 1, 6, 10, 0, 0).unwrap(),
+        user_id: None,
+        daily: None,
+        classification_confidence: None,
+        replayed: false,
     };
 
     let json = serde_json::to_string(&state).unwrap();
@@ -69,7 +144,7 @@ fn test_processed_state_deserialization() {
     }"#;
 
     let state: ProcessedState = serde_json::from_str(json).unwrap();
-    assert_eq!(state.state, "ACTIVE");
+    assert_eq!(state.state, ActivityState::Active);
     assert_eq!(state.timer, 0);
     assert!(!state.alert);
 }
@@ -78,11 +153,15 @@ fn test_processed_state_deserialization() {
 fn test_processed_state_alert_threshold() {
     // Test case for alert being true (sedentary for too long)
     let state = ProcessedState {
-        state: "SEDENTARY".to_string(),
+        state: ActivityState::Sedentary,
         timer: 1800, // 30 minutes
         val: 0.01,
         alert: true,
         timestamp: Utc.with_ymd_and_hms(2026, 1, 6, 10, 30, 0).unwrap(),
+        user_id: None,
+        daily: None,
+        classification_confidence: None,
+        replayed: false,
     };
 
     assert!(state.alert);
@@ -92,11 +171,15 @@ fn test_processed_state_alert_threshold() {
 #[test]
 fn test_processed_state_no_alert() {
     let state = ProcessedState {
-        state: "FIDGET".to_string(),
+        state: ActivityState::Fidget,
         timer: 60,
         val: 0.2,
         alert: false,
         timestamp: Utc.with_ymd_and_hms(2026, 1, 6, 10, 1, 0).unwrap(),
+        user_id: None,
+        daily: None,
+        classification_confidence: None,
+        replayed: false,
     };
 
     assert!(!state.alert);
@@ -105,11 +188,15 @@ fn test_processed_state_no_alert() {
 #[test]
 fn test_processed_state_clone() {
     let state = ProcessedState {
-        state: "ACTIVE".to_string(),
+        state: ActivityState::Active,
         timer: 0,
         val: 1.5,
         alert: false,
         timestamp: Utc.with_ymd_and_hms(2026, 1, 6, 10, 0, 0).unwrap(),
+        user_id: None,
+        daily: None,
+        classification_confidence: None,
+        replayed: false,
     };
 
     let cloned = state.clone();
@@ -119,11 +206,15 @@ fn test_processed_state_clone() {
 #[test]
 fn test_processed_state_roundtrip() {
     let original = ProcessedState {
-        state: "SEDENTARY".to_string(),
+        state: ActivityState::Sedentary,
         timer: 900,
         val: 0.05,
         alert: false,
         timestamp: Utc.with_ymd_and_hms(2026, 1, 6, 10, 15, 0).unwrap(),
+        user_id: None,
+        daily: None,
+        classification_confidence: None,
+        replayed: false,
     };
 
     let json = serde_json::to_string(&original).unwrap();
@@ -131,3 +222,120 @@ fn test_processed_state_roundtrip() {
 
     assert_eq!(original, restored);
 }
+
+// Per-user Stream Tests
+
+fn tagged_reading(user_id: Option<Uuid>) -> String {
+    serde_json::to_string(&ProcessedState {
+        state: ActivityState::Sedentary,
+        timer: 30,
+        val: 0.01,
+        alert: false,
+        timestamp: Utc.with_ymd_and_hms(2026, 1, 6, 10, 0, 30).unwrap(),
+        user_id,
+        daily: None,
+        classification_confidence: None,
+        replayed: false,
+    })
+    .unwrap()
+}
+
+#[test]
+fn test_processed_state_omits_missing_user_id() {
+    let json = tagged_reading(None);
+    assert!(!json.contains("user_id"));
+}
+
+#[test]
+fn test_processed_state_replayed_flag_only_when_set() {
+    let mut reading: ProcessedState = serde_json::from_str(&tagged_reading(None)).unwrap();
+    assert!(!reading.replayed);
+    assert!(!serde_json::to_string(&reading)
+        .unwrap()
+        .contains("replayed"));
+
+    reading.replayed = true;
+    let json = serde_json::to_string(&reading).unwrap();
+    assert!(json.contains("\"replayed\":true"));
+}
+
+#[test]
+fn test_processed_state_includes_user_id() {
+    let user_id = Uuid::new_v4();
+    let json = tagged_reading(Some(user_id));
+    assert!(json.contains(&format!("\"user_id\":\"{}\"", user_id)));
+}
+
+#[test]
+fn test_visible_to_anonymous_sees_everything() {
+    assert!(visible_to(&tagged_reading(None), None));
+    assert!(visible_to(&tagged_reading(Some(Uuid::new_v4())), None));
+}
+
+#[test]
+fn test_visible_to_filters_other_users() {
+    let me = Uuid::new_v4();
+    assert!(visible_to(&tagged_reading(Some(me)), Some(me)));
+    assert!(!visible_to(&tagged_reading(Some(Uuid::new_v4())), Some(me)));
+    assert!(!visible_to(&tagged_reading(None), Some(me)));
+}
+
+#[test]
+fn test_visible_to_rejects_unparseable_payload() {
+    assert!(!visible_to("not json", Some(Uuid::new_v4())));
+}
+
+#[test]
+fn test_visible_to_routes_state_changes_by_owner() {
+    let me = Uuid::new_v4();
+    let change = serde_json::to_string(&StateChange {
+        old_state: ActivityState::Sedentary,
+        new_state: ActivityState::Active,
+        duration_seconds: 600,
+        timestamp: Utc.with_ymd_and_hms(2026, 1, 6, 10, 10, 0).unwrap(),
+        user_id: Some(me),
+    })
+    .unwrap();
+
+    assert!(visible_to(&change, Some(me)));
+    assert!(!visible_to(&change, Some(Uuid::new_v4())));
+}
+
+// Daily Totals Tests
+
+#[test]
+fn test_processed_state_flattens_daily_totals() {
+    let state = ProcessedState {
+        state: ActivityState::Sedentary,
+        timer: 30,
+        val: 0.01,
+        alert: false,
+        timestamp: Utc.with_ymd_and_hms(2026, 1, 6, 10, 0, 30).unwrap(),
+        user_id: None,
+        daily: Some(DailyTotals {
+            daily_sedentary_sec: 1800,
+            daily_active_sec: 600,
+            daily_fidget_sec: 120,
+        }),
+        classification_confidence: Some(0.4),
+        replayed: false,
+    };
+
+    let json = serde_json::to_string(&state).unwrap();
+    assert!(json.contains("\"classification_confidence\":0.4"));
+    assert!(json.contains("\"daily_sedentary_sec\":1800"));
+    assert!(json.contains("\"daily_active_sec\":600"));
+    assert!(json.contains("\"daily_fidget_sec\":120"));
+
+    let restored: ProcessedState = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, state);
+}
+
+#[test]
+fn test_processed_state_without_daily_totals() {
+    let json = tagged_reading(None);
+    assert!(!json.contains("daily_"));
+
+    let restored: ProcessedState = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.daily, None);
+}
@@ -0,0 +1,194 @@
+use crate::{
+    auth::{generate_token, hash_password, revoke_refresh_tokens},
+    signup::{validate_password, weak_password_response},
+    state::AppState,
+};
+use axum::{
+    extract::{Form, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use redis::AsyncCommands;
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+// Reset tokens expire after 15 minutes
+const RESET_TOKEN_TTL_SECONDS: u64 = 900;
+
+fn reset_key(token: &str) -> String {
+    format!("reset:{}", token)
+}
+
+/// Delivers reset tokens to account holders
+pub trait ResetMailer: Send + Sync {
+    fn send(&self, email: &str, token: &str);
+}
+
+/// Production default until email delivery exists: the token is stored but
+/// never written anywhere, so the logs can't be used to take over an account
+pub struct DisabledResetMailer;
+
+impl ResetMailer for DisabledResetMailer {
+    fn send(&self, _email: &str, _token: &str) {
+        println!("Password reset requested; no mailer configured, token not delivered");
+    }
+}
+
+/// Development only (PASSWORD_RESET_LOG_TOKENS=true): prints the token so a
+/// local reset can be finished without email
+pub struct LogResetMailer;
+
+impl ResetMailer for LogResetMailer {
+    fn send(&self, email: &str, token: &str) {
+        println!("Password reset token for {}: {}", email, token);
+    }
+}
+
+/// The mailer selected by PASSWORD_RESET_LOG_TOKENS
+pub fn reset_mailer(log_tokens: bool) -> Arc<dyn ResetMailer> {
+    if log_tokens {
+        Arc::new(LogResetMailer)
+    } else {
+        Arc::new(DisabledResetMailer)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ForgotPasswordForm {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordForm {
+    pub token: String,
+    pub password: String,
+}
+
+/// Issues a single-use reset token for the account, if it exists.
+/// Always answers with the same 200 so the endpoint can't be used to discover emails;
+/// storing and delivering the token happens off the request path, so a known
+/// email takes no longer to answer than an unknown one.
+pub async fn forgot_password_handler(
+    State(state): State<AppState>,
+    Form(form): Form<ForgotPasswordForm>,
+) -> Response {
+    let generic_response = (
+        StatusCode::OK,
+        "If that email is registered, a reset link has been sent.".to_string(),
+    );
+
+    let user_result = sqlx::query!(r#"SELECT user_id FROM users WHERE email = $1"#, form.email)
+        .fetch_optional(&state.db)
+        .await;
+
+    let user_id = match user_result {
+        Ok(Some(user)) => user.user_id,
+        Ok(None) => return generic_response.into_response(),
+        Err(e) => {
+            eprintln!("Database error: {e:?}");
+            return generic_response.into_response();
+        }
+    };
+
+    let redis = state.redis.clone();
+    let mailer = state.reset_mailer.clone();
+    tokio::spawn(async move {
+        let token = generate_token();
+        let stored: redis::RedisResult<()> = match redis.get_multiplexed_async_connection().await {
+            Ok(mut con) => {
+                con.set_ex(
+                    reset_key(&token),
+                    user_id.to_string(),
+                    RESET_TOKEN_TTL_SECONDS,
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+        match stored {
+            Ok(()) => mailer.send(&form.email, &token),
+            Err(e) => eprintln!("Failed to store reset token: {e:?}"),
+        }
+    });
+
+    generic_response.into_response()
+}
+
+/// Consumes a reset token and sets a new password.
+/// The token is deleted as it is read, so it can never be used twice.
+pub async fn reset_password_handler(
+    State(state): State<AppState>,
+    Form(form): Form<ResetPasswordForm>,
+) -> Response {
+    // Validate before consuming the token so a weak password doesn't burn it
+    if let Err(failed) = validate_password(&form.password, state.config.auth.password_min_length) {
+        return weak_password_response(&failed);
+    }
+
+    let mut redis_conn = match state.redis.get_multiplexed_async_connection().await {
+        Ok(conn) => conn,
+        Err(_) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Service unavailable".to_string(),
+            )
+                .into_response();
+        }
+    };
+
+    let stored: Option<String> = redis_conn
+        .get_del(reset_key(&form.token))
+        .await
+        .unwrap_or(None);
+
+    let user_id = match stored.and_then(|id| Uuid::parse_str(&id).ok()) {
+        Some(id) => id,
+        None => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "Invalid or expired reset token.".to_string(),
+            )
+                .into_response();
+        }
+    };
+
+    let password_hash = match hash_password(&form.password, &state.config.auth) {
+        Ok(hash) => hash,
+        Err(_) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to hash password".to_string(),
+            )
+                .into_response();
+        }
+    };
+
+    let result = sqlx::query!(
+        r#"UPDATE users SET password_hash = $1 WHERE user_id = $2"#,
+        password_hash,
+        user_id
+    )
+    .execute(&state.db)
+    .await;
+
+    if let Err(e) = result {
+        eprintln!("Failed to update password: {e:?}");
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Could not reset password".to_string(),
+        )
+            .into_response();
+    }
+
+    // Existing sessions shouldn't survive a password change
+    if let Err(e) = revoke_refresh_tokens(&state.db, user_id).await {
+        eprintln!("Failed to revoke refresh tokens: {e:?}");
+    }
+
+    (
+        StatusCode::OK,
+        "Password updated. You can now log in.".to_string(),
+    )
+        .into_response()
+}
@@ -0,0 +1,66 @@
+use crate::{auth::AuthUser, state::AppState};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize)]
+pub struct UserProfile {
+    user_id: Uuid,
+    email: String,
+    name: String,
+    created_at: DateTime<Utc>,
+    role: String,
+}
+
+/// Profile of the authenticated user, read from the DB rather than the token
+/// Endpoint: GET /auth/me
+pub async fn get_current_user(user: AuthUser, State(state): State<AppState>) -> impl IntoResponse {
+    let result = sqlx::query!(
+        r#"
+        SELECT user_id, email, name, created_at, role
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user.user_id
+    )
+    .fetch_optional(&state.db)
+    .await;
+
+    match result {
+        Ok(Some(row)) => (
+            StatusCode::OK,
+            Json(UserProfile {
+                user_id: row.user_id,
+                email: row.email,
+                name: row.name,
+                created_at: row.created_at,
+                role: row.role,
+            }),
+        )
+            .into_response(),
+        // Token is still valid but the account has been deleted
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "error": "User not found"
+            })),
+        )
+            .into_response(),
+        Err(e) => {
+            eprintln!("Database error: {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({
+                    "error": "Failed to fetch user profile"
+                })),
+            )
+                .into_response()
+        }
+    }
+}
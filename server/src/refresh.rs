@@ -0,0 +1,94 @@
+use crate::{
+    auth::{create_jwt, create_refresh_token, hash_refresh_token, AuthError, LoginResponse},
+    state::AppState,
+};
+use axum::{
+    extract::{Json, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+/// Exchanges a valid refresh token for a new access token.
+/// The presented token is rotated: it is revoked and a new one is issued,
+/// so a stolen refresh token can only be used once.
+pub async fn refresh_handler(
+    State(state): State<AppState>,
+    Json(req): Json<RefreshRequest>,
+) -> Response {
+    // Consume the token in a single statement so concurrent reuse can't race
+    let consumed = sqlx::query!(
+        r#"
+        UPDATE refresh_tokens rt
+        SET revoked_at = NOW()
+        FROM users u
+        WHERE rt.user_id = u.user_id
+          AND rt.token_hash = $1
+          AND rt.revoked_at IS NULL
+          AND rt.expires_at > NOW()
+        RETURNING rt.user_id, u.name, u.role
+        "#,
+        hash_refresh_token(&req.refresh_token)
+    )
+    .fetch_optional(&state.db)
+    .await;
+
+    let user = match consumed {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return AuthError {
+                message: "Invalid refresh token",
+            }
+            .into_response()
+        }
+        Err(e) => {
+            eprintln!("Database error: {e:?}");
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal Server Error.".to_string(),
+            )
+                .into_response();
+        }
+    };
+
+    let token = match create_jwt(
+        &state.config,
+        &user.user_id.to_string(),
+        &user.name,
+        &user.role,
+    ) {
+        Ok(token) => token,
+        Err(_) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to generate token".to_string(),
+            )
+                .into_response()
+        }
+    };
+
+    match create_refresh_token(
+        &state.db,
+        user.user_id,
+        state.config.auth.refresh_token_ttl_days,
+    )
+    .await
+    {
+        Ok(refresh_token) => {
+            Json(LoginResponse::new(token, refresh_token, user.user_id)).into_response()
+        }
+        Err(e) => {
+            eprintln!("Failed to store refresh token: {e:?}");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to generate token".to_string(),
+            )
+                .into_response()
+        }
+    }
+}
@@ -1,163 +1,825 @@
-use crate::models::{ProcessedState, RawReading};
-use crate::serial::alert_limit_sec;
-use chrono::{NaiveTime, Utc};
+use crate::auth::{AuthError, AuthUser};
+use crate::config::Config;
+use crate::daily_totals::DailyAccumulator;
+use crate::history::{push_history, HistorySource, SENSOR_HISTORY_KEY};
+use crate::models::{ActivityState, ProcessedState, RawReading, StateChange};
+use crate::serial::{
+    classification_confidence, classify_state, parse_csv_reading, smooth, timer_state,
+    AlertCooldown, PirDebouncer, SedentaryTimer, SerialFormat, SharedThresholds, SmoothingMode,
+    Thresholds, TimestampResolver,
+};
+use crate::state::AppState;
+use crate::state_change::StateChangeDetector;
+use axum::{
+    extract::{Multipart, Path as UrlPath, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, NaiveTime, Utc};
+use futures::StreamExt;
 use redis::AsyncCommands;
-use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashMap, VecDeque};
 use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, Notify};
 use tokio::time::sleep;
+use uuid::Uuid;
 
-const SMOOTHING_WINDOW: usize = 10;
+/// Pause/stop switch and progress for one running replay, checked before every reading
+#[derive(Default)]
+pub struct ReplayControl {
+    paused: AtomicBool,
+    stopped: AtomicBool,
+    notify: Notify,
+    // Progress, read by GET /api/replay/:id/status
+    pass: AtomicUsize,
+    lines_processed: AtomicUsize,
+    total_lines: AtomicUsize,
+    readings_broadcast: AtomicUsize,
+    latest: Mutex<Option<LatestReading>>,
+}
 
-fn thresh_fidget() -> f32 {
-    env::var("THRESH_FIDGET")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(0.020)
+#[derive(Debug, Clone, Serialize)]
+struct LatestReading {
+    timestamp: DateTime<Utc>,
+    state: ActivityState,
+    // When it was broadcast, to spot a stalled replay
+    replayed_at: DateTime<Utc>,
 }
 
-fn thresh_active() -> f32 {
-    env::var("THRESH_ACTIVE")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(0.040)
+/// Snapshot returned by the status endpoint
+#[derive(Debug, Serialize)]
+pub struct ReplayStatus {
+    pub status: &'static str,
+    pub pass: usize,
+    pub lines_processed: usize,
+    pub total_lines: usize,
+    pub readings_broadcast: usize,
+    pub current_timestamp: Option<DateTime<Utc>>,
+    pub current_state: Option<ActivityState>,
+    pub last_broadcast_at: Option<DateTime<Utc>>,
+}
+
+impl ReplayControl {
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// A new pass over a log of `total_lines` lines begins
+    fn start_pass(&self, total_lines: usize) {
+        self.pass.fetch_add(1, Ordering::Relaxed);
+        self.lines_processed.store(0, Ordering::Relaxed);
+        self.total_lines.store(total_lines, Ordering::Relaxed);
+    }
+
+    fn line_processed(&self) {
+        self.lines_processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn reading_broadcast(&self, output: &ProcessedState) {
+        self.readings_broadcast.fetch_add(1, Ordering::Relaxed);
+        *self.latest.lock().unwrap() = Some(LatestReading {
+            timestamp: output.timestamp,
+            state: output.state,
+            replayed_at: Utc::now(),
+        });
+    }
+
+    pub fn status(&self) -> ReplayStatus {
+        let status = if self.stopped.load(Ordering::SeqCst) {
+            "stopping"
+        } else if self.is_paused() {
+            "paused"
+        } else {
+            "running"
+        };
+        let latest = self.latest.lock().unwrap().clone();
+        ReplayStatus {
+            status,
+            pass: self.pass.load(Ordering::Relaxed),
+            lines_processed: self.lines_processed.load(Ordering::Relaxed),
+            total_lines: self.total_lines.load(Ordering::Relaxed),
+            readings_broadcast: self.readings_broadcast.load(Ordering::Relaxed),
+            current_timestamp: latest.as_ref().map(|l| l.timestamp),
+            current_state: latest.as_ref().map(|l| l.state),
+            last_broadcast_at: latest.map(|l| l.replayed_at),
+        }
+    }
+
+    /// Waits out a pause; `false` once the replay has been stopped
+    pub async fn proceed(&self) -> bool {
+        loop {
+            // Registered before the flags are read so a resume in between isn't missed
+            let notified = self.notify.notified();
+            if self.stopped.load(Ordering::SeqCst) {
+                return false;
+            }
+            if !self.is_paused() {
+                return true;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Running replays by id, so the control endpoints can reach them
+#[derive(Clone, Default)]
+pub struct ReplayRegistry(Arc<Mutex<HashMap<Uuid, Arc<ReplayControl>>>>);
+
+impl ReplayRegistry {
+    pub fn register(&self) -> (Uuid, Arc<ReplayControl>) {
+        let id = Uuid::new_v4();
+        let control = Arc::new(ReplayControl::default());
+        self.0.lock().unwrap().insert(id, control.clone());
+        (id, control)
+    }
+
+    pub fn get(&self, id: &Uuid) -> Option<Arc<ReplayControl>> {
+        self.0.lock().unwrap().get(id).cloned()
+    }
+
+    pub fn remove(&self, id: &Uuid) {
+        self.0.lock().unwrap().remove(id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// Each replay caches its readings in its own list, apart from live `sensor_history`
+const REPLAY_HISTORY_PREFIX: &str = "sensor_history:replay";
+
+/// Redis list holding one replay's readings
+pub fn replay_history_key(id: &Uuid) -> String {
+    format!("{}:{}", REPLAY_HISTORY_PREFIX, id)
+}
+
+/// History list for `source`: a replay's own list while it runs, else live
+/// `sensor_history` (also for a replay that has already finished)
+pub fn history_key(replays: &ReplayRegistry, source: HistorySource) -> String {
+    match source {
+        HistorySource::Replay(id) if replays.get(&id).is_some() => replay_history_key(&id),
+        _ => SENSOR_HISTORY_KEY.to_string(),
+    }
+}
+
+/// Drops a replay's history so it never outlives the replay
+async fn clear_replay_history(redis_client: &redis::Client, key: &str) {
+    if let Ok(mut con) = redis_client.get_multiplexed_async_connection().await {
+        let _: () = con.del(key).await.unwrap_or(());
+    }
+}
+
+/// Drops every replay history list, e.g. those left by replays cut short by a restart
+async fn clear_stale_replay_histories(redis_client: &redis::Client) {
+    let Ok(mut con) = redis_client.get_multiplexed_async_connection().await else {
+        return;
+    };
+    let keys: Vec<String> = match con
+        .scan_match::<_, String>(format!("{}*", REPLAY_HISTORY_PREFIX))
+        .await
+    {
+        Ok(keys) => keys.collect().await,
+        Err(_) => return,
+    };
+    if !keys.is_empty() {
+        let _: () = con.del(keys).await.unwrap_or(());
+    }
+}
+
+/// Where a replay starts and whether it repeats (`?loop=`, `?skip=`, `?start_ts=`)
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct ReplayOptions {
+    // Restart from the top of the log at EOF until stopped
+    #[serde(rename = "loop", default)]
+    pub looping: bool,
+    // Fast-forward past this many readings before broadcasting
+    pub skip: Option<usize>,
+    // Fast-forward to the first reading at or after this RTC time (HH:MM:SS)
+    pub start_ts: Option<NaiveTime>,
+    // Sleep the logged gap between readings instead of REPLAY_SPEED_MS
+    #[serde(default)]
+    pub realtime: bool,
+    // Divides realtime gaps (2.0 plays twice as fast); ignored otherwise
+    pub speed: Option<f64>,
+    // Owner tagged on every replayed reading, so the DB worker mirrors them to
+    // this user's `sensor_data` (admin only)
+    pub user_id: Option<Uuid>,
+    // Line format of the log (`json` or `csv`); defaults to SERIAL_FORMAT
+    pub format: Option<SerialFormat>,
+}
+
+impl ReplayOptions {
+    /// The log's line format: `?format=`, else the live serial format
+    pub fn format(&self, config: &Config) -> SerialFormat {
+        self.format.unwrap_or(config.serial.format)
+    }
+
+    /// Whether `reading` (the `index`-th of the first pass) is before the seek point
+    fn before_start(&self, index: usize, reading: &RawReading) -> bool {
+        if self.skip.is_some_and(|skip| index < skip) {
+            return true;
+        }
+        match (
+            self.start_ts,
+            NaiveTime::parse_from_str(&reading.ts, "%H:%M:%S"),
+        ) {
+            (Some(start), Ok(ts)) => ts < start,
+            _ => false,
+        }
+    }
 }
 
-fn classify_state(pir: i32, smoothed_acc: f32) -> String {
-    if pir == 1 || smoothed_acc > thresh_active() {
-        "ACTIVE".to_string()
-    } else if smoothed_acc > thresh_fidget() {
-        "FIDGET".to_string()
+/// Logged gap between two readings scaled by `speed` and capped at `max_gap`;
+/// out-of-order timestamps give no delay
+pub fn realtime_delay(
+    previous: DateTime<Utc>,
+    current: DateTime<Utc>,
+    speed: f64,
+    max_gap: Duration,
+) -> Duration {
+    let gap = (current - previous).to_std().unwrap_or(Duration::ZERO);
+    let speed = if speed.is_finite() && speed > 0.0 {
+        speed
     } else {
-        "SEDENTARY".to_string()
+        1.0
+    };
+    gap.div_f64(speed).min(max_gap)
+}
+
+/// Extracts the reading from a log line, skipping timestamp prefixes such as
+/// "[2026-01-23 16:12:03.123] {...}" (or "[...] 16:12:03,0,0.045" for CSV)
+fn parse_log_line(line: &str, format: SerialFormat) -> Option<RawReading> {
+    let clean_line = line.trim();
+    match format {
+        SerialFormat::Json => {
+            let json_start = clean_line.find('{')?;
+            serde_json::from_str(&clean_line[json_start..]).ok()
+        }
+        SerialFormat::Csv => {
+            let row = match clean_line.strip_prefix('[') {
+                Some(prefixed) => prefixed.split_once(']')?.1,
+                None => clean_line,
+            };
+            parse_csv_reading(row).ok()
+        }
     }
 }
 
-fn sensor_history_limit() -> isize {
-    env::var("SENSOR_HISTORY_LIMIT")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(500)
+/// Channels and shared state a replay publishes through, taken from `AppState`
+pub struct ReplayContext {
+    pub config: Arc<Config>,
+    pub tx: broadcast::Sender<String>,
+    pub state_tx: broadcast::Sender<String>,
+    pub redis_client: redis::Client,
+    pub thresholds: SharedThresholds,
+    // This replay's history list (`replay_history_key`)
+    pub history_key: String,
 }
 
+impl ReplayContext {
+    pub fn from_state(state: &AppState, id: &Uuid) -> Self {
+        Self {
+            config: state.config.clone(),
+            tx: state.tx.clone(),
+            state_tx: state.state_tx.clone(),
+            redis_client: state.redis.clone(),
+            thresholds: state.thresholds.clone(),
+            history_key: replay_history_key(id),
+        }
+    }
+}
+
+/// Smoothing, classification and timer state carried from one reading to the next
+struct ReplayPipeline {
+    config: Arc<Config>,
+    window: usize,
+    mode: SmoothingMode,
+    acc_buffer: VecDeque<f32>,
+    sedentary_timer: SedentaryTimer,
+    current_state: Option<ActivityState>,
+    state_changes: StateChangeDetector,
+    timestamps: TimestampResolver,
+    pir_debounce: PirDebouncer,
+    alerts: AlertCooldown,
+    // Separate from the live serial totals so a replay never inflates them
+    daily_totals: DailyAccumulator,
+}
+
+impl ReplayPipeline {
+    fn new(config: Arc<Config>) -> Self {
+        let window = config.serial.smoothing_window;
+        Self {
+            mode: config.serial.smoothing_mode,
+            pir_debounce: PirDebouncer::new(config.serial.pir_debounce_samples),
+            alerts: AlertCooldown::new(config.serial.alert_cooldown_sec),
+            daily_totals: DailyAccumulator::new(config.timezone),
+            sedentary_timer: SedentaryTimer::new(
+                config.serial.timer_source,
+                config.serial.timer_max_gap,
+            ),
+            config,
+            window,
+            acc_buffer: VecDeque::with_capacity(window),
+            current_state: None,
+            state_changes: StateChangeDetector::new(),
+            timestamps: TimestampResolver::new(),
+        }
+    }
+
+    /// Classifies one reading, returning the output and any state transition
+    fn process(
+        &mut self,
+        reading: &RawReading,
+        thresholds: Thresholds,
+        user_id: Option<Uuid>,
+    ) -> (ProcessedState, Option<StateChange>) {
+        // Add to smoothing buffer
+        while self.acc_buffer.len() >= self.window {
+            self.acc_buffer.pop_front();
+        }
+        self.acc_buffer.push_back(reading.acc);
+
+        // Calculate smoothed acceleration
+        let smoothed_acc = smooth(&self.acc_buffer, self.mode);
+
+        // Classify state, with how well PIR and acceleration agree on it
+        let pir = self.pir_debounce.observe(reading.pir);
+        let fusion = self.config.serial.fusion;
+        let state = classify_state(
+            pir,
+            smoothed_acc,
+            self.current_state,
+            thresholds,
+            self.config.serial.hysteresis,
+        );
+        let confidence = classification_confidence(state, pir, smoothed_acc, thresholds, fusion);
+        self.current_state = Some(state);
+
+        // Build processed output; the timestamp also drives the sedentary timer
+        let timestamp = self.timestamps.resolve(reading);
+        let timer = self.sedentary_timer.observe(
+            timer_state(state, confidence, fusion),
+            reading,
+            timestamp,
+        );
+        let change = self.state_changes.observe(state, timestamp, user_id);
+        let output = ProcessedState {
+            state,
+            timer,
+            val: smoothed_acc,
+            alert: self
+                .alerts
+                .observe(timer, timer >= self.config.serial.alert_limit_sec),
+            timestamp,
+            user_id,
+            daily: Some(self.daily_totals.observe(state, timestamp)),
+            classification_confidence: Some(confidence),
+            replayed: false,
+        };
+        (output, change)
+    }
+}
+
+/// Replays a log; readings before the seek point only advance the pipeline so
+/// the first broadcast reading carries the timer/smoothing state it had in the
+/// original run. `replay_speed_ms` applies between broadcast readings only,
+/// unless `realtime` reproduces the logged gaps instead.
 pub async fn replay_log_file(
-    tx: broadcast::Sender<String>,
-    redis_client: redis::Client,
+    context: ReplayContext,
     log_path: &Path,
     replay_speed_ms: u64,
+    options: &ReplayOptions,
+    control: &ReplayControl,
 ) -> Result<usize, String> {
-    let file = File::open(log_path).map_err(|e| format!("Failed to open log file: {}", e))?;
-    let reader = BufReader::new(file);
+    let ReplayContext {
+        config,
+        tx,
+        state_tx,
+        redis_client,
+        thresholds,
+        history_key,
+    } = context;
+    let format = options.format(&config);
 
     // Get Redis connection for caching history
     let mut redis_con = redis_client.get_multiplexed_async_connection().await.ok();
 
-    let mut acc_buffer: VecDeque<f32> = VecDeque::with_capacity(SMOOTHING_WINDOW);
-    let mut sedentary_timer: u64 = 0;
-    let mut last_second: Option<String> = None;
+    let max_gap = Duration::from_millis(config.replay.max_gap_ms);
     let mut count = 0;
+    let mut first_pass = true;
 
-    for line in reader.lines() {
-        let line = match line {
-            Ok(l) => l,
-            Err(_) => continue,
-        };
+    // Counted once up front so progress can be reported as a fraction
+    let total_lines = File::open(log_path)
+        .map(|file| BufReader::new(file).lines().count())
+        .map_err(|e| format!("Failed to open log file: {}", e))?;
 
-        let clean_line = line.trim();
+    'passes: loop {
+        let file = File::open(log_path).map_err(|e| format!("Failed to open log file: {}", e))?;
+        let reader = BufReader::new(file);
+        control.start_pass(total_lines);
 
-        // Skip timestamp prefixes if present (e.g., "[2026-01-23 16:12:03.123] {...}")
-        let json_start = clean_line.find('{');
-        let json_str = match json_start {
-            Some(idx) => &clean_line[idx..],
-            None => continue,
-        };
+        // Each pass (including every loop restart) starts from a cold pipeline
+        let mut pipeline = ReplayPipeline::new(config.clone());
+        let mut index = 0;
+        let mut previous_timestamp: Option<DateTime<Utc>> = None;
 
-        if let Ok(reading) = serde_json::from_str::<RawReading>(json_str) {
-            // Add to smoothing buffer
-            if acc_buffer.len() >= SMOOTHING_WINDOW {
-                acc_buffer.pop_front();
+        for line in reader.lines() {
+            if !control.proceed().await {
+                println!("Replay stopped after {} records", count);
+                break 'passes;
             }
-            acc_buffer.push_back(reading.acc);
+            control.line_processed();
 
-            // Calculate smoothed acceleration
-            let smoothed_acc: f32 = if acc_buffer.is_empty() {
-                0.0
-            } else {
-                acc_buffer.iter().sum::<f32>() / acc_buffer.len() as f32
+            let line = match line {
+                Ok(l) => l,
+                Err(_) => continue,
+            };
+            let Some(reading) = parse_log_line(&line, format)
+                .filter(|reading| reading.validate(config.serial.max_acc).is_ok())
+            else {
+                continue;
             };
 
-            // Classify state
-            let state = classify_state(reading.pir, smoothed_acc);
-
-            // Update sedentary timer (once per second)
-            let current_second = reading.ts.clone();
-            if last_second.as_ref() != Some(&current_second) {
-                last_second = Some(current_second);
+            let seeking = first_pass && options.before_start(index, &reading);
+            index += 1;
+            let (output, change) =
+                pipeline.process(&reading, thresholds.current(), options.user_id);
+            if seeking {
+                continue;
+            }
 
-                match state.as_str() {
-                    "ACTIVE" => sedentary_timer = 0,
-                    "FIDGET" => {}
-                    "SEDENTARY" => sedentary_timer += 1,
-                    _ => {}
+            // Realtime: wait out the logged gap before showing this reading
+            if options.realtime {
+                if let Some(previous) = previous_timestamp {
+                    let delay = realtime_delay(
+                        previous,
+                        output.timestamp,
+                        options.speed.unwrap_or(1.0),
+                        max_gap,
+                    );
+                    if !delay.is_zero() {
+                        sleep(delay).await;
+                    }
                 }
+                previous_timestamp = Some(output.timestamp);
             }
 
-            // Build processed output
-            let timestamp = NaiveTime::parse_from_str(&reading.ts, "%H:%M:%S")
-                .map(|time| Utc::now().date_naive().and_time(time).and_utc())
-                .unwrap_or_else(|_| Utc::now());
-
-            let output = ProcessedState {
-                state: state.clone(),
-                timer: sedentary_timer,
-                val: smoothed_acc,
-                alert: sedentary_timer >= alert_limit_sec(),
-                timestamp,
-            };
-
             let json_out = serde_json::to_string(&output).unwrap();
 
-            // Cache in Redis for SSE history (like serial.rs does)
+            if let Some(change) = change {
+                let _ = state_tx.send(serde_json::to_string(&change).unwrap());
+            }
+
+            // Cache in Redis for SSE/WebSocket history (like serial.rs does),
+            // under the replay key so live history stays untouched
             if let Some(ref mut con) = redis_con {
-                let _: () = con.lpush("sensor_history", &json_out).await.unwrap_or(());
-                let _: () = con
-                    .ltrim("sensor_history", 0, sensor_history_limit() - 1)
-                    .await
-                    .unwrap_or(());
+                let _ = push_history(
+                    con,
+                    std::slice::from_ref(&history_key),
+                    std::slice::from_ref(&json_out),
+                    config.server.history_limit,
+                )
+                .await;
             }
 
             // Broadcast to connected clients
             let _ = tx.send(json_out);
             count += 1;
+            control.reading_broadcast(&output);
 
             // Replay delay
-            if replay_speed_ms > 0 {
+            if !options.realtime && replay_speed_ms > 0 {
                 sleep(Duration::from_millis(replay_speed_ms)).await;
             }
         }
+
+        // A log with no readings would otherwise loop without ever yielding
+        if !options.looping || index == 0 {
+            break;
+        }
+        first_pass = false;
     }
 
     Ok(count)
 }
 
-/// Spawns a background task to replay log data
+/// Spawns a background task to replay log data, returning its id for the control endpoints.
+/// A `temporary` log (an upload) is deleted once the replay finishes or is stopped.
 pub fn spawn_replay_task(
-    tx: broadcast::Sender<String>,
-    redis_client: redis::Client,
+    state: &AppState,
     log_path: String,
     replay_speed_ms: u64,
-) {
+    options: ReplayOptions,
+    temporary: bool,
+) -> Uuid {
+    let replays = state.replays.clone();
+    let (id, control) = replays.register();
+    let first = replays.len() == 1;
+    let context = ReplayContext::from_state(state, &id);
+    let redis_client = context.redis_client.clone();
+    let history_key = context.history_key.clone();
     tokio::spawn(async move {
         let path = Path::new(&log_path);
-        println!("Starting replay from: {}", log_path);
+        println!("Starting replay {} from: {}", id, log_path);
+
+        // Leftovers from an earlier replay (e.g. one cut short by a restart)
+        if first {
+            clear_stale_replay_histories(&redis_client).await;
+        }
 
-        match replay_log_file(tx, redis_client, path, replay_speed_ms).await {
+        match replay_log_file(context, path, replay_speed_ms, &options, &control).await {
             Ok(count) => println!("Replay complete: {} records processed", count),
             Err(e) => eprintln!("Replay error: {}", e),
         }
+        replays.remove(&id);
+        clear_replay_history(&redis_client, &history_key).await;
+        if temporary {
+            if let Err(e) = std::fs::remove_file(path) {
+                eprintln!("Failed to remove uploaded replay log {}: {}", log_path, e);
+            }
+        }
     });
+    id
+}
+
+/// Whether any line of an uploaded log is a usable `RawReading` in `format`
+pub fn contains_reading(log: &[u8], format: SerialFormat) -> bool {
+    String::from_utf8_lossy(log)
+        .lines()
+        .any(|line| parse_log_line(line, format).is_some())
 }
+
+fn upload_error(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(json!({"error": message.into()}))).into_response()
+}
+
+/// `?user_id=` writes rows under someone's account, so it needs an admin and
+/// an existing user (an unknown id would fail the `sensor_data` foreign key)
+pub async fn authorize_target_user(
+    state: &AppState,
+    options: &ReplayOptions,
+    user: Result<AuthUser, AuthError>,
+) -> Result<(), Response> {
+    let Some(target) = options.user_id else {
+        return Ok(());
+    };
+    let user = user.map_err(IntoResponse::into_response)?;
+    if user.role != "admin" {
+        return Err(upload_error(
+            StatusCode::FORBIDDEN,
+            "Replaying into a user's data requires an admin",
+        ));
+    }
+
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM users WHERE user_id = $1) AS "exists!""#,
+        target
+    )
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| {
+        eprintln!("Database error checking replay user: {:?}", e);
+        upload_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to look up user")
+    })?;
+    if !exists {
+        return Err(upload_error(StatusCode::NOT_FOUND, "User not found"));
+    }
+    Ok(())
+}
+
+/// Uploaded logs are broadcast to every stream and persisted, so uploading
+/// always needs a token: a user may replay into their own `?user_id=`, anything
+/// else (unowned, or someone else's data) needs an admin
+pub async fn authorize_upload(
+    state: &AppState,
+    options: &ReplayOptions,
+    user: Result<AuthUser, AuthError>,
+) -> Result<(), Response> {
+    let user = user.map_err(IntoResponse::into_response)?;
+    if options.user_id == Some(user.user_id) {
+        return Ok(());
+    }
+    if user.role != "admin" {
+        return Err(upload_error(
+            StatusCode::FORBIDDEN,
+            "Uploading a replay requires an admin, or ?user_id= set to your own account",
+        ));
+    }
+    authorize_target_user(state, options, Ok(user)).await
+}
+
+/// Replays a log uploaded as multipart form data (first file field)
+/// Endpoint: POST /api/replay/upload, same query options as /api/replay
+pub async fn upload_replay(
+    State(state): State<AppState>,
+    Query(options): Query<ReplayOptions>,
+    user: Result<AuthUser, AuthError>,
+    mut multipart: Multipart,
+) -> Response {
+    if let Err(rejection) = authorize_upload(&state, &options, user).await {
+        return rejection;
+    }
+    let limit = state.config.replay.max_upload_bytes;
+    let mut log = Vec::new();
+    loop {
+        let mut field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => {
+                return upload_error(StatusCode::BAD_REQUEST, "Multipart body has no log file")
+            }
+            Err(e) => return upload_error(e.status(), e.body_text()),
+        };
+        if field.file_name().is_none() {
+            continue;
+        }
+        loop {
+            match field.chunk().await {
+                Ok(Some(chunk)) => {
+                    if log.len() + chunk.len() > limit {
+                        return upload_error(
+                            StatusCode::PAYLOAD_TOO_LARGE,
+                            format!("Replay log exceeds {} bytes", limit),
+                        );
+                    }
+                    log.extend_from_slice(&chunk);
+                }
+                Ok(None) => break,
+                Err(e) => return upload_error(e.status(), e.body_text()),
+            }
+        }
+        break;
+    }
+
+    let format = options.format(&state.config);
+    if !contains_reading(&log, format) {
+        let expected = match format {
+            SerialFormat::Json => r#"JSON lines such as {"ts":"10:00:00","pir":0,"acc":0.01}"#,
+            SerialFormat::Csv => "CSV lines such as 10:00:00,0,0.01",
+        };
+        return upload_error(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "No line in the upload parses as a reading; expected {}",
+                expected
+            ),
+        );
+    }
+
+    let path = env::temp_dir().join(format!("replay-upload-{}.log", Uuid::new_v4()));
+    if let Err(e) = tokio::fs::write(&path, &log).await {
+        eprintln!("Failed to save uploaded replay log: {}", e);
+        return upload_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to save uploaded log",
+        );
+    }
+
+    let replay_speed = state.config.replay.speed_ms;
+    let id = spawn_replay_task(
+        &state,
+        path.to_string_lossy().into_owned(),
+        replay_speed,
+        options,
+        true,
+    );
+    Json(json!({
+        "id": id,
+        "message": format!(
+            "Replay of uploaded log started ({} bytes, speed: {}ms per reading)",
+            log.len(),
+            replay_speed
+        ),
+    }))
+    .into_response()
+}
+
+/// Replays REPLAY_LOG_PATH, with the same query options as /api/replay/upload
+/// Endpoint: GET /api/replay
+pub async fn start_replay(
+    State(state): State<AppState>,
+    Query(options): Query<ReplayOptions>,
+    user: Result<AuthUser, AuthError>,
+) -> Response {
+    if let Err(rejection) = authorize_target_user(&state, &options, user).await {
+        return rejection;
+    }
+    let log_path = state.config.replay.log_path.clone();
+    let replay_speed = state.config.replay.speed_ms;
+
+    let id = spawn_replay_task(&state, log_path.clone(), replay_speed, options, false);
+
+    Json(json!({
+        "id": id,
+        "message": format!(
+            "Replay started from: {} (speed: {}ms per reading)",
+            log_path, replay_speed
+        ),
+    }))
+    .into_response()
+}
+
+#[derive(Clone, Copy)]
+enum ReplayAction {
+    Pause,
+    Resume,
+    Stop,
+}
+
+fn control_replay(state: &AppState, id: &str, action: ReplayAction) -> Response {
+    let Some(control) = Uuid::parse_str(id)
+        .ok()
+        .and_then(|id| state.replays.get(&id))
+    else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Replay not found"})),
+        )
+            .into_response();
+    };
+
+    let status = match action {
+        ReplayAction::Pause => {
+            control.pause();
+            "paused"
+        }
+        ReplayAction::Resume => {
+            control.resume();
+            "running"
+        }
+        ReplayAction::Stop => {
+            control.stop();
+            "stopped"
+        }
+    };
+    Json(json!({"id": id, "status": status})).into_response()
+}
+
+/// Progress of a running replay; 404 once it has finished
+/// Endpoint: GET /api/replay/:id/status
+pub async fn replay_status(
+    State(state): State<AppState>,
+    UrlPath(id): UrlPath<String>,
+) -> Response {
+    let Some(control) = Uuid::parse_str(&id)
+        .ok()
+        .and_then(|id| state.replays.get(&id))
+    else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Replay not found"})),
+        )
+            .into_response();
+    };
+    let mut body = json!(control.status());
+    body["id"] = json!(id);
+    Json(body).into_response()
+}
+
+/// Endpoint: POST /api/replay/:id/pause
+pub async fn pause_replay(State(state): State<AppState>, UrlPath(id): UrlPath<String>) -> Response {
+    control_replay(&state, &id, ReplayAction::Pause)
+}
+
+/// Endpoint: POST /api/replay/:id/resume
+pub async fn resume_replay(
+    State(state): State<AppState>,
+    UrlPath(id): UrlPath<String>,
+) -> Response {
+    control_replay(&state, &id, ReplayAction::Resume)
+}
+
+/// Endpoint: POST /api/replay/:id/stop (a stopped replay cannot be resumed)
+pub async fn stop_replay(State(state): State<AppState>, UrlPath(id): UrlPath<String>) -> Response {
+    control_replay(&state, &id, ReplayAction::Stop)
+}
+
+#[cfg(test)]
+#[path = "replay_tests.rs"]
+mod tests;
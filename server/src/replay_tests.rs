@@ -0,0 +1,593 @@
+use super::*;
+
+// Replay Control Tests
+
+#[tokio::test]
+async fn test_control_proceeds_when_running() {
+    let control = ReplayControl::default();
+    assert!(control.proceed().await);
+}
+
+#[tokio::test]
+async fn test_control_stop_ends_replay() {
+    let control = ReplayControl::default();
+    control.stop();
+    assert!(!control.proceed().await);
+}
+
+#[tokio::test]
+async fn test_control_resume_releases_pause() {
+    let control = Arc::new(ReplayControl::default());
+    control.pause();
+    assert!(control.is_paused());
+
+    let waiter = tokio::spawn({
+        let control = control.clone();
+        async move { control.proceed().await }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!waiter.is_finished());
+
+    control.resume();
+    assert!(waiter.await.unwrap());
+}
+
+#[tokio::test]
+async fn test_control_stop_releases_pause() {
+    let control = Arc::new(ReplayControl::default());
+    control.pause();
+    let waiter = tokio::spawn({
+        let control = control.clone();
+        async move { control.proceed().await }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    control.stop();
+    assert!(!waiter.await.unwrap());
+}
+
+#[test]
+fn test_registry_lookup_and_remove() {
+    let replays = ReplayRegistry::default();
+    let (id, control) = replays.register();
+    control.pause();
+    assert!(replays.get(&id).unwrap().is_paused());
+
+    replays.remove(&id);
+    assert!(replays.get(&id).is_none());
+    assert!(replays.get(&Uuid::new_v4()).is_none());
+}
+
+#[test]
+fn test_history_key_is_chosen_per_connection() {
+    let replays = ReplayRegistry::default();
+    let (id, _control) = replays.register();
+
+    // A running replay doesn't change what other clients are sent
+    assert_eq!(history_key(&replays, HistorySource::Live), "sensor_history");
+    assert_eq!(
+        history_key(&replays, HistorySource::Replay(id)),
+        format!("sensor_history:replay:{}", id)
+    );
+    assert_ne!(replay_history_key(&id), replay_history_key(&Uuid::new_v4()));
+
+    // Finished or unknown replays fall back to live history
+    replays.remove(&id);
+    assert_eq!(
+        history_key(&replays, HistorySource::Replay(id)),
+        "sensor_history"
+    );
+}
+
+// Replay Loop Tests
+
+fn context(tx: broadcast::Sender<String>, state_tx: broadcast::Sender<String>) -> ReplayContext {
+    let config = Config::for_tests();
+    ReplayContext {
+        thresholds: SharedThresholds::new(config.serial.thresholds),
+        config: Arc::new(config),
+        tx,
+        state_tx,
+        // Nothing listens here, so history caching is skipped
+        redis_client: redis::Client::open("redis://127.0.0.1:1/").unwrap(),
+        history_key: replay_history_key(&Uuid::new_v4()),
+    }
+}
+
+fn log_file(name: &str, lines: usize) -> std::path::PathBuf {
+    let path = env::temp_dir().join(format!("{}-{}.log", name, Uuid::new_v4()));
+    let body: String = (0..lines)
+        .map(|i| {
+            format!(
+                "{{\"ts\":\"10:00:{:02}\",\"pir\":0,\"acc\":0.01}}\n",
+                i % 60
+            )
+        })
+        .collect();
+    std::fs::write(&path, body).unwrap();
+    path
+}
+
+#[tokio::test]
+async fn test_replay_runs_to_completion() {
+    let (tx, _rx) = broadcast::channel(100);
+    let (state_tx, _state_rx) = broadcast::channel(100);
+    let path = log_file("replay-complete", 5);
+    let count = replay_log_file(
+        context(tx, state_tx),
+        &path,
+        0,
+        &ReplayOptions::default(),
+        &ReplayControl::default(),
+    )
+    .await;
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(count, Ok(5));
+}
+
+#[tokio::test]
+async fn test_stopped_replay_processes_nothing() {
+    let (tx, _rx) = broadcast::channel(100);
+    let (state_tx, _state_rx) = broadcast::channel(100);
+    let path = log_file("replay-stopped", 5);
+    let control = ReplayControl::default();
+    control.stop();
+    let count = replay_log_file(
+        context(tx, state_tx),
+        &path,
+        0,
+        &ReplayOptions::default(),
+        &control,
+    )
+    .await;
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(count, Ok(0));
+}
+
+async fn replay_timers(lines: usize, options: ReplayOptions, control: &ReplayControl) -> Vec<u64> {
+    let (tx, mut rx) = broadcast::channel(1000);
+    let (state_tx, _state_rx) = broadcast::channel(1000);
+    let path = log_file("replay-seek", lines);
+    replay_log_file(context(tx, state_tx), &path, 0, &options, control)
+        .await
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let mut timers = Vec::new();
+    while let Ok(msg) = rx.try_recv() {
+        let output: ProcessedState = serde_json::from_str(&msg).unwrap();
+        timers.push(output.timer);
+    }
+    timers
+}
+
+// Replay Seek Tests
+
+#[tokio::test]
+async fn test_skip_fast_forwards_timer() {
+    let full = replay_timers(6, ReplayOptions::default(), &ReplayControl::default()).await;
+    let options = ReplayOptions {
+        skip: Some(3),
+        ..Default::default()
+    };
+    let seeked = replay_timers(6, options, &ReplayControl::default()).await;
+    assert_eq!(seeked.len(), 3);
+    // The first reading shown continues the timer instead of cold-starting
+    assert_eq!(seeked, full[3..]);
+}
+
+#[tokio::test]
+async fn test_start_ts_fast_forwards_timer() {
+    let full = replay_timers(6, ReplayOptions::default(), &ReplayControl::default()).await;
+    let options = ReplayOptions {
+        start_ts: Some(NaiveTime::from_hms_opt(10, 0, 4).unwrap()),
+        ..Default::default()
+    };
+    let seeked = replay_timers(6, options, &ReplayControl::default()).await;
+    assert_eq!(seeked, full[4..]);
+}
+
+#[test]
+fn test_options_from_query() {
+    let uri = "/api/replay?loop=true&skip=10&start_ts=10:15:00"
+        .parse()
+        .unwrap();
+    let axum::extract::Query(options) =
+        axum::extract::Query::<ReplayOptions>::try_from_uri(&uri).unwrap();
+    assert!(options.looping);
+    assert_eq!(options.skip, Some(10));
+    assert_eq!(options.start_ts, NaiveTime::from_hms_opt(10, 15, 0));
+
+    let uri = "/api/replay".parse().unwrap();
+    let axum::extract::Query(options) =
+        axum::extract::Query::<ReplayOptions>::try_from_uri(&uri).unwrap();
+    assert!(!options.looping);
+}
+
+// Replay Loop Restart Tests
+
+#[tokio::test]
+async fn test_loop_restarts_cold_until_stopped() {
+    // Stop once the second pass is under way
+    let control = Arc::new(ReplayControl::default());
+    let (tx, mut rx) = broadcast::channel(1000);
+    let (state_tx, _state_rx) = broadcast::channel(1000);
+    let path = log_file("replay-loop", 3);
+    let options = ReplayOptions {
+        looping: true,
+        ..Default::default()
+    };
+    let replay = tokio::spawn({
+        let control = control.clone();
+        let path = path.clone();
+        async move { replay_log_file(context(tx, state_tx), &path, 1, &options, &control).await }
+    });
+
+    let mut timers = Vec::new();
+    while timers.len() < 5 {
+        let msg = rx.recv().await.unwrap();
+        timers.push(serde_json::from_str::<ProcessedState>(&msg).unwrap().timer);
+    }
+    control.stop();
+    replay.await.unwrap().unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    // The sedentary timer starts over on the second pass
+    assert_eq!(timers[3], timers[0]);
+    assert_eq!(timers[4], timers[1]);
+}
+
+// Realtime Delay Tests
+
+fn at(s: &str) -> DateTime<Utc> {
+    s.parse().unwrap()
+}
+
+const CAP: Duration = Duration::from_secs(5);
+
+#[test]
+fn test_realtime_delay_logged_gap() {
+    let delay = realtime_delay(
+        at("2026-01-23T10:00:00Z"),
+        at("2026-01-23T10:00:02Z"),
+        1.0,
+        CAP,
+    );
+    assert_eq!(delay, Duration::from_secs(2));
+}
+
+#[test]
+fn test_realtime_delay_speed_factor() {
+    let delay = realtime_delay(
+        at("2026-01-23T10:00:00Z"),
+        at("2026-01-23T10:00:02Z"),
+        4.0,
+        CAP,
+    );
+    assert_eq!(delay, Duration::from_millis(500));
+}
+
+#[test]
+fn test_realtime_delay_capped() {
+    let delay = realtime_delay(
+        at("2026-01-23T10:00:00Z"),
+        at("2026-01-23T10:30:00Z"),
+        1.0,
+        CAP,
+    );
+    assert_eq!(delay, CAP);
+}
+
+#[test]
+fn test_realtime_delay_backwards_or_same_second() {
+    let t = at("2026-01-23T10:00:00Z");
+    assert_eq!(realtime_delay(t, t, 1.0, CAP), Duration::ZERO);
+    assert_eq!(
+        realtime_delay(at("2026-01-23T10:00:05Z"), t, 1.0, CAP),
+        Duration::ZERO
+    );
+}
+
+#[test]
+fn test_realtime_delay_invalid_speed_is_one() {
+    let from = at("2026-01-23T10:00:00Z");
+    let to = at("2026-01-23T10:00:01Z");
+    assert_eq!(realtime_delay(from, to, 0.0, CAP), Duration::from_secs(1));
+    assert_eq!(realtime_delay(from, to, -2.0, CAP), Duration::from_secs(1));
+}
+
+// Replay Task Smoke Tests
+
+fn test_state() -> AppState {
+    let config = Config::for_tests();
+    let thresholds = config.serial.thresholds;
+    AppState::new(
+        Arc::new(config),
+        // Lazy pool: the replay path never touches the database
+        sqlx::PgPool::connect_lazy("postgres://localhost/unused").unwrap(),
+        redis::Client::open("redis://127.0.0.1:1/").unwrap(),
+        thresholds,
+    )
+}
+
+#[tokio::test]
+async fn test_spawn_replay_task_broadcasts_and_unregisters() {
+    let state = test_state();
+    let mut rx = state.tx.subscribe();
+    let path = log_file("replay-task", 3);
+
+    let id = spawn_replay_task(
+        &state,
+        path.to_string_lossy().into_owned(),
+        0,
+        ReplayOptions::default(),
+        false,
+    );
+    for _ in 0..3 {
+        let msg = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(serde_json::from_str::<ProcessedState>(&msg).is_ok());
+    }
+
+    // The registry entry goes away once the replay finishes
+    for _ in 0..100 {
+        if state.replays.get(&id).is_none() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(state.replays.get(&id).is_none());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_temporary_log_removed_after_replay() {
+    let state = test_state();
+    let path = log_file("replay-upload", 2);
+    let id = spawn_replay_task(
+        &state,
+        path.to_string_lossy().into_owned(),
+        0,
+        ReplayOptions::default(),
+        true,
+    );
+    for _ in 0..100 {
+        if state.replays.get(&id).is_none() && !path.exists() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(!path.exists());
+}
+
+// Upload Validation Tests
+
+#[test]
+fn test_contains_reading_accepts_prefixed_lines() {
+    let log = b"garbage\n[2026-01-23 16:12:03.123] {\"ts\":\"16:12:03\",\"pir\":1,\"acc\":0.02}\n";
+    assert!(contains_reading(log, SerialFormat::Json));
+}
+
+#[test]
+fn test_contains_reading_rejects_non_readings() {
+    for format in [SerialFormat::Json, SerialFormat::Csv] {
+        assert!(!contains_reading(b"", format));
+        assert!(!contains_reading(b"hello\nworld\n", format));
+        assert!(!contains_reading(&[0xff, 0xfe, 0x00], format));
+    }
+    assert!(!contains_reading(
+        b"{\"ts\":\"10:00:00\"}\n",
+        SerialFormat::Json
+    ));
+    assert!(!contains_reading(b"10:00:00,0\n", SerialFormat::Csv));
+}
+
+#[test]
+fn test_contains_reading_matches_the_format() {
+    let csv = b"ts,pir,acc\n10:00:00,0,0.01\n";
+    assert!(contains_reading(csv, SerialFormat::Csv));
+    assert!(!contains_reading(csv, SerialFormat::Json));
+}
+
+// CSV Log Tests
+
+#[test]
+fn test_parse_csv_log_lines() {
+    let reading = parse_log_line("16:12:03,1,0.02", SerialFormat::Csv).unwrap();
+    assert_eq!(reading.ts, "16:12:03");
+    assert_eq!(reading.pir, 1);
+
+    // Capture tools prefix each line with the host time
+    let prefixed = parse_log_line(
+        "[2026-01-23 16:12:03.123] 16:12:03,0,0.01",
+        SerialFormat::Csv,
+    );
+    assert_eq!(prefixed.unwrap().ts, "16:12:03");
+}
+
+#[test]
+fn test_malformed_csv_log_lines_are_skipped() {
+    for line in [
+        "ts,pir,acc",
+        "",
+        "16:12:03,1",
+        "16:12:03,one,0.02",
+        "[2026-01-23 16:12:03.123 16:12:03,0,0.01",
+        "Booting sensor...",
+    ] {
+        assert!(
+            parse_log_line(line, SerialFormat::Csv).is_none(),
+            "{}",
+            line
+        );
+    }
+}
+
+#[test]
+fn test_format_option_overrides_serial_format() {
+    let config = Config::for_tests();
+    assert_eq!(ReplayOptions::default().format(&config), SerialFormat::Json);
+    let options = ReplayOptions {
+        format: Some(SerialFormat::Csv),
+        ..Default::default()
+    };
+    assert_eq!(options.format(&config), SerialFormat::Csv);
+}
+
+// Replay Status Tests
+
+#[test]
+fn test_status_before_first_pass() {
+    let status = ReplayControl::default().status();
+    assert_eq!(status.status, "running");
+    assert_eq!(status.pass, 0);
+    assert_eq!(status.readings_broadcast, 0);
+    assert!(status.current_state.is_none());
+}
+
+#[test]
+fn test_status_reflects_pause_and_stop() {
+    let control = ReplayControl::default();
+    control.pause();
+    assert_eq!(control.status().status, "paused");
+    control.stop();
+    assert_eq!(control.status().status, "stopping");
+}
+
+#[tokio::test]
+async fn test_status_tracks_progress() {
+    let (tx, _rx) = broadcast::channel(100);
+    let (state_tx, _state_rx) = broadcast::channel(100);
+    let path = log_file("replay-status", 4);
+    std::fs::write(
+        &path,
+        std::fs::read_to_string(&path).unwrap() + "not a reading\n",
+    )
+    .unwrap();
+    let control = ReplayControl::default();
+    replay_log_file(
+        context(tx, state_tx),
+        &path,
+        0,
+        &ReplayOptions::default(),
+        &control,
+    )
+    .await
+    .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let status = control.status();
+    assert_eq!(status.pass, 1);
+    assert_eq!(status.total_lines, 5);
+    assert_eq!(status.lines_processed, 5);
+    assert_eq!(status.readings_broadcast, 4);
+    assert_eq!(status.current_state, Some(ActivityState::Sedentary));
+    assert!(status.current_timestamp.is_some());
+    assert!(status.last_broadcast_at.is_some());
+}
+
+// Replay Owner Tests
+
+#[tokio::test]
+async fn test_replay_tags_target_user() {
+    let (tx, mut rx) = broadcast::channel(100);
+    let (state_tx, _state_rx) = broadcast::channel(100);
+    let path = log_file("replay-owner", 2);
+    let owner = Uuid::new_v4();
+    let options = ReplayOptions {
+        user_id: Some(owner),
+        ..Default::default()
+    };
+    replay_log_file(
+        context(tx, state_tx),
+        &path,
+        0,
+        &options,
+        &ReplayControl::default(),
+    )
+    .await
+    .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    while let Ok(msg) = rx.try_recv() {
+        let output: ProcessedState = serde_json::from_str(&msg).unwrap();
+        assert_eq!(output.user_id, Some(owner));
+    }
+}
+
+#[tokio::test]
+async fn test_replay_untagged_by_default() {
+    let (tx, mut rx) = broadcast::channel(100);
+    let (state_tx, _state_rx) = broadcast::channel(100);
+    let path = log_file("replay-unowned", 2);
+    replay_log_file(
+        context(tx, state_tx),
+        &path,
+        0,
+        &ReplayOptions::default(),
+        &ReplayControl::default(),
+    )
+    .await
+    .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let msg = rx.try_recv().unwrap();
+    let output: ProcessedState = serde_json::from_str(&msg).unwrap();
+    assert_eq!(output.user_id, None);
+}
+
+#[tokio::test]
+async fn test_target_user_requires_login() {
+    let state = test_state();
+    let options = ReplayOptions {
+        user_id: Some(Uuid::new_v4()),
+        ..Default::default()
+    };
+    let rejection = authorize_target_user(
+        &state,
+        &options,
+        Err(AuthError {
+            message: "Missing token",
+        }),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(rejection.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_target_user_forbidden_for_non_admin() {
+    let state = test_state();
+    let user_id = Uuid::new_v4();
+    let options = ReplayOptions {
+        user_id: Some(user_id),
+        ..Default::default()
+    };
+    let user = AuthUser {
+        user_id,
+        name: "Pat".to_string(),
+        jti: "jti".to_string(),
+        exp: 0,
+        role: "user".to_string(),
+    };
+    let rejection = authorize_target_user(&state, &options, Ok(user))
+        .await
+        .unwrap_err();
+    assert_eq!(rejection.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_no_target_user_needs_no_login() {
+    let state = test_state();
+    let result = authorize_target_user(
+        &state,
+        &ReplayOptions::default(),
+        Err(AuthError {
+            message: "Missing token",
+        }),
+    )
+    .await;
+    assert!(result.is_ok());
+}
@@ -0,0 +1,71 @@
+use axum::{
+    http::{header, HeaderName, Request, Response, StatusCode},
+    Router,
+};
+use std::time::Duration;
+use tower::ServiceBuilder;
+use tower_http::{
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+use tracing::Span;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// SSE and WebSocket connections stay open for minutes; their completion is
+/// only logged at debug so dashboards reconnecting don't flood the log
+pub fn is_stream<B>(response: &Response<B>) -> bool {
+    response.status() == StatusCode::SWITCHING_PROTOCOLS
+        || response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"))
+}
+
+fn make_span<B>(request: &Request<B>) -> Span {
+    let request_id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        path = %request.uri().path(),
+        request_id = %request_id,
+    )
+}
+
+fn on_response<B>(response: &Response<B>, latency: Duration, _span: &Span) {
+    let status = response.status().as_u16();
+    let latency_ms = latency.as_millis() as u64;
+    if is_stream(response) {
+        tracing::debug!(status, latency_ms, "stream opened");
+    } else {
+        tracing::info!(status, latency_ms, "finished");
+    }
+}
+
+/// Logs method, path, status and latency for every request under a request
+/// id, reusing a client's `x-request-id` or generating one, and echoes the id
+/// back in the response header
+pub fn with_request_tracing(app: Router) -> Router {
+    app.layer(
+        ServiceBuilder::new()
+            .layer(SetRequestIdLayer::new(
+                REQUEST_ID_HEADER.clone(),
+                MakeRequestUuid,
+            ))
+            .layer(
+                TraceLayer::new_for_http()
+                    .make_span_with(make_span)
+                    .on_response(on_response),
+            )
+            .layer(PropagateRequestIdLayer::new(REQUEST_ID_HEADER.clone())),
+    )
+}
+
+#[cfg(test)]
+#[path = "request_trace_tests.rs"]
+mod tests;
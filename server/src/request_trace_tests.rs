@@ -0,0 +1,71 @@
+use super::*;
+use axum::{body::Body, routing::get};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+fn app() -> Router {
+    with_request_tracing(Router::new().route("/health/live", get(|| async { "ok" })))
+}
+
+fn response_with(status: StatusCode, content_type: &str) -> Response<()> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, content_type)
+        .body(())
+        .unwrap()
+}
+
+// Request Id Tests
+
+#[tokio::test]
+async fn test_generates_request_id() {
+    let request = Request::get("/health/live").body(Body::empty()).unwrap();
+    let response = app().oneshot(request).await.unwrap();
+    let id = response.headers()[&REQUEST_ID_HEADER].to_str().unwrap();
+    assert!(Uuid::parse_str(id).is_ok(), "{}", id);
+}
+
+#[tokio::test]
+async fn test_keeps_client_request_id() {
+    let request = Request::get("/health/live")
+        .header(&REQUEST_ID_HEADER, "from-proxy-42")
+        .body(Body::empty())
+        .unwrap();
+    let response = app().oneshot(request).await.unwrap();
+    assert_eq!(response.headers()[&REQUEST_ID_HEADER], "from-proxy-42");
+}
+
+#[tokio::test]
+async fn test_request_id_on_unmatched_route() {
+    let request = Request::get("/nope").body(Body::empty()).unwrap();
+    let response = app().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert!(response.headers().contains_key(&REQUEST_ID_HEADER));
+}
+
+// Stream Detection Tests
+
+#[test]
+fn test_event_stream_is_stream() {
+    assert!(is_stream(&response_with(
+        StatusCode::OK,
+        "text/event-stream"
+    )));
+}
+
+#[test]
+fn test_websocket_upgrade_is_stream() {
+    let response = Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .body(())
+        .unwrap();
+    assert!(is_stream(&response));
+}
+
+#[test]
+fn test_json_is_not_stream() {
+    assert!(!is_stream(&response_with(
+        StatusCode::OK,
+        "application/json"
+    )));
+}
@@ -0,0 +1,249 @@
+use crate::auth::AdminUser;
+use crate::config::Config;
+use crate::daily_summary::summarize_day;
+use crate::metrics::Metrics;
+use crate::state::AppState;
+use crate::stats::{date_start, local_date};
+use axum::{extract::State, response::Json};
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+// Breather between delete batches so live inserts and queries get the tables
+const BATCH_PAUSE: Duration = Duration::from_millis(50);
+
+/// Start of the oldest local day (TIMEZONE) kept when `days` whole days are
+/// retained, so a prune never leaves half a day behind; `None` when pruning is off
+pub fn prune_cutoff(tz: Tz, now: DateTime<Utc>, days: u32) -> Option<DateTime<Utc>> {
+    if days == 0 {
+        return None;
+    }
+    local_date(tz, now)
+        .checked_sub_days(Days::new(u64::from(days)))
+        .map(|date| date_start(tz, date))
+}
+
+/// Outcome of one prune run, kept for GET /api/retention/status
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PruneStats {
+    pub cutoff: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    // Days of sensor_data summarized into activity_summary before deletion
+    pub days_summarized: usize,
+    pub sensor_data_deleted: u64,
+    pub sedentary_log_deleted: u64,
+    pub batches: u32,
+    // Set when the run stopped early; the counts cover what was done before
+    pub error: Option<String>,
+}
+
+/// The most recent prune, shared between the job and the status endpoint
+#[derive(Default)]
+pub struct RetentionStatus(Mutex<Option<PruneStats>>);
+
+impl RetentionStatus {
+    pub fn record(&self, stats: PruneStats) {
+        *self.0.lock().unwrap() = Some(stats);
+    }
+
+    pub fn last(&self) -> Option<PruneStats> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// Local days before `cutoff` that have `sensor_data` but no daily summary for
+/// at least one of its users
+async fn unsummarized_days(
+    pool: &PgPool,
+    tz: Tz,
+    cutoff: DateTime<Utc>,
+) -> Result<Vec<NaiveDate>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT DISTINCT (d.timestamp AT TIME ZONE $2)::date AS "date!"
+        FROM sensor_data d
+        WHERE d.timestamp < $1
+          AND NOT EXISTS (
+              SELECT 1 FROM activity_summary s
+              WHERE s.user_id = d.user_id AND s.period_type = 'daily'
+                AND s.date = (d.timestamp AT TIME ZONE $2)::date
+          )
+        ORDER BY 1
+        "#,
+        cutoff,
+        tz.name()
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Deletes one batch of readings older than `cutoff` from each table; every
+/// statement commits on its own, so locks are held for one batch at most
+async fn delete_batch(
+    pool: &PgPool,
+    cutoff: DateTime<Utc>,
+    batch_size: i64,
+) -> Result<(u64, u64), sqlx::Error> {
+    let sensor_data = sqlx::query!(
+        r#"
+        DELETE FROM sensor_data
+        WHERE id IN (
+            SELECT id FROM sensor_data
+            WHERE timestamp < $1
+            LIMIT $2
+        )
+        "#,
+        cutoff,
+        batch_size
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    let sedentary_log = sqlx::query!(
+        r#"
+        DELETE FROM sedentary_log
+        WHERE id IN (
+            SELECT id FROM sedentary_log
+            WHERE created_at < $1
+            LIMIT $2
+        )
+        "#,
+        cutoff,
+        batch_size
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok((sensor_data, sedentary_log))
+}
+
+/// Deletes `sensor_data` and `sedentary_log` rows older than `cutoff` in
+/// batches of RETENTION_BATCH_SIZE. With RETENTION_SUMMARIZE, days that were
+/// never summarized are summarized first so `activity_summary` keeps their
+/// aggregate (unowned `sedentary_log` rows have no per-user summary). Stops
+/// between batches when `shutdown` is cancelled.
+pub async fn prune_before(
+    pool: &PgPool,
+    config: &Config,
+    cutoff: DateTime<Utc>,
+    shutdown: &CancellationToken,
+) -> PruneStats {
+    let started = Instant::now();
+    let settings = &config.retention;
+    let mut stats = PruneStats {
+        cutoff,
+        finished_at: Utc::now(),
+        duration_ms: 0,
+        days_summarized: 0,
+        sensor_data_deleted: 0,
+        sedentary_log_deleted: 0,
+        batches: 0,
+        error: None,
+    };
+
+    let result: Result<(), sqlx::Error> = async {
+        if settings.summarize {
+            for date in unsummarized_days(pool, config.timezone, cutoff).await? {
+                summarize_day(pool, config, date).await?;
+                stats.days_summarized += 1;
+            }
+        }
+
+        while !shutdown.is_cancelled() {
+            let (sensor_data, sedentary_log) =
+                delete_batch(pool, cutoff, settings.batch_size).await?;
+            stats.batches += 1;
+            stats.sensor_data_deleted += sensor_data;
+            stats.sedentary_log_deleted += sedentary_log;
+
+            let batch_size = settings.batch_size as u64;
+            if sensor_data < batch_size && sedentary_log < batch_size {
+                break;
+            }
+            tokio::time::sleep(BATCH_PAUSE).await;
+        }
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = result {
+        stats.error = Some(e.to_string());
+    } else if shutdown.is_cancelled() {
+        stats.error = Some("interrupted by shutdown".to_string());
+    }
+    stats.finished_at = Utc::now();
+    stats.duration_ms = started.elapsed().as_millis() as u64;
+    stats
+}
+
+/// Prunes raw readings past RETENTION_DAYS at startup and then every
+/// RETENTION_INTERVAL_HOURS; not started when RETENTION_DAYS=0
+pub fn spawn_retention_job(
+    config: Arc<Config>,
+    pool: PgPool,
+    status: Arc<RetentionStatus>,
+    metrics: Arc<Metrics>,
+    shutdown: CancellationToken,
+) -> Option<JoinHandle<()>> {
+    let settings = &config.retention;
+    if settings.days == 0 {
+        println!("Data retention disabled (RETENTION_DAYS=0)");
+        return None;
+    }
+    println!(
+        "Data retention job started (keeping {} days, every {}h)",
+        settings.days,
+        settings.interval.as_secs() / 3600
+    );
+
+    Some(tokio::spawn(async move {
+        loop {
+            if let Some(cutoff) = prune_cutoff(config.timezone, Utc::now(), config.retention.days) {
+                let stats = prune_before(&pool, &config, cutoff, &shutdown).await;
+                match &stats.error {
+                    None => println!(
+                        "Pruned readings before {}: {} sensor_data, {} sedentary_log ({} day(s) summarized first)",
+                        cutoff,
+                        stats.sensor_data_deleted,
+                        stats.sedentary_log_deleted,
+                        stats.days_summarized
+                    ),
+                    Some(e) => eprintln!("Data retention error: {}", e),
+                }
+                metrics.record_prune(&stats);
+                status.record(stats);
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(config.retention.interval) => {}
+                _ = shutdown.cancelled() => break,
+            }
+        }
+    }))
+}
+
+/// Retention settings and the last prune run (null before the first)
+/// Endpoint: GET /api/retention/status (admin only)
+pub async fn retention_status(_admin: AdminUser, State(state): State<AppState>) -> Json<Value> {
+    let settings = &state.config.retention;
+    Json(json!({
+        "enabled": settings.days > 0,
+        "retention_days": settings.days,
+        "summarize": settings.summarize,
+        "next_cutoff": prune_cutoff(state.config.timezone, Utc::now(), settings.days),
+        "last_prune": state.retention.last(),
+    }))
+}
+
+#[cfg(test)]
+#[path = "retention_tests.rs"]
+mod tests;
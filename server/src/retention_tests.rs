@@ -0,0 +1,54 @@
+use super::*;
+use chrono::TimeZone;
+
+fn stats(sensor_data_deleted: u64) -> PruneStats {
+    let at = Utc.with_ymd_and_hms(2026, 2, 7, 3, 0, 0).unwrap();
+    PruneStats {
+        cutoff: at,
+        finished_at: at,
+        duration_ms: 12,
+        days_summarized: 0,
+        sensor_data_deleted,
+        sedentary_log_deleted: 0,
+        batches: 1,
+        error: None,
+    }
+}
+
+// Cutoff Tests
+
+#[test]
+fn test_zero_days_disables_pruning() {
+    assert_eq!(prune_cutoff(Tz::UTC, Utc::now(), 0), None);
+}
+
+#[test]
+fn test_cutoff_is_midnight_days_ago() {
+    let now = Utc.with_ymd_and_hms(2026, 2, 7, 15, 30, 0).unwrap();
+    assert_eq!(
+        prune_cutoff(Tz::UTC, now, 30),
+        Some(Utc.with_ymd_and_hms(2026, 1, 8, 0, 0, 0).unwrap())
+    );
+}
+
+#[test]
+fn test_cutoff_follows_local_day() {
+    // 23:30 UTC is already the 8th in Berlin, so its midnight is an hour earlier
+    let now = Utc.with_ymd_and_hms(2026, 2, 7, 23, 30, 0).unwrap();
+    assert_eq!(
+        prune_cutoff(chrono_tz::Europe::Berlin, now, 1),
+        Some(Utc.with_ymd_and_hms(2026, 2, 6, 23, 0, 0).unwrap())
+    );
+}
+
+// Status Tests
+
+#[test]
+fn test_status_keeps_latest_run() {
+    let status = RetentionStatus::default();
+    assert_eq!(status.last(), None);
+
+    status.record(stats(10));
+    status.record(stats(3));
+    assert_eq!(status.last(), Some(stats(3)));
+}
@@ -0,0 +1,111 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, HeaderValue},
+    middleware,
+    response::Response,
+    Router,
+};
+use std::sync::Arc;
+
+/// Default CONTENT_SECURITY_POLICY: same-origin everything, plus the D3 and
+/// Font Awesome CDNs and the inline scripts the bundled dashboard pages use.
+/// `frame-ancestors 'none'` keeps the dashboard out of other sites' frames.
+pub const DEFAULT_CONTENT_SECURITY_POLICY: &str = "default-src 'self'; \
+script-src 'self' 'unsafe-inline' https://d3js.org; \
+style-src 'self' 'unsafe-inline' https://cdnjs.cloudflare.com; \
+font-src 'self' https://cdnjs.cloudflare.com; \
+img-src 'self' data:; \
+connect-src 'self'; \
+frame-ancestors 'none'; \
+base-uri 'self'; \
+form-action 'self'";
+
+/// Headers added to every response that doesn't already set them. `None`
+/// leaves a header out (the setting was `off`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecurityHeaders {
+    pub content_security_policy: Option<HeaderValue>,
+    pub frame_options: Option<HeaderValue>,
+    pub referrer_policy: Option<HeaderValue>,
+}
+
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        Self {
+            content_security_policy: Some(HeaderValue::from_static(
+                DEFAULT_CONTENT_SECURITY_POLICY,
+            )),
+            frame_options: Some(HeaderValue::from_static("DENY")),
+            referrer_policy: Some(HeaderValue::from_static("strict-origin-when-cross-origin")),
+        }
+    }
+}
+
+/// Parses a header setting such as CONTENT_SECURITY_POLICY: the value to
+/// send, or `off` to send nothing
+pub fn parse_header_setting(raw: &str) -> Result<Option<HeaderValue>, String> {
+    if raw.eq_ignore_ascii_case("off") {
+        return Ok(None);
+    }
+    HeaderValue::from_str(raw)
+        .map(Some)
+        .map_err(|_| format!("'{}' is not a valid header value", raw))
+}
+
+/// Parses X_FRAME_OPTIONS: DENY, SAMEORIGIN or off (ALLOW-FROM is obsolete;
+/// allow specific embedders with `frame-ancestors` in the CSP instead)
+pub fn parse_frame_options(raw: &str) -> Result<Option<HeaderValue>, String> {
+    match raw.to_ascii_uppercase().as_str() {
+        "DENY" => Ok(Some(HeaderValue::from_static("DENY"))),
+        "SAMEORIGIN" => Ok(Some(HeaderValue::from_static("SAMEORIGIN"))),
+        "OFF" => Ok(None),
+        _ => Err(format!(
+            "unknown value '{}' (expected DENY, SAMEORIGIN or off)",
+            raw
+        )),
+    }
+}
+
+/// Adds the configured headers plus `X-Content-Type-Options: nosniff`,
+/// keeping any a handler set itself
+pub fn apply(headers: &SecurityHeaders, response: &mut HeaderMap) {
+    let settings = [
+        (
+            header::X_CONTENT_TYPE_OPTIONS,
+            Some(HeaderValue::from_static("nosniff")),
+        ),
+        (
+            header::CONTENT_SECURITY_POLICY,
+            headers.content_security_policy.clone(),
+        ),
+        (header::X_FRAME_OPTIONS, headers.frame_options.clone()),
+        (header::REFERRER_POLICY, headers.referrer_policy.clone()),
+    ];
+    for (name, value) in settings {
+        if let Some(value) = value {
+            response.entry(name).or_insert(value);
+        }
+    }
+}
+
+async fn set_security_headers(
+    State(headers): State<Arc<SecurityHeaders>>,
+    mut response: Response,
+) -> Response {
+    apply(&headers, response.headers_mut());
+    response
+}
+
+/// Security headers on every response: API, static files and streams alike.
+/// Only the response head is touched, so SSE bodies keep streaming event by
+/// event instead of being buffered.
+pub fn with_security_headers(app: Router, headers: &SecurityHeaders) -> Router {
+    app.layer(middleware::map_response_with_state(
+        Arc::new(headers.clone()),
+        set_security_headers,
+    ))
+}
+
+#[cfg(test)]
+#[path = "security_headers_tests.rs"]
+mod tests;
@@ -0,0 +1,92 @@
+use super::*;
+use axum::{body::Body, http::Request, routing::get};
+use tower::ServiceExt;
+
+fn app(headers: &SecurityHeaders) -> Router {
+    let app = Router::new()
+        .route(
+            "/events",
+            get(|| async {
+                (
+                    [(header::CONTENT_TYPE, "text/event-stream")],
+                    "data: {}\n\n",
+                )
+            }),
+        )
+        .route(
+            "/embed",
+            get(|| async { ([(header::X_FRAME_OPTIONS, "SAMEORIGIN")], "ok") }),
+        );
+    with_security_headers(app, headers)
+}
+
+async fn response_headers(app: Router, uri: &str) -> HeaderMap {
+    let request = Request::get(uri).body(Body::empty()).unwrap();
+    app.oneshot(request).await.unwrap().headers().clone()
+}
+
+// Parsing Tests
+
+#[test]
+fn test_off_disables_a_header() {
+    assert_eq!(parse_header_setting("off"), Ok(None));
+    assert_eq!(parse_header_setting("OFF"), Ok(None));
+    assert_eq!(
+        parse_header_setting("default-src 'none'"),
+        Ok(Some(HeaderValue::from_static("default-src 'none'")))
+    );
+}
+
+#[test]
+fn test_invalid_header_value_rejected() {
+    assert!(parse_header_setting("default-src\n'self'").is_err());
+}
+
+#[test]
+fn test_frame_options_values() {
+    assert_eq!(
+        parse_frame_options("sameorigin"),
+        Ok(Some(HeaderValue::from_static("SAMEORIGIN")))
+    );
+    assert_eq!(parse_frame_options("off"), Ok(None));
+    assert!(parse_frame_options("ALLOW-FROM https://example.org").is_err());
+}
+
+// Middleware Tests
+
+#[tokio::test]
+async fn test_defaults_applied_to_streams() {
+    let headers = response_headers(app(&SecurityHeaders::default()), "/events").await;
+    assert_eq!(headers[header::CONTENT_TYPE], "text/event-stream");
+    assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+    assert_eq!(headers[header::X_FRAME_OPTIONS], "DENY");
+    assert_eq!(
+        headers[header::REFERRER_POLICY],
+        "strict-origin-when-cross-origin"
+    );
+    assert!(headers[header::CONTENT_SECURITY_POLICY]
+        .to_str()
+        .unwrap()
+        .contains("frame-ancestors 'none'"));
+}
+
+#[tokio::test]
+async fn test_handler_headers_win() {
+    let headers = response_headers(app(&SecurityHeaders::default()), "/embed").await;
+    assert_eq!(headers[header::X_FRAME_OPTIONS], "SAMEORIGIN");
+}
+
+#[tokio::test]
+async fn test_disabled_headers_are_left_out() {
+    let settings = SecurityHeaders {
+        content_security_policy: None,
+        frame_options: None,
+        referrer_policy: None,
+    };
+    let headers = response_headers(app(&settings), "/events").await;
+    assert!(headers.get(header::CONTENT_SECURITY_POLICY).is_none());
+    assert!(headers.get(header::X_FRAME_OPTIONS).is_none());
+    assert!(headers.get(header::REFERRER_POLICY).is_none());
+    // nosniff has no setting: it is always sent
+    assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+}
@@ -1,166 +1,1197 @@
+use crate::config::Config;
+use crate::daily_totals::DailyAccumulator;
 use crate::fallback::FallbackState;
-use crate::models::{ProcessedState, RawReading};
-use chrono::{NaiveTime, Utc};
-use redis::AsyncCommands;
-use std::collections::VecDeque;
-use std::env;
-use std::io::{BufRead, BufReader};
-use std::sync::Arc;
+use crate::history::{push_history, SENSOR_HISTORY_KEY};
+use crate::metrics::Metrics;
+use crate::models::{ActivityState, ProcessedState, RawReading};
+use crate::state::AppState;
+use crate::state_change::StateChangeDetector;
+use axum::{extract::State, response::Json};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufRead, BufReader};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
 use std::thread;
-use std::time::Duration;
-use tokio::sync::broadcast;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
 
-// CLASSIFICATION THRESHOLDS - Load from environment
-fn thresh_fidget() -> f32 {
-    env::var("THRESH_FIDGET")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(0.020)
+/// Acceleration thresholds used by `classify_state`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Thresholds {
+    #[serde(rename = "thresh_fidget")]
+    pub fidget: f32,
+    #[serde(rename = "thresh_active")]
+    pub active: f32,
 }
 
-fn thresh_active() -> f32 {
-    env::var("THRESH_ACTIVE")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(0.040)
+impl Thresholds {
+    /// Both thresholds positive and fidget strictly below active
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if !(self.fidget.is_finite() && self.active.is_finite()) {
+            return Err("Thresholds must be finite numbers");
+        }
+        if self.fidget <= 0.0 {
+            return Err("thresh_fidget must be greater than 0");
+        }
+        if self.fidget >= self.active {
+            return Err("thresh_fidget must be less than thresh_active");
+        }
+        Ok(())
+    }
+}
+
+/// Live classification thresholds shared by the serial listeners, replay and
+/// the admin endpoints. Loaded once at startup; updates apply to the next reading.
+#[derive(Clone)]
+pub struct SharedThresholds(Arc<RwLock<Thresholds>>);
+
+impl SharedThresholds {
+    pub fn new(initial: Thresholds) -> Self {
+        Self(Arc::new(RwLock::new(initial)))
+    }
+
+    pub fn current(&self) -> Thresholds {
+        *self.0.read().unwrap()
+    }
+
+    pub fn set(&self, thresholds: Thresholds) {
+        *self.0.write().unwrap() = thresholds;
+    }
+}
+
+/// Comma-separated `SERIAL_PORTS`, trimmed and without duplicates
+pub fn parse_serial_ports(raw: &str) -> Vec<String> {
+    let mut ports: Vec<String> = Vec::new();
+    for port in raw.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        if !ports.iter().any(|p| p == port) {
+            ports.push(port.to_string());
+        }
+    }
+    ports
+}
+
+/// Common Arduino `Serial.begin` rates tried by BAUD_AUTO_DETECT
+pub const DEFAULT_BAUD_CANDIDATES: [u32; 3] = [9600, 57600, 115200];
+
+/// Comma-separated `BAUD_CANDIDATES`, in order and without duplicates
+pub fn parse_baud_rates(raw: &str) -> Result<Vec<u32>, String> {
+    let mut rates: Vec<u32> = Vec::new();
+    for rate in raw.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+        let rate = match rate.parse::<u32>() {
+            Ok(rate) if rate > 0 => rate,
+            _ => return Err(format!("'{}' is not a baud rate", rate)),
+        };
+        if !rates.contains(&rate) {
+            rates.push(rate);
+        }
+    }
+    if rates.is_empty() {
+        return Err("expected at least one baud rate".to_string());
+    }
+    Ok(rates)
+}
+
+/// Per-device history list; readings are also kept in the combined `sensor_history`
+/// that SSE/WebSocket clients replay on connect
+fn port_history_key(port_name: &str) -> String {
+    format!("sensor_history:{}", port_name)
+}
+
+/// Parses `DEVICE_USER_MAP` entries of the form `port=user_uuid`, comma separated.
+/// The first malformed entry is reported.
+pub fn parse_device_user_map(raw: &str) -> Result<HashMap<String, Uuid>, String> {
+    let mut map = HashMap::new();
+    for entry in raw.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (port, user) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("expected port=user_uuid, got '{}'", entry))?;
+        let user_id =
+            Uuid::parse_str(user.trim()).map_err(|_| format!("invalid user id in '{}'", entry))?;
+        map.insert(port.trim().to_string(), user_id);
+    }
+    Ok(map)
+}
+
+// Number of samples in the smoothing buffer (1 disables smoothing)
+pub const DEFAULT_SMOOTHING_WINDOW: usize = 10;
+const MAX_SMOOTHING_WINDOW: usize = 200;
+
+/// Smoothing buffer size shared by the serial listener and replay; accepts 1..=200
+pub fn parse_smoothing_window(raw: Option<&str>) -> Result<usize, String> {
+    let Some(raw) = raw else {
+        return Ok(DEFAULT_SMOOTHING_WINDOW);
+    };
+    match raw.trim().parse::<usize>() {
+        Ok(n) if (1..=MAX_SMOOTHING_WINDOW).contains(&n) => Ok(n),
+        _ => Err(format!(
+            "'{}' is out of range (expected 1-{})",
+            raw, MAX_SMOOTHING_WINDOW
+        )),
+    }
+}
+
+/// How the smoothing buffer is reduced to a single acceleration value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SmoothingMode {
+    Mean,
+    Median,
+    /// Exponentially weighted moving average with smoothing factor alpha (0 < alpha <= 1)
+    Ewma(f32),
+}
+
+const DEFAULT_EWMA_ALPHA: f32 = 0.3;
+
+/// Parses `SMOOTHING_MODE` (mean, median, ewma) and `SMOOTHING_ALPHA` for ewma
+pub fn parse_smoothing_mode(
+    mode: Option<&str>,
+    alpha: Option<&str>,
+) -> Result<SmoothingMode, String> {
+    match mode.map(|m| m.trim().to_ascii_lowercase()).as_deref() {
+        None | Some("mean") => Ok(SmoothingMode::Mean),
+        Some("median") => Ok(SmoothingMode::Median),
+        Some("ewma") => match alpha.map(|a| a.trim().parse::<f32>()) {
+            None => Ok(SmoothingMode::Ewma(DEFAULT_EWMA_ALPHA)),
+            Some(Ok(a)) if a > 0.0 && a <= 1.0 => Ok(SmoothingMode::Ewma(a)),
+            Some(_) => Err("SMOOTHING_ALPHA must be 0 < alpha <= 1".to_string()),
+        },
+        Some(other) => Err(format!(
+            "unknown mode '{}' (expected mean, median or ewma)",
+            other
+        )),
+    }
 }
 
-pub fn alert_limit_sec() -> u64 {
-    env::var("ALERT_LIMIT_SECONDS")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(1200)
+/// Reduces the smoothing buffer (oldest first) to one acceleration value
+pub fn smooth(buffer: &VecDeque<f32>, mode: SmoothingMode) -> f32 {
+    if buffer.is_empty() {
+        return 0.0;
+    }
+    match mode {
+        SmoothingMode::Mean => buffer.iter().sum::<f32>() / buffer.len() as f32,
+        SmoothingMode::Median => {
+            let mut sorted: Vec<f32> = buffer.iter().copied().collect();
+            sorted.sort_by(|a, b| a.total_cmp(b));
+            let mid = sorted.len() / 2;
+            if sorted.len().is_multiple_of(2) {
+                (sorted[mid - 1] + sorted[mid]) / 2.0
+            } else {
+                sorted[mid]
+            }
+        }
+        SmoothingMode::Ewma(alpha) => buffer
+            .iter()
+            .skip(1)
+            .fold(buffer[0], |avg, &x| alpha * x + (1.0 - alpha) * avg),
+    }
 }
 
-fn sensor_history_limit() -> isize {
-    env::var("SENSOR_HISTORY_LIMIT")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(500)
+/// Turns reading timestamps into full UTC timestamps for one stream.
+///
+/// A reading's `datetime` is used as-is. Time-only `ts` values are placed on
+/// the current stream date (today when the stream starts); a time earlier than
+/// the previous reading's is taken as a midnight rollover and advances the date.
+#[derive(Default)]
+pub struct TimestampResolver {
+    date: Option<NaiveDate>,
+    last_time: Option<NaiveTime>,
 }
 
-const SMOOTHING_WINDOW: usize = 10; // Number of samples for smoothing buffer
+impl TimestampResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[cfg(test)]
+    fn starting_on(date: NaiveDate) -> Self {
+        Self {
+            date: Some(date),
+            last_time: None,
+        }
+    }
+
+    pub fn resolve(&mut self, reading: &RawReading) -> DateTime<Utc> {
+        if let Some(datetime) = reading.datetime {
+            // Later time-only readings continue from the full timestamp
+            self.date = Some(datetime.date_naive());
+            self.last_time = Some(datetime.time());
+            return datetime;
+        }
+
+        let Ok(time) = NaiveTime::parse_from_str(&reading.ts, "%H:%M:%S") else {
+            return Utc::now();
+        };
+
+        let mut date = self.date.unwrap_or_else(|| Utc::now().date_naive());
+        if self.last_time.is_some_and(|last| time < last) {
+            date = date.succ_opt().unwrap_or(date);
+        }
+        self.date = Some(date);
+        self.last_time = Some(time);
+
+        date.and_time(time).and_utc()
+    }
+}
 
-/// Classifies activity state based on PIR and smoothed acceleration
-fn classify_state(pir: i32, smoothed_acc: f32) -> String {
-    if pir == 1 || smoothed_acc > thresh_active() {
-        "ACTIVE".to_string()
-    } else if smoothed_acc > thresh_fidget() {
-        "FIDGET".to_string()
+/// Suppresses lone PIR blips (e.g. someone walking past the desk) by only
+/// reporting motion once PIR has read 1 for `required` consecutive samples
+/// (PIR_DEBOUNCE_SAMPLES)
+pub struct PirDebouncer {
+    required: u32,
+    consecutive: u32,
+}
+
+impl PirDebouncer {
+    pub fn new(required: u32) -> Self {
+        Self {
+            required: required.max(1),
+            consecutive: 0,
+        }
+    }
+
+    /// Feeds one raw PIR sample; returns the debounced PIR value (0 or 1)
+    pub fn observe(&mut self, pir: i32) -> i32 {
+        if pir == 1 {
+            self.consecutive = self.consecutive.saturating_add(1);
+        } else {
+            self.consecutive = 0;
+        }
+        i32::from(self.consecutive >= self.required)
+    }
+}
+
+/// Turns "timer is past ALERT_LIMIT_SECONDS" into alert events: the first
+/// reading over the limit alerts, then further alerts are held back until the
+/// timer has run another `cooldown` seconds (ALERT_COOLDOWN_SECONDS). When the
+/// timer goes back down (activity or a reset) the next crossing alerts again.
+pub struct AlertCooldown {
+    cooldown: u64,
+    last_timer: u64,
+    // Timer value of the last alert in the current sedentary period
+    fired_at: Option<u64>,
+}
+
+impl AlertCooldown {
+    pub fn new(cooldown: u64) -> Self {
+        Self {
+            cooldown,
+            last_timer: 0,
+            fired_at: None,
+        }
+    }
+
+    /// Feeds one reading's timer and whether it is over the limit; true if it should alert
+    pub fn observe(&mut self, timer: u64, over_limit: bool) -> bool {
+        if timer < self.last_timer {
+            self.fired_at = None;
+        }
+        self.last_timer = timer;
+        if !over_limit {
+            return false;
+        }
+        match self.fired_at {
+            Some(fired_at) if timer < fired_at + self.cooldown => false,
+            _ => {
+                self.fired_at = Some(timer);
+                true
+            }
+        }
+    }
+}
+
+/// Advances the sedentary timer by one second of the given state
+pub fn next_sedentary_timer(timer: u64, state: ActivityState) -> u64 {
+    match state {
+        ActivityState::Active => 0,            // Reset on activity
+        ActivityState::Sedentary => timer + 1, // Increment
+        ActivityState::Fidget => timer,        // Pauses
+    }
+}
+
+/// What advances the sedentary timer between readings (SEDENTARY_TIMER_SOURCE)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimerSource {
+    /// Real time elapsed between reading timestamps, so bursts of readings in
+    /// one second or skipped seconds don't make the timer drift
+    Elapsed,
+    /// One second per distinct `ts` string (the original behaviour)
+    Ticks,
+}
+
+/// Parses `SEDENTARY_TIMER_SOURCE` (elapsed, ticks)
+pub fn parse_timer_source(raw: &str) -> Result<TimerSource, String> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "elapsed" => Ok(TimerSource::Elapsed),
+        "ticks" => Ok(TimerSource::Ticks),
+        other => Err(format!(
+            "unknown source '{}' (expected elapsed or ticks)",
+            other
+        )),
+    }
+}
+
+/// The sedentary timer of one stream. Sedentary time counts up, fidgeting
+/// pauses it and activity resets it; the time since the previous reading is
+/// credited to the current reading's state. With `TimerSource::Elapsed` that
+/// time comes from the resolved timestamps, capped at `max_gap` so a device
+/// that went quiet for an hour doesn't add the hour, and a timestamp going
+/// backwards adds nothing. The first reading counts as one second either way.
+pub struct SedentaryTimer {
+    source: TimerSource,
+    max_gap_ms: i64,
+    elapsed_ms: u64,
+    last_timestamp: Option<DateTime<Utc>>,
+    last_second: Option<String>,
+}
+
+impl SedentaryTimer {
+    pub fn new(source: TimerSource, max_gap: Duration) -> Self {
+        Self {
+            source,
+            max_gap_ms: max_gap.as_millis() as i64,
+            elapsed_ms: 0,
+            last_timestamp: None,
+            last_second: None,
+        }
+    }
+
+    /// Advances the timer for a reading and returns it in whole seconds
+    pub fn observe(
+        &mut self,
+        state: ActivityState,
+        reading: &RawReading,
+        timestamp: DateTime<Utc>,
+    ) -> u64 {
+        let step_ms = match self.source {
+            TimerSource::Elapsed => match self.last_timestamp.replace(timestamp) {
+                None => 1000,
+                Some(previous) => (timestamp - previous)
+                    .num_milliseconds()
+                    .clamp(0, self.max_gap_ms) as u64,
+            },
+            TimerSource::Ticks => {
+                if self.last_second.as_ref() == Some(&reading.ts) {
+                    0
+                } else {
+                    self.last_second = Some(reading.ts.clone());
+                    1000
+                }
+            }
+        };
+
+        match state {
+            ActivityState::Active => self.elapsed_ms = 0,
+            ActivityState::Sedentary => self.elapsed_ms += step_ms,
+            ActivityState::Fidget => {}
+        }
+        self.seconds()
+    }
+
+    pub fn seconds(&self) -> u64 {
+        self.elapsed_ms / 1000
+    }
+
+    /// Starts the current sitting over (a user's timer reset request)
+    pub fn reset(&mut self) {
+        self.elapsed_ms = 0;
+    }
+}
+
+/// A user's request to reset the sedentary timer (WebSocket reset_timer command)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerReset {
+    pub user_id: Uuid,
+    pub admin: bool,
+}
+
+impl TimerReset {
+    /// A device bound to a user is reset only by that user; an unbound device
+    /// is shared by everyone, so only an admin may reset it
+    pub fn applies_to(&self, owner: Option<Uuid>) -> bool {
+        match owner {
+            Some(owner) => owner == self.user_id,
+            None => self.admin,
+        }
+    }
+}
+
+/// Drains pending reset requests; true if any applies to a device bound to `owner`
+fn reset_requested(resets: &mut broadcast::Receiver<TimerReset>, owner: Option<Uuid>) -> bool {
+    let mut requested = false;
+    loop {
+        match resets.try_recv() {
+            Ok(reset) => requested |= reset.applies_to(owner),
+            Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+            Err(_) => return requested,
+        }
+    }
+}
+
+/// Margin a smoothed value must clear beyond a threshold before the state changes
+pub const DEFAULT_HYSTERESIS: f32 = 0.005;
+
+/// Classifies activity state based on PIR and smoothed acceleration.
+///
+/// With a `current` state, thresholds are widened by `hysteresis` (THRESH_HYSTERESIS):
+/// moving into a more active state requires exceeding threshold + margin,
+/// and leaving it requires dropping below threshold - margin, so values
+/// hovering around a threshold keep the current state.
+pub fn classify_state(
+    pir: i32,
+    smoothed_acc: f32,
+    current: Option<ActivityState>,
+    thresholds: Thresholds,
+    hysteresis: f32,
+) -> ActivityState {
+    let margin = if current.is_some() { hysteresis } else { 0.0 };
+    let level = |state: ActivityState| match state {
+        ActivityState::Active => 2,
+        ActivityState::Fidget => 1,
+        ActivityState::Sedentary => 0,
+    };
+    let current_level = current.map(level).unwrap_or(0);
+
+    // Already at or above a level: stay there until below threshold - margin
+    let crosses = |threshold: f32, target_level: i32| {
+        if current_level >= target_level {
+            smoothed_acc > threshold - margin
+        } else {
+            smoothed_acc > threshold + margin
+        }
+    };
+
+    if pir == 1 || crosses(thresholds.active, 2) {
+        ActivityState::Active
+    } else if crosses(thresholds.fidget, 1) {
+        ActivityState::Fidget
     } else {
-        "SEDENTARY".to_string()
+        ActivityState::Sedentary
     }
 }
 
-pub fn spawn_serial_listener(
-    tx: broadcast::Sender<String>,
+/// How PIR and acceleration evidence are weighed in a classification's
+/// confidence (FUSION_PIR_WEIGHT, FUSION_ACC_WEIGHT), and the confidence an
+/// ACTIVE reading needs to reset the sedentary timer (FUSION_MIN_RESET_CONFIDENCE)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FusionWeights {
+    pub pir: f32,
+    pub acc: f32,
+    pub min_reset_confidence: f32,
+}
+
+impl Default for FusionWeights {
+    fn default() -> Self {
+        // A PIR-only ACTIVE scores 0.4, below the 0.5 needed to reset the timer
+        Self {
+            pir: 0.4,
+            acc: 0.6,
+            min_reset_confidence: 0.5,
+        }
+    }
+}
+
+/// `value / limit` clamped to 0..=1 (1 when there is no limit)
+fn ratio(value: f32, limit: f32) -> f32 {
+    if limit > 0.0 {
+        (value / limit).clamp(0.0, 1.0)
+    } else {
+        1.0
+    }
+}
+
+/// How strongly the sensors agree with `state` (0 to 1, two decimals): the
+/// weighted mean of the PIR's and the smoothed acceleration's support for it.
+/// ACTIVE is supported by motion on the PIR and by acceleration up to the
+/// active threshold, so a PIR firing over a flat accelerometer (someone
+/// walking past the desk) scores only the PIR's share. FIDGET and SEDENTARY
+/// are supported by a quiet PIR and acceleration inside their band.
+pub fn classification_confidence(
+    state: ActivityState,
+    pir: i32,
+    smoothed_acc: f32,
+    thresholds: Thresholds,
+    weights: FusionWeights,
+) -> f32 {
+    let motion = pir == 1;
+    let (pir_support, acc_support) = match state {
+        ActivityState::Active => (motion, ratio(smoothed_acc, thresholds.active)),
+        ActivityState::Fidget => (
+            !motion,
+            // Hysteresis can hold FIDGET slightly outside its band
+            if smoothed_acc < thresholds.fidget {
+                ratio(smoothed_acc, thresholds.fidget)
+            } else {
+                ratio(thresholds.active, smoothed_acc)
+            },
+        ),
+        ActivityState::Sedentary => (!motion, 1.0 - ratio(smoothed_acc, thresholds.fidget)),
+    };
+    let total = weights.pir + weights.acc;
+    if total <= 0.0 {
+        return 1.0;
+    }
+    let pir_support = if pir_support { 1.0 } else { 0.0 };
+    let confidence = (weights.pir * pir_support + weights.acc * acc_support) / total;
+    (confidence * 100.0).round() / 100.0
+}
+
+/// The state the sedentary timer is advanced with: an ACTIVE classification
+/// below `min_reset_confidence` only pauses the timer, like fidgeting,
+/// instead of resetting it
+pub fn timer_state(state: ActivityState, confidence: f32, weights: FusionWeights) -> ActivityState {
+    if state == ActivityState::Active && confidence < weights.min_reset_confidence {
+        ActivityState::Fidget
+    } else {
+        state
+    }
+}
+
+/// Line-level counters shared by every serial listener
+#[derive(Default)]
+pub struct SerialMetrics {
+    lines_received: AtomicU64,
+    malformed_lines: AtomicU64,
+    parse_failures: AtomicU64,
+    rejected_readings: AtomicU64,
+    resynced_lines: AtomicU64,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct SerialMetricsSnapshot {
+    pub lines_received: u64,
+    pub malformed_lines: u64,
+    pub parse_failures: u64,
+    // Parsed readings with implausible values (see RawReading::validate)
+    pub rejected_readings: u64,
+    pub resynced_lines: u64,
+}
+
+impl SerialMetrics {
+    fn record_line(&self) {
+        self.lines_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_malformed(&self) {
+        self.malformed_lines.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_parse_failure(&self) {
+        self.parse_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_rejected(&self) {
+        self.rejected_readings.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_resynced(&self) {
+        self.resynced_lines.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> SerialMetricsSnapshot {
+        SerialMetricsSnapshot {
+            lines_received: self.lines_received.load(Ordering::Relaxed),
+            malformed_lines: self.malformed_lines.load(Ordering::Relaxed),
+            parse_failures: self.parse_failures.load(Ordering::Relaxed),
+            rejected_readings: self.rejected_readings.load(Ordering::Relaxed),
+            resynced_lines: self.resynced_lines.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Serial line counters
+/// Endpoint: GET /api/serial/metrics
+pub async fn get_serial_metrics(State(state): State<AppState>) -> Json<SerialMetricsSnapshot> {
+    Json(state.serial_metrics.snapshot())
+}
+
+/// Line protocol of the serial stream and of replayed logs (SERIAL_FORMAT)
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SerialFormat {
+    /// One JSON object per line: {"ts":"12:34:56","pir":0,"acc":0.045}
+    Json,
+    /// Compact `ts,pir,acc` rows for slow links: 12:34:56,0,0.045
+    Csv,
+}
+
+/// Parses `SERIAL_FORMAT` (json, csv)
+pub fn parse_serial_format(raw: &str) -> Result<SerialFormat, String> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "json" => Ok(SerialFormat::Json),
+        "csv" => Ok(SerialFormat::Csv),
+        other => Err(format!("unknown format '{}' (expected json or csv)", other)),
+    }
+}
+
+/// Parses a CSV reading: `ts,pir,acc`, optionally followed by a `datetime`
+/// column (RFC 3339) that plays the role of the JSON `datetime` field
+pub fn parse_csv_reading(line: &str) -> Result<RawReading, String> {
+    let fields: Vec<&str> = line.trim().split(',').map(str::trim).collect();
+    let (ts, pir, acc, datetime) = match fields.as_slice() {
+        [ts, pir, acc] => (*ts, *pir, *acc, None),
+        [ts, pir, acc, datetime] => (*ts, *pir, *acc, Some(*datetime)),
+        _ => return Err(format!("expected ts,pir,acc columns, got {}", fields.len())),
+    };
+    if ts.is_empty() {
+        return Err("ts is empty".to_string());
+    }
+    let pir = pir
+        .parse::<i32>()
+        .map_err(|_| format!("pir '{}' is not an integer", pir))?;
+    let acc = acc
+        .parse::<f32>()
+        .map_err(|_| format!("acc '{}' is not a number", acc))?;
+    let datetime = match datetime.filter(|d| !d.is_empty()) {
+        None => None,
+        Some(datetime) => Some(
+            DateTime::parse_from_rfc3339(datetime)
+                .map_err(|_| format!("datetime '{}' is not RFC 3339", datetime))?
+                .with_timezone(&Utc),
+        ),
+    };
+    Ok(RawReading {
+        ts: ts.to_string(),
+        pir,
+        acc,
+        datetime,
+    })
+}
+
+/// Returns the last complete `{...}` object on the line, if any.
+/// Readings are flat JSON, so the last `{` before the last `}` starts the newest object.
+fn extract_json_object(line: &str) -> Option<&str> {
+    let end = line.rfind('}')?;
+    let start = line[..end].rfind('{')?;
+    Some(&line[start..=end])
+}
+
+// Lines per window when checking the malformed-line rate
+const MALFORMED_RATE_WINDOW: u32 = 100;
+
+/// Tracks bad lines over fixed windows of `MALFORMED_RATE_WINDOW` lines; a
+/// window above SERIAL_MALFORMED_WARN_RATIO logs a baud-rate warning
+struct MalformedRateTracker {
+    threshold: f64,
+    lines: u32,
+    bad: u32,
+}
+
+impl MalformedRateTracker {
+    fn new(threshold: f64) -> Self {
+        Self {
+            threshold,
+            lines: 0,
+            bad: 0,
+        }
+    }
+
+    /// Records one line; returns the window's bad-line rate when a window
+    /// completes above the threshold
+    fn record(&mut self, bad: bool) -> Option<f64> {
+        self.lines += 1;
+        if bad {
+            self.bad += 1;
+        }
+        if self.lines < MALFORMED_RATE_WINDOW {
+            return None;
+        }
+        let rate = self.bad as f64 / self.lines as f64;
+        self.lines = 0;
+        self.bad = 0;
+        (rate > self.threshold).then_some(rate)
+    }
+}
+
+/// Which baud rate a listener opens its port at. Without auto-detect it is
+/// always BAUD_RATE. With BAUD_AUTO_DETECT, BAUD_RATE is tried first and then
+/// each of BAUD_CANDIDATES in turn (wrapping around) whenever a rate yields no
+/// parseable reading within BAUD_DETECT_TIMEOUT_SECONDS; the first rate that
+/// does is kept for the rest of the run, reconnects included.
+pub struct BaudDetector {
+    rates: Vec<u32>,
+    index: usize,
+    locked: bool,
+}
+
+impl BaudDetector {
+    pub fn new(configured: u32, candidates: Option<&[u32]>) -> Self {
+        let mut rates = vec![configured];
+        if let Some(candidates) = candidates {
+            rates.extend(candidates.iter().filter(|&&rate| rate != configured));
+        }
+        Self {
+            locked: rates.len() == 1,
+            rates,
+            index: 0,
+        }
+    }
+
+    pub fn rate(&self) -> u32 {
+        self.rates[self.index]
+    }
+
+    /// True once `elapsed` at the current, still unconfirmed rate reaches `timeout`
+    pub fn timed_out(&self, elapsed: Duration, timeout: Duration) -> bool {
+        !self.locked && elapsed >= timeout
+    }
+
+    /// Moves on to the next candidate rate and returns it
+    pub fn advance(&mut self) -> u32 {
+        self.index = (self.index + 1) % self.rates.len();
+        self.rate()
+    }
+
+    /// A reading parsed at the current rate: keeps it. Returns the rate the
+    /// first time, so the detection can be logged once.
+    pub fn confirm(&mut self) -> Option<u32> {
+        if self.locked {
+            return None;
+        }
+        self.locked = true;
+        Some(self.rate())
+    }
+}
+
+fn warn_malformed_rate(port_name: &str, rate: f64) {
+    eprintln!(
+        "Warning: {:.0}% of recent lines on {} were malformed - check BAUD_RATE matches the Arduino sketch",
+        rate * 100.0,
+        port_name
+    );
+}
+
+// Readings waiting for the Redis history writer
+const HISTORY_QUEUE_CAPACITY: usize = 1024;
+// Most readings written to Redis in one pipeline
+const HISTORY_BATCH_MAX: usize = 64;
+
+/// Owns the Redis connection for one listener's history lists.
+/// Drains whatever has queued up since the last write and pushes it in a
+/// single pipeline, so a slow Redis never blocks the serial read loop.
+async fn write_history(
     redis_client: redis::Client,
-    port_name: String,
-    baud_rate: u32,
-    fallback_state: Arc<FallbackState>,
+    keys: Vec<String>,
+    limit: isize,
+    mut rx: mpsc::Receiver<String>,
 ) {
-    thread::spawn(move || {
-        println!("Connecting to serial device...");
+    let mut con = None;
 
-        let port = serialport::new(&port_name, baud_rate)
-            .timeout(Duration::from_millis(1000))
-            .open();
+    while let Some(first) = rx.recv().await {
+        let mut batch = vec![first];
+        while batch.len() < HISTORY_BATCH_MAX {
+            match rx.try_recv() {
+                Ok(msg) => batch.push(msg),
+                Err(_) => break,
+            }
+        }
+
+        if con.is_none() {
+            con = redis_client.get_multiplexed_async_connection().await.ok();
+        }
+        let Some(c) = con.as_mut() else {
+            continue;
+        };
+
+        if let Err(e) = push_history(c, &keys, &batch, limit).await {
+            eprintln!("Redis error writing sensor history: {:?}", e);
+            con = None;
+        }
+    }
+}
+
+const INITIAL_RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Doubles the reconnect delay, capped at `max`
+fn next_backoff(current: Duration, max: Duration) -> Duration {
+    current.saturating_mul(2).min(max)
+}
+
+/// Shared handles every serial listener needs
+#[derive(Clone)]
+pub struct SerialPipeline {
+    pub config: Arc<Config>,
+    // Processed readings (SSE "sensor-data", WebSocket, DB worker)
+    pub tx: broadcast::Sender<String>,
+    // State transitions (SSE "state-change")
+    pub state_tx: broadcast::Sender<String>,
+    // Users asking for their sedentary timer to be reset
+    pub timer_reset_tx: broadcast::Sender<TimerReset>,
+    pub redis_client: redis::Client,
+    pub fallback_state: Arc<FallbackState>,
+    pub serial_metrics: Arc<SerialMetrics>,
+    pub metrics: Arc<Metrics>,
+    pub thresholds: SharedThresholds,
+    // Stops the listener; queued Redis history is flushed before the thread ends
+    pub shutdown: CancellationToken,
+}
+
+impl SerialPipeline {
+    pub fn from_state(state: &AppState) -> Self {
+        Self {
+            config: state.config.clone(),
+            tx: state.tx.clone(),
+            state_tx: state.state_tx.clone(),
+            timer_reset_tx: state.timer_reset_tx.clone(),
+            redis_client: state.redis.clone(),
+            fallback_state: state.fallback_state.clone(),
+            serial_metrics: state.serial_metrics.clone(),
+            metrics: state.metrics.clone(),
+            thresholds: state.thresholds.clone(),
+            shutdown: state.shutdown.clone(),
+        }
+    }
+}
+
+/// One device's path from raw reading to subscribers: smoothing, classification,
+/// the sedentary timer, alerts, daily totals, the Redis history lists, metrics
+/// and the broadcast. Its state lives as long as the stream, so it carries
+/// across serial reconnects. The serial listener and the simulator both feed it.
+pub struct DeviceStream {
+    config: Arc<Config>,
+    name: String,
+    user_id: Option<Uuid>,
+    tx: broadcast::Sender<String>,
+    state_tx: broadcast::Sender<String>,
+    fallback_state: Arc<FallbackState>,
+    metrics: Arc<Metrics>,
+    thresholds: SharedThresholds,
+    shutdown: CancellationToken,
+    // Dedicated async runtime that hosts the Redis history writer
+    rt: tokio::runtime::Runtime,
+    history_tx: mpsc::Sender<String>,
+    history_writer: tokio::task::JoinHandle<()>,
+    dropping_history: bool,
+    acc_buffer: VecDeque<f32>,
+    sedentary_timer: SedentaryTimer,
+    current_state: Option<ActivityState>,
+    state_changes: StateChangeDetector,
+    timestamps: TimestampResolver,
+    pir_debounce: PirDebouncer,
+    alerts: AlertCooldown,
+    daily_totals: DailyAccumulator,
+    timer_resets: broadcast::Receiver<TimerReset>,
+}
+
+impl DeviceStream {
+    /// Starts a stream named after its port (or "simulator"); readings are
+    /// owned by the user `DEVICE_USER_MAP` binds to that name, if any
+    pub fn open(pipeline: SerialPipeline, name: &str) -> Self {
+        let SerialPipeline {
+            config,
+            tx,
+            state_tx,
+            timer_reset_tx,
+            redis_client,
+            fallback_state,
+            metrics,
+            thresholds,
+            shutdown,
+            ..
+        } = pipeline;
+        let settings = &config.serial;
+
+        let user_id = settings.device_user_map.get(name).copied();
+        if let Some(user_id) = user_id {
+            println!("Device {} bound to user {}", name, user_id);
+        }
 
-        // Create a dedicated async runtime for the serial thread
         let rt = tokio::runtime::Runtime::new().unwrap();
+        let (history_tx, history_rx) = mpsc::channel(HISTORY_QUEUE_CAPACITY);
+        let history_writer = rt.spawn(write_history(
+            redis_client,
+            vec![SENSOR_HISTORY_KEY.to_string(), port_history_key(name)],
+            config.server.history_limit,
+            history_rx,
+        ));
 
-        // State tracking
-        let mut acc_buffer: VecDeque<f32> = VecDeque::with_capacity(SMOOTHING_WINDOW);
-        let mut sedentary_timer: u64 = 0;
-        let mut last_second: Option<String> = None;
-
-        match port {
-            Ok(p) => {
-                println!("Serial Connected! Processing raw sensor data...");
-                let mut reader = BufReader::new(p);
-                let mut line = String::new();
-
-                loop {
-                    line.clear();
-                    if let Ok(bytes_read) = reader.read_line(&mut line) {
-                        if bytes_read == 0 {
-                            continue;
-                        }
+        Self {
+            name: name.to_string(),
+            user_id,
+            tx,
+            state_tx,
+            fallback_state,
+            metrics,
+            thresholds,
+            shutdown,
+            rt,
+            history_tx,
+            history_writer,
+            dropping_history: false,
+            acc_buffer: VecDeque::with_capacity(settings.smoothing_window),
+            sedentary_timer: SedentaryTimer::new(settings.timer_source, settings.timer_max_gap),
+            current_state: None,
+            state_changes: StateChangeDetector::new(),
+            timestamps: TimestampResolver::new(),
+            pir_debounce: PirDebouncer::new(settings.pir_debounce_samples),
+            alerts: AlertCooldown::new(settings.alert_cooldown_sec),
+            daily_totals: DailyAccumulator::new(config.timezone),
+            timer_resets: timer_reset_tx.subscribe(),
+            config,
+        }
+    }
 
-                        let clean_line = line.trim();
-                        if clean_line.starts_with('{') {
-                            // Parse raw Arduino data
-                            if let Ok(reading) = serde_json::from_str::<RawReading>(clean_line) {
-                                // Notify fallback monitor that real hardware data is arriving
-                                fallback_state.record_data_received();
-                                // Add to smoothing buffer
-                                if acc_buffer.len() >= SMOOTHING_WINDOW {
-                                    acc_buffer.pop_front();
-                                }
-                                acc_buffer.push_back(reading.acc);
+    /// Classifies one reading and publishes it like any live reading
+    pub fn process(&mut self, reading: &RawReading) {
+        let settings = &self.config.serial;
 
-                                // Calculate smoothed acceleration (mean of buffer)
-                                let smoothed_acc: f32 = if acc_buffer.is_empty() {
-                                    0.0
-                                } else {
-                                    acc_buffer.iter().sum::<f32>() / acc_buffer.len() as f32
-                                };
+        // Notify fallback monitor that real hardware data is arriving
+        self.fallback_state.record_data_received();
+        // Add to smoothing buffer
+        while self.acc_buffer.len() >= settings.smoothing_window {
+            self.acc_buffer.pop_front();
+        }
+        self.acc_buffer.push_back(reading.acc);
 
-                                // Classify state
-                                let state = classify_state(reading.pir, smoothed_acc);
+        // Calculate smoothed acceleration
+        let smoothed_acc = smooth(&self.acc_buffer, settings.smoothing_mode);
 
-                                // Update sedentary timer (once per second based on timestamp)
-                                let current_second = reading.ts.clone();
-                                if last_second.as_ref() != Some(&current_second) {
-                                    last_second = Some(current_second);
+        // Classify state, with how well PIR and acceleration agree on it
+        let pir = self.pir_debounce.observe(reading.pir);
+        let thresholds = self.thresholds.current();
+        let state = classify_state(
+            pir,
+            smoothed_acc,
+            self.current_state,
+            thresholds,
+            settings.hysteresis,
+        );
+        let confidence =
+            classification_confidence(state, pir, smoothed_acc, thresholds, settings.fusion);
+        self.current_state = Some(state);
 
-                                    match state.as_str() {
-                                        "ACTIVE" => sedentary_timer = 0,     // Reset on activity
-                                        "FIDGET" => {}                       // Pause
-                                        "SEDENTARY" => sedentary_timer += 1, // Increment
-                                        _ => {}
-                                    }
-                                }
+        // Full UTC timestamp, which also drives the sedentary timer
+        let timestamp = self.timestamps.resolve(reading);
+        self.sedentary_timer.observe(
+            timer_state(state, confidence, settings.fusion),
+            reading,
+            timestamp,
+        );
 
-                                // Build processed output with full UTC timestamp
-                                let timestamp = NaiveTime::parse_from_str(&reading.ts, "%H:%M:%S")
-                                    .map(|time| Utc::now().date_naive().and_time(time).and_utc())
-                                    .unwrap_or_else(|_| Utc::now());
-
-                                let output = ProcessedState {
-                                    state: state.clone(),
-                                    timer: sedentary_timer,
-                                    val: smoothed_acc,
-                                    alert: sedentary_timer >= alert_limit_sec(),
-                                    timestamp,
-                                };
+        // Reset requested by the device's user (unbound devices accept any user)
+        if reset_requested(&mut self.timer_resets, self.user_id) {
+            self.sedentary_timer.reset();
+        }
+        let timer = self.sedentary_timer.seconds();
+
+        let output = ProcessedState {
+            state,
+            timer,
+            val: smoothed_acc,
+            alert: self
+                .alerts
+                .observe(timer, timer >= settings.alert_limit_sec),
+            timestamp,
+            user_id: self.user_id,
+            daily: Some(self.daily_totals.observe(state, timestamp)),
+            classification_confidence: Some(confidence),
+            replayed: false,
+        };
+
+        let json_out = serde_json::to_string(&output).unwrap();
+
+        if let Some(change) = self.state_changes.observe(state, timestamp, self.user_id) {
+            let _ = self.state_tx.send(serde_json::to_string(&change).unwrap());
+        }
+
+        // Queue for the Redis history writer; if Redis is behind,
+        // drop the history entry rather than stall ingestion
+        match self.history_tx.try_send(json_out.clone()) {
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                if !self.dropping_history {
+                    eprintln!(
+                        "Redis history queue full for {}, dropping entries",
+                        self.name
+                    );
+                    self.dropping_history = true;
+                }
+            }
+            _ => self.dropping_history = false,
+        }
+
+        self.metrics.record_reading(output.state);
+
+        // Push to WebSocket
+        let _ = self.tx.send(json_out);
+    }
+
+    /// Sleeps for `duration`; false if shutdown was requested meanwhile
+    pub fn pause(&self, duration: Duration) -> bool {
+        self.rt.block_on(async {
+            tokio::select! {
+                _ = tokio::time::sleep(duration) => true,
+                _ = self.shutdown.cancelled() => false,
+            }
+        })
+    }
 
-                                let json_out = serde_json::to_string(&output).unwrap();
-
-                                // Broadcast to WebSocket and cache in Redis
-                                rt.block_on(async {
-                                    // Redis cache for reconnection
-                                    if let Ok(mut con) =
-                                        redis_client.get_multiplexed_async_connection().await
-                                    {
-                                        let _: () = con
-                                            .lpush("sensor_history", &json_out)
-                                            .await
-                                            .unwrap_or(());
-                                        let _: () = con
-                                            .ltrim("sensor_history", 0, sensor_history_limit() - 1)
-                                            .await
-                                            .unwrap_or(());
+    /// Closing the queue lets the writer push what is left, then end
+    pub fn close(self) {
+        drop(self.history_tx);
+        let _ = self.rt.block_on(self.history_writer);
+    }
+}
+
+pub fn spawn_serial_listener(
+    pipeline: SerialPipeline,
+    port_name: String,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let config = pipeline.config.clone();
+        let fallback_state = pipeline.fallback_state.clone();
+        let serial_metrics = pipeline.serial_metrics.clone();
+        let shutdown = pipeline.shutdown.clone();
+        let settings = &config.serial;
+        let mut stream = DeviceStream::open(pipeline, &port_name);
+
+        let mut malformed_rate = MalformedRateTracker::new(settings.malformed_warn_ratio);
+        // Log the first of a run of rejected readings; the metric counts them all
+        let mut rejecting_readings = false;
+
+        let max_backoff = settings.reconnect_max;
+        let mut backoff = INITIAL_RECONNECT_BACKOFF;
+        let mut attempt: u32 = 0;
+        let mut baud = BaudDetector::new(
+            settings.baud_rate,
+            settings
+                .baud_auto_detect
+                .then_some(settings.baud_candidates.as_slice()),
+        );
+
+        while !shutdown.is_cancelled() {
+            attempt += 1;
+            println!(
+                "Connecting to serial device {} at {} baud (attempt {})...",
+                port_name,
+                baud.rate(),
+                attempt
+            );
+
+            let port = serialport::new(&port_name, baud.rate())
+                .timeout(Duration::from_millis(1000))
+                .open();
+            // Set when the port is reopened at the next candidate baud rate
+            let mut switch_rate = false;
+
+            match port {
+                Ok(p) => {
+                    println!(
+                        "Serial Connected on {}! Processing raw sensor data...",
+                        port_name
+                    );
+                    attempt = 0;
+                    backoff = INITIAL_RECONNECT_BACKOFF;
+                    let opened = Instant::now();
+                    let mut reader = BufReader::new(p);
+                    let mut line = String::new();
+
+                    while !shutdown.is_cancelled() {
+                        if baud.timed_out(opened.elapsed(), settings.baud_detect_timeout) {
+                            let tried = baud.rate();
+                            eprintln!(
+                                "No readings on {} at {} baud within {}s, trying {} baud",
+                                port_name,
+                                tried,
+                                settings.baud_detect_timeout.as_secs(),
+                                baud.advance()
+                            );
+                            switch_rate = true;
+                            break;
+                        }
+
+                        line.clear();
+                        match reader.read_line(&mut line) {
+                            Ok(0) => continue,
+                            Ok(_) => {}
+                            // Timeouts are normal between readings; garbled bytes aren't a disconnect
+                            Err(e)
+                                if matches!(
+                                    e.kind(),
+                                    io::ErrorKind::TimedOut | io::ErrorKind::InvalidData
+                                ) =>
+                            {
+                                continue
+                            }
+                            Err(e) => {
+                                eprintln!("Serial read error on {}: {}", port_name, e);
+                                break;
+                            }
+                        }
+
+                        serial_metrics.record_line();
+                        let clean_line = line.trim();
+
+                        // Parse raw Arduino data
+                        let parsed = match settings.format {
+                            SerialFormat::Json => {
+                                // Resync on the last complete {...} so a half-written line
+                                // glued to a good one doesn't lose the good reading
+                                let Some(json) = extract_json_object(clean_line) else {
+                                    serial_metrics.record_malformed();
+                                    if let Some(rate) = malformed_rate.record(true) {
+                                        warn_malformed_rate(&port_name, rate);
                                     }
-                                    // Push to WebSocket
-                                    let _ = tx.send(json_out);
-                                });
+                                    continue;
+                                };
+                                if json.len() != clean_line.len() {
+                                    serial_metrics.record_resynced();
+                                }
+                                serde_json::from_str::<RawReading>(json).map_err(|e| e.to_string())
                             }
+                            // A bad row (boot banner, header, garbled bytes) is a parse failure
+                            SerialFormat::Csv => parse_csv_reading(clean_line),
+                        };
+                        if let Some(rate) = malformed_rate.record(parsed.is_err()) {
+                            warn_malformed_rate(&port_name, rate);
                         }
+                        let reading = match parsed {
+                            Ok(reading) => reading,
+                            Err(_) => {
+                                serial_metrics.record_parse_failure();
+                                continue;
+                            }
+                        };
+                        if let Some(rate) = baud.confirm() {
+                            println!("Detected baud rate {} on {}", rate, port_name);
+                        }
+                        if let Err(problem) = reading.validate(settings.max_acc) {
+                            serial_metrics.record_rejected();
+                            if !rejecting_readings {
+                                eprintln!("Rejected reading on {}: {}", port_name, problem);
+                                rejecting_readings = true;
+                            }
+                            continue;
+                        }
+                        rejecting_readings = false;
+
+                        stream.process(&reading);
                     }
                 }
+                // Only this port retries; listeners on other ports keep running
+                Err(e) => eprintln!("Serial Error on {}: {}", port_name, e),
+            }
+
+            if shutdown.is_cancelled() {
+                break;
             }
-            Err(e) => eprintln!("Serial Error: {}", e),
+            if switch_rate {
+                continue;
+            }
+
+            // Let the fallback monitor backfill while the device is away
+            fallback_state.record_device_lost();
+            println!("Reconnecting to {} in {}s", port_name, backoff.as_secs());
+            stream.pause(backoff);
+            backoff = next_backoff(backoff, max_backoff);
         }
-    });
+
+        stream.close();
+        println!("Serial listener on {} stopped", port_name);
+    })
 }
+
+#[cfg(test)]
+#[path = "serial_tests.rs"]
+mod tests;
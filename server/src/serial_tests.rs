@@ -0,0 +1,933 @@
+use super::*;
+use chrono::TimeZone;
+
+// Default THRESH_FIDGET / THRESH_ACTIVE
+const DEFAULTS: Thresholds = Thresholds {
+    fidget: 0.020,
+    active: 0.040,
+};
+
+// Serial Port List Tests
+
+#[test]
+fn test_parse_serial_ports() {
+    assert_eq!(
+        parse_serial_ports("/dev/ttyACM0, /dev/ttyACM1 ,COM3"),
+        vec!["/dev/ttyACM0", "/dev/ttyACM1", "COM3"]
+    );
+}
+
+#[test]
+fn test_parse_serial_ports_skips_empty_and_duplicates() {
+    assert_eq!(
+        parse_serial_ports("/dev/ttyACM0,,/dev/ttyACM0, "),
+        vec!["/dev/ttyACM0"]
+    );
+    assert!(parse_serial_ports(" , ").is_empty());
+}
+
+#[test]
+fn test_port_history_key() {
+    assert_eq!(
+        port_history_key("/dev/ttyACM0"),
+        "sensor_history:/dev/ttyACM0"
+    );
+}
+
+// Baud Rate Detection Tests
+
+#[test]
+fn test_parse_baud_rates() {
+    assert_eq!(
+        parse_baud_rates("9600, 115200,9600"),
+        Ok(vec![9600, 115200])
+    );
+    assert!(parse_baud_rates("9600,fast").is_err());
+    assert!(parse_baud_rates("0").is_err());
+    assert!(parse_baud_rates(" , ").is_err());
+}
+
+#[test]
+fn test_fixed_baud_rate_never_times_out() {
+    let mut baud = BaudDetector::new(57600, None);
+    assert_eq!(baud.rate(), 57600);
+    assert!(!baud.timed_out(Duration::from_secs(3600), Duration::from_secs(5)));
+    assert_eq!(baud.confirm(), None);
+}
+
+#[test]
+fn test_detection_cycles_candidates_after_configured_rate() {
+    let mut baud = BaudDetector::new(57600, Some(&DEFAULT_BAUD_CANDIDATES));
+    let timeout = Duration::from_secs(5);
+    assert_eq!(baud.rate(), 57600);
+    assert!(!baud.timed_out(Duration::from_secs(4), timeout));
+    assert!(baud.timed_out(timeout, timeout));
+
+    // The configured rate isn't tried twice, and the list wraps around
+    assert_eq!(baud.advance(), 9600);
+    assert_eq!(baud.advance(), 115200);
+    assert_eq!(baud.advance(), 57600);
+}
+
+#[test]
+fn test_first_reading_locks_the_rate() {
+    let mut baud = BaudDetector::new(9600, Some(&DEFAULT_BAUD_CANDIDATES));
+    assert_eq!(baud.advance(), 57600);
+    assert_eq!(baud.confirm(), Some(57600));
+    // Logged once; later readings and reconnects keep the rate
+    assert_eq!(baud.confirm(), None);
+    assert!(!baud.timed_out(Duration::from_secs(60), Duration::from_secs(5)));
+    assert_eq!(baud.rate(), 57600);
+}
+
+// Device User Map Tests
+
+const USER_A: &str = "11111111-1111-1111-1111-111111111111";
+const USER_B: &str = "22222222-2222-2222-2222-222222222222";
+
+#[test]
+fn test_parse_device_user_map_multiple_ports() {
+    let raw = format!("/dev/ttyACM0={},/dev/ttyUSB0={}", USER_A, USER_B);
+    let map = parse_device_user_map(&raw).unwrap();
+
+    assert_eq!(map.len(), 2);
+    assert_eq!(map["/dev/ttyACM0"], Uuid::parse_str(USER_A).unwrap());
+    assert_eq!(map["/dev/ttyUSB0"], Uuid::parse_str(USER_B).unwrap());
+}
+
+#[test]
+fn test_parse_device_user_map_trims_whitespace() {
+    let raw = format!(" COM3 = {} , ", USER_A);
+    let map = parse_device_user_map(&raw).unwrap();
+
+    assert_eq!(map["COM3"], Uuid::parse_str(USER_A).unwrap());
+}
+
+#[test]
+fn test_parse_device_user_map_rejects_malformed_entries() {
+    let missing_user = format!("/dev/ttyACM0,COM3={}", USER_B);
+    assert!(parse_device_user_map(&missing_user)
+        .unwrap_err()
+        .contains("/dev/ttyACM0"));
+
+    let bad_uuid = format!("/dev/ttyUSB0=not-a-uuid,COM3={}", USER_B);
+    assert!(parse_device_user_map(&bad_uuid)
+        .unwrap_err()
+        .contains("not-a-uuid"));
+}
+
+#[test]
+fn test_parse_device_user_map_empty() {
+    assert!(parse_device_user_map("").unwrap().is_empty());
+}
+
+// Smoothing Window Tests
+
+#[test]
+fn test_smoothing_window_default_when_unset() {
+    assert_eq!(parse_smoothing_window(None), Ok(10));
+}
+
+#[test]
+fn test_smoothing_window_accepts_bounds() {
+    assert_eq!(parse_smoothing_window(Some("1")), Ok(1));
+    assert_eq!(parse_smoothing_window(Some("200")), Ok(200));
+    assert_eq!(parse_smoothing_window(Some(" 25 ")), Ok(25));
+}
+
+#[test]
+fn test_smoothing_window_rejects_out_of_range() {
+    for raw in ["0", "201", "-5", "abc"] {
+        assert!(parse_smoothing_window(Some(raw)).is_err(), "{}", raw);
+    }
+}
+
+// Smoothing Mode Tests
+
+fn buffer(values: &[f32]) -> VecDeque<f32> {
+    values.iter().copied().collect()
+}
+
+// Nine quiet samples and one glitch well above THRESH_ACTIVE
+fn window_with_spike() -> VecDeque<f32> {
+    buffer(&[0.01, 0.01, 0.01, 0.01, 0.5, 0.01, 0.01, 0.01, 0.01, 0.01])
+}
+
+#[test]
+fn test_parse_smoothing_mode() {
+    assert_eq!(parse_smoothing_mode(None, None), Ok(SmoothingMode::Mean));
+    assert_eq!(
+        parse_smoothing_mode(Some("MEDIAN"), None),
+        Ok(SmoothingMode::Median)
+    );
+    assert_eq!(
+        parse_smoothing_mode(Some("ewma"), Some("0.5")),
+        Ok(SmoothingMode::Ewma(0.5))
+    );
+    assert_eq!(
+        parse_smoothing_mode(Some("ewma"), None),
+        Ok(SmoothingMode::Ewma(DEFAULT_EWMA_ALPHA))
+    );
+}
+
+#[test]
+fn test_parse_smoothing_mode_rejects_bad_input() {
+    assert!(parse_smoothing_mode(Some("mode"), None).is_err());
+    assert!(parse_smoothing_mode(Some("ewma"), Some("1.5")).is_err());
+    assert!(parse_smoothing_mode(Some("ewma"), Some("0")).is_err());
+}
+
+#[test]
+fn test_smooth_empty_buffer() {
+    assert_eq!(smooth(&VecDeque::new(), SmoothingMode::Median), 0.0);
+}
+
+#[test]
+fn test_smooth_mean() {
+    let smoothed = smooth(&buffer(&[0.01, 0.02, 0.03]), SmoothingMode::Mean);
+    assert!((smoothed - 0.02).abs() < 1e-6);
+}
+
+#[test]
+fn test_smooth_median_even_length() {
+    let smoothed = smooth(&buffer(&[0.04, 0.01, 0.03, 0.02]), SmoothingMode::Median);
+    assert!((smoothed - 0.025).abs() < 1e-6);
+}
+
+#[test]
+fn test_smooth_ewma_weights_recent_samples() {
+    let smoothed = smooth(&buffer(&[0.0, 0.0, 1.0]), SmoothingMode::Ewma(0.5));
+    assert!((smoothed - 0.5).abs() < 1e-6);
+}
+
+#[test]
+fn test_mean_is_flipped_by_lone_outlier() {
+    let smoothed = smooth(&window_with_spike(), SmoothingMode::Mean);
+    assert_eq!(
+        classify_state(0, smoothed, None, DEFAULTS, DEFAULT_HYSTERESIS),
+        ActivityState::Active
+    );
+}
+
+#[test]
+fn test_median_ignores_lone_outlier() {
+    let smoothed = smooth(&window_with_spike(), SmoothingMode::Median);
+    assert_eq!(
+        classify_state(0, smoothed, None, DEFAULTS, DEFAULT_HYSTERESIS),
+        ActivityState::Sedentary
+    );
+}
+
+// Hysteresis Tests
+// Defaults: THRESH_FIDGET 0.020, THRESH_ACTIVE 0.040, THRESH_HYSTERESIS 0.005
+
+// Feeds values through the classifier the way the listener does, returning each reported state
+fn classify_sequence(start: Option<ActivityState>, values: &[f32]) -> Vec<ActivityState> {
+    let mut current = start;
+    values
+        .iter()
+        .map(|&v| {
+            let state = classify_state(0, v, current, DEFAULTS, DEFAULT_HYSTERESIS);
+            current = Some(state);
+            state
+        })
+        .collect()
+}
+
+#[test]
+fn test_classify_without_current_state_uses_plain_thresholds() {
+    assert_eq!(
+        classify_state(0, 0.021, None, DEFAULTS, DEFAULT_HYSTERESIS),
+        ActivityState::Fidget
+    );
+    assert_eq!(
+        classify_state(0, 0.041, None, DEFAULTS, DEFAULT_HYSTERESIS),
+        ActivityState::Active
+    );
+    assert_eq!(
+        classify_state(0, 0.019, None, DEFAULTS, DEFAULT_HYSTERESIS),
+        ActivityState::Sedentary
+    );
+}
+
+#[test]
+fn test_pir_always_active() {
+    assert_eq!(
+        classify_state(
+            1,
+            0.0,
+            Some(ActivityState::Sedentary),
+            DEFAULTS,
+            DEFAULT_HYSTERESIS
+        ),
+        ActivityState::Active
+    );
+}
+
+#[test]
+fn test_sedentary_stable_when_straddling_fidget_threshold() {
+    let states = classify_sequence(
+        Some(ActivityState::Sedentary),
+        &[0.019, 0.021, 0.018, 0.022, 0.020],
+    );
+    assert!(
+        states.iter().all(|&s| s == ActivityState::Sedentary),
+        "{:?}",
+        states
+    );
+}
+
+#[test]
+fn test_fidget_stable_when_straddling_fidget_threshold() {
+    let states = classify_sequence(
+        Some(ActivityState::Fidget),
+        &[0.019, 0.021, 0.018, 0.022, 0.020],
+    );
+    assert!(
+        states.iter().all(|&s| s == ActivityState::Fidget),
+        "{:?}",
+        states
+    );
+}
+
+#[test]
+fn test_fidget_stable_when_straddling_active_threshold() {
+    let states = classify_sequence(Some(ActivityState::Fidget), &[0.039, 0.042, 0.038, 0.044]);
+    assert!(
+        states.iter().all(|&s| s == ActivityState::Fidget),
+        "{:?}",
+        states
+    );
+}
+
+#[test]
+fn test_transitions_once_margin_is_cleared() {
+    let states = classify_sequence(Some(ActivityState::Sedentary), &[0.026, 0.019, 0.014]);
+    assert_eq!(
+        states,
+        vec![
+            ActivityState::Fidget,
+            ActivityState::Fidget,
+            ActivityState::Sedentary
+        ]
+    );
+}
+
+#[test]
+fn test_active_decays_through_fidget() {
+    let states = classify_sequence(Some(ActivityState::Active), &[0.036, 0.034, 0.016, 0.014]);
+    assert_eq!(
+        states,
+        vec![
+            ActivityState::Active,
+            ActivityState::Fidget,
+            ActivityState::Fidget,
+            ActivityState::Sedentary
+        ]
+    );
+}
+
+// Reconnect Backoff Tests
+
+#[test]
+fn test_next_backoff_doubles() {
+    let max = Duration::from_secs(30);
+    assert_eq!(
+        next_backoff(Duration::from_secs(1), max),
+        Duration::from_secs(2)
+    );
+    assert_eq!(
+        next_backoff(Duration::from_secs(8), max),
+        Duration::from_secs(16)
+    );
+}
+
+#[test]
+fn test_next_backoff_is_capped() {
+    let max = Duration::from_secs(30);
+    assert_eq!(next_backoff(Duration::from_secs(16), max), max);
+    assert_eq!(next_backoff(max, max), max);
+}
+
+// Line Resync Tests
+
+#[test]
+fn test_extract_json_object_clean_line() {
+    let line = r#"{"ts":"12:34:56","pir":0,"acc":0.01}"#;
+    assert_eq!(extract_json_object(line), Some(line));
+}
+
+#[test]
+fn test_extract_json_object_skips_partial_prefix() {
+    let line = r#"{"ts":"12:34:5{"ts":"12:34:56","pir":0,"acc":0.01}"#;
+    assert_eq!(
+        extract_json_object(line),
+        Some(r#"{"ts":"12:34:56","pir":0,"acc":0.01}"#)
+    );
+}
+
+#[test]
+fn test_extract_json_object_skips_noise_prefix() {
+    let line = r#"@@garbage{"ts":"12:34:56","pir":1,"acc":0.2}"#;
+    let json = extract_json_object(line).unwrap();
+    assert!(serde_json::from_str::<RawReading>(json).is_ok());
+}
+
+#[test]
+fn test_extract_json_object_rejects_incomplete_lines() {
+    assert_eq!(extract_json_object(r#"{"ts":"12:34:56","pir":0"#), None);
+    assert_eq!(extract_json_object("Booting sensor..."), None);
+    assert_eq!(extract_json_object("}{"), None);
+}
+
+// CSV Line Tests
+
+#[test]
+fn test_parse_csv_reading() {
+    let reading = parse_csv_reading("12:34:56,1,0.045").unwrap();
+    assert_eq!(
+        reading,
+        RawReading {
+            ts: "12:34:56".to_string(),
+            pir: 1,
+            acc: 0.045,
+            datetime: None,
+        }
+    );
+    // Whitespace and a CRLF line ending are tolerated
+    assert_eq!(
+        parse_csv_reading(" 12:34:56 , 0 , 0.01\r\n").unwrap().pir,
+        0
+    );
+}
+
+#[test]
+fn test_parse_csv_reading_with_datetime() {
+    let reading = parse_csv_reading("12:34:56,0,0.01,2026-01-06T12:34:56Z").unwrap();
+    assert_eq!(
+        reading.datetime,
+        Some("2026-01-06T12:34:56Z".parse::<DateTime<Utc>>().unwrap())
+    );
+    // An empty datetime column is the same as none
+    assert_eq!(
+        parse_csv_reading("12:34:56,0,0.01,").unwrap().datetime,
+        None
+    );
+}
+
+#[test]
+fn test_parse_csv_reading_rejects_malformed_rows() {
+    for row in [
+        "",
+        "ts,pir,acc",
+        "12:34:56,0",
+        "12:34:56,0,0.01,2026-01-06T12:34:56Z,extra",
+        ",0,0.01",
+        "12:34:56,yes,0.01",
+        "12:34:56,0,fast",
+        "12:34:56,0,0.01,yesterday",
+        r#"{"ts":"12:34:56","pir":0,"acc":0.01}"#,
+    ] {
+        assert!(parse_csv_reading(row).is_err(), "{:?}", row);
+    }
+}
+
+#[test]
+fn test_csv_range_checks_are_left_to_validate() {
+    // Parsing only checks the shape; implausible values fail `validate`
+    let reading = parse_csv_reading("12:34:56,7,NaN").unwrap();
+    assert!(reading.validate(16.0).is_err());
+}
+
+#[test]
+fn test_parse_serial_format() {
+    assert_eq!(parse_serial_format("json"), Ok(SerialFormat::Json));
+    assert_eq!(parse_serial_format(" CSV "), Ok(SerialFormat::Csv));
+    assert!(parse_serial_format("msgpack").is_err());
+}
+
+// Serial Metrics Tests
+
+#[test]
+fn test_serial_metrics_snapshot() {
+    let metrics = SerialMetrics::default();
+    metrics.record_line();
+    metrics.record_line();
+    metrics.record_malformed();
+    metrics.record_parse_failure();
+    metrics.record_rejected();
+    metrics.record_resynced();
+
+    assert_eq!(
+        metrics.snapshot(),
+        SerialMetricsSnapshot {
+            lines_received: 2,
+            malformed_lines: 1,
+            parse_failures: 1,
+            rejected_readings: 1,
+            resynced_lines: 1,
+        }
+    );
+}
+
+#[test]
+fn test_malformed_rate_warns_above_threshold() {
+    let mut tracker = MalformedRateTracker::new(0.2);
+    let mut warning = None;
+    for i in 0..MALFORMED_RATE_WINDOW {
+        warning = tracker.record(i % 2 == 0);
+    }
+    assert_eq!(warning, Some(0.5));
+}
+
+#[test]
+fn test_malformed_rate_quiet_below_threshold() {
+    let mut tracker = MalformedRateTracker::new(0.2);
+    for i in 0..MALFORMED_RATE_WINDOW * 2 {
+        assert_eq!(tracker.record(i % 10 == 0), None);
+    }
+}
+
+#[test]
+fn test_classify_uses_given_thresholds() {
+    let sensitive = Thresholds {
+        fidget: 0.005,
+        active: 0.010,
+    };
+    assert_eq!(
+        classify_state(0, 0.015, None, sensitive, DEFAULT_HYSTERESIS),
+        ActivityState::Active
+    );
+    assert_eq!(
+        classify_state(0, 0.015, None, DEFAULTS, DEFAULT_HYSTERESIS),
+        ActivityState::Sedentary
+    );
+}
+
+// Shared Threshold Tests
+
+#[test]
+fn test_shared_thresholds_update_is_visible_to_clones() {
+    let shared = SharedThresholds::new(DEFAULTS);
+    let reader = shared.clone();
+    let tuned = Thresholds {
+        fidget: 0.011,
+        active: 0.033,
+    };
+    shared.set(tuned);
+
+    assert_eq!(reader.current(), tuned);
+}
+
+#[test]
+fn test_thresholds_validate() {
+    assert!(DEFAULTS.validate().is_ok());
+    assert!(Thresholds {
+        fidget: 0.04,
+        active: 0.02
+    }
+    .validate()
+    .is_err());
+    assert!(Thresholds {
+        fidget: 0.0,
+        active: 0.02
+    }
+    .validate()
+    .is_err());
+    assert!(Thresholds {
+        fidget: f32::NAN,
+        active: 0.02
+    }
+    .validate()
+    .is_err());
+}
+
+#[test]
+fn test_thresholds_json_field_names() {
+    let json = serde_json::to_value(DEFAULTS).unwrap();
+    assert!(json.get("thresh_fidget").is_some());
+    assert!(json.get("thresh_active").is_some());
+}
+
+// Timestamp Resolution Tests
+
+fn reading(json: &str) -> RawReading {
+    serde_json::from_str(json).unwrap()
+}
+
+fn at_time(ts: &str) -> RawReading {
+    reading(&format!(r#"{{"ts":"{}","pir":0,"acc":0.01}}"#, ts))
+}
+
+fn jan(day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2026, 1, day).unwrap()
+}
+
+#[test]
+fn test_timestamp_time_only_uses_stream_date() {
+    let mut resolver = TimestampResolver::starting_on(jan(6));
+    let ts = resolver.resolve(&at_time("12:34:56"));
+    assert_eq!(ts.to_rfc3339(), "2026-01-06T12:34:56+00:00");
+}
+
+#[test]
+fn test_timestamp_crosses_midnight_monotonically() {
+    let mut resolver = TimestampResolver::starting_on(jan(6));
+    let times = ["23:59:58", "23:59:59", "00:00:00", "00:00:01"];
+    let resolved: Vec<_> = times
+        .iter()
+        .map(|t| resolver.resolve(&at_time(t)))
+        .collect();
+
+    assert!(resolved.windows(2).all(|w| w[0] < w[1]), "{:?}", resolved);
+    assert_eq!(resolved[2].to_rfc3339(), "2026-01-07T00:00:00+00:00");
+}
+
+#[test]
+fn test_timestamp_repeated_second_does_not_roll_over() {
+    let mut resolver = TimestampResolver::starting_on(jan(6));
+    let first = resolver.resolve(&at_time("10:00:00"));
+    let second = resolver.resolve(&at_time("10:00:00"));
+    assert_eq!(first, second);
+}
+
+#[test]
+fn test_timestamp_prefers_full_datetime() {
+    let mut resolver = TimestampResolver::starting_on(jan(6));
+    let ts = resolver.resolve(&reading(
+        r#"{"ts":"08:00:00","datetime":"2025-12-31T23:59:59Z","pir":0,"acc":0.01}"#,
+    ));
+    assert_eq!(ts.to_rfc3339(), "2025-12-31T23:59:59+00:00");
+}
+
+#[test]
+fn test_timestamp_time_only_continues_after_full_datetime() {
+    let mut resolver = TimestampResolver::starting_on(jan(6));
+    resolver.resolve(&reading(
+        r#"{"ts":"23:59:59","datetime":"2025-12-31T23:59:59Z","pir":0,"acc":0.01}"#,
+    ));
+    let ts = resolver.resolve(&at_time("00:00:01"));
+    assert_eq!(ts.to_rfc3339(), "2026-01-01T00:00:01+00:00");
+}
+
+// PIR Debounce Tests
+
+#[test]
+fn test_pir_debounce_default_passes_every_sample() {
+    let mut debounce = PirDebouncer::new(1);
+    assert_eq!(debounce.observe(1), 1);
+    assert_eq!(debounce.observe(0), 0);
+}
+
+#[test]
+fn test_pir_debounce_requires_consecutive_samples() {
+    let mut debounce = PirDebouncer::new(3);
+    assert_eq!(debounce.observe(1), 0);
+    assert_eq!(debounce.observe(1), 0);
+    assert_eq!(debounce.observe(1), 1);
+    assert_eq!(debounce.observe(1), 1);
+    assert_eq!(debounce.observe(0), 0);
+}
+
+#[test]
+fn test_pir_debounce_gap_restarts_count() {
+    let mut debounce = PirDebouncer::new(2);
+    assert_eq!(debounce.observe(1), 0);
+    assert_eq!(debounce.observe(0), 0);
+    assert_eq!(debounce.observe(1), 0);
+}
+
+#[test]
+fn test_lone_pir_spike_does_not_reset_sedentary_timer() {
+    let mut debounce = PirDebouncer::new(3);
+    let mut current: Option<ActivityState> = None;
+    let mut timer = 0;
+
+    // Quiet accelerometer throughout; someone walks past on the third sample
+    for pir in [0, 0, 1, 0, 0] {
+        let state = classify_state(
+            debounce.observe(pir),
+            0.005,
+            current,
+            DEFAULTS,
+            DEFAULT_HYSTERESIS,
+        );
+        timer = next_sedentary_timer(timer, state);
+        current = Some(state);
+    }
+
+    assert_eq!(timer, 5);
+}
+
+#[test]
+fn test_sustained_pir_resets_sedentary_timer() {
+    let mut debounce = PirDebouncer::new(3);
+    let mut timer = 10;
+
+    for pir in [1, 1, 1] {
+        let state = classify_state(
+            debounce.observe(pir),
+            0.005,
+            None,
+            DEFAULTS,
+            DEFAULT_HYSTERESIS,
+        );
+        timer = next_sedentary_timer(timer, state);
+    }
+
+    assert_eq!(timer, 0);
+}
+
+// Sedentary Timer Tests
+
+// Timer after feeding time-only readings, all in `state`, through a fresh timer
+fn timer_after(source: TimerSource, state: ActivityState, times: &[&str]) -> u64 {
+    let mut timer = SedentaryTimer::new(source, Duration::from_secs(10));
+    let mut timestamps =
+        TimestampResolver::starting_on(NaiveDate::from_ymd_opt(2026, 1, 6).unwrap());
+    let mut seconds = 0;
+    for ts in times {
+        let reading = at_time(ts);
+        seconds = timer.observe(state, &reading, timestamps.resolve(&reading));
+    }
+    seconds
+}
+
+#[test]
+fn test_timer_ignores_duplicated_seconds() {
+    let times = [
+        "12:00:00", "12:00:00", "12:00:00", "12:00:01", "12:00:01", "12:00:02",
+    ];
+    // First reading counts one second, then two seconds pass
+    assert_eq!(
+        timer_after(TimerSource::Elapsed, ActivityState::Sedentary, &times),
+        3
+    );
+    assert_eq!(
+        timer_after(TimerSource::Ticks, ActivityState::Sedentary, &times),
+        3
+    );
+}
+
+#[test]
+fn test_timer_counts_skipped_seconds() {
+    let times = ["12:00:00", "12:00:02", "12:00:05", "12:00:06"];
+    assert_eq!(
+        timer_after(TimerSource::Elapsed, ActivityState::Sedentary, &times),
+        7
+    );
+    // Counting distinct seconds falls behind real time
+    assert_eq!(
+        timer_after(TimerSource::Ticks, ActivityState::Sedentary, &times),
+        4
+    );
+}
+
+#[test]
+fn test_timer_caps_long_gaps() {
+    let times = ["12:00:00", "12:30:00", "12:30:01"];
+    assert_eq!(
+        timer_after(TimerSource::Elapsed, ActivityState::Sedentary, &times),
+        12
+    );
+}
+
+#[test]
+fn test_timer_accumulates_sub_second_readings() {
+    let mut timer = SedentaryTimer::new(TimerSource::Elapsed, Duration::from_secs(10));
+    let start = Utc.with_ymd_and_hms(2026, 1, 6, 12, 0, 0).unwrap();
+    let reading = at_time("12:00:00");
+    let mut seconds = 0;
+    // Eight readings 250ms apart: one second for the first plus 1.75s
+    for i in 0..8 {
+        let timestamp = start + chrono::Duration::milliseconds(250 * i);
+        seconds = timer.observe(ActivityState::Sedentary, &reading, timestamp);
+    }
+    assert_eq!(seconds, 2);
+}
+
+#[test]
+fn test_timer_ignores_time_going_backwards() {
+    let mut timer = SedentaryTimer::new(TimerSource::Elapsed, Duration::from_secs(10));
+    let reading = at_time("12:00:00");
+    let at = |s| Utc.with_ymd_and_hms(2026, 1, 6, 12, 0, s).unwrap();
+    timer.observe(ActivityState::Sedentary, &reading, at(5));
+    assert_eq!(timer.observe(ActivityState::Sedentary, &reading, at(3)), 1);
+    assert_eq!(timer.observe(ActivityState::Sedentary, &reading, at(4)), 2);
+}
+
+#[test]
+fn test_timer_pauses_on_fidget_and_resets_on_activity() {
+    let mut timer = SedentaryTimer::new(TimerSource::Elapsed, Duration::from_secs(10));
+    let reading = at_time("12:00:00");
+    let at = |s| Utc.with_ymd_and_hms(2026, 1, 6, 12, 0, s).unwrap();
+    timer.observe(ActivityState::Sedentary, &reading, at(0));
+    timer.observe(ActivityState::Sedentary, &reading, at(4));
+    assert_eq!(timer.observe(ActivityState::Fidget, &reading, at(6)), 5);
+    assert_eq!(timer.observe(ActivityState::Sedentary, &reading, at(7)), 6);
+    assert_eq!(timer.observe(ActivityState::Active, &reading, at(8)), 0);
+
+    timer.observe(ActivityState::Sedentary, &reading, at(9));
+    timer.reset();
+    assert_eq!(timer.seconds(), 0);
+}
+
+#[test]
+fn test_parse_timer_source() {
+    assert_eq!(parse_timer_source("elapsed"), Ok(TimerSource::Elapsed));
+    assert_eq!(parse_timer_source(" Ticks "), Ok(TimerSource::Ticks));
+    assert!(parse_timer_source("wallclock").is_err());
+}
+
+// Sensor Fusion Tests
+
+fn confidence(state: ActivityState, pir: i32, acc: f32) -> f32 {
+    classification_confidence(state, pir, acc, DEFAULTS, FusionWeights::default())
+}
+
+#[test]
+fn test_pir_only_active_is_low_confidence() {
+    // Someone walking past: the PIR fires, the accelerometer is flat
+    assert_eq!(confidence(ActivityState::Active, 1, 0.0), 0.4);
+    // Both sensors agree
+    assert_eq!(confidence(ActivityState::Active, 1, 0.05), 1.0);
+    // Acceleration alone outweighs the PIR alone
+    assert_eq!(confidence(ActivityState::Active, 0, 0.05), 0.6);
+    // Some movement backs the PIR up partially
+    assert_eq!(confidence(ActivityState::Active, 1, 0.02), 0.7);
+}
+
+#[test]
+fn test_quiet_states_confidence() {
+    assert_eq!(confidence(ActivityState::Sedentary, 0, 0.0), 1.0);
+    assert_eq!(confidence(ActivityState::Sedentary, 0, 0.01), 0.7);
+    assert_eq!(confidence(ActivityState::Fidget, 0, 0.03), 1.0);
+    // Held in FIDGET by hysteresis just below the threshold
+    assert_eq!(confidence(ActivityState::Fidget, 0, 0.018), 0.94);
+}
+
+#[test]
+fn test_fusion_weights_are_configurable() {
+    let trust_pir = FusionWeights {
+        pir: 1.0,
+        acc: 0.0,
+        min_reset_confidence: 0.5,
+    };
+    let score = classification_confidence(ActivityState::Active, 1, 0.0, DEFAULTS, trust_pir);
+    assert_eq!(score, 1.0);
+    assert_eq!(
+        timer_state(ActivityState::Active, score, trust_pir),
+        ActivityState::Active
+    );
+}
+
+#[test]
+fn test_low_confidence_active_pauses_instead_of_resetting() {
+    let weights = FusionWeights::default();
+    assert_eq!(
+        timer_state(ActivityState::Active, 0.4, weights),
+        ActivityState::Fidget
+    );
+    assert_eq!(
+        timer_state(ActivityState::Active, 0.6, weights),
+        ActivityState::Active
+    );
+    assert_eq!(
+        timer_state(ActivityState::Sedentary, 0.1, weights),
+        ActivityState::Sedentary
+    );
+
+    let mut timer = SedentaryTimer::new(TimerSource::Elapsed, Duration::from_secs(10));
+    let reading = at_time("12:00:00");
+    let at = |s| Utc.with_ymd_and_hms(2026, 1, 6, 12, 0, s).unwrap();
+    timer.observe(ActivityState::Sedentary, &reading, at(0));
+    timer.observe(ActivityState::Sedentary, &reading, at(3));
+    let passer_by = timer_state(
+        ActivityState::Active,
+        confidence(ActivityState::Active, 1, 0.0),
+        weights,
+    );
+    assert_eq!(timer.observe(passer_by, &reading, at(4)), 4);
+    let walking = timer_state(
+        ActivityState::Active,
+        confidence(ActivityState::Active, 1, 0.06),
+        weights,
+    );
+    assert_eq!(timer.observe(walking, &reading, at(5)), 0);
+}
+
+// Timer Reset Tests
+
+fn reset_by(user_id: Uuid, admin: bool) -> TimerReset {
+    TimerReset { user_id, admin }
+}
+
+#[test]
+fn test_reset_requested_for_bound_user_only() {
+    let (tx, mut rx) = broadcast::channel(4);
+    let owner = Uuid::new_v4();
+
+    tx.send(reset_by(Uuid::new_v4(), false)).unwrap();
+    // Not even an admin resets someone else's device
+    tx.send(reset_by(Uuid::new_v4(), true)).unwrap();
+    assert!(!reset_requested(&mut rx, Some(owner)));
+
+    tx.send(reset_by(owner, false)).unwrap();
+    assert!(reset_requested(&mut rx, Some(owner)));
+    assert!(!reset_requested(&mut rx, Some(owner)));
+}
+
+#[test]
+fn test_reset_requested_unbound_device_needs_admin() {
+    let (tx, mut rx) = broadcast::channel(4);
+    tx.send(reset_by(Uuid::new_v4(), false)).unwrap();
+    assert!(!reset_requested(&mut rx, None));
+
+    tx.send(reset_by(Uuid::new_v4(), true)).unwrap();
+    assert!(reset_requested(&mut rx, None));
+}
+
+// Alert Cooldown Tests
+
+// Alerts for a run of timer values against a 1200s limit
+fn alerts_for(cooldown: u64, timers: impl IntoIterator<Item = u64>) -> Vec<u64> {
+    let mut alerts = AlertCooldown::new(cooldown);
+    timers
+        .into_iter()
+        .filter(|&timer| alerts.observe(timer, timer >= 1200))
+        .collect()
+}
+
+#[test]
+fn test_alert_fires_once_on_crossing() {
+    assert_eq!(alerts_for(300, 1190..1400), vec![1200]);
+}
+
+#[test]
+fn test_alert_repeats_after_cooldown() {
+    assert_eq!(alerts_for(300, 1190..1900), vec![1200, 1500, 1800]);
+}
+
+#[test]
+fn test_several_readings_per_second_alert_once() {
+    let timers = (1199..1210).flat_map(|t| [t, t, t, t]);
+    assert_eq!(alerts_for(300, timers), vec![1200]);
+}
+
+#[test]
+fn test_fidget_pause_does_not_refire() {
+    // Timer holds while fidgeting; still the same sitting
+    let timers = (1195..1205).chain([1204; 50]).chain(1205..1210);
+    assert_eq!(alerts_for(300, timers), vec![1200]);
+}
+
+#[test]
+fn test_timer_reset_rearms_alert() {
+    let timers = (1199..1205).chain(0..3).chain(1200..1203);
+    assert_eq!(alerts_for(300, timers), vec![1200, 1200]);
+}
+
+#[test]
+fn test_zero_cooldown_alerts_every_reading() {
+    assert_eq!(alerts_for(0, 1199..1203), vec![1200, 1201, 1202]);
+}
@@ -1,15 +1,24 @@
-use crate::state::AppState;
+use crate::{
+    auth::{generate_token, hash_password, AdminUser},
+    state::AppState,
+};
 use axum::{
-    extract::{Form, State},
+    extract::{Form, Query, State},
     http::StatusCode,
-    response::{IntoResponse, Redirect},
+    response::{IntoResponse, Json, Redirect, Response},
 };
-use serde::Deserialize;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::Acquire;
+use uuid::Uuid;
 
-use argon2::{
-    password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
-    Argon2,
-};
+// Verification tokens are valid for 24 hours
+const VERIFICATION_TOKEN_TTL_SECONDS: u64 = 86400;
+
+fn verify_key(token: &str) -> String {
+    format!("verify:{}", token)
+}
 
 #[derive(Deserialize)]
 pub struct SignUpForm {
@@ -18,6 +27,66 @@ pub struct SignUpForm {
     pub password: String,
 }
 
+#[derive(Deserialize)]
+pub struct VerifyQuery {
+    pub token: String,
+}
+
+/// Checks password strength, returning every rule the password fails.
+/// Length is counted in characters, not bytes, so unicode passwords aren't penalised.
+pub fn validate_password(password: &str, min_length: usize) -> Result<(), Vec<String>> {
+    let mut failed = Vec::new();
+
+    if password.chars().count() < min_length {
+        failed.push(format!("at least {} characters", min_length));
+    }
+    if !password.chars().any(char::is_alphabetic) {
+        failed.push("at least one letter".to_string());
+    }
+    if !password.chars().any(|c| c.is_ascii_digit()) {
+        failed.push("at least one digit".to_string());
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(failed)
+    }
+}
+
+/// 400 response listing the password rules that failed
+pub fn weak_password_response(failed: &[String]) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        format!("Password does not meet requirements: {}", failed.join(", ")),
+    )
+        .into_response()
+}
+
+// Postgres SQLSTATE for unique_violation
+const UNIQUE_VIOLATION: &str = "23505";
+
+fn is_unique_violation(err: &sqlx::Error) -> bool {
+    err.as_database_error()
+        .and_then(|e| e.code())
+        .is_some_and(|code| code == UNIQUE_VIOLATION)
+}
+
+/// Maps an insert failure to a response: 409 for a taken email, 500 otherwise
+fn signup_error_response(err: &sqlx::Error) -> Response {
+    if is_unique_violation(err) {
+        (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "error": "email_taken"
+            })),
+        )
+            .into_response()
+    } else {
+        (StatusCode::INTERNAL_SERVER_ERROR, "Could not sign up").into_response()
+    }
+}
+
 pub async fn show_signup_form() -> Redirect {
     Redirect::permanent("/signup.html")
 }
@@ -25,22 +94,47 @@ pub async fn show_signup_form() -> Redirect {
 pub async fn signup_handler(
     State(state): State<AppState>,
     Form(form): Form<SignUpForm>,
-) -> impl IntoResponse {
+) -> Response {
+    // Reject weak passwords before spending Argon2 cycles or touching the DB
+    if let Err(failed) = validate_password(&form.password, state.config.auth.password_min_length) {
+        return weak_password_response(&failed);
+    }
+
     // Hash password
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let password_hash = match argon2.hash_password(form.password.as_bytes(), &salt) {
-        Ok(hash) => hash.to_string(),
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to hash password"),
+    let password_hash = match hash_password(&form.password, &state.config.auth) {
+        Ok(hash) => hash,
+        Err(_) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to hash password").into_response()
+        }
     };
 
-    // Insert user
+    // Store the verification token first so a created account always has one
+    let user_id = Uuid::new_v4();
+    let verification_token = generate_token();
+    let stored: redis::RedisResult<()> = match state.redis.get_multiplexed_async_connection().await
+    {
+        Ok(mut con) => {
+            con.set_ex(
+                verify_key(&verification_token),
+                user_id.to_string(),
+                VERIFICATION_TOKEN_TTL_SECONDS,
+            )
+            .await
+        }
+        Err(e) => Err(e),
+    };
+    if let Err(e) = stored {
+        eprintln!("Failed to store verification token: {e:?}");
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Could not sign up").into_response();
+    }
+
+    // Insert user (unverified until the token is redeemed)
     let result = sqlx::query!(
         r#"
-        INSERT INTO users (user_id, email, name, password_hash, created_at)
-        VALUES ($1, $2, $3, $4, $5)
+        INSERT INTO users (user_id, email, name, password_hash, role, verified, created_at)
+        VALUES ($1, $2, $3, $4, 'user', FALSE, $5)
         "#,
-        uuid::Uuid::new_v4(),
+        user_id,
         form.email,
         form.name,
         password_hash,
@@ -50,10 +144,198 @@ pub async fn signup_handler(
     .await;
 
     match result {
-        Ok(_) => (StatusCode::OK, "Welcome! You can now log in."),
+        // Email delivery is out of scope; the token is returned to the caller instead
+        Ok(_) => (
+            StatusCode::OK,
+            Json(json!({
+                "message": "Welcome! Please verify your email before logging in.",
+                "verification_token": verification_token
+            })),
+        )
+            .into_response(),
         Err(e) => {
-            eprintln!("Failed to insert user: {e:?}");
-            (StatusCode::INTERNAL_SERVER_ERROR, "Could not sign up")
+            if !is_unique_violation(&e) {
+                eprintln!("Failed to insert user: {e:?}");
+            }
+            signup_error_response(&e)
         }
     }
 }
+
+/// Redeems a verification token and marks the account as verified
+/// Endpoint: GET /auth/verify?token=...
+pub async fn verify_email_handler(
+    State(state): State<AppState>,
+    Query(query): Query<VerifyQuery>,
+) -> Response {
+    let mut redis_conn = match state.redis.get_multiplexed_async_connection().await {
+        Ok(conn) => conn,
+        Err(_) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Service unavailable").into_response();
+        }
+    };
+
+    let stored: Option<String> = redis_conn
+        .get_del(verify_key(&query.token))
+        .await
+        .unwrap_or(None);
+
+    let user_id = match stored.and_then(|id| Uuid::parse_str(&id).ok()) {
+        Some(id) => id,
+        None => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "Invalid or expired verification token.",
+            )
+                .into_response();
+        }
+    };
+
+    let result = sqlx::query!(
+        r#"UPDATE users SET verified = TRUE WHERE user_id = $1"#,
+        user_id
+    )
+    .execute(&state.db)
+    .await;
+
+    match result {
+        Ok(_) => (StatusCode::OK, "Email verified. You can now log in.").into_response(),
+        Err(e) => {
+            eprintln!("Failed to verify user: {e:?}");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Could not verify email").into_response()
+        }
+    }
+}
+
+// Most accounts one seeding request may create
+pub const MAX_SEED_USERS: usize = 100;
+
+/// One requested account and what became of it; exactly one of `user_id`
+/// (created) and `error` (skipped) is set
+#[derive(Debug, Serialize, PartialEq)]
+pub struct SeedResult {
+    pub email: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl SeedResult {
+    fn created(email: String, user_id: Uuid) -> Self {
+        Self {
+            email,
+            user_id: Some(user_id),
+            error: None,
+        }
+    }
+
+    fn skipped(email: String, error: impl Into<String>) -> Self {
+        Self {
+            email,
+            user_id: None,
+            error: Some(error.into()),
+        }
+    }
+}
+
+/// Creates demo/test accounts in one go, already verified (no token round trip).
+/// Accounts are inserted in one transaction; a weak password or taken email
+/// only skips that row, while a database failure rolls the whole batch back.
+/// Endpoint: POST /api/admin/seed-users (admin only, and only with SEEDING_ENABLED=true)
+pub async fn seed_users(
+    _admin: AdminUser,
+    State(state): State<AppState>,
+    Json(users): Json<Vec<SignUpForm>>,
+) -> Response {
+    if !state.config.auth.seeding_enabled {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "User seeding is disabled (SEEDING_ENABLED)"})),
+        )
+            .into_response();
+    }
+    if users.len() > MAX_SEED_USERS {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": format!("At most {} users per request", MAX_SEED_USERS)
+            })),
+        )
+            .into_response();
+    }
+
+    match insert_seed_users(&state, users).await {
+        Ok(results) => {
+            let created = results.iter().filter(|r| r.user_id.is_some()).count();
+            Json(json!({"created": created, "results": results})).into_response()
+        }
+        Err(e) => {
+            eprintln!("Failed to seed users: {e:?}");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": "Could not seed users"})),
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn insert_seed_users(
+    state: &AppState,
+    users: Vec<SignUpForm>,
+) -> Result<Vec<SeedResult>, sqlx::Error> {
+    let min_length = state.config.auth.password_min_length;
+    let mut results = Vec::with_capacity(users.len());
+    let mut tx = state.db.begin().await?;
+
+    for user in users {
+        if let Err(failed) = validate_password(&user.password, min_length) {
+            results.push(SeedResult::skipped(
+                user.email,
+                format!("weak_password: {}", failed.join(", ")),
+            ));
+            continue;
+        }
+        let Ok(password_hash) = hash_password(&user.password, &state.config.auth) else {
+            results.push(SeedResult::skipped(user.email, "hash_failed"));
+            continue;
+        };
+
+        // A savepoint per row, so a taken email doesn't abort the transaction
+        let user_id = Uuid::new_v4();
+        let mut row = (&mut *tx).begin().await?;
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO users (user_id, email, name, password_hash, role, verified, created_at)
+            VALUES ($1, $2, $3, $4, 'user', TRUE, $5)
+            "#,
+            user_id,
+            user.email,
+            user.name,
+            password_hash,
+            chrono::Utc::now()
+        )
+        .execute(&mut *row)
+        .await;
+
+        match inserted {
+            Ok(_) => {
+                row.commit().await?;
+                results.push(SeedResult::created(user.email, user_id));
+            }
+            Err(e) if is_unique_violation(&e) => {
+                row.rollback().await?;
+                results.push(SeedResult::skipped(user.email, "email_taken"));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    tx.commit().await?;
+    Ok(results)
+}
+
+#[cfg(test)]
+#[path = "signup_tests.rs"]
+mod tests;
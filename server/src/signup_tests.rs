@@ -0,0 +1,169 @@
+use super::*;
+use std::borrow::Cow;
+use std::fmt;
+
+// Minimal database error carrying only a SQLSTATE code
+#[derive(Debug)]
+struct MockDbError {
+    code: &'static str,
+}
+
+impl fmt::Display for MockDbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "mock database error {}", self.code)
+    }
+}
+
+impl std::error::Error for MockDbError {}
+
+impl sqlx::error::DatabaseError for MockDbError {
+    fn message(&self) -> &str {
+        "mock database error"
+    }
+
+    fn code(&self) -> Option<Cow<'_, str>> {
+        Some(Cow::Borrowed(self.code))
+    }
+
+    fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+
+    fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+
+    fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+        self
+    }
+
+    fn kind(&self) -> sqlx::error::ErrorKind {
+        sqlx::error::ErrorKind::Other
+    }
+}
+
+// Password Length Tests
+
+#[test]
+fn test_password_exactly_min_length_passes() {
+    assert!(validate_password("abcdefg1", 8).is_ok());
+}
+
+#[test]
+fn test_password_one_below_min_length_fails() {
+    let failed = validate_password("abcdef1", 8).unwrap_err();
+    assert_eq!(failed, vec!["at least 8 characters".to_string()]);
+}
+
+#[test]
+fn test_password_empty_fails_every_rule() {
+    let failed = validate_password("", 8).unwrap_err();
+    assert_eq!(failed.len(), 3);
+}
+
+#[test]
+fn test_password_respects_configured_min_length() {
+    assert!(validate_password("abc1", 4).is_ok());
+    assert!(validate_password("abcdefghijk1", 16).is_err());
+}
+
+// Character Class Tests
+
+#[test]
+fn test_password_all_digits_fails() {
+    let failed = validate_password("1234567890", 8).unwrap_err();
+    assert_eq!(failed, vec!["at least one letter".to_string()]);
+}
+
+#[test]
+fn test_password_all_letters_fails() {
+    let failed = validate_password("abcdefghij", 8).unwrap_err();
+    assert_eq!(failed, vec!["at least one digit".to_string()]);
+}
+
+#[test]
+fn test_password_lists_all_failed_rules() {
+    let failed = validate_password("123", 8).unwrap_err();
+    assert_eq!(
+        failed,
+        vec![
+            "at least 8 characters".to_string(),
+            "at least one letter".to_string()
+        ]
+    );
+}
+
+// Unicode Tests
+
+#[test]
+fn test_password_unicode_letters_count_as_letters() {
+    assert!(validate_password("пароль12", 8).is_ok());
+    assert!(validate_password("密码密码密码密1", 8).is_ok());
+}
+
+#[test]
+fn test_password_length_counts_characters_not_bytes() {
+    // 7 characters but 13 bytes: still too short
+    assert!(validate_password("éééééé1", 8).is_err());
+    // 8 characters: long enough
+    assert!(validate_password("ééééééé1", 8).is_ok());
+}
+
+#[test]
+fn test_password_non_ascii_digits_are_not_digits() {
+    let failed = validate_password("abcdefg١", 8).unwrap_err();
+    assert_eq!(failed, vec!["at least one digit".to_string()]);
+}
+
+// Duplicate Email Tests
+
+#[tokio::test]
+async fn test_duplicate_email_returns_409_json() {
+    let err = sqlx::Error::Database(Box::new(MockDbError { code: "23505" }));
+    let response = signup_error_response(&err);
+
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(&body[..], br#"{"error":"email_taken"}"#);
+}
+
+#[test]
+fn test_other_database_error_returns_500() {
+    // not_null_violation
+    let err = sqlx::Error::Database(Box::new(MockDbError { code: "23502" }));
+    assert_eq!(
+        signup_error_response(&err).status(),
+        StatusCode::INTERNAL_SERVER_ERROR
+    );
+}
+
+#[test]
+fn test_non_database_error_returns_500() {
+    let err = sqlx::Error::RowNotFound;
+    assert!(!is_unique_violation(&err));
+    assert_eq!(
+        signup_error_response(&err).status(),
+        StatusCode::INTERNAL_SERVER_ERROR
+    );
+}
+
+// Seed Result Tests
+
+#[test]
+fn test_seed_result_serializes_only_its_outcome() {
+    let id = Uuid::new_v4();
+    let created = serde_json::to_value(SeedResult::created("a@example.com".into(), id)).unwrap();
+    assert_eq!(
+        created,
+        json!({"email": "a@example.com", "user_id": id.to_string()})
+    );
+
+    let skipped =
+        serde_json::to_value(SeedResult::skipped("a@example.com".into(), "email_taken")).unwrap();
+    assert_eq!(
+        skipped,
+        json!({"email": "a@example.com", "error": "email_taken"})
+    );
+}
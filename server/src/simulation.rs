@@ -0,0 +1,156 @@
+use crate::models::{ActivityState, RawReading};
+use crate::serial::{DeviceStream, SerialPipeline, Thresholds};
+use crate::synthetic::acceleration_for;
+use chrono::{DateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::ops::RangeInclusive;
+use std::thread;
+
+/// Stream name of the simulated device: its Redis list is `sensor_history:simulator`
+/// and `DEVICE_USER_MAP=simulator=<uuid>` assigns its readings to a user
+pub const SIMULATOR_DEVICE: &str = "simulator";
+
+/// Office-day default for SIMULATION_PROFILE
+pub const DEFAULT_SIMULATION_PROFILE: &str =
+    "00:00=sedentary,07:00=active,08:30=sedentary,12:00=active,13:00=sedentary,17:30=active,19:00=sedentary";
+
+// Ticks a simulated spell lasts before the next state is picked
+const PHASE_TICKS: RangeInclusive<u32> = 30..=300;
+// Chance that a new spell follows the profile rather than a brief deviation
+const PROFILE_SHARE: f64 = 0.75;
+// Chance of a PIR trigger on each tick of an active spell
+const ACTIVE_PIR_CHANCE: f64 = 0.5;
+
+/// The state a simulated person is mostly in at each local time of day.
+/// Each entry holds from its time until the next; before the first entry the
+/// last one carries over from the previous day.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActivityProfile(Vec<(NaiveTime, ActivityState)>);
+
+impl ActivityProfile {
+    pub fn state_at(&self, time: NaiveTime) -> ActivityState {
+        self.0
+            .iter()
+            .rev()
+            .find(|(start, _)| *start <= time)
+            .or(self.0.last())
+            .map_or(ActivityState::Sedentary, |&(_, state)| state)
+    }
+}
+
+/// Parses `SIMULATION_PROFILE`: comma-separated `HH:MM=state` entries with
+/// state sedentary, fidget or active, e.g. "09:00=sedentary,12:00=active"
+pub fn parse_activity_profile(raw: &str) -> Result<ActivityProfile, String> {
+    let mut entries = Vec::new();
+    for entry in raw.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (time, state) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("expected HH:MM=state, got '{}'", entry))?;
+        let time = NaiveTime::parse_from_str(time.trim(), "%H:%M")
+            .map_err(|_| format!("invalid time in '{}'", entry))?;
+        let state = state
+            .trim()
+            .to_ascii_uppercase()
+            .parse::<ActivityState>()
+            .map_err(|_| format!("unknown state in '{}'", entry))?;
+        if entries.iter().any(|&(t, _)| t == time) {
+            return Err(format!("{} is listed twice", time.format("%H:%M")));
+        }
+        entries.push((time, state));
+    }
+    if entries.is_empty() {
+        return Err("expected at least one HH:MM=state entry".to_string());
+    }
+    entries.sort_by_key(|&(time, _)| time);
+    Ok(ActivityProfile(entries))
+}
+
+/// Raw readings as an Arduino worn through the profile's day would send them.
+///
+/// Readings come in spells of a few minutes' worth of ticks. Most spells are
+/// the profile's state for the time of day; the rest are brief deviations
+/// (fidgeting while seated, a pause while active) so the stream isn't flat.
+pub struct SimulatedDevice<R: Rng> {
+    rng: R,
+    profile: ActivityProfile,
+    phase: ActivityState,
+    remaining: u32,
+}
+
+impl<R: Rng> SimulatedDevice<R> {
+    pub fn new(rng: R, profile: ActivityProfile) -> Self {
+        Self {
+            rng,
+            profile,
+            phase: ActivityState::Sedentary,
+            remaining: 0,
+        }
+    }
+
+    fn next_phase(&mut self, planned: ActivityState) -> ActivityState {
+        if self.rng.gen_bool(PROFILE_SHARE) {
+            return planned;
+        }
+        match planned {
+            ActivityState::Fidget => ActivityState::Sedentary,
+            _ => ActivityState::Fidget,
+        }
+    }
+
+    /// The reading sent at `now`, with acceleration inside the phase's band of `thresholds`
+    pub fn next_reading(
+        &mut self,
+        now: DateTime<Utc>,
+        tz: Tz,
+        thresholds: Thresholds,
+    ) -> RawReading {
+        let local_time = now.with_timezone(&tz).time();
+        if self.remaining == 0 {
+            self.phase = self.next_phase(self.profile.state_at(local_time));
+            self.remaining = self.rng.gen_range(PHASE_TICKS);
+        }
+        self.remaining -= 1;
+
+        let pir = self.phase == ActivityState::Active && self.rng.gen_bool(ACTIVE_PIR_CHANCE);
+        RawReading {
+            ts: local_time.format("%H:%M:%S").to_string(),
+            pir: i32::from(pir),
+            acc: acceleration_for(&mut self.rng, self.phase, thresholds),
+            datetime: Some(now),
+        }
+    }
+}
+
+/// Stands in for the serial listeners when SIMULATION_MODE is on: one reading
+/// every SIMULATION_TICK_MS goes through the same `DeviceStream` as hardware
+/// data, so classification, history, broadcasts and the DB worker all run.
+pub fn spawn_simulator(pipeline: SerialPipeline) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let config = pipeline.config.clone();
+        let thresholds = pipeline.thresholds.clone();
+        let settings = &config.simulation;
+        let mut stream = DeviceStream::open(pipeline, SIMULATOR_DEVICE);
+        let mut device = SimulatedDevice::new(StdRng::from_entropy(), settings.profile.clone());
+        println!(
+            "Simulation mode: generating a reading every {}ms",
+            settings.tick.as_millis()
+        );
+
+        loop {
+            let reading = device.next_reading(Utc::now(), config.timezone, thresholds.current());
+            stream.process(&reading);
+            if !stream.pause(settings.tick) {
+                break;
+            }
+        }
+
+        stream.close();
+        println!("Simulator stopped");
+    })
+}
+
+#[cfg(test)]
+#[path = "simulation_tests.rs"]
+mod tests;
@@ -0,0 +1,105 @@
+use super::*;
+use crate::serial::{classify_state, DEFAULT_HYSTERESIS};
+use chrono::{Duration, TimeZone};
+
+const THRESHOLDS: Thresholds = Thresholds {
+    fidget: 0.02,
+    active: 0.04,
+};
+
+fn time(hhmm: &str) -> NaiveTime {
+    NaiveTime::parse_from_str(hhmm, "%H:%M").unwrap()
+}
+
+// Profile Parsing Tests
+
+#[test]
+fn test_default_profile_parses() {
+    let profile = parse_activity_profile(DEFAULT_SIMULATION_PROFILE).unwrap();
+    assert_eq!(profile.state_at(time("03:00")), ActivityState::Sedentary);
+    assert_eq!(profile.state_at(time("07:30")), ActivityState::Active);
+    assert_eq!(profile.state_at(time("10:00")), ActivityState::Sedentary);
+    assert_eq!(profile.state_at(time("12:30")), ActivityState::Active);
+}
+
+#[test]
+fn test_profile_entries_are_sorted_and_wrap_past_midnight() {
+    let profile = parse_activity_profile("18:00=fidget, 09:00=active").unwrap();
+    assert_eq!(profile.state_at(time("09:00")), ActivityState::Active);
+    assert_eq!(profile.state_at(time("20:00")), ActivityState::Fidget);
+    // Before the first entry the evening's state carries over
+    assert_eq!(profile.state_at(time("06:00")), ActivityState::Fidget);
+}
+
+#[test]
+fn test_invalid_profiles_rejected() {
+    assert!(parse_activity_profile("").is_err());
+    assert!(parse_activity_profile("09:00").is_err());
+    assert!(parse_activity_profile("25:00=active").is_err());
+    assert!(parse_activity_profile("09:00=walking").is_err());
+    assert!(parse_activity_profile("09:00=active,09:00=sedentary").is_err());
+}
+
+// Simulated Device Tests
+
+fn simulate(profile: &str, start: DateTime<Utc>, count: i64) -> Vec<RawReading> {
+    let mut device = SimulatedDevice::new(
+        StdRng::seed_from_u64(3),
+        parse_activity_profile(profile).unwrap(),
+    );
+    (0..count)
+        .map(|i| device.next_reading(start + Duration::seconds(i), Tz::UTC, THRESHOLDS))
+        .collect()
+}
+
+fn classified(reading: &RawReading) -> ActivityState {
+    classify_state(
+        reading.pir,
+        reading.acc,
+        None,
+        THRESHOLDS,
+        DEFAULT_HYSTERESIS,
+    )
+}
+
+#[test]
+fn test_readings_follow_the_profile() {
+    let start = Utc.with_ymd_and_hms(2026, 1, 6, 9, 0, 0).unwrap();
+    for (profile, expected) in [
+        ("00:00=sedentary", ActivityState::Sedentary),
+        ("00:00=active", ActivityState::Active),
+    ] {
+        let readings = simulate(profile, start, 5_000);
+        let matching = readings
+            .iter()
+            .filter(|r| classified(r) == expected)
+            .count();
+        assert!(
+            matching > readings.len() / 2,
+            "only {} of {} readings {}",
+            matching,
+            readings.len(),
+            expected
+        );
+    }
+}
+
+#[test]
+fn test_readings_are_valid_hardware_input() {
+    let start = Utc.with_ymd_and_hms(2026, 1, 6, 7, 0, 0).unwrap();
+    for reading in simulate(DEFAULT_SIMULATION_PROFILE, start, 5_000) {
+        assert_eq!(reading.validate(16.0), Ok(()));
+    }
+}
+
+#[test]
+fn test_readings_carry_local_time_and_full_timestamp() {
+    let start = Utc.with_ymd_and_hms(2026, 1, 6, 9, 15, 30).unwrap();
+    let mut device = SimulatedDevice::new(
+        StdRng::seed_from_u64(1),
+        parse_activity_profile(DEFAULT_SIMULATION_PROFILE).unwrap(),
+    );
+    let reading = device.next_reading(start, chrono_tz::Europe::Berlin, THRESHOLDS);
+    assert_eq!(reading.ts, "10:15:30");
+    assert_eq!(reading.datetime, Some(start));
+}
@@ -1,75 +1,258 @@
-use crate::state::AppState;
+use crate::{
+    auth::StreamUser,
+    config::ServerConfig,
+    history::{reading_timestamp, replay_on_connect, HistorySource},
+    metrics::{acquire_stream, ConnectionGuard, StreamKind, Subscriber},
+    models::{visible_to, ActivityState, ProcessedState},
+    state::AppState,
+};
 use axum::{
-    extract::State,
+    extract::{Query, State},
+    http::HeaderMap,
     response::{
         sse::{Event, KeepAlive, Sse},
-        IntoResponse,
+        IntoResponse, Response,
     },
 };
 use futures::Stream;
-use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
 use std::convert::Infallible;
-use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+pub struct StreamQuery {
+    /// Comma-separated states to forward, e.g. `SEDENTARY,ALERT`
+    pub states: Option<String>,
+    /// `minimal` or `full` (the default) reading payloads
+    pub format: Option<String>,
+    /// `false` starts this connection on the live stream without the history
+    /// replay, as SKIP_HISTORY does for every connection
+    pub history: Option<bool>,
+    /// A running replay's id: its history is replayed instead of live `sensor_history`
+    pub replay: Option<Uuid>,
+}
+
+/// Shape of `sensor-data` payloads. Small clients such as an LED display only
+/// need the state, dashboards want the whole `ProcessedState`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EventFormat {
+    #[default]
+    Full,
+    Minimal,
+}
+
+#[derive(Serialize)]
+struct MinimalReading {
+    state: ActivityState,
+}
+
+impl EventFormat {
+    /// Parses `?format=`; unknown values get the full payload
+    pub fn parse(format: Option<&str>) -> Self {
+        match format.map(|f| f.trim().to_ascii_lowercase()).as_deref() {
+            Some("minimal") => EventFormat::Minimal,
+            _ => EventFormat::Full,
+        }
+    }
 
-/// Server-Sent Events handler for real-time sensor data streaming
-pub async fn sse_handler(State(state): State<AppState>) -> impl IntoResponse {
-    let stream = create_sensor_stream(state);
+    /// Projects a broadcast reading into this format. Full payloads, and any
+    /// payload that isn't a `ProcessedState`, are passed through unchanged.
+    pub fn project(self, payload: String) -> String {
+        if self == EventFormat::Full {
+            return payload;
+        }
+        match serde_json::from_str::<ProcessedState>(&payload) {
+            Ok(reading) => serde_json::to_string(&MinimalReading {
+                state: reading.state,
+            })
+            .unwrap_or(payload),
+            Err(_) => payload,
+        }
+    }
+}
+
+/// Parses `?states=` into an upper-cased set; `None` means no filtering.
+/// `ALERT` matches any reading with its alert flag set.
+pub fn parse_state_filter(states: Option<&str>) -> Option<HashSet<String>> {
+    states.map(|list| {
+        list.split(',')
+            .map(|s| s.trim().to_uppercase())
+            .filter(|s| !s.is_empty())
+            .collect()
+    })
+}
+
+/// True if a reading (`state`/`alert`) or state change (`new_state`) passes the filter
+pub fn matches_state_filter(payload: &str, filter: Option<&HashSet<String>>) -> bool {
+    let Some(filter) = filter else {
+        return true;
+    };
+    let Ok(value) = serde_json::from_str::<Value>(payload) else {
+        return false;
+    };
+    let state = value
+        .get("state")
+        .or_else(|| value.get("new_state"))
+        .and_then(Value::as_str);
+    let alert = value.get("alert").and_then(Value::as_bool) == Some(true);
 
-    Sse::new(stream).keep_alive(
-        KeepAlive::new()
-            .interval(Duration::from_secs(15))
-            .text("keepalive"),
-    )
+    state.is_some_and(|s| filter.contains(s)) || (alert && filter.contains("ALERT"))
+}
+
+/// Drops the replay up to and including the reading with `last_id` (its timestamp).
+/// Without an id, or when the id is no longer in the history, everything is kept.
+pub fn history_since(history: Vec<String>, last_id: Option<&str>) -> Vec<String> {
+    let Some(last_id) = last_id else {
+        return history;
+    };
+    match history
+        .iter()
+        .rposition(|m| reading_timestamp(m).as_deref() == Some(last_id))
+    {
+        Some(pos) => history.into_iter().skip(pos + 1).collect(),
+        None => history,
+    }
+}
+
+/// A `sensor-data` event whose id is the reading timestamp, so EventSource
+/// reconnects report where they left off via `Last-Event-ID` (in every format)
+fn sensor_event(msg: String, format: EventFormat) -> Event {
+    let event = Event::default().event("sensor-data");
+    match reading_timestamp(&msg) {
+        Some(ts) => event.id(ts).data(format.project(msg)),
+        None => event.data(format.project(msg)),
+    }
+}
+
+/// Keepalive sent every SSE_KEEPALIVE_SECONDS while no event goes out: a
+/// `: <text>` comment, or with SSE_KEEPALIVE_EVENT a `keepalive` event whose
+/// data is the text, for clients that need to observe the heartbeat
+pub fn keep_alive(server: &ServerConfig) -> KeepAlive {
+    let keep_alive = KeepAlive::new().interval(server.sse_keepalive);
+    if server.sse_keepalive_event {
+        keep_alive.event(
+            Event::default()
+                .event("keepalive")
+                .data(&server.sse_keepalive_text),
+        )
+    } else {
+        keep_alive.text(&server.sse_keepalive_text)
+    }
+}
+
+/// Server-Sent Events handler for real-time sensor data streaming.
+/// Authenticated clients (Authorization header or `?token=`) only receive
+/// readings tagged with their own user id; STREAM_AUTH_REQUIRED makes that mandatory.
+/// With `?states=` only matching events are sent; if nothing matches, the
+/// connection carries keepalives only and is still healthy.
+/// On reconnect, `Last-Event-ID` limits the replay to readings newer than that id.
+/// `?format=minimal` trims readings to `{"state": ...}`; `?history=false`
+/// skips the replay for this connection.
+pub async fn sse_handler(
+    State(state): State<AppState>,
+    Query(query): Query<StreamQuery>,
+    headers: HeaderMap,
+    StreamUser(user): StreamUser,
+) -> Response {
+    let connection = match acquire_stream(&state, StreamKind::Sse) {
+        Ok(connection) => connection,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let filter = parse_state_filter(query.states.as_deref());
+    let format = EventFormat::parse(query.format.as_deref());
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let keep_alive = keep_alive(&state.config.server);
+    let stream = create_sensor_stream(
+        state,
+        connection,
+        user.map(|u| u.user_id),
+        filter,
+        format,
+        (query.history != Some(false)).then(|| HistorySource::from_query(query.replay)),
+        last_event_id,
+    );
+
+    Sse::new(stream).keep_alive(keep_alive).into_response()
 }
 
 /// Creates a stream of sensor data events
 ///
 /// Flow:
-/// 1. Optionally fetch historical data from Redis (disabled with SKIP_HISTORY=true)
-/// 2. Stream live updates from broadcast channel
-fn create_sensor_stream(state: AppState) -> impl Stream<Item = Result<Event, Infallible>> {
+/// 1. Subscribe to the live channels so nothing is missed during the replay
+/// 2. Optionally fetch `history` from Redis (disabled with SKIP_HISTORY=true,
+///    or `history` None for this connection),
+///    followed by the readings buffered meanwhile, minus duplicates; only what
+///    follows `last_event_id` when the client is resuming
+/// 3. Stream live readings ("sensor-data", projected to `format`) and transitions
+///    ("state-change")
+fn create_sensor_stream(
+    state: AppState,
+    connection: ConnectionGuard,
+    subscriber: Option<Uuid>,
+    filter: Option<HashSet<String>>,
+    format: EventFormat,
+    history: Option<HistorySource>,
+    last_event_id: Option<String>,
+) -> impl Stream<Item = Result<Event, Infallible>> {
     async_stream::stream! {
-        // Step 1: Fetch historical data from Redis (skip if SKIP_HISTORY=true)
-        let skip_history = std::env::var("SKIP_HISTORY")
-            .map(|v| v == "true")
-            .unwrap_or(false);
-
-        if !skip_history {
-            if let Ok(mut con) = state.redis.get_multiplexed_async_connection().await {
-                let limit: isize = std::env::var("SENSOR_HISTORY_LIMIT")
-                    .ok()
-                    .and_then(|s| s.parse().ok())
-                    .unwrap_or(500);
-                let history: Vec<String> = con
-                    .lrange("sensor_history", 0, limit - 1)
-                    .await
-                    .unwrap_or_else(|e| {
-                        eprintln!("Redis error fetching history: {:?}", e);
-                        vec![]
-                    });
-
-                // Send history to client (reversed because lpush stores newest first)
-                for msg in history.into_iter().rev() {
-                    yield Ok::<_, Infallible>(
-                        Event::default()
-                            .event("sensor-data")
-                            .data(msg)
-                    );
-                }
-            } else {
-                eprintln!("Failed to connect to Redis for SSE history");
-            }
-        }
+        // Counted until the client disconnects and the stream is dropped
+        let _connection = connection;
+        let metrics = state.metrics.clone();
 
-        // Step 2: Live stream from broadcast channel
+        // Step 1: Subscribe first; the receivers buffer whatever arrives during the replay
         let mut rx = state.tx.subscribe();
+        let mut state_rx = state.state_tx.subscribe();
 
-        while let Ok(msg) = rx.recv().await {
-            yield Ok::<_, Infallible>(
-                Event::default()
-                    .event("sensor-data")
-                    .data(msg)
-            );
+        // Step 2: Replay Redis history plus what arrived meanwhile (skip if SKIP_HISTORY=true)
+        let history = match history {
+            Some(source) => replay_on_connect(&state, &mut rx, source).await,
+            None => Vec::new(),
+        };
+        let replay = history_since(history, last_event_id.as_deref());
+        for msg in replay
+            .into_iter()
+            .filter(|m| visible_to(m, subscriber) && matches_state_filter(m, filter.as_ref()))
+        {
+            yield Ok::<_, Infallible>(sensor_event(msg, format));
+        }
+
+        // Step 3: Live stream from the readings and state-change channels
+        loop {
+            let (is_reading, msg) = tokio::select! {
+                msg = rx.recv() => (true, msg),
+                msg = state_rx.recv() => (false, msg),
+                // Server shutting down: end the stream so the connection can close
+                _ = state.shutdown.cancelled() => break,
+            };
+            let msg = match msg {
+                Ok(msg) => msg,
+                // Slow client: drop what it missed and carry on with the live stream
+                Err(RecvError::Lagged(skipped)) => {
+                    eprintln!("SSE client lagged, skipped {} messages", skipped);
+                    metrics.record_lagged(Subscriber::Sse, skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            if !visible_to(&msg, subscriber) || !matches_state_filter(&msg, filter.as_ref()) {
+                continue;
+            }
+            let event = if is_reading {
+                sensor_event(msg, format)
+            } else {
+                Event::default().event("state-change").data(msg)
+            };
+            yield Ok::<_, Infallible>(event);
         }
     }
 }
+
+#[cfg(test)]
+#[path = "sse_tests.rs"]
+mod tests;
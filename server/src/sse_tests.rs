@@ -0,0 +1,147 @@
+use super::*;
+
+fn reading_at(second: u32) -> String {
+    serde_json::json!({
+        "state": "ACTIVE",
+        "timer": 0,
+        "val": 0.0,
+        "alert": false,
+        "timestamp": format!("2026-01-01T00:00:{second:02}Z")
+    })
+    .to_string()
+}
+
+fn reading(state: &str, alert: bool) -> String {
+    serde_json::json!({
+        "state": state,
+        "timer": 0,
+        "val": 0.0,
+        "alert": alert,
+        "timestamp": "2026-01-01T00:00:00Z"
+    })
+    .to_string()
+}
+
+// State Filter Parsing Tests
+
+#[test]
+fn test_parse_state_filter_absent() {
+    assert!(parse_state_filter(None).is_none());
+}
+
+#[test]
+fn test_parse_state_filter_normalizes() {
+    let filter = parse_state_filter(Some(" sedentary ,ALERT,,")).unwrap();
+    assert_eq!(filter.len(), 2);
+    assert!(filter.contains("SEDENTARY"));
+    assert!(filter.contains("ALERT"));
+}
+
+// State Filter Matching Tests
+
+#[test]
+fn test_no_filter_matches_everything() {
+    assert!(matches_state_filter(&reading("ACTIVE", false), None));
+}
+
+#[test]
+fn test_filter_matches_state() {
+    let filter = parse_state_filter(Some("SEDENTARY"));
+    assert!(matches_state_filter(
+        &reading("SEDENTARY", false),
+        filter.as_ref()
+    ));
+    assert!(!matches_state_filter(
+        &reading("ACTIVE", false),
+        filter.as_ref()
+    ));
+}
+
+#[test]
+fn test_filter_matches_alert_flag() {
+    let filter = parse_state_filter(Some("ALERT"));
+    assert!(matches_state_filter(
+        &reading("SEDENTARY", true),
+        filter.as_ref()
+    ));
+    assert!(!matches_state_filter(
+        &reading("SEDENTARY", false),
+        filter.as_ref()
+    ));
+}
+
+#[test]
+fn test_filter_matches_state_change_new_state() {
+    let filter = parse_state_filter(Some("SEDENTARY"));
+    let change = r#"{"old_state":"ACTIVE","new_state":"SEDENTARY","duration_seconds":5,"timestamp":"2026-01-01T00:00:00Z"}"#;
+    assert!(matches_state_filter(change, filter.as_ref()));
+}
+
+#[test]
+fn test_empty_filter_matches_nothing() {
+    let filter = parse_state_filter(Some(""));
+    assert!(!matches_state_filter(
+        &reading("ACTIVE", true),
+        filter.as_ref()
+    ));
+}
+
+// Last-Event-ID Resume Tests
+
+#[test]
+fn test_history_since_resumes_after_id() {
+    let history: Vec<String> = (1..=5).map(reading_at).collect();
+    let resumed = history_since(history, Some("2026-01-01T00:00:03Z"));
+    assert_eq!(resumed, vec![reading_at(4), reading_at(5)]);
+}
+
+#[test]
+fn test_history_since_without_id_keeps_all() {
+    let history: Vec<String> = (1..=3).map(reading_at).collect();
+    assert_eq!(history_since(history.clone(), None), history);
+}
+
+#[test]
+fn test_history_since_unknown_id_falls_back_to_full_history() {
+    let history: Vec<String> = (1..=3).map(reading_at).collect();
+    assert_eq!(
+        history_since(history.clone(), Some("2025-12-31T23:00:00Z")),
+        history
+    );
+}
+
+#[test]
+fn test_history_since_latest_id_replays_nothing() {
+    let history: Vec<String> = (1..=3).map(reading_at).collect();
+    assert!(history_since(history, Some("2026-01-01T00:00:03Z")).is_empty());
+}
+
+// Payload Format Tests
+
+#[test]
+fn test_parse_event_format() {
+    assert_eq!(EventFormat::parse(None), EventFormat::Full);
+    assert_eq!(EventFormat::parse(Some("full")), EventFormat::Full);
+    assert_eq!(EventFormat::parse(Some("Minimal")), EventFormat::Minimal);
+    // Unknown formats pass payloads through
+    assert_eq!(EventFormat::parse(Some("compact")), EventFormat::Full);
+}
+
+#[test]
+fn test_minimal_format_keeps_only_state() {
+    let projected = EventFormat::Minimal.project(reading("FIDGET", true));
+    assert_eq!(projected, r#"{"state":"FIDGET"}"#);
+}
+
+#[test]
+fn test_full_format_passes_through() {
+    let payload = reading("ACTIVE", false);
+    assert_eq!(EventFormat::Full.project(payload.clone()), payload);
+}
+
+#[test]
+fn test_minimal_format_passes_through_other_payloads() {
+    let payload = r#"{"old_state":"ACTIVE","new_state":"SEDENTARY"}"#.to_string();
+    assert_eq!(EventFormat::Minimal.project(payload.clone()), payload);
+    assert_eq!(EventFormat::Minimal.project("oops".to_string()), "oops");
+}
@@ -1,11 +1,78 @@
+use crate::auth::dummy_password_hash;
+use crate::config::Config;
+use crate::fallback::FallbackState;
+use crate::metrics::Metrics;
+use crate::password_reset::{reset_mailer, ResetMailer};
+use crate::replay::ReplayRegistry;
+use crate::retention::RetentionStatus;
+use crate::serial::{SerialMetrics, SharedThresholds, Thresholds, TimerReset};
 use sqlx::PgPool;
+use std::sync::Arc;
 use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
 
 #[derive(Clone)]
 pub struct AppState {
+    // Settings validated at startup
+    pub config: Arc<Config>,
     pub db: PgPool,
     // The "Hub" that broadcasts JSON strings to everyone (WebSocket + DB Worker)
     pub tx: broadcast::Sender<String>,
+    // State transitions only, streamed as SSE "state-change" events
+    pub state_tx: broadcast::Sender<String>,
+    // Sedentary timer resets requested by a user (WebSocket reset_timer command)
+    pub timer_reset_tx: broadcast::Sender<TimerReset>,
     // Redis client for caching and pub/sub
     pub redis: redis::Client,
+    // Line counters updated by the serial listeners
+    pub serial_metrics: Arc<SerialMetrics>,
+    // Prometheus counters/gauges shared with background tasks
+    pub metrics: Arc<Metrics>,
+    // Hardware-gap tracking (exposed as the fallback-active gauge)
+    pub fallback_state: Arc<FallbackState>,
+    // Live classification thresholds (calibration / admin updates apply immediately)
+    pub thresholds: SharedThresholds,
+    // Running log replays, paused/resumed/stopped via /api/replay/:id/*
+    pub replays: ReplayRegistry,
+    // Cancelled on shutdown so open SSE/WebSocket streams end
+    pub shutdown: CancellationToken,
+    // Last data-retention prune, shown by /api/retention/status
+    pub retention: Arc<RetentionStatus>,
+    // Verified in place of a password hash when a login email is unknown
+    pub dummy_hash: Arc<str>,
+    // Delivers password reset tokens (PASSWORD_RESET_LOG_TOKENS picks the dev logger)
+    pub reset_mailer: Arc<dyn ResetMailer>,
+}
+
+impl AppState {
+    /// Fresh channels, counters and registries around the given connections.
+    /// `thresholds` seeds the live classification thresholds.
+    pub fn new(
+        config: Arc<Config>,
+        db: PgPool,
+        redis: redis::Client,
+        thresholds: Thresholds,
+    ) -> Self {
+        let capacity = config.server.broadcast_capacity;
+        let dummy_hash = dummy_password_hash(&config.auth).into();
+        let reset_mailer = reset_mailer(config.auth.reset_log_tokens);
+        Self {
+            config,
+            db,
+            tx: broadcast::channel(capacity).0,
+            state_tx: broadcast::channel(capacity).0,
+            timer_reset_tx: broadcast::channel(16).0,
+            redis,
+            serial_metrics: Arc::new(SerialMetrics::default()),
+            metrics: Arc::new(Metrics::default()),
+            fallback_state: Arc::new(FallbackState::new()),
+            thresholds: SharedThresholds::new(thresholds),
+            replays: ReplayRegistry::default(),
+            // Cancelled on SIGINT/SIGTERM; background tasks flush and stop
+            shutdown: CancellationToken::new(),
+            retention: Arc::new(RetentionStatus::default()),
+            dummy_hash,
+            reset_mailer,
+        }
+    }
 }
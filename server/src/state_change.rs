@@ -0,0 +1,48 @@
+use crate::models::{ActivityState, StateChange};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+/// Tracks the classified state of one stream and reports transitions.
+/// Durations use reading timestamps rather than wall-clock time, so they
+/// stay correct when a log is replayed faster than real time.
+#[derive(Default)]
+pub struct StateChangeDetector {
+    current: Option<(ActivityState, DateTime<Utc>)>,
+}
+
+impl StateChangeDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds one classified reading; returns a transition when the state differs
+    /// from the previous reading. The first reading only establishes the state.
+    pub fn observe(
+        &mut self,
+        state: ActivityState,
+        timestamp: DateTime<Utc>,
+        user_id: Option<Uuid>,
+    ) -> Option<StateChange> {
+        let (old_state, since) = match &self.current {
+            Some((old_state, since)) if *old_state != state => (*old_state, *since),
+            Some(_) => return None,
+            None => {
+                self.current = Some((state, timestamp));
+                return None;
+            }
+        };
+
+        self.current = Some((state, timestamp));
+        Some(StateChange {
+            old_state,
+            new_state: state,
+            duration_seconds: (timestamp - since).num_seconds().max(0) as u64,
+            timestamp,
+            user_id,
+        })
+    }
+}
+
+#[cfg(test)]
+#[path = "state_change_tests.rs"]
+mod tests;
@@ -0,0 +1,78 @@
+use super::*;
+use chrono::TimeZone;
+
+fn at(secs: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 1, 6, 10, 0, 0).unwrap() + chrono::Duration::seconds(secs as i64)
+}
+
+// State Change Detection Tests
+
+#[test]
+fn test_first_reading_emits_nothing() {
+    let mut detector = StateChangeDetector::new();
+    assert_eq!(
+        detector.observe(ActivityState::Sedentary, at(0), None),
+        None
+    );
+}
+
+#[test]
+fn test_same_state_emits_nothing() {
+    let mut detector = StateChangeDetector::new();
+    detector.observe(ActivityState::Sedentary, at(0), None);
+    assert_eq!(
+        detector.observe(ActivityState::Sedentary, at(5), None),
+        None
+    );
+}
+
+#[test]
+fn test_transition_reports_old_state_and_duration() {
+    let mut detector = StateChangeDetector::new();
+    detector.observe(ActivityState::Sedentary, at(0), None);
+    detector.observe(ActivityState::Sedentary, at(30), None);
+
+    let change = detector
+        .observe(ActivityState::Active, at(90), None)
+        .unwrap();
+    assert_eq!(change.old_state, ActivityState::Sedentary);
+    assert_eq!(change.new_state, ActivityState::Active);
+    assert_eq!(change.duration_seconds, 90);
+    assert_eq!(change.timestamp, at(90));
+}
+
+#[test]
+fn test_duration_measured_from_previous_transition() {
+    let mut detector = StateChangeDetector::new();
+    detector.observe(ActivityState::Sedentary, at(0), None);
+    detector.observe(ActivityState::Active, at(60), None);
+
+    let change = detector
+        .observe(ActivityState::Fidget, at(75), None)
+        .unwrap();
+    assert_eq!(change.old_state, ActivityState::Active);
+    assert_eq!(change.duration_seconds, 15);
+}
+
+#[test]
+fn test_backwards_timestamp_clamps_duration() {
+    let mut detector = StateChangeDetector::new();
+    detector.observe(ActivityState::Sedentary, at(60), None);
+
+    let change = detector
+        .observe(ActivityState::Active, at(0), None)
+        .unwrap();
+    assert_eq!(change.duration_seconds, 0);
+}
+
+#[test]
+fn test_transition_carries_user_id() {
+    let user_id = Uuid::new_v4();
+    let mut detector = StateChangeDetector::new();
+    detector.observe(ActivityState::Sedentary, at(0), Some(user_id));
+
+    let change = detector
+        .observe(ActivityState::Fidget, at(1), Some(user_id))
+        .unwrap();
+    assert_eq!(change.user_id, Some(user_id));
+}
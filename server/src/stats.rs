@@ -0,0 +1,163 @@
+use crate::{auth::AuthUser, state::AppState};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize)]
+pub struct TodayStats {
+    date: NaiveDate,
+    sedentary_minutes: f64,
+    fidget_minutes: f64,
+    active_minutes: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ActivityScore {
+    score: i32,
+    date: NaiveDate,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserStats {
+    user_id: Uuid,
+    today: TodayStats,
+    // State of the latest reading and how long the sedentary timer has run
+    current_state: Option<String>,
+    current_streak_seconds: i32,
+    latest_activity_score: Option<ActivityScore>,
+    last_alert_at: Option<DateTime<Utc>>,
+}
+
+/// Calendar date of `now` in `tz`, which days are bucketed by (TIMEZONE)
+pub fn local_date(tz: Tz, now: DateTime<Utc>) -> NaiveDate {
+    now.with_timezone(&tz).date_naive()
+}
+
+/// Start of `now`'s local day in `tz`, as UTC (the earliest midnight on DST days)
+pub fn day_start(tz: Tz, now: DateTime<Utc>) -> DateTime<Utc> {
+    date_start(tz, local_date(tz, now))
+}
+
+/// Local midnight at the start of `date` in `tz`, as UTC
+pub fn date_start(tz: Tz, date: NaiveDate) -> DateTime<Utc> {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+    tz.from_local_datetime(&midnight)
+        .earliest()
+        .map(|start| start.with_timezone(&Utc))
+        .unwrap_or_else(|| midnight.and_utc())
+}
+
+/// Counted seconds as minutes, one decimal place
+pub fn to_minutes(seconds: i64) -> f64 {
+    (seconds as f64 / 6.0).round() / 10.0
+}
+
+async fn load_stats(pool: &PgPool, user_id: Uuid, tz: Tz) -> Result<UserStats, sqlx::Error> {
+    let now = Utc::now();
+    let since = day_start(tz, now);
+
+    // Like the live daily totals, each distinct second of reading time counts once
+    let today = sqlx::query!(
+        r#"
+        SELECT
+            COUNT(DISTINCT date_trunc('second', timestamp))
+                FILTER (WHERE state = 'SEDENTARY') AS "sedentary_seconds!",
+            COUNT(DISTINCT date_trunc('second', timestamp))
+                FILTER (WHERE state = 'FIDGET') AS "fidget_seconds!",
+            COUNT(DISTINCT date_trunc('second', timestamp))
+                FILTER (WHERE state = 'ACTIVE') AS "active_seconds!"
+        FROM sensor_data
+        WHERE user_id = $1 AND timestamp >= $2
+        "#,
+        user_id,
+        since
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let latest = sqlx::query!(
+        r#"
+        SELECT state, timer_seconds
+        FROM sensor_data
+        WHERE user_id = $1
+        ORDER BY timestamp DESC
+        LIMIT 1
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let last_alert_at = sqlx::query_scalar!(
+        r#"
+        SELECT MAX(timestamp)
+        FROM sensor_data
+        WHERE user_id = $1 AND alert_triggered
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let score = sqlx::query!(
+        r#"
+        SELECT activity_score, date
+        FROM activity_summary
+        WHERE user_id = $1 AND period_type = 'daily'
+        ORDER BY date DESC
+        LIMIT 1
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(UserStats {
+        user_id,
+        today: TodayStats {
+            date: local_date(tz, now),
+            sedentary_minutes: to_minutes(today.sedentary_seconds),
+            fidget_minutes: to_minutes(today.fidget_seconds),
+            active_minutes: to_minutes(today.active_seconds),
+        },
+        current_streak_seconds: latest.as_ref().map_or(0, |l| l.timer_seconds),
+        current_state: latest.map(|l| l.state),
+        latest_activity_score: score.map(|s| ActivityScore {
+            score: s.activity_score,
+            date: s.date,
+        }),
+        last_alert_at,
+    })
+}
+
+/// Summary-card aggregates for the authenticated user: today's minutes per
+/// state (local day, TIMEZONE), current sedentary streak, latest daily
+/// activity score and most recent alert
+/// Endpoint: GET /stats
+pub async fn get_user_stats(user: AuthUser, State(state): State<AppState>) -> impl IntoResponse {
+    match load_stats(&state.db, user.user_id, state.config.timezone).await {
+        Ok(stats) => (StatusCode::OK, Json(stats)).into_response(),
+        Err(e) => {
+            eprintln!("Database error: {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({
+                    "error": "Failed to fetch user stats"
+                })),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "stats_tests.rs"]
+mod tests;
@@ -0,0 +1,92 @@
+use super::*;
+
+// Day Boundary Tests
+
+#[test]
+fn test_day_start_utc() {
+    let now: DateTime<Utc> = "2026-03-10T15:30:00Z".parse().unwrap();
+    let start = day_start(Tz::UTC, now);
+    assert_eq!(
+        start,
+        "2026-03-10T00:00:00Z".parse::<DateTime<Utc>>().unwrap()
+    );
+}
+
+#[test]
+fn test_day_start_uses_local_date() {
+    // 02:00 UTC is still the previous evening in New York (UTC-5 in January)
+    let now: DateTime<Utc> = "2026-01-15T02:00:00Z".parse().unwrap();
+    let start = day_start(chrono_tz::America::New_York, now);
+    assert_eq!(
+        start,
+        "2026-01-14T05:00:00Z".parse::<DateTime<Utc>>().unwrap()
+    );
+}
+
+#[test]
+fn test_day_start_ahead_of_utc() {
+    let now: DateTime<Utc> = "2026-06-01T20:00:00Z".parse().unwrap();
+    let start = day_start(chrono_tz::Asia::Tokyo, now);
+    assert_eq!(
+        start,
+        "2026-06-01T15:00:00Z".parse::<DateTime<Utc>>().unwrap()
+    );
+}
+
+#[test]
+fn test_local_date_across_day_boundary() {
+    // 15:30 UTC is already the next morning in Brisbane (UTC+10)
+    let now: DateTime<Utc> = "2026-03-10T15:30:00Z".parse().unwrap();
+    assert_eq!(
+        local_date(Tz::UTC, now),
+        "2026-03-10".parse::<NaiveDate>().unwrap()
+    );
+    assert_eq!(
+        local_date(chrono_tz::Australia::Brisbane, now),
+        "2026-03-11".parse::<NaiveDate>().unwrap()
+    );
+    // ...and the evening before it belongs to the 10th there
+    let evening: DateTime<Utc> = "2026-03-10T13:59:59Z".parse().unwrap();
+    assert_eq!(
+        local_date(chrono_tz::Australia::Brisbane, evening),
+        "2026-03-10".parse::<NaiveDate>().unwrap()
+    );
+    assert_eq!(
+        day_start(chrono_tz::Australia::Brisbane, now),
+        "2026-03-10T14:00:00Z".parse::<DateTime<Utc>>().unwrap()
+    );
+}
+
+// Minutes Conversion Tests
+
+#[test]
+fn test_to_minutes_rounds_to_tenths() {
+    assert_eq!(to_minutes(0), 0.0);
+    assert_eq!(to_minutes(60), 1.0);
+    assert_eq!(to_minutes(90), 1.5);
+    assert_eq!(to_minutes(100), 1.7);
+}
+
+// Response Shape Tests
+
+#[test]
+fn test_stats_serialization_without_history() {
+    let stats = UserStats {
+        user_id: Uuid::nil(),
+        today: TodayStats {
+            date: NaiveDate::from_ymd_opt(2026, 3, 10).unwrap(),
+            sedentary_minutes: 0.0,
+            fidget_minutes: 0.0,
+            active_minutes: 0.0,
+        },
+        current_state: None,
+        current_streak_seconds: 0,
+        latest_activity_score: None,
+        last_alert_at: None,
+    };
+    let json = serde_json::to_value(&stats).unwrap();
+    assert_eq!(json["today"]["date"], "2026-03-10");
+    assert_eq!(json["current_streak_seconds"], 0);
+    assert!(json["latest_activity_score"].is_null());
+    assert!(json["last_alert_at"].is_null());
+}
@@ -0,0 +1,105 @@
+use crate::models::{ActivityState, ProcessedState};
+use crate::serial::{classify_state, next_sedentary_timer, AlertCooldown, Thresholds};
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use std::ops::RangeInclusive;
+
+// Phase lengths in readings (one reading = one second of the sedentary timer).
+// Long sitting spells broken by short fidgets and occasional movement.
+const SEDENTARY_SECONDS: RangeInclusive<u32> = 300..=1500;
+const FIDGET_SECONDS: RangeInclusive<u32> = 20..=120;
+const ACTIVE_SECONDS: RangeInclusive<u32> = 60..=300;
+
+/// Acceleration in the middle of `state`'s band, clear of both thresholds
+pub fn acceleration_for(rng: &mut impl Rng, state: ActivityState, thresholds: Thresholds) -> f32 {
+    let Thresholds { fidget, active } = thresholds;
+    match state {
+        ActivityState::Active => rng.gen_range(active * 1.2..=active * 2.0),
+        ActivityState::Fidget => {
+            let span = active - fidget;
+            rng.gen_range(fidget + span * 0.25..=fidget + span * 0.75)
+        }
+        ActivityState::Sedentary => rng.gen_range(0.0..=fidget * 0.5),
+    }
+}
+
+/// Produces a plausible SEDENTARY/FIDGET/ACTIVE day, one reading per call.
+///
+/// Acceleration is drawn well inside each state's band of the configured
+/// thresholds and then run through `classify_state`, so the broadcast state
+/// always matches what the live pipeline would have classified.
+pub struct SyntheticGenerator<R: Rng> {
+    rng: R,
+    thresholds: Thresholds,
+    alert_limit: u64,
+    alerts: AlertCooldown,
+    phase: ActivityState,
+    remaining: u32,
+    timer: u64,
+}
+
+impl<R: Rng> SyntheticGenerator<R> {
+    pub fn new(rng: R, thresholds: Thresholds, alert_limit: u64, alert_cooldown: u64) -> Self {
+        let mut generator = Self {
+            rng,
+            thresholds,
+            alert_limit,
+            alerts: AlertCooldown::new(alert_cooldown),
+            phase: ActivityState::Sedentary,
+            remaining: 0,
+            timer: 0,
+        };
+        generator.remaining = generator.phase_length(ActivityState::Sedentary);
+        generator
+    }
+
+    fn phase_length(&mut self, phase: ActivityState) -> u32 {
+        let range = match phase {
+            ActivityState::Active => ACTIVE_SECONDS,
+            ActivityState::Fidget => FIDGET_SECONDS,
+            ActivityState::Sedentary => SEDENTARY_SECONDS,
+        };
+        self.rng.gen_range(range)
+    }
+
+    /// Sitting mostly gives way to fidgeting; movement always ends seated
+    fn next_phase(&mut self) -> ActivityState {
+        match self.phase {
+            ActivityState::Sedentary if self.rng.gen_bool(0.7) => ActivityState::Fidget,
+            ActivityState::Sedentary => ActivityState::Active,
+            ActivityState::Fidget if self.rng.gen_bool(0.3) => ActivityState::Active,
+            _ => ActivityState::Sedentary,
+        }
+    }
+
+    pub fn next_reading(&mut self, timestamp: DateTime<Utc>) -> ProcessedState {
+        if self.remaining == 0 {
+            self.phase = self.next_phase();
+            self.remaining = self.phase_length(self.phase);
+        }
+        self.remaining -= 1;
+
+        let val = acceleration_for(&mut self.rng, self.phase, self.thresholds);
+        // No current state, so no hysteresis margin applies
+        let state = classify_state(0, val, None, self.thresholds, 0.0);
+        self.timer = next_sedentary_timer(self.timer, state);
+
+        ProcessedState {
+            state,
+            timer: self.timer,
+            val,
+            alert: self
+                .alerts
+                .observe(self.timer, self.timer >= self.alert_limit),
+            timestamp,
+            user_id: None,
+            daily: None,
+            classification_confidence: None,
+            replayed: true,
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "synthetic_tests.rs"]
+mod tests;
@@ -0,0 +1,135 @@
+use super::*;
+use chrono::Duration;
+use rand::{rngs::StdRng, SeedableRng};
+
+const THRESHOLDS: Thresholds = Thresholds {
+    fidget: 0.02,
+    active: 0.04,
+};
+
+fn generate(seed: u64, count: usize) -> Vec<ProcessedState> {
+    let mut generator = SyntheticGenerator::new(StdRng::seed_from_u64(seed), THRESHOLDS, 1200, 0);
+    let start = Utc::now();
+    (0..count)
+        .map(|i| generator.next_reading(start + Duration::seconds(i as i64)))
+        .collect()
+}
+
+// State Distribution Tests
+
+#[test]
+fn test_every_state_appears() {
+    let readings = generate(7, 20_000);
+    for state in [
+        ActivityState::Sedentary,
+        ActivityState::Fidget,
+        ActivityState::Active,
+    ] {
+        assert!(
+            readings.iter().any(|r| r.state == state),
+            "{} never generated",
+            state
+        );
+    }
+}
+
+#[test]
+fn test_mostly_sedentary() {
+    for seed in 0..5 {
+        let readings = generate(seed, 20_000);
+        let sedentary = readings
+            .iter()
+            .filter(|r| r.state == ActivityState::Sedentary)
+            .count();
+        let share = sedentary as f64 / readings.len() as f64;
+        assert!(
+            (0.5..0.95).contains(&share),
+            "seed {}: sedentary share {}",
+            seed,
+            share
+        );
+    }
+}
+
+#[test]
+fn test_states_come_in_runs() {
+    // Phases last tens of seconds at least, not a coin flip per reading
+    let readings = generate(3, 20_000);
+    let changes = readings
+        .windows(2)
+        .filter(|w| w[0].state != w[1].state)
+        .count();
+    assert!(changes < readings.len() / 20, "{} state changes", changes);
+}
+
+// Classification Consistency Tests
+
+#[test]
+fn test_state_matches_thresholds() {
+    for reading in generate(11, 5_000) {
+        assert_eq!(
+            reading.state,
+            classify_state(0, reading.val, None, THRESHOLDS, 0.0),
+            "val {}",
+            reading.val
+        );
+    }
+}
+
+#[test]
+fn test_bands_follow_configured_thresholds() {
+    let high = Thresholds {
+        fidget: 0.5,
+        active: 1.0,
+    };
+    let mut generator = SyntheticGenerator::new(StdRng::seed_from_u64(5), high, 1200, 0);
+    let readings: Vec<_> = (0..10_000)
+        .map(|_| generator.next_reading(Utc::now()))
+        .collect();
+    assert!(readings
+        .iter()
+        .filter(|r| r.state == ActivityState::Active)
+        .all(|r| r.val > 1.0));
+    assert!(readings.iter().any(|r| r.state == ActivityState::Active));
+}
+
+// Timer Tests
+
+#[test]
+fn test_timer_follows_state() {
+    let readings = generate(9, 10_000);
+    for pair in readings.windows(2) {
+        assert_eq!(
+            pair[1].timer,
+            next_sedentary_timer(pair[0].timer, pair[1].state)
+        );
+    }
+}
+
+#[test]
+fn test_alert_once_timer_reaches_limit() {
+    let mut generator = SyntheticGenerator::new(StdRng::seed_from_u64(1), THRESHOLDS, 60, 0);
+    let readings: Vec<_> = (0..5_000)
+        .map(|_| generator.next_reading(Utc::now()))
+        .collect();
+    assert!(readings.iter().any(|r| r.alert));
+    assert!(readings.iter().all(|r| r.alert == (r.timer >= 60)));
+}
+
+#[test]
+fn test_alert_cooldown_limits_repeats() {
+    let mut generator = SyntheticGenerator::new(StdRng::seed_from_u64(1), THRESHOLDS, 60, 30);
+    let readings: Vec<_> = (0..5_000)
+        .map(|_| generator.next_reading(Utc::now()))
+        .collect();
+    let alerts = readings.iter().filter(|r| r.alert).count();
+    let over_limit = readings.iter().filter(|r| r.timer >= 60).count();
+    assert!(alerts > 0);
+    assert!(alerts < over_limit, "{} of {}", alerts, over_limit);
+}
+
+#[test]
+fn test_readings_marked_replayed() {
+    let readings = generate(2, 10);
+    assert!(readings.iter().all(|r| r.replayed && r.user_id.is_none()));
+}
@@ -1,34 +1,251 @@
-use crate::state::AppState;
+use crate::{
+    auth::{AuthUser, StreamUser},
+    calibration::save_thresholds,
+    history::{replay_on_connect, HistoryQuery, HistorySource},
+    metrics::{acquire_stream, ConnectionGuard, StreamKind, Subscriber},
+    models::visible_to,
+    serial::{Thresholds, TimerReset},
+    state::AppState,
+};
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        Query, State,
     },
-    response::IntoResponse,
+    response::{IntoResponse, Response},
 };
-use redis::AsyncCommands;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{interval, sleep_until, Instant, MissedTickBehavior};
 
-pub async fn ws_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+/// Commands a client can send as text frames, e.g. `{"cmd":"reset_timer"}`
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum ClientCommand {
+    /// Zero the sedentary timer on the caller's device streams
+    ResetTimer,
+    /// Change one or both live thresholds (admin only)
+    SetThreshold {
+        fidget: Option<f32>,
+        active: Option<f32>,
+    },
 }
 
-async fn handle_socket(mut socket: WebSocket, state: AppState) {
-    // 1. RECONNECTION BACKUP (Fetch from Redis)
-    // This fills the graph immediately upon connection
-    if let Ok(mut con) = state.redis.get_multiplexed_async_connection().await {
-        let history: Vec<String> = con.lrange("sensor_history", 0, 99).await.unwrap_or(vec![]);
+/// Ping/pong liveness for one socket. A ping that gets no answer within the
+/// timeout marks the peer dead (e.g. a laptop that slept mid-connection); any
+/// frame from the client counts as an answer, since it proves the peer is there.
+#[derive(Debug)]
+pub struct Heartbeat {
+    timeout: Duration,
+    // Set while a ping is unanswered
+    deadline: Option<Instant>,
+}
 
-        // Send history to frontend (reversed because lpush stores newest first)
-        for msg in history.into_iter().rev() {
-            let _ = socket.send(Message::Text(msg)).await;
+impl Heartbeat {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            deadline: None,
         }
     }
 
-    // 2. LIVE STREAM Zero Latency
+    /// Starts the pong timeout, unless an earlier ping is still waiting
+    pub fn ping_sent(&mut self, now: Instant) {
+        self.deadline.get_or_insert(now + self.timeout);
+    }
+
+    pub fn heard_from_peer(&mut self) {
+        self.deadline = None;
+    }
+
+    /// When the socket is considered dead if nothing arrives first
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    pub fn is_dead(&self, now: Instant) -> bool {
+        self.deadline.is_some_and(|deadline| now >= deadline)
+    }
+}
+
+/// WebSocket fallback for clients that can't use `/events`: the same Redis
+/// history replay on connect (SKIP_HISTORY, SENSOR_HISTORY_LIMIT) followed by
+/// live readings, plus client commands. Authenticated clients (Authorization
+/// header or `?token=`) only receive readings tagged with their own user id;
+/// STREAM_AUTH_REQUIRED rejects the upgrade without a valid token.
+/// `?replay=<id>` replays that running replay's history instead of the live one.
+pub async fn ws_handler(
+    // Before the upgrade extractor, so an unauthenticated request is a 401 either way
+    StreamUser(user): StreamUser,
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Query(query): Query<HistoryQuery>,
+) -> Response {
+    let source = HistorySource::from_query(query.replay);
+    match acquire_stream(&state, StreamKind::WebSocket) {
+        Ok(connection) => {
+            ws.on_upgrade(move |socket| handle_socket(socket, state, user, source, connection))
+        }
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+/// `_connection` keeps this socket counted until it closes
+async fn handle_socket(
+    mut socket: WebSocket,
+    state: AppState,
+    user: Option<AuthUser>,
+    source: HistorySource,
+    _connection: ConnectionGuard,
+) {
+    let subscriber = user.as_ref().map(|u| u.user_id);
+    let metrics = state.metrics.clone();
+
+    // 1. RECONNECTION BACKUP: the same history replay SSE clients get, so the
+    // graph fills immediately. Subscribed first so nothing is lost in between.
     let mut rx = state.tx.subscribe();
-    while let Ok(msg) = rx.recv().await {
+    for msg in replay_on_connect(&state, &mut rx, source)
+        .await
+        .into_iter()
+        .filter(|m| visible_to(m, subscriber))
+    {
         if socket.send(Message::Text(msg)).await.is_err() {
-            break;
+            return;
+        }
+    }
+
+    // 2. LIVE STREAM Zero Latency, plus client commands on the same socket.
+    // Pings every WS_PING_INTERVAL_SECONDS find half-open connections, which
+    // would otherwise hold their broadcast receiver and connection slot forever.
+    let settings = &state.config.server;
+    let mut ping = interval(settings.ws_ping_interval);
+    ping.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ping.tick().await;
+    let mut heartbeat = Heartbeat::new(settings.ws_pong_timeout);
+    loop {
+        let pong_deadline = heartbeat.deadline();
+        tokio::select! {
+            // Server shutting down: close the socket so the connection can drain
+            _ = state.shutdown.cancelled() => {
+                let _ = socket.send(Message::Close(None)).await;
+                break;
+            }
+            msg = rx.recv() => {
+                let msg = match msg {
+                    Ok(msg) => msg,
+                    // Slow client: drop what it missed and carry on with the live stream
+                    Err(RecvError::Lagged(skipped)) => {
+                        eprintln!("WebSocket client lagged, skipped {} messages", skipped);
+                        metrics.record_lagged(Subscriber::WebSocket, skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                if !visible_to(&msg, subscriber) {
+                    continue;
+                }
+                if socket.send(Message::Text(msg)).await.is_err() {
+                    break;
+                }
+            }
+            _ = ping.tick() => {
+                heartbeat.ping_sent(Instant::now());
+                if socket.send(Message::Ping(Vec::new())).await.is_err() {
+                    break;
+                }
+            }
+            _ = sleep_until(pong_deadline.unwrap_or_else(Instant::now)), if pong_deadline.is_some() => {
+                if heartbeat.is_dead(Instant::now()) {
+                    eprintln!("WebSocket client missed its pong, closing");
+                    let _ = socket.send(Message::Close(None)).await;
+                    break;
+                }
+            }
+            incoming = socket.recv() => {
+                if let Some(Ok(_)) = incoming {
+                    heartbeat.heard_from_peer();
+                }
+                let text = match incoming {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+                let reply = handle_command(&state, user.as_ref(), &text).await;
+                if socket.send(Message::Text(reply.to_string())).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn error_frame(error: &str) -> Value {
+    json!({
+        "type": "error",
+        "error": error
+    })
+}
+
+/// Parses and applies one client command, returning the ack or error frame.
+/// Bad input gets an error frame; the connection stays open.
+async fn handle_command(state: &AppState, user: Option<&AuthUser>, text: &str) -> Value {
+    let command = match parse_command(text) {
+        Ok(command) => command,
+        Err(error) => return error_frame(error),
+    };
+
+    let Some(user) = user else {
+        return error_frame("unauthorized");
+    };
+
+    match command {
+        ClientCommand::ResetTimer => {
+            let _ = state.timer_reset_tx.send(TimerReset {
+                user_id: user.user_id,
+                admin: user.role == "admin",
+            });
+            json!({
+                "type": "ack",
+                "cmd": "reset_timer"
+            })
+        }
+        ClientCommand::SetThreshold { fidget, active } => {
+            if user.role != "admin" {
+                return error_frame("forbidden");
+            }
+            let current = state.thresholds.current();
+            let thresholds = Thresholds {
+                fidget: fidget.unwrap_or(current.fidget),
+                active: active.unwrap_or(current.active),
+            };
+            if let Err(message) = thresholds.validate() {
+                return error_frame(message);
+            }
+            if let Err(e) = save_thresholds(&state.db, thresholds).await {
+                eprintln!("Failed to save thresholds: {e:?}");
+                return error_frame("Failed to save thresholds");
+            }
+            state.thresholds.set(thresholds);
+            json!({
+                "type": "ack",
+                "cmd": "set_threshold",
+                "thresholds": thresholds
+            })
         }
     }
 }
+
+/// Distinguishes malformed frames from well-formed but unknown commands
+fn parse_command(text: &str) -> Result<ClientCommand, &'static str> {
+    let value: Value = serde_json::from_str(text).map_err(|_| "invalid_json")?;
+    if !value.get("cmd").is_some_and(Value::is_string) {
+        return Err("missing_cmd");
+    }
+    serde_json::from_value(value).map_err(|_| "unknown_command")
+}
+
+#[cfg(test)]
+#[path = "websocket_tests.rs"]
+mod tests;
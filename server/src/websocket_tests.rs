@@ -0,0 +1,85 @@
+use super::*;
+
+// Client Command Parsing Tests
+
+#[test]
+fn test_parse_reset_timer() {
+    assert_eq!(
+        parse_command(r#"{"cmd":"reset_timer"}"#),
+        Ok(ClientCommand::ResetTimer)
+    );
+}
+
+#[test]
+fn test_parse_set_threshold_partial() {
+    assert_eq!(
+        parse_command(r#"{"cmd":"set_threshold","active":0.05}"#),
+        Ok(ClientCommand::SetThreshold {
+            fidget: None,
+            active: Some(0.05),
+        })
+    );
+}
+
+#[test]
+fn test_parse_unknown_command() {
+    assert_eq!(
+        parse_command(r#"{"cmd":"self_destruct"}"#),
+        Err("unknown_command")
+    );
+}
+
+#[test]
+fn test_parse_invalid_json() {
+    assert_eq!(parse_command("reset_timer"), Err("invalid_json"));
+}
+
+#[test]
+fn test_parse_missing_cmd() {
+    assert_eq!(parse_command(r#"{"active":0.05}"#), Err("missing_cmd"));
+}
+
+#[test]
+fn test_error_frame_shape() {
+    assert_eq!(
+        error_frame("unauthorized"),
+        json!({"type": "error", "error": "unauthorized"})
+    );
+}
+
+// Heartbeat Tests
+
+#[test]
+fn test_heartbeat_idle_until_ping() {
+    let heartbeat = Heartbeat::new(Duration::from_secs(10));
+    assert_eq!(heartbeat.deadline(), None);
+    assert!(!heartbeat.is_dead(Instant::now() + Duration::from_secs(3600)));
+}
+
+#[test]
+fn test_unanswered_ping_times_out() {
+    let now = Instant::now();
+    let mut heartbeat = Heartbeat::new(Duration::from_secs(10));
+    heartbeat.ping_sent(now);
+    assert!(!heartbeat.is_dead(now + Duration::from_secs(9)));
+    assert!(heartbeat.is_dead(now + Duration::from_secs(10)));
+}
+
+#[test]
+fn test_reply_clears_deadline() {
+    let now = Instant::now();
+    let mut heartbeat = Heartbeat::new(Duration::from_secs(10));
+    heartbeat.ping_sent(now);
+    heartbeat.heard_from_peer();
+    assert_eq!(heartbeat.deadline(), None);
+    assert!(!heartbeat.is_dead(now + Duration::from_secs(60)));
+}
+
+#[test]
+fn test_later_ping_keeps_first_deadline() {
+    let now = Instant::now();
+    let mut heartbeat = Heartbeat::new(Duration::from_secs(10));
+    heartbeat.ping_sent(now);
+    heartbeat.ping_sent(now + Duration::from_secs(5));
+    assert_eq!(heartbeat.deadline(), Some(now + Duration::from_secs(10)));
+}
@@ -0,0 +1,1353 @@
+// Server Integration Tests
+// These tests drive the full router against throwaway Postgres and Redis
+// containers (testcontainers, so a Docker daemon must be reachable).
+// Set TEST_DATABASE_URL and TEST_REDIS_URL to use already running services
+// instead; the database is migrated on first use.
+
+use axum::{
+    body::Body,
+    extract::connect_info::MockConnectInfo,
+    http::{header, HeaderMap, Request, StatusCode},
+    Router,
+};
+use futures::StreamExt;
+use serde_json::Value;
+use server::{
+    auth::LoginResponse,
+    build_app,
+    config::Config,
+    daily_summary, db_worker,
+    metrics::Metrics,
+    models::{ActivityState, ProcessedState},
+    replay, retention,
+    state::AppState,
+};
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use testcontainers_modules::{
+    postgres::Postgres,
+    redis::Redis,
+    testcontainers::{runners::AsyncRunner, ContainerAsync},
+};
+use tokio_util::sync::CancellationToken;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+const PASSWORD: &str = "correct-horse-42";
+
+struct TestApp {
+    app: Router,
+    pool: PgPool,
+    redis: redis::Client,
+    config: Arc<Config>,
+    // Containers are removed when dropped, so they live as long as the test
+    _postgres: Option<ContainerAsync<Postgres>>,
+    _redis: Option<ContainerAsync<Redis>>,
+}
+
+async fn spawn_app() -> TestApp {
+    spawn_app_with(&[]).await
+}
+
+/// Like `spawn_app`, with extra settings on top of the test defaults
+async fn spawn_app_with(settings: &[(&str, &str)]) -> TestApp {
+    let (database_url, postgres) = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => (url, None),
+        Err(_) => {
+            let container = Postgres::default()
+                .start()
+                .await
+                .expect("Failed to start Postgres container");
+            let host = container.get_host().await.unwrap();
+            let port = container.get_host_port_ipv4(5432).await.unwrap();
+            (
+                format!("postgres://postgres:postgres@{}:{}/postgres", host, port),
+                Some(container),
+            )
+        }
+    };
+    let (redis_url, redis) = match std::env::var("TEST_REDIS_URL") {
+        Ok(url) => (url, None),
+        Err(_) => {
+            let container = Redis::default()
+                .start()
+                .await
+                .expect("Failed to start Redis container");
+            let host = container.get_host().await.unwrap();
+            let port = container.get_host_port_ipv4(6379).await.unwrap();
+            (format!("redis://{}:{}/", host, port), Some(container))
+        }
+    };
+
+    let pool = PgPoolOptions::new()
+        .max_connections(5)
+        .connect(&database_url)
+        .await
+        .expect("Failed to connect to Postgres.");
+    sqlx::migrate!("../migrations")
+        .run(&pool)
+        .await
+        .expect("Failed to run migrations");
+
+    let config = Config::from_lookup(|key| {
+        if let Some((_, value)) = settings.iter().find(|(name, _)| *name == key) {
+            return Some(value.to_string());
+        }
+        let value = match key {
+            "DATABASE_URL" => database_url.as_str(),
+            "REDIS_URL" => redis_url.as_str(),
+            "JWT_SECRET" => "integration-test-secret",
+            "SERIAL_PORT" => "/dev/null",
+            "BAUD_RATE" => "9600",
+            "DISABLE_FALLBACK" => "true",
+            _ => return None,
+        };
+        Some(value.to_string())
+    })
+    .expect("test config is valid");
+
+    let config = Arc::new(config);
+    let thresholds = config.serial.thresholds;
+    let redis_client = redis::Client::open(redis_url.as_str()).unwrap();
+    let state = AppState::new(
+        config.clone(),
+        pool.clone(),
+        redis_client.clone(),
+        thresholds,
+    );
+
+    // Login reads the peer address for the audit log
+    let app = build_app(state).layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 40000))));
+
+    TestApp {
+        app,
+        pool,
+        redis: redis_client,
+        config,
+        _postgres: postgres,
+        _redis: redis,
+    }
+}
+
+impl TestApp {
+    async fn send_with_headers(&self, request: Request<Body>) -> (StatusCode, HeaderMap, String) {
+        let response = self.app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, headers, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    async fn send(&self, request: Request<Body>) -> (StatusCode, String) {
+        let (status, _, body) = self.send_with_headers(request).await;
+        (status, body)
+    }
+
+    async fn post_form(&self, uri: &str, form: &str) -> (StatusCode, String) {
+        let request = Request::post(uri)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(form.to_string()))
+            .unwrap();
+        self.send(request).await
+    }
+
+    async fn post(&self, uri: &str, token: &str) -> (StatusCode, String) {
+        let request = Request::post(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        self.send(request).await
+    }
+
+    async fn get(&self, uri: &str, token: Option<&str>) -> (StatusCode, String) {
+        let mut request = Request::get(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        self.send(request.body(Body::empty()).unwrap()).await
+    }
+
+    async fn signup(&self, email: &str) -> String {
+        let (status, body) = self
+            .post_form(
+                "/signup",
+                &format!("email={}&name=Test+User&password={}", email, PASSWORD),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let body: Value = serde_json::from_str(&body).unwrap();
+        body["verification_token"].as_str().unwrap().to_string()
+    }
+
+    async fn login(&self, email: &str) -> (StatusCode, String) {
+        self.post_form("/login", &format!("email={}&password={}", email, PASSWORD))
+            .await
+    }
+
+    /// Signs up, verifies and logs in a fresh user, returning its token and id
+    async fn signed_in_user(&self) -> (String, Uuid) {
+        let email = unique_email();
+        let verification_token = self.signup(&email).await;
+        self.get(&format!("/auth/verify?token={}", verification_token), None)
+            .await;
+        let (status, body) = self.login(&email).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let token = serde_json::from_str::<Value>(&body).unwrap()["token"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let (_, body) = self.get("/auth/me", Some(&token)).await;
+        let user_id = serde_json::from_str::<Value>(&body).unwrap()["user_id"]
+            .as_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        (token, user_id)
+    }
+
+    /// A verified user granted the `admin` role before logging in; returns its token
+    async fn signed_in_admin(&self) -> String {
+        let email = unique_email();
+        let verification_token = self.signup(&email).await;
+        self.get(&format!("/auth/verify?token={}", verification_token), None)
+            .await;
+        sqlx::query("UPDATE users SET role = 'admin' WHERE email = $1")
+            .bind(&email)
+            .execute(&self.pool)
+            .await
+            .expect("Failed to grant admin role");
+        let (status, body) = self.login(&email).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        serde_json::from_str::<Value>(&body).unwrap()["token"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    }
+
+    /// POSTs `{"refresh_token": ...}` with the Bearer token (when given)
+    async fn post_refresh_token(
+        &self,
+        uri: &str,
+        token: Option<&str>,
+        refresh_token: &str,
+    ) -> (StatusCode, String) {
+        let mut request = Request::post(uri).header(header::CONTENT_TYPE, "application/json");
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let body = serde_json::json!({ "refresh_token": refresh_token }).to_string();
+        self.send(request.body(Body::from(body)).unwrap()).await
+    }
+
+    async fn login_session(&self, email: &str) -> LoginResponse {
+        let (status, body) = self.login(email).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        serde_json::from_str(&body).unwrap()
+    }
+}
+
+fn unique_email() -> String {
+    format!("it-{}@example.com", Uuid::new_v4())
+}
+
+// Auth Flow Tests
+
+#[tokio::test]
+async fn test_signup_verify_login_and_protected_endpoints() {
+    let app = spawn_app().await;
+    let email = unique_email();
+
+    let verification_token = app.signup(&email).await;
+
+    // Unverified accounts can't log in yet
+    let (status, _) = app.login(&email).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, body) = app
+        .get(&format!("/auth/verify?token={}", verification_token), None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let request = Request::post("/login")
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(format!("email={}&password={}", email, PASSWORD)))
+        .unwrap();
+    let (status, headers, body) = app.send_with_headers(request).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(headers[header::CONTENT_TYPE], "application/json");
+    let tokens: LoginResponse = serde_json::from_str(&body).expect("login body is JSON");
+    assert_eq!(tokens.token_type, "Bearer");
+    assert_eq!(tokens.expires_at.timestamp() as usize, tokens.exp);
+    let token = tokens.token.as_str();
+
+    let (status, body) = app.get("/auth/me", Some(token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let profile: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(profile["email"], email.as_str());
+    assert_eq!(profile["role"], "user");
+    assert_eq!(profile["user_id"], tokens.user_id.to_string());
+
+    let (status, body) = app.get("/stats", Some(token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let stats: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(stats["user_id"], profile["user_id"]);
+    assert_eq!(stats["current_streak_seconds"], 0);
+}
+
+#[tokio::test]
+async fn test_logout_ends_only_the_current_session() {
+    let app = spawn_app().await;
+    let email = unique_email();
+    let verification_token = app.signup(&email).await;
+    app.get(&format!("/auth/verify?token={}", verification_token), None)
+        .await;
+    let laptop = app.login_session(&email).await;
+    let phone = app.login_session(&email).await;
+
+    let (status, body) = app
+        .post_refresh_token("/logout", Some(&laptop.token), &laptop.refresh_token)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, _) = app
+        .post_refresh_token("/auth/refresh", None, &laptop.refresh_token)
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // The other device keeps its session
+    let (status, body) = app
+        .post_refresh_token("/auth/refresh", None, &phone.refresh_token)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let phone: LoginResponse = serde_json::from_str(&body).unwrap();
+
+    // Signing out everywhere revokes every refresh token
+    let tablet = app.login_session(&email).await;
+    let (status, body) = app.post("/logout/all", &phone.token).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    for refresh_token in [&phone.refresh_token, &tablet.refresh_token] {
+        let (status, _) = app
+            .post_refresh_token("/auth/refresh", None, refresh_token)
+            .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
+
+#[tokio::test]
+async fn test_protected_endpoint_requires_token() {
+    let app = spawn_app().await;
+
+    let (status, _) = app.get("/stats", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = app.get("/auth/me", Some("not-a-jwt")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_wrong_password_rejected() {
+    let app = spawn_app().await;
+    let email = unique_email();
+    let token = app.signup(&email).await;
+    app.get(&format!("/auth/verify?token={}", token), None)
+        .await;
+
+    let (status, _) = app
+        .post_form("/login", &format!("email={}&password=wrong-guess-1", email))
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_duplicate_signup_conflicts() {
+    let app = spawn_app().await;
+    let email = unique_email();
+    app.signup(&email).await;
+
+    let (status, body) = app
+        .post_form(
+            "/signup",
+            &format!("email={}&name=Again&password={}", email, PASSWORD),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert!(body.contains("email_taken"));
+}
+
+// Alert History Tests
+
+#[tokio::test]
+async fn test_alert_history_collapses_bouts() {
+    let app = spawn_app().await;
+    let (token, user_id) = app.signed_in_user().await;
+
+    // Two alerting sittings on 2026-03-10, split by a burst of activity
+    let readings = [
+        ("09:00:00", "SEDENTARY", 1199, false),
+        ("09:00:01", "SEDENTARY", 1200, true),
+        ("09:00:02", "FIDGET", 1200, true),
+        ("09:00:03", "SEDENTARY", 1201, true),
+        ("09:00:04", "ACTIVE", 0, false),
+        ("11:30:00", "SEDENTARY", 1250, true),
+        ("11:30:01", "SEDENTARY", 1251, true),
+    ];
+    for (time, state, timer, alert) in readings {
+        sqlx::query(
+            r#"
+            INSERT INTO sensor_data (user_id, state, timer_seconds, alert_triggered, timestamp)
+            VALUES ($1, $2, $3, $4, ('2026-03-10 ' || $5)::TIMESTAMPTZ)
+            "#,
+        )
+        .bind(user_id)
+        .bind(state)
+        .bind(timer)
+        .bind(alert)
+        .bind(format!("{}+00", time))
+        .execute(&app.pool)
+        .await
+        .expect("Failed to seed sensor data");
+    }
+
+    let uri = format!("/api/alerts/user/{}?date=2026-03-10", user_id);
+    let (status, body) = app.get(&uri, Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["total"], 2);
+    assert_eq!(body["alerts"][0]["started_at"], "2026-03-10T09:00:01Z");
+    assert_eq!(body["alerts"][0]["duration_seconds"], 2);
+    assert_eq!(body["alerts"][0]["peak_timer_seconds"], 1201);
+    assert_eq!(body["alerts"][1]["started_at"], "2026-03-10T11:30:00Z");
+
+    // Other days are empty
+    let uri = format!("/api/alerts/user/{}?date=2026-03-11", user_id);
+    let (_, body) = app.get(&uri, Some(&token)).await;
+    assert_eq!(serde_json::from_str::<Value>(&body).unwrap()["total"], 0);
+
+    // Cooldown alerts 300s apart still span the whole sitting
+    let (token, user_id) = app.signed_in_user().await;
+    let readings = [
+        ("09:00:00", "SEDENTARY", 1200, true),
+        ("09:02:30", "SEDENTARY", 1350, false),
+        ("09:05:00", "SEDENTARY", 1500, true),
+        ("09:07:00", "SEDENTARY", 1620, false),
+        ("09:07:01", "ACTIVE", 0, false),
+    ];
+    for (time, state, timer, alert) in readings {
+        sqlx::query(
+            r#"
+            INSERT INTO sensor_data (user_id, state, timer_seconds, alert_triggered, timestamp)
+            VALUES ($1, $2, $3, $4, ('2026-03-10 ' || $5)::TIMESTAMPTZ)
+            "#,
+        )
+        .bind(user_id)
+        .bind(state)
+        .bind(timer)
+        .bind(alert)
+        .bind(format!("{}+00", time))
+        .execute(&app.pool)
+        .await
+        .expect("Failed to seed sensor data");
+    }
+
+    let uri = format!("/api/alerts/user/{}?date=2026-03-10", user_id);
+    let (status, body) = app.get(&uri, Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["total"], 1);
+    assert_eq!(body["alerts"][0]["ended_at"], "2026-03-10T09:07:00Z");
+    assert_eq!(body["alerts"][0]["duration_seconds"], 420);
+    assert_eq!(body["alerts"][0]["peak_timer_seconds"], 1620);
+}
+
+#[tokio::test]
+async fn test_alert_history_is_scoped_to_owner() {
+    let app = spawn_app().await;
+    let (token, _) = app.signed_in_user().await;
+    let (_, other_user) = app.signed_in_user().await;
+
+    let uri = format!("/api/alerts/user/{}", other_user);
+    let (status, _) = app.get(&uri, Some(&token)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = app.get(&uri, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+// FHIR Analytics Tests
+
+#[tokio::test]
+async fn test_fhir_analytics_returns_seeded_summaries() {
+    let app = spawn_app().await;
+    let user_id = Uuid::new_v4();
+
+    sqlx::query(
+        r#"
+        INSERT INTO users (user_id, email, name, password_hash, created_at)
+        VALUES ($1, $2, 'Analytics User', 'hash', NOW())
+        "#,
+    )
+    .bind(user_id)
+    .bind(unique_email())
+    .execute(&app.pool)
+    .await
+    .expect("Failed to seed user");
+
+    for (days_ago, sedentary, score) in [(1, 480.0f32, 40), (2, 300.0f32, 70)] {
+        sqlx::query(
+            r#"
+            INSERT INTO activity_summary (
+                user_id, date, period_type,
+                sedentary_minutes, fidget_minutes, active_minutes, total_minutes,
+                dominant_state, activity_score
+            )
+            VALUES ($1, CURRENT_DATE - $2::INT, 'daily', $3, 60, 120, $3 + 180, 'SEDENTARY', $4)
+            "#,
+        )
+        .bind(user_id)
+        .bind(days_ago)
+        .bind(sedentary)
+        .bind(score)
+        .execute(&app.pool)
+        .await
+        .expect("Failed to seed activity summary");
+    }
+
+    let request = Request::get(format!("/api/fhir/analytics/user/{}", user_id))
+        .body(Body::empty())
+        .unwrap();
+    let (status, headers, body) = app.send_with_headers(request).await;
+    let timing = headers.get("server-timing").map(|v| v.to_str().unwrap());
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let bundle: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(bundle["resourceType"], "Bundle");
+    assert!(timing.unwrap().starts_with("db;dur="));
+    assert_eq!(bundle["total"], 2);
+
+    let entries = bundle["entry"].as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    for entry in entries {
+        let observation = &entry["resource"];
+        assert_eq!(observation["resourceType"], "Observation");
+        assert_eq!(observation["code"]["coding"][0]["code"], "87705-0");
+        assert_eq!(
+            observation["subject"]["reference"],
+            format!("Patient/{}", user_id)
+        );
+    }
+
+    // A user with no summaries yet gets an empty bundle
+    let (_, idle_user) = app.signed_in_user().await;
+    let (status, body) = app
+        .get(&format!("/api/fhir/analytics/user/{}", idle_user), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(serde_json::from_str::<Value>(&body).unwrap()["total"], 0);
+
+    // Unknown users and malformed ids get an OperationOutcome
+    let (status, body) = app
+        .get(
+            &format!("/api/fhir/analytics/user/{}", Uuid::new_v4()),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let outcome: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(outcome["resourceType"], "OperationOutcome");
+    assert_eq!(outcome["issue"][0]["code"], "not-found");
+
+    let (status, body) = app.get("/api/fhir/analytics/user/nope", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("OperationOutcome"));
+}
+
+// Same statement as get_latest_analytics
+const LATEST_ANALYTICS_QUERY: &str = r#"
+    SELECT id, user_id, date, sedentary_minutes, activity_score, dominant_state
+    FROM (
+        (
+            SELECT latest.*
+            FROM users u
+            CROSS JOIN LATERAL (
+                SELECT id, user_id, date, sedentary_minutes, activity_score, dominant_state
+                FROM activity_summary s
+                WHERE s.user_id = u.user_id AND s.period_type = $1
+                ORDER BY s.date DESC
+                LIMIT 1
+            ) latest
+            ORDER BY u.user_id
+            LIMIT $2
+        )
+        UNION ALL
+        (
+            SELECT id, user_id, date, sedentary_minutes, activity_score, dominant_state
+            FROM activity_summary
+            WHERE user_id IS NULL AND period_type = $1
+            ORDER BY user_id, period_type, date DESC
+            LIMIT 1
+        )
+    ) latest
+    ORDER BY user_id NULLS LAST
+    LIMIT $2
+"#;
+
+#[tokio::test]
+async fn test_latest_analytics_plan_uses_user_period_index() {
+    let app = spawn_app().await;
+    let mut tx = app.pool.begin().await.unwrap();
+    // A near-empty test database is cheap to scan sequentially; with that ruled
+    // out the planner still has to pick between this index and the unique one
+    sqlx::query("SET LOCAL enable_seqscan = off")
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+    let plan: Vec<String> = sqlx::query_scalar(&format!("EXPLAIN {}", LATEST_ANALYTICS_QUERY))
+        .bind("daily")
+        .bind(10_i64)
+        .fetch_all(&mut *tx)
+        .await
+        .unwrap();
+    let plan = plan.join("\n");
+
+    // Both the per-user probe and the unowned row read the composite index
+    assert_eq!(
+        plan.matches("idx_activity_summary_user_period_date")
+            .count(),
+        2,
+        "{}",
+        plan
+    );
+    assert!(!plan.contains("Seq Scan"), "{}", plan);
+}
+
+#[tokio::test]
+async fn test_fhir_analytics_summary_operation() {
+    let app = spawn_app().await;
+    let (_, user_id) = app.signed_in_user().await;
+    let uri = format!("/api/fhir/analytics/user/{}/$summary", user_id);
+
+    // Nothing summarized yet: an Observation with no value
+    let (status, body) = app.get(&uri, None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let observation: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(observation["resourceType"], "Observation");
+    assert!(observation["valueQuantity"].is_null());
+
+    for (days_ago, sedentary, score, alerts) in [
+        (1, 480.0f32, 40, 3),
+        (2, 240.0f32, 70, 1),
+        (3, 360.0f32, 55, 2),
+        // Outside the default 30-day window
+        (45, 900.0f32, 5, 9),
+    ] {
+        sqlx::query(
+            r#"
+            INSERT INTO activity_summary (
+                user_id, date, period_type,
+                sedentary_minutes, fidget_minutes, active_minutes, total_minutes,
+                dominant_state, activity_score, alert_count
+            )
+            VALUES ($1, CURRENT_DATE - $2::INT, 'daily', $3, 60, 120, $3 + 180, 'SEDENTARY', $4, $5)
+            "#,
+        )
+        .bind(user_id)
+        .bind(days_ago)
+        .bind(sedentary)
+        .bind(score)
+        .bind(alerts)
+        .execute(&app.pool)
+        .await
+        .expect("Failed to seed activity summary");
+    }
+
+    let (status, headers, body) = app
+        .send_with_headers(Request::get(&uri).body(Body::empty()).unwrap())
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(headers.contains_key("server-timing"));
+    let observation: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(observation["valueQuantity"]["value"], 6.0);
+    let component = |code: &str| {
+        observation["component"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|c| c["code"]["coding"][0]["code"] == code)
+            .cloned()
+            .unwrap_or_else(|| panic!("missing component {}", code))
+    };
+    assert_eq!(component("CUSTOM-DAY-COUNT")["valueInteger"], 3);
+    assert_eq!(component("CUSTOM-ALERT-COUNT")["valueInteger"], 6);
+    assert_eq!(
+        component("CUSTOM-MEDIAN-SEDENTARY-HOURS")["valueQuantity"]["value"],
+        6.0
+    );
+    assert_eq!(
+        component("CUSTOM-MAX-SEDENTARY-HOURS")["valueQuantity"]["value"],
+        8.0
+    );
+    assert_eq!(component("CUSTOM-BEST-DAY-SCORE")["valueInteger"], 70);
+    assert_eq!(component("CUSTOM-WORST-DAY-SCORE")["valueInteger"], 40);
+
+    let (status, _) = app
+        .get(&format!("{}?start=2026-02-01&end=2026-01-01", uri), None)
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = app
+        .get(
+            &format!("/api/fhir/analytics/user/{}/$summary", Uuid::new_v4()),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+// Stream Auth Tests
+
+impl TestApp {
+    /// Status of a streaming endpoint; the endless body is never read
+    async fn stream_status(&self, uri: &str, token: Option<&str>) -> StatusCode {
+        let mut request = Request::get(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let response = self
+            .app
+            .clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        response.status()
+    }
+}
+
+#[tokio::test]
+async fn test_events_open_by_default() {
+    let app = spawn_app().await;
+    assert_eq!(app.stream_status("/events", None).await, StatusCode::OK);
+    // A bad token is rejected rather than served the unscoped stream
+    assert_eq!(
+        app.stream_status("/events?token=garbage", None).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        app.stream_status("/ws", Some("garbage")).await,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[tokio::test]
+async fn test_stream_auth_required() {
+    let app = spawn_app_with(&[("STREAM_AUTH_REQUIRED", "true")]).await;
+    let (token, _) = app.signed_in_user().await;
+
+    assert_eq!(
+        app.stream_status("/events", None).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        app.stream_status("/events?token=garbage", None).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        app.stream_status("/ws", None).await,
+        StatusCode::UNAUTHORIZED
+    );
+
+    // EventSource can't set headers, so the token may come as a query parameter
+    assert_eq!(
+        app.stream_status(&format!("/events?token={}", token), None)
+            .await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        app.stream_status("/events", Some(&token)).await,
+        StatusCode::OK
+    );
+
+    // A logged-out token is revoked for streams too
+    let (status, _) = app.post("/logout", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        app.stream_status("/events", Some(&token)).await,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+// User Seeding Tests
+
+#[tokio::test]
+async fn test_seed_users_requires_flag() {
+    let app = spawn_app().await;
+    let admin = app.signed_in_admin().await;
+    let request = Request::post("/api/admin/seed-users")
+        .header(header::AUTHORIZATION, format!("Bearer {}", admin))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from("[]"))
+        .unwrap();
+    let (status, _) = app.send(request).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_seed_users_reports_each_row() {
+    let app = spawn_app_with(&[("SEEDING_ENABLED", "true")]).await;
+    let admin = app.signed_in_admin().await;
+    let (user, _) = app.signed_in_user().await;
+    let (first, second) = (unique_email(), unique_email());
+    let users = serde_json::json!([
+        {"email": first, "name": "Ada", "password": PASSWORD},
+        {"email": second, "name": "Grace", "password": PASSWORD},
+        {"email": first, "name": "Ada again", "password": PASSWORD},
+        {"email": unique_email(), "name": "Weak", "password": "short"},
+    ]);
+    let seed = |token: &str| {
+        let request = Request::post("/api/admin/seed-users")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(users.to_string()))
+            .unwrap();
+        app.send(request)
+    };
+
+    let (status, _) = seed(&user).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, body) = seed(&admin).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["created"], 2);
+    let results = body["results"].as_array().unwrap();
+    assert!(results[0]["user_id"].is_string());
+    assert!(results[1]["user_id"].is_string());
+    assert_eq!(results[2]["error"], "email_taken");
+    assert!(results[3]["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("weak_password"));
+
+    // Seeded accounts are verified and can log in straight away
+    let (status, body) = app.login(&second).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+// Latest Observation Tests
+
+#[tokio::test]
+async fn test_latest_observation_conditional_get() {
+    let app = spawn_app().await;
+    let insert = || {
+        sqlx::query(
+            "INSERT INTO sedentary_log (state, timer_seconds, acceleration_val) VALUES ('SEDENTARY', 30, 0.01)",
+        )
+        .execute(&app.pool)
+    };
+    insert().await.expect("Failed to seed sedentary_log");
+
+    let latest = |etag: Option<&str>| {
+        let mut request = Request::get("/api/fhir/observation/latest");
+        if let Some(etag) = etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        app.send_with_headers(request.body(Body::empty()).unwrap())
+    };
+
+    let (status, headers, body) = latest(None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(headers[header::CACHE_CONTROL], "no-cache");
+    let etag = headers[header::ETAG].to_str().unwrap().to_string();
+    assert!(etag.starts_with("W/"));
+
+    // Unchanged: 304 with the same validator and no body
+    let (status, headers, body) = latest(Some(&etag)).await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+    assert_eq!(headers[header::ETAG], etag.as_str());
+    assert!(body.is_empty());
+
+    // A newer reading invalidates the old ETag
+    insert().await.expect("Failed to seed sedentary_log");
+    let (status, headers, _) = latest(Some(&etag)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_ne!(headers[header::ETAG], etag.as_str());
+}
+
+// Daily Summary Job Tests
+
+#[tokio::test]
+async fn test_daily_summary_aggregates_sensor_data() {
+    let app = spawn_app().await;
+    let (_, user_id) = app.signed_in_user().await;
+
+    // Three sedentary seconds (two alerting), one fidget and one active
+    let readings = [
+        ("09:00:00", "SEDENTARY", 1199, false),
+        ("09:00:01", "SEDENTARY", 1200, true),
+        ("09:00:02", "SEDENTARY", 1201, true),
+        ("09:00:03", "FIDGET", 1201, false),
+        ("09:00:04", "ACTIVE", 0, false),
+    ];
+    for (time, state, timer, alert) in readings {
+        sqlx::query(
+            r#"
+            INSERT INTO sensor_data (user_id, state, timer_seconds, alert_triggered, timestamp)
+            VALUES ($1, $2, $3, $4, ('2026-03-12 ' || $5)::TIMESTAMPTZ)
+            "#,
+        )
+        .bind(user_id)
+        .bind(state)
+        .bind(timer)
+        .bind(alert)
+        .bind(format!("{}+00", time))
+        .execute(&app.pool)
+        .await
+        .expect("Failed to seed sensor data");
+    }
+
+    let date = "2026-03-12".parse().unwrap();
+    // Reruns overwrite the day's row instead of adding another
+    for _ in 0..2 {
+        daily_summary::summarize_day(&app.pool, &app.config, date)
+            .await
+            .expect("Failed to summarize day");
+    }
+
+    let rows: Vec<(String, i32, i32, i32, f32)> = sqlx::query_as(
+        r#"
+        SELECT dominant_state, activity_score, alert_count, longest_sedentary_period,
+               sedentary_percentage
+        FROM activity_summary
+        WHERE user_id = $1 AND period_type = 'daily' AND date = $2
+        "#,
+    )
+    .bind(user_id)
+    .bind(date)
+    .fetch_all(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(rows, vec![("SEDENTARY".to_string(), 52, 1, 1201, 60.0)]);
+}
+
+// DB Worker Tests
+
+#[tokio::test]
+async fn test_db_worker_writes_1000_readings_in_few_transactions() {
+    // Only full batches flush while the readings arrive
+    let app = spawn_app_with(&[("DB_BATCH_SIZE", "100"), ("DB_BATCH_INTERVAL_MS", "60000")]).await;
+    let (_, user_id) = app.signed_in_user().await;
+
+    let (tx, rx) = tokio::sync::broadcast::channel(1024);
+    let worker = db_worker::spawn_db_worker(
+        app.config.clone(),
+        app.pool.clone(),
+        rx,
+        Arc::new(Metrics::default()),
+        CancellationToken::new(),
+    )
+    .await;
+
+    let start = chrono::Utc::now();
+    for timer in 0..1000 {
+        let reading = ProcessedState {
+            state: ActivityState::Sedentary,
+            timer,
+            val: 0.01,
+            alert: false,
+            timestamp: start + chrono::Duration::milliseconds(timer as i64),
+            user_id: Some(user_id),
+            daily: None,
+            classification_confidence: None,
+            replayed: false,
+        };
+        tx.send(serde_json::to_string(&reading).unwrap()).unwrap();
+    }
+    // With the channel closed the worker drains it, flushes the rest and stops
+    drop(tx);
+    worker.await.unwrap();
+
+    // created_at defaults to the transaction start, so each batch shares one value
+    let (rows, transactions): (i64, i64) = sqlx::query_as(
+        "SELECT COUNT(*), COUNT(DISTINCT created_at) FROM sensor_data WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(rows, 1000);
+    // Ten full batches, plus at most one partial flush from the interval's first tick
+    assert!(transactions <= 11, "{} transactions", transactions);
+}
+
+// SSE Keepalive Tests
+
+#[tokio::test]
+async fn test_sse_keepalive_event_without_history() {
+    let app = spawn_app_with(&[
+        ("SSE_KEEPALIVE_SECONDS", "1"),
+        ("SSE_KEEPALIVE_EVENT", "true"),
+        ("SSE_KEEPALIVE_TEXT", "ping"),
+    ])
+    .await;
+    let response = app
+        .app
+        .clone()
+        .oneshot(
+            Request::get("/events?history=false")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Nothing is replayed, so the first frame is the heartbeat
+    let mut body = response.into_body().into_data_stream();
+    let frame = tokio::time::timeout(std::time::Duration::from_secs(5), body.next())
+        .await
+        .expect("no keepalive within 5s")
+        .expect("stream ended")
+        .unwrap();
+    assert_eq!(
+        std::str::from_utf8(&frame).unwrap(),
+        "event: keepalive\ndata: ping\n\n"
+    );
+}
+
+// Compression Tests
+
+/// Inflates a gzip body received so far (a stream may not be finished yet)
+fn gunzip(compressed: &[u8]) -> String {
+    use std::io::Write;
+    let mut decoder = flate2::write::GzDecoder::new(Vec::new());
+    decoder.write_all(compressed).unwrap();
+    decoder.flush().unwrap();
+    String::from_utf8_lossy(decoder.get_ref()).into_owned()
+}
+
+#[tokio::test]
+async fn test_fhir_analytics_gzip_encoded_with_compression() {
+    let app = spawn_app_with(&[("ENABLE_COMPRESSION", "true")]).await;
+    let (_, user_id) = app.signed_in_user().await;
+    sqlx::query(
+        r#"
+        INSERT INTO activity_summary (
+            user_id, date, period_type,
+            sedentary_minutes, fidget_minutes, active_minutes, total_minutes,
+            dominant_state, activity_score
+        )
+        VALUES ($1, CURRENT_DATE - 1, 'daily', 300, 60, 120, 480, 'SEDENTARY', 70)
+        "#,
+    )
+    .bind(user_id)
+    .execute(&app.pool)
+    .await
+    .expect("Failed to seed activity summary");
+    let uri = format!("/api/fhir/analytics/user/{}", user_id);
+
+    let request = Request::get(&uri)
+        .header(header::ACCEPT_ENCODING, "gzip")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let bundle: Value = serde_json::from_str(&gunzip(&body)).unwrap();
+    assert_eq!(bundle["total"], 1);
+
+    // Clients that don't ask for gzip get the plain body
+    let (_, headers, body) = app
+        .send_with_headers(Request::get(&uri).body(Body::empty()).unwrap())
+        .await;
+    assert!(headers.get(header::CONTENT_ENCODING).is_none());
+    assert_eq!(serde_json::from_str::<Value>(&body).unwrap()["total"], 1);
+}
+
+#[tokio::test]
+async fn test_compressed_sse_flushes_keepalive() {
+    let app = spawn_app_with(&[
+        ("ENABLE_COMPRESSION", "true"),
+        ("SSE_KEEPALIVE_SECONDS", "1"),
+        ("SSE_KEEPALIVE_EVENT", "true"),
+    ])
+    .await;
+    let request = Request::get("/events?history=false")
+        .header(header::ACCEPT_ENCODING, "gzip")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+
+    // The encoder flushes when the stream goes idle, so the first keepalive
+    // decodes on its own instead of waiting in the gzip buffer
+    let mut body = response.into_body().into_data_stream();
+    let mut received = Vec::new();
+    let keepalive = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while let Some(frame) = body.next().await {
+            received.extend_from_slice(&frame.unwrap());
+            let text = gunzip(&received);
+            if text.contains("event: keepalive") {
+                return text;
+            }
+        }
+        panic!("stream ended");
+    })
+    .await
+    .expect("no keepalive within 5s");
+    assert_eq!(keepalive, "event: keepalive\ndata: keepalive\n\n");
+}
+
+// Replay Upload Tests
+
+#[tokio::test]
+async fn test_replay_upload_requires_owner_or_admin() {
+    let app = spawn_app().await;
+    let (token, user_id) = app.signed_in_user().await;
+    let upload = |uri: String, token: Option<&str>| {
+        let body = "--BOUNDARY\r\n\
+            Content-Disposition: form-data; name=\"log\"; filename=\"run.log\"\r\n\r\n\
+            {\"ts\":\"10:00:00\",\"pir\":0,\"acc\":0.01}\r\n\
+            --BOUNDARY--\r\n";
+        let mut request = Request::post(uri).header(
+            header::CONTENT_TYPE,
+            "multipart/form-data; boundary=BOUNDARY",
+        );
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        request.body(Body::from(body)).unwrap()
+    };
+
+    let (status, _) = app.send(upload("/api/replay/upload".into(), None)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // Unowned readings reach every stream, so only an admin may replay them
+    let (status, _) = app
+        .send(upload("/api/replay/upload".into(), Some(&token)))
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let uri = format!("/api/replay/upload?user_id={}", Uuid::new_v4());
+    let (status, _) = app.send(upload(uri, Some(&token))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // ?skip=1 passes over the only reading, so the accepted replays leave
+    // nothing in Redis for the history tests running alongside
+    let uri = format!("/api/replay/upload?user_id={}&skip=1", user_id);
+    let (status, body) = app.send(upload(uri, Some(&token))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let admin = app.signed_in_admin().await;
+    let (status, body) = app
+        .send(upload("/api/replay/upload?skip=1".into(), Some(&admin)))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+// Security Header Tests
+
+#[tokio::test]
+async fn test_security_headers_on_static_api_and_stream_responses() {
+    let app = spawn_app().await;
+    for uri in ["/index.html", "/health/live", "/events"] {
+        let response = app
+            .app
+            .clone()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+        let headers = response.headers();
+        assert_eq!(
+            headers[header::X_CONTENT_TYPE_OPTIONS],
+            "nosniff",
+            "{}",
+            uri
+        );
+        assert_eq!(headers[header::X_FRAME_OPTIONS], "DENY", "{}", uri);
+        assert!(
+            headers.contains_key(header::CONTENT_SECURITY_POLICY),
+            "{}",
+            uri
+        );
+        assert!(headers.contains_key(header::REFERRER_POLICY), "{}", uri);
+    }
+}
+
+// Log Export Tests
+
+#[tokio::test]
+async fn test_export_log_round_trips_to_replay_format() {
+    let app = spawn_app().await;
+    // Days no other test writes to, cleared first so reruns start empty
+    sqlx::query(
+        "DELETE FROM sedentary_log WHERE created_at >= '2002-04-09' AND created_at < '2002-04-12'",
+    )
+    .execute(&app.pool)
+    .await
+    .expect("Failed to clear sedentary_log");
+    let rows = [
+        ("2002-04-09 23:59:59+00", "SEDENTARY", 0.01),
+        ("2002-04-10 08:00:00+00", "SEDENTARY", 0.012),
+        ("2002-04-10 08:00:01+00", "ACTIVE", 0.09),
+        ("2002-04-11 00:00:00+00", "FIDGET", 0.03),
+    ];
+    for (created_at, state, acc) in rows {
+        sqlx::query(
+            r#"
+            INSERT INTO sedentary_log (state, timer_seconds, acceleration_val, created_at)
+            VALUES ($1, 0, $2, $3::TIMESTAMPTZ)
+            "#,
+        )
+        .bind(state)
+        .bind(acc as f32)
+        .bind(created_at)
+        .execute(&app.pool)
+        .await
+        .expect("Failed to seed sedentary_log");
+    }
+
+    let uri = "/api/export/log?from=2002-04-10&to=2002-04-10";
+    let (token, _) = app.signed_in_user().await;
+    let (status, _) = app.get(uri, Some(&token)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let admin = app.signed_in_admin().await;
+    let request = Request::get(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {}", admin))
+        .body(Body::empty())
+        .unwrap();
+    let (status, headers, body) = app.send_with_headers(request).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(
+        headers[header::CONTENT_DISPOSITION],
+        "attachment; filename=\"sedentary_log_2002-04-10_2002-04-10.log\""
+    );
+
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines.len(), 2, "{}", body);
+    assert!(lines[0].starts_with("[2002-04-10 08:00:00.000] {\"ts\":\"08:00:00\",\"pir\":0,"));
+    assert!(lines[1].contains("\"pir\":1"));
+    assert!(replay::contains_reading(
+        body.as_bytes(),
+        server::serial::SerialFormat::Json
+    ));
+
+    let (status, _) = app
+        .get(
+            "/api/export/log?from=2002-04-11&to=2002-04-10",
+            Some(&admin),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+// Retention Tests
+
+#[tokio::test]
+async fn test_retention_prunes_old_readings_after_summarizing() {
+    let app = spawn_app().await;
+    let (_, user_id) = app.signed_in_user().await;
+
+    // Years before any other test's data, so a concurrent test keeps its rows
+    let readings = [
+        ("2001-03-05 09:00:00+00", "SEDENTARY"),
+        ("2001-03-05 09:00:01+00", "SEDENTARY"),
+        ("2001-03-05 09:00:02+00", "ACTIVE"),
+        ("2001-07-01 09:00:00+00", "SEDENTARY"),
+    ];
+    for (timestamp, state) in readings {
+        sqlx::query(
+            r#"
+            INSERT INTO sensor_data (user_id, state, timer_seconds, alert_triggered, timestamp)
+            VALUES ($1, $2, 0, false, $3::TIMESTAMPTZ)
+            "#,
+        )
+        .bind(user_id)
+        .bind(state)
+        .bind(timestamp)
+        .execute(&app.pool)
+        .await
+        .expect("Failed to seed sensor data");
+        sqlx::query(
+            r#"
+            INSERT INTO sedentary_log (state, timer_seconds, acceleration_val, created_at)
+            VALUES ($1, 0, 0.01, $2::TIMESTAMPTZ)
+            "#,
+        )
+        .bind(state)
+        .bind(timestamp)
+        .execute(&app.pool)
+        .await
+        .expect("Failed to seed sedentary_log");
+    }
+
+    let cutoff = "2001-06-01T00:00:00Z".parse().unwrap();
+    let stats =
+        retention::prune_before(&app.pool, &app.config, cutoff, &CancellationToken::new()).await;
+    assert_eq!(stats.error, None);
+    assert_eq!(stats.sensor_data_deleted, 3);
+    assert_eq!(stats.sedentary_log_deleted, 3);
+
+    let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sensor_data WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining, 1);
+
+    // The pruned day lives on as a daily summary
+    let summary: (String, f32) = sqlx::query_as(
+        r#"
+        SELECT dominant_state, sedentary_percentage
+        FROM activity_summary
+        WHERE user_id = $1 AND period_type = 'daily' AND date = '2001-03-05'
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(&app.pool)
+    .await
+    .expect("Pruned day was not summarized");
+    assert_eq!(summary.0, "SEDENTARY");
+    assert_eq!(summary.1, 66.67);
+}
+
+// Stream History Tests
+
+#[tokio::test]
+async fn test_history_inspect_and_clear() {
+    let app = spawn_app().await;
+    let admin = app.signed_in_admin().await;
+    let (user, _) = app.signed_in_user().await;
+
+    let mut con = app.redis.get_multiplexed_async_connection().await.unwrap();
+    redis::pipe()
+        .lpush(
+            "sensor_history",
+            &["{\"state\":\"ACTIVE\"}", "{\"state\":\"FIDGET\"}"],
+        )
+        .lpush("sensor_history:COM3", "{\"state\":\"FIDGET\"}")
+        .query_async::<_, ()>(&mut con)
+        .await
+        .expect("Failed to seed history");
+
+    let (status, _) = app.get("/api/history", Some(&user)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, body) = app.get("/api/history?limit=1", Some(&admin)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["total"], 2);
+    // Newest first, parsed back into JSON
+    assert_eq!(body["entries"], serde_json::json!([{"state": "FIDGET"}]));
+
+    let (status, body) = app.post("/api/history/clear", &admin).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(serde_json::from_str::<Value>(&body).unwrap()["removed"], 3);
+
+    let (_, body) = app.get("/api/history", Some(&admin)).await;
+    assert_eq!(serde_json::from_str::<Value>(&body).unwrap()["total"], 0);
+}